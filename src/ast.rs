@@ -39,6 +39,8 @@ pub enum Statement {
         name: String,
         params: Vec<String>,
         body: Vec<Statement>,
+        /// The `##` doc comment above the definition
+        doc: Option<String>,
     },
     Give(Expression),
     AttemptRescue {
@@ -51,6 +53,44 @@ pub enum Statement {
         steps: Vec<ChainStep>,
     },
     Expression(Expression),
+    /// `test "name" ( ... )`. Only `tilde test` runs these; running the
+    /// script normally skips them.
+    Test {
+        name: String,
+        body: Vec<Statement>,
+    },
+    /// `setup ( ... )`, run by `tilde test` before each test
+    Setup {
+        body: Vec<Statement>,
+    },
+    /// `teardown ( ... )`, run by `tilde test` after each test, even a failed one
+    Teardown {
+        body: Vec<Statement>,
+    },
+    /// `expect condition`: an error, with a description of the difference,
+    /// unless the condition holds
+    Expect(Expression),
+    /// A statement tagged with the 1-based source line it starts on. Only
+    /// produced by `Parser::with_locations`, for tools like the debugger.
+    Located {
+        line: usize,
+        statement: Box<Statement>,
+    },
+    /// A comment or blank line between statements. Only produced by
+    /// `Parser::with_comments`, for tools like `tilde fmt`; it does nothing.
+    Trivia(Trivia),
+    /// A statement that didn't parse, holding the error. Only produced by
+    /// `Parser::parse_recovering`, so tools can work with the rest of the program.
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia {
+    /// A comment on a line of its own
+    Comment(String),
+    /// A comment after code, belonging to the line before it
+    TrailingComment(String),
+    BlankLine,
 }
 
 #[derive(Debug, Clone, PartialEq)]
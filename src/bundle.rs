@@ -0,0 +1,435 @@
+//! `tilde build`: standalone executables that carry their script.
+//!
+//! A built executable is a copy of the `tilde` binary with a bundle appended:
+//! the script, every module it imports and any data files given with
+//! `--include`, followed by a trailer giving the bundle's length. On startup
+//! `tilde` checks its own executable for a trailer, and if there is one runs
+//! the bundled script instead of reading its command line. Scripts see the
+//! bundled files through `read` and `import`, at the paths they had when the
+//! executable was built, so nothing needs to be installed alongside it.
+
+use crate::ast::{Expression, Statement};
+use crate::package::{self, ImportFiles, MANIFEST_FILE, MODULES_DIR};
+use crate::parser::Parser;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+/// Marks the end of an executable that carries a bundle
+const MAGIC: &[u8; 8] = b"~TILDE1~";
+
+/// The bundle's length followed by `MAGIC`
+const TRAILER_LEN: u64 = 16;
+
+/// A script and the files it needs, keyed by their normalized paths
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bundle {
+    entry: String,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Bundle {
+    /// Bundle `script` with the modules it imports, found by following each
+    /// `import` written with a string literal, and the files and directories
+    /// in `includes`. Fails if any script doesn't parse.
+    pub fn build(script: &Path, includes: &[PathBuf]) -> Result<Bundle, String> {
+        let mut bundle = Bundle {
+            entry: key(script),
+            files: BTreeMap::new(),
+        };
+
+        let mut pending = VecDeque::from([script.to_path_buf()]);
+        while let Some(path) = pending.pop_front() {
+            if bundle.files.contains_key(&key(&path)) {
+                continue;
+            }
+            let source = fs::read_to_string(&path)
+                .map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+            let program = Parser::new(&source)
+                .parse()
+                .map_err(|e| format!("Parse error in {}: {}", path.display(), e))?;
+            let mut imports = Vec::new();
+            collect_imports(&program, &mut imports);
+            for spec in imports {
+                let module = package::resolve_import(&spec, Some(&path))?;
+                if let Some(manifest) = package_manifest(&module) {
+                    bundle.add_file(&manifest)?;
+                }
+                pending.push_back(module);
+            }
+            bundle.files.insert(key(&path), source.into_bytes());
+        }
+
+        for include in includes {
+            if include.is_dir() {
+                for file in files_under(include)? {
+                    bundle.add_file(&file)?;
+                }
+            } else if include.is_file() {
+                bundle.add_file(include)?;
+            } else {
+                return Err(format!("Can't include '{}': not found", include.display()));
+            }
+        }
+        Ok(bundle)
+    }
+
+    fn add_file(&mut self, path: &Path) -> Result<(), String> {
+        let contents =
+            fs::read(path).map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+        self.files.insert(key(path), contents);
+        Ok(())
+    }
+
+    /// The path of the script the bundle runs
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+
+    /// The script the bundle runs
+    pub fn source(&self) -> Result<String, String> {
+        self.read(Path::new(&self.entry))
+    }
+
+    /// A bundled file's contents
+    pub fn file(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(&key(path)).map(Vec::as_slice)
+    }
+
+    /// The paths of the bundled files, sorted
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_chunk(&mut bytes, self.entry.as_bytes());
+        bytes.extend((self.files.len() as u64).to_le_bytes());
+        for (path, contents) in &self.files {
+            write_chunk(&mut bytes, path.as_bytes());
+            write_chunk(&mut bytes, contents);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Bundle, String> {
+        let mut reader = ChunkReader { bytes };
+        let entry = reader.string()?;
+        let count = reader.u64()?;
+        let mut files = BTreeMap::new();
+        for _ in 0..count {
+            let path = reader.string()?;
+            files.insert(path, reader.chunk()?.to_vec());
+        }
+        Ok(Bundle { entry, files })
+    }
+
+    /// Write an executable to `output` that runs this bundle, made from the
+    /// `tilde` binary at `interpreter`
+    pub fn write_executable(&self, interpreter: &Path, output: &Path) -> Result<(), String> {
+        let mut contents = fs::read(interpreter)
+            .map_err(|e| format!("Error reading '{}': {}", interpreter.display(), e))?;
+        // Building from a built executable replaces its bundle
+        if let Some(len) = bundle_len(&contents)
+            && let Some(end) = contents.len().checked_sub((len + TRAILER_LEN) as usize)
+        {
+            contents.truncate(end);
+        }
+        let payload = self.to_bytes();
+        contents.extend(&payload);
+        contents.extend((payload.len() as u64).to_le_bytes());
+        contents.extend(MAGIC);
+
+        fs::write(output, contents)
+            .map_err(|e| format!("Error writing '{}': {}", output.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(output, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Error making '{}' executable: {}", output.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// The bundle carried by the executable at `path`, if it has one
+    ///
+    /// An executable that can't be read, e.g. an execute-only install, has no
+    /// bundle. It's only an error when a trailer is found but the bundle it
+    /// describes is damaged.
+    pub fn from_executable(path: &Path) -> Result<Option<Bundle>, String> {
+        let Some((mut file, size, len)) = read_trailer(path) else {
+            return Ok(None);
+        };
+        if len > size - TRAILER_LEN {
+            return Err(format!("'{}' has a damaged bundle", path.display()));
+        }
+
+        let error = |e: std::io::Error| format!("Error reading '{}': {}", path.display(), e);
+        let mut payload = vec![0; len as usize];
+        file.seek(SeekFrom::End(-((len + TRAILER_LEN) as i64)))
+            .map_err(error)?;
+        file.read_exact(&mut payload).map_err(error)?;
+        Bundle::from_bytes(&payload)
+            .map(Some)
+            .map_err(|e| format!("'{}' has a damaged bundle: {}", path.display(), e))
+    }
+
+    /// The bundle carried by the running executable, if it has one
+    pub fn from_current_exe() -> Result<Option<Bundle>, String> {
+        match std::env::current_exe() {
+            Ok(path) => Bundle::from_executable(&path),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl ImportFiles for Bundle {
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(&key(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let prefix = format!("{}/", key(path));
+        self.files.keys().any(|file| file.starts_with(&prefix))
+    }
+
+    fn read(&self, path: &Path) -> Result<String, String> {
+        let contents = self
+            .file(path)
+            .ok_or_else(|| format!("'{}' isn't built into this executable", path.display()))?;
+        String::from_utf8(contents.to_vec())
+            .map_err(|_| format!("'{}' isn't valid UTF-8", path.display()))
+    }
+}
+
+/// The open executable, its size and the bundle length from its trailer, or
+/// `None` if it can't be read or has no trailer
+fn read_trailer(path: &Path) -> Option<(File, u64, u64)> {
+    let mut file = File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    if size < TRAILER_LEN {
+        return None;
+    }
+    let mut trailer = [0; TRAILER_LEN as usize];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64))).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    let len = bundle_len(&trailer)?;
+    Some((file, size, len))
+}
+
+/// The bundle length from the trailer at the end of `bytes`, if there is one
+fn bundle_len(bytes: &[u8]) -> Option<u64> {
+    let trailer = bytes
+        .len()
+        .checked_sub(TRAILER_LEN as usize)
+        .map(|start| &bytes[start..])?;
+    if &trailer[8..] != MAGIC {
+        return None;
+    }
+    Some(u64::from_le_bytes(trailer[..8].try_into().ok()?))
+}
+
+/// A path with `.` and `..` worked out and `/` separators, so the same file
+/// has one key however a script spells it
+fn key(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match parts.last().map(String::as_str) {
+                None | Some("..") => parts.push("..".to_string()),
+                // Above the root is still the root
+                Some("") => {}
+                Some(_) => {
+                    parts.pop();
+                }
+            },
+            Component::RootDir => parts.push(String::new()),
+            Component::Prefix(prefix) => {
+                parts.push(prefix.as_os_str().to_string_lossy().into_owned())
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+        }
+    }
+    match parts.as_slice() {
+        [] => ".".to_string(),
+        [root] if root.is_empty() => "/".to_string(),
+        _ => parts.join("/"),
+    }
+}
+
+/// The `tilde.toml` of the installed package `module` is in, which says
+/// what `import "name"` loads
+fn package_manifest(module: &Path) -> Option<PathBuf> {
+    module
+        .ancestors()
+        .find(|dir| {
+            dir.parent()
+                .and_then(Path::file_name)
+                .is_some_and(|name| name == MODULES_DIR)
+        })
+        .map(|package| package.join(MANIFEST_FILE))
+        .filter(|manifest| manifest.is_file())
+}
+
+/// The specs of `import`s written with a string literal, anywhere in `body`
+fn collect_imports(body: &[Statement], imports: &mut Vec<String>) {
+    for statement in body {
+        match statement {
+            Statement::Expression(Expression::FunctionCall { name, args }) if name == "import" => {
+                if let [Expression::String(spec)] = args.as_slice() {
+                    imports.push(spec.clone());
+                }
+            }
+            Statement::If {
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                collect_imports(std::slice::from_ref(then_stmt), imports);
+                if let Some(else_stmt) = else_stmt {
+                    collect_imports(std::slice::from_ref(else_stmt), imports);
+                }
+            }
+            Statement::AttemptRescue {
+                attempt_body,
+                rescue_body,
+                ..
+            } => {
+                collect_imports(attempt_body, imports);
+                collect_imports(rescue_body, imports);
+            }
+            Statement::Located { statement, .. } => {
+                collect_imports(std::slice::from_ref(statement), imports)
+            }
+            Statement::Loop { body }
+            | Statement::ForEach { body, .. }
+            | Statement::Block { body }
+            | Statement::FunctionDefinition { body, .. }
+            | Statement::Test { body, .. }
+            | Statement::Setup { body }
+            | Statement::Teardown { body } => collect_imports(body, imports),
+            _ => {}
+        }
+    }
+}
+
+/// Every file below `dir`, skipping version control
+fn files_under(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Error reading '{}': {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Error reading '{}': {}", dir.display(), e))?
+                .path();
+            if path.file_name().is_some_and(|name| name == ".git") {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, chunk: &[u8]) {
+    bytes.extend((chunk.len() as u64).to_le_bytes());
+    bytes.extend(chunk);
+}
+
+struct ChunkReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ChunkReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.bytes.len() {
+            return Err("unexpected end of data".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().expect("took 8 bytes")))
+    }
+
+    fn chunk(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u64()?;
+        self.take(usize::try_from(len).map_err(|_| "chunk too large".to_string())?)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.chunk()?.to_vec()).map_err(|_| "invalid path".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_normalizes_paths() {
+        assert_eq!(key(Path::new("./data/../lib/./a.tde")), "lib/a.tde");
+        assert_eq!(key(Path::new("../shared/a.tde")), "../shared/a.tde");
+        assert_eq!(key(Path::new("/srv/app/x.json")), "/srv/app/x.json");
+        assert_eq!(key(Path::new(".")), ".");
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut bundle = Bundle {
+            entry: "tool.tde".to_string(),
+            files: BTreeMap::new(),
+        };
+        bundle
+            .files
+            .insert("tool.tde".to_string(), b"say 1".to_vec());
+        bundle
+            .files
+            .insert("data/blob.bin".to_string(), vec![0, 255, 7]);
+        let bytes = bundle.to_bytes();
+        assert_eq!(Bundle::from_bytes(&bytes).unwrap(), bundle);
+        assert!(Bundle::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        assert!(bundle.is_dir(Path::new("./data")));
+        assert!(!bundle.is_dir(Path::new("dat")));
+        assert_eq!(bundle.source().unwrap(), "say 1");
+        assert!(bundle.read(Path::new("data/blob.bin")).is_err());
+    }
+
+    #[test]
+    fn test_trailer() {
+        let mut bytes = b"binary".to_vec();
+        assert_eq!(bundle_len(&bytes), None);
+        bytes.extend(42u64.to_le_bytes());
+        bytes.extend(MAGIC);
+        assert_eq!(bundle_len(&bytes), Some(42));
+    }
+
+    #[test]
+    fn test_unreadable_executables_have_no_bundle() {
+        let dir = std::env::temp_dir().join(format!("tilde-bundle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(Bundle::from_executable(&dir.join("missing")), Ok(None));
+        assert_eq!(Bundle::from_executable(&dir), Ok(None));
+
+        // A trailer promising more bytes than the file holds is damaged
+        let damaged = dir.join("damaged");
+        let mut bytes = b"binary".to_vec();
+        bytes.extend(42u64.to_le_bytes());
+        bytes.extend(MAGIC);
+        fs::write(&damaged, bytes).unwrap();
+        assert!(Bundle::from_executable(&damaged).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,588 @@
+use crate::ast::Expression;
+use crate::embed::NativeFunction;
+use crate::evaluator::{Evaluator, Function};
+use crate::io::IoStreams;
+use crate::limits::ResourceUsage;
+use crate::sandbox::EvaluatorConfig;
+use crate::trace::Tracer;
+use crate::value::{ErrorValue, Value};
+use crossbeam_channel::{Receiver, Sender, bounded, unbounded};
+#[cfg(not(target_arch = "wasm32"))]
+use crossbeam_channel::{RecvTimeoutError, Select};
+#[cfg(target_arch = "wasm32")]
+use crossbeam_channel::TryRecvError;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Result of a finished task: the produced value or a structured error
+pub type TaskResult = Result<Value, ErrorValue>;
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_CHANNEL_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Whether this thread is a pool worker, which mustn't sit idle while it
+    /// waits for another task
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Shared completion slot a worker fills in and awaiting code blocks on
+#[derive(Default)]
+struct TaskSlot {
+    result: Mutex<Option<TaskResult>>,
+    ready: Condvar,
+}
+
+/// Handle to a task running on a worker pool.
+/// Cloning the handle shares the same underlying task.
+#[derive(Clone)]
+pub struct TaskHandle {
+    id: u64,
+    slot: Arc<TaskSlot>,
+}
+
+impl TaskHandle {
+    fn new() -> Self {
+        TaskHandle {
+            id: NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed),
+            slot: Arc::new(TaskSlot::default()),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Check whether the task has finished without blocking
+    pub fn is_done(&self) -> bool {
+        self.slot.result.lock().unwrap().is_some()
+    }
+
+    /// Block until the task finishes and return its result.
+    /// Awaiting the same task more than once returns the same result.
+    pub fn wait(&self) -> TaskResult {
+        let mut result = self.slot.result.lock().unwrap();
+        while result.is_none() {
+            result = self.slot.ready.wait(result).unwrap();
+        }
+        result.clone().unwrap()
+    }
+
+    fn complete(&self, outcome: TaskResult) {
+        let mut result = self.slot.result.lock().unwrap();
+        *result = Some(outcome);
+        self.slot.ready.notify_all();
+    }
+}
+
+impl PartialEq for TaskHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl fmt::Debug for TaskHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskHandle")
+            .field("id", &self.id)
+            .field("done", &self.is_done())
+            .finish()
+    }
+}
+
+/// Why a channel operation didn't produce a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelError {
+    /// The channel was closed (and, for receives, fully drained)
+    Closed,
+    /// No message arrived before the timeout
+    Timeout,
+    /// No message is waiting, and in the browser none can arrive while the
+    /// script waits, since tasks there run to completion when spawned
+    WouldBlock,
+}
+
+/// Handle to a channel of values shared between tasks.
+/// Cloning the handle (including copying it into a spawned task) shares the
+/// same underlying channel.
+#[derive(Clone)]
+pub struct ChannelHandle {
+    id: u64,
+    capacity: Option<usize>,
+    // The only sender lives here so closing can disconnect every receiver
+    sender: Arc<Mutex<Option<Sender<Value>>>>,
+    receiver: Receiver<Value>,
+}
+
+impl ChannelHandle {
+    /// Create a channel holding at most `capacity` pending messages,
+    /// or an unbounded one when `capacity` is None
+    pub fn new(capacity: Option<usize>) -> Self {
+        let (sender, receiver) = match capacity {
+            Some(capacity) => bounded(capacity),
+            None => unbounded(),
+        };
+        ChannelHandle {
+            id: NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed),
+            capacity,
+            sender: Arc::new(Mutex::new(Some(sender))),
+            receiver,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Number of messages waiting to be received
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.lock().unwrap().is_none()
+    }
+
+    /// Send a value, blocking while a bounded channel is full
+    pub fn send(&self, value: Value) -> Result<(), ChannelError> {
+        // Clone the sender out of the lock so a blocked send doesn't block close
+        let sender = self.sender.lock().unwrap().clone();
+        match sender {
+            Some(sender) => sender.send(value).map_err(|_| ChannelError::Closed),
+            None => Err(ChannelError::Closed),
+        }
+    }
+
+    /// Receive the next value, blocking until one arrives.
+    /// Messages sent before the channel was closed are still delivered.
+    pub fn receive(&self) -> Result<Value, ChannelError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.receiver.recv().map_err(|_| ChannelError::Closed)
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.try_receive()
+        }
+    }
+
+    pub fn receive_timeout(&self, timeout: Duration) -> Result<Value, ChannelError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.receiver.recv_timeout(timeout).map_err(|err| match err {
+                RecvTimeoutError::Timeout => ChannelError::Timeout,
+                RecvTimeoutError::Disconnected => ChannelError::Closed,
+            })
+        }
+
+        // Waiting can't help in the browser, and `Instant` isn't available
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = timeout;
+            self.try_receive()
+        }
+    }
+
+    /// Take a message only if one is already waiting
+    #[cfg(target_arch = "wasm32")]
+    fn try_receive(&self) -> Result<Value, ChannelError> {
+        self.receiver.try_recv().map_err(|err| match err {
+            TryRecvError::Empty => ChannelError::WouldBlock,
+            TryRecvError::Disconnected => ChannelError::Closed,
+        })
+    }
+
+    /// Close the channel. Pending messages can still be received; further
+    /// sends fail. Returns false if the channel was already closed.
+    pub fn close(&self) -> bool {
+        self.sender.lock().unwrap().take().is_some()
+    }
+}
+
+impl PartialEq for ChannelHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl fmt::Debug for ChannelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChannelHandle")
+            .field("id", &self.id)
+            .field("capacity", &self.capacity)
+            .field("pending", &self.len())
+            .finish()
+    }
+}
+
+/// Wait for a message on any of the channels.
+/// Returns the index of the channel that produced the message and the value.
+/// Closed and drained channels are skipped; once every channel is closed the
+/// result is `ChannelError::Closed`.
+/// In the browser only messages already waiting are taken, and
+/// `ChannelError::WouldBlock` is returned when there are none.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn select(
+    channels: &[ChannelHandle],
+    timeout: Option<Duration>,
+) -> Result<(usize, Value), ChannelError> {
+    let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+    let mut open: Vec<usize> = (0..channels.len()).collect();
+
+    while !open.is_empty() {
+        let mut select = Select::new();
+        for &index in &open {
+            select.recv(&channels[index].receiver);
+        }
+
+        let operation = match deadline {
+            Some(deadline) => select
+                .select_deadline(deadline)
+                .map_err(|_| ChannelError::Timeout)?,
+            None => select.select(),
+        };
+
+        let position = operation.index();
+        let index = open[position];
+        match operation.recv(&channels[index].receiver) {
+            Ok(value) => return Ok((index, value)),
+            // Disconnected: stop watching this channel and wait on the rest
+            Err(_) => {
+                open.remove(position);
+            }
+        }
+    }
+
+    Err(ChannelError::Closed)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn select(
+    channels: &[ChannelHandle],
+    _timeout: Option<Duration>,
+) -> Result<(usize, Value), ChannelError> {
+    let mut open = false;
+    for (index, channel) in channels.iter().enumerate() {
+        match channel.try_receive() {
+            Ok(value) => return Ok((index, value)),
+            Err(ChannelError::Closed) => {}
+            Err(_) => open = true,
+        }
+    }
+    Err(if open {
+        ChannelError::WouldBlock
+    } else {
+        ChannelError::Closed
+    })
+}
+
+/// Snapshot of evaluator state a task starts from.
+/// Tasks never share mutable state with the spawning evaluator; values are
+/// copied in and the result is copied back out.
+#[derive(Debug, Clone, Default)]
+pub struct TaskContext {
+    pub variables: HashMap<String, Value>,
+    pub functions: HashMap<String, Function>,
+    pub native_functions: HashMap<String, NativeFunction>,
+    pub host_blocks: HashSet<String>,
+    /// Tasks run with the same capabilities as the evaluator that spawned them
+    pub config: EvaluatorConfig,
+    /// Tasks write to and read from the same streams as their spawner
+    pub streams: IoStreams,
+    pub tracer: Option<Tracer>,
+    /// The spawner's pool, which tasks spawned inside a task also run on
+    pub pool: Option<Arc<TaskPool>>,
+    /// The spawner's step, time and output budget, which its tasks draw on
+    pub(crate) usage: Option<ResourceUsage>,
+}
+
+/// A unit of work: an expression evaluated against a context plus extra bindings
+struct Job {
+    context: Arc<TaskContext>,
+    bindings: Vec<(String, Value)>,
+    expression: Expression,
+    handle: TaskHandle,
+}
+
+impl Job {
+    fn run(self) {
+        let mut evaluator = Evaluator::with_config(self.context.config.clone());
+        evaluator.variables = self.context.variables.clone();
+        evaluator.functions = self.context.functions.clone();
+        evaluator.native_functions = self.context.native_functions.clone();
+        evaluator.host_blocks = self.context.host_blocks.clone();
+        evaluator.set_streams(self.context.streams.clone());
+        evaluator.join_task_tree(self.context.pool.clone(), self.context.usage.clone());
+        if let Some(tracer) = &self.context.tracer {
+            evaluator.set_tracer(tracer.clone());
+        }
+        for (name, value) in self.bindings {
+            evaluator.set_variable(name, value);
+        }
+
+        let outcome = match evaluator.eval_expression(self.expression) {
+            Ok(value) => Ok(value),
+            Err(message) => Err(task_error(&mut evaluator, message, self.handle.id)),
+        };
+        self.handle.complete(outcome);
+    }
+}
+
+/// Build the structured error for a failed task, keeping any structured
+/// error (e.g. from HTTP) the task produced
+fn task_error(evaluator: &mut Evaluator, message: String, task_id: u64) -> ErrorValue {
+    let error = match evaluator.take_last_error() {
+        Some(Value::Error(err)) => err,
+        _ => ErrorValue::new(message)
+            .with_code("task_failed")
+            .with_source("spawn"),
+    };
+    error.with_context("task", Value::Number(task_id as f64))
+}
+
+/// Fixed-size pool of worker threads, each running tasks on its own evaluator.
+/// Tasks spawned inside a task run on the same pool.
+pub struct TaskPool {
+    size: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    sender: Sender<Job>,
+    #[cfg(not(target_arch = "wasm32"))]
+    receiver: Receiver<Job>,
+}
+
+impl TaskPool {
+    /// Create a pool with `size` workers (at least one)
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (sender, receiver) = unbounded::<Job>();
+            for index in 0..size {
+                let receiver = receiver.clone();
+                // Workers exit once the pool is dropped and the queue is drained
+                std::thread::Builder::new()
+                    .name(format!("tilde-worker-{}", index))
+                    .spawn(move || {
+                        IS_WORKER.with(|worker| worker.set(true));
+                        while let Ok(job) = receiver.recv() {
+                            job.run();
+                        }
+                    })
+                    .expect("failed to start task worker");
+            }
+            TaskPool {
+                size,
+                sender,
+                receiver,
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            TaskPool { size }
+        }
+    }
+
+    /// Default pool size: one worker per available CPU
+    pub fn default_size() -> usize {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Queue an expression for evaluation and return a handle to its result
+    pub fn spawn(
+        &self,
+        context: Arc<TaskContext>,
+        bindings: Vec<(String, Value)>,
+        expression: Expression,
+    ) -> TaskHandle {
+        let handle = TaskHandle::new();
+        let job = Job {
+            context,
+            bindings,
+            expression,
+            handle: handle.clone(),
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Err(err) = self.sender.send(job) {
+                // Workers are gone; run inline so the task still completes
+                err.into_inner().run();
+            }
+        }
+
+        // No threads in the browser: run the task to completion immediately
+        #[cfg(target_arch = "wasm32")]
+        job.run();
+
+        handle
+    }
+
+    /// Block until a task finishes and return its result. A worker runs
+    /// queued tasks while it waits, so tasks that await tasks of their own
+    /// can't tie up every worker and deadlock the pool.
+    pub fn wait(&self, task: &TaskHandle) -> TaskResult {
+        #[cfg(not(target_arch = "wasm32"))]
+        if IS_WORKER.with(Cell::get) {
+            while !task.is_done() {
+                if let Ok(job) = self.receiver.recv_timeout(Duration::from_millis(1)) {
+                    job.run();
+                }
+            }
+        }
+        task.wait()
+    }
+}
+
+impl fmt::Debug for TaskPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskPool").field("size", &self.size).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BinaryOperator;
+
+    fn add_expr(left: &str, right: f64) -> Expression {
+        Expression::BinaryOp {
+            left: Box::new(Expression::Variable(left.to_string())),
+            op: BinaryOperator::Add,
+            right: Box::new(Expression::Number(right, false)),
+        }
+    }
+
+    #[test]
+    fn test_task_runs_with_context_variables() {
+        let pool = TaskPool::new(2);
+        let mut context = TaskContext::default();
+        context
+            .variables
+            .insert("x".to_string(), Value::Number(41.0));
+
+        let handle = pool.spawn(Arc::new(context), Vec::new(), add_expr("x", 1.0));
+        assert_eq!(handle.wait(), Ok(Value::Number(42.0)));
+        assert!(handle.is_done());
+    }
+
+    #[test]
+    fn test_bindings_override_context() {
+        let pool = TaskPool::new(1);
+        let mut context = TaskContext::default();
+        context
+            .variables
+            .insert("x".to_string(), Value::Number(1.0));
+
+        let handle = pool.spawn(
+            Arc::new(context),
+            vec![("x".to_string(), Value::Number(10.0))],
+            add_expr("x", 5.0),
+        );
+        assert_eq!(handle.wait(), Ok(Value::Number(15.0)));
+    }
+
+    #[test]
+    fn test_failed_task_returns_structured_error() {
+        let pool = TaskPool::new(1);
+        let handle = pool.spawn(
+            Arc::new(TaskContext::default()),
+            Vec::new(),
+            add_expr("missing", 1.0),
+        );
+
+        let err = handle.wait().unwrap_err();
+        assert_eq!(err.code.as_deref(), Some("task_failed"));
+        assert_eq!(err.source.as_deref(), Some("spawn"));
+        assert!(err.message.contains("Undefined variable"));
+        assert_eq!(
+            err.context.get("task"),
+            Some(&Value::Number(handle.id() as f64))
+        );
+    }
+
+    #[test]
+    fn test_wait_is_repeatable() {
+        let pool = TaskPool::new(1);
+        let handle = pool.spawn(
+            Arc::new(TaskContext::default()),
+            Vec::new(),
+            Expression::Number(7.0, false),
+        );
+        assert_eq!(handle.wait(), handle.wait());
+    }
+
+    #[test]
+    fn test_pool_size_is_at_least_one() {
+        assert_eq!(TaskPool::new(0).size(), 1);
+    }
+
+    #[test]
+    fn test_channel_delivers_pending_messages_after_close() {
+        let channel = ChannelHandle::new(Some(2));
+        channel.send(Value::Number(1.0)).unwrap();
+        channel.send(Value::Number(2.0)).unwrap();
+        assert!(channel.close());
+        assert!(!channel.close());
+
+        assert_eq!(channel.send(Value::Null), Err(ChannelError::Closed));
+        assert_eq!(channel.receive(), Ok(Value::Number(1.0)));
+        assert_eq!(channel.receive(), Ok(Value::Number(2.0)));
+        assert_eq!(channel.receive(), Err(ChannelError::Closed));
+    }
+
+    #[test]
+    fn test_channel_receive_timeout() {
+        let channel = ChannelHandle::new(None);
+        assert_eq!(
+            channel.receive_timeout(Duration::from_millis(10)),
+            Err(ChannelError::Timeout)
+        );
+    }
+
+    #[test]
+    fn test_select_skips_closed_channels() {
+        let closed = ChannelHandle::new(None);
+        closed.close();
+        let open = ChannelHandle::new(None);
+        open.send(Value::String("hi".to_string())).unwrap();
+
+        let channels = vec![closed.clone(), open.clone()];
+        assert_eq!(
+            select(&channels, None),
+            Ok((1, Value::String("hi".to_string())))
+        );
+
+        open.close();
+        assert_eq!(select(&channels, None), Err(ChannelError::Closed));
+    }
+
+    #[test]
+    fn test_select_timeout() {
+        let channels = vec![ChannelHandle::new(None)];
+        assert_eq!(
+            select(&channels, Some(Duration::from_millis(10))),
+            Err(ChannelError::Timeout)
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! Interactive debugging: breakpoints, stepping and inspection.
+//!
+//! The evaluator calls its `DebugHook` before every statement that carries a
+//! source line, which means the program has to be parsed with
+//! `Parser::with_locations`. `Debugger` is the hook behind `tilde debug`. It
+//! pauses at breakpoints and after steps, then reads commands from an input
+//! stream until one resumes execution. Other front-ends, such as the web REPL
+//! or a Debug Adapter Protocol server, can drive `Debugger::execute` with
+//! their own commands or implement `DebugHook` themselves.
+//!
+//! Spawned tasks run without the hook.
+
+use crate::evaluator::Evaluator;
+use crate::io::{InputStream, OutputStream};
+use crate::parser::Parser;
+use crate::value::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// Where execution is about to continue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// 1-based source line of the next statement
+    pub line: usize,
+    /// Number of active function scopes; 0 at the top level
+    pub depth: usize,
+}
+
+/// One entry of the call stack, innermost first
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    /// Function name, `<anonymous>` for callbacks or `<script>` for the top level
+    pub function: String,
+    /// Line the frame is executing, if known
+    pub line: Option<usize>,
+}
+
+/// Called by the evaluator before each located statement
+pub trait DebugHook {
+    /// Inspect or change the evaluator before the statement runs. Returning
+    /// an error stops the program with that error.
+    fn before_statement(&mut self, evaluator: &mut Evaluator, location: Location) -> Result<(), String>;
+}
+
+impl fmt::Debug for dyn DebugHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DebugHook")
+    }
+}
+
+/// Where to pause
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Breakpoint {
+    Line(usize),
+    Function(String),
+}
+
+impl Breakpoint {
+    /// Parse `12` as a line and `name` or `*name` as a function
+    pub fn parse(text: &str) -> Result<Breakpoint, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Expected a line number or function name".to_string());
+        }
+        match text.parse::<usize>() {
+            Ok(0) => Err("Line numbers start at 1".to_string()),
+            Ok(line) => Ok(Breakpoint::Line(line)),
+            Err(_) => Ok(Breakpoint::Function(
+                text.trim_start_matches('*').to_string(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line(line) => write!(f, "line {}", line),
+            Breakpoint::Function(name) => write!(f, "function {}", name),
+        }
+    }
+}
+
+/// A debugger command, as typed at the `(debug)` prompt
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugCommand {
+    Continue,
+    StepInto,
+    StepOver,
+    StepOut,
+    Break(Breakpoint),
+    Delete(Breakpoint),
+    Breakpoints,
+    Stack,
+    Locals,
+    Print(String),
+    Set { variable: String, value: String },
+    Eval(String),
+    List,
+    Help,
+    Quit,
+}
+
+impl DebugCommand {
+    pub fn parse(input: &str) -> Result<DebugCommand, String> {
+        let input = input.trim();
+        let (name, rest) = match input.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, rest.trim()),
+            None => (input, ""),
+        };
+
+        let command = match name {
+            "c" | "continue" => DebugCommand::Continue,
+            "s" | "step" => DebugCommand::StepInto,
+            "n" | "next" => DebugCommand::StepOver,
+            "o" | "out" | "finish" => DebugCommand::StepOut,
+            "b" | "break" => DebugCommand::Break(Breakpoint::parse(rest)?),
+            "d" | "delete" => DebugCommand::Delete(Breakpoint::parse(rest)?),
+            "breakpoints" => DebugCommand::Breakpoints,
+            "bt" | "stack" | "where" => DebugCommand::Stack,
+            "locals" | "vars" => DebugCommand::Locals,
+            "p" | "print" if !rest.is_empty() => DebugCommand::Print(rest.to_string()),
+            "set" => {
+                let (variable, value) = rest
+                    .split_once(char::is_whitespace)
+                    .filter(|(variable, _)| variable.starts_with('~'))
+                    .ok_or("Usage: set ~variable expression")?;
+                DebugCommand::Set {
+                    variable: variable.trim_start_matches('~').to_string(),
+                    value: value.trim().to_string(),
+                }
+            }
+            "e" | "eval" if !rest.is_empty() => DebugCommand::Eval(rest.to_string()),
+            "l" | "list" => DebugCommand::List,
+            "h" | "help" | "?" => DebugCommand::Help,
+            "q" | "quit" | "exit" => DebugCommand::Quit,
+            "p" | "print" | "e" | "eval" => {
+                return Err(format!("Usage: {} expression", name));
+            }
+            _ => return Err(format!("Unknown command '{}'. Type 'help' for a list.", name)),
+        };
+        Ok(command)
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  c, continue        Run until the next breakpoint
+  s, step            Step into the next statement
+  n, next            Step over function calls
+  o, out             Run until the current function returns
+  b, break <line|fn> Set a breakpoint on a line or function
+  d, delete <line|fn> Remove a breakpoint
+  breakpoints        List breakpoints
+  bt, stack          Show the call stack
+  locals             Show variables in the current scope
+  p, print <expr>    Evaluate an expression and print the result
+  set ~var <expr>    Change a variable
+  e, eval <code>     Run code at the paused point
+  l, list            Show the source around the current line
+  q, quit            Stop the program";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepMode {
+    Continue,
+    Into,
+    Over(usize),
+    Out(usize),
+}
+
+/// The `tilde debug` hook: pauses and reads commands from an input stream
+pub struct Debugger {
+    source: Vec<String>,
+    breakpoints: BTreeSet<Breakpoint>,
+    mode: StepMode,
+    last: Option<Location>,
+    input: Arc<dyn InputStream>,
+    output: Arc<dyn OutputStream>,
+}
+
+impl Debugger {
+    /// A debugger for `source` that pauses before the first statement
+    pub fn new(source: &str, input: Arc<dyn InputStream>, output: Arc<dyn OutputStream>) -> Self {
+        Debugger {
+            source: source.lines().map(str::to_string).collect(),
+            breakpoints: BTreeSet::new(),
+            mode: StepMode::Into,
+            last: None,
+            input,
+            output,
+        }
+    }
+
+    /// Start running without pausing until a breakpoint
+    pub fn run_to_breakpoint(mut self) -> Self {
+        self.mode = StepMode::Continue;
+        self
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    /// Whether to pause at `location`
+    fn should_pause(&self, evaluator: &Evaluator, location: Location) -> bool {
+        let stepped = match self.mode {
+            StepMode::Continue => false,
+            StepMode::Into => true,
+            StepMode::Over(depth) => location.depth <= depth,
+            StepMode::Out(depth) => location.depth < depth,
+        };
+        if stepped || self.breakpoints.contains(&Breakpoint::Line(location.line)) {
+            return true;
+        }
+
+        let entered_function = location.depth > self.last.map_or(0, |last| last.depth);
+        entered_function
+            && evaluator.call_stack().first().is_some_and(|frame| {
+                self.breakpoints
+                    .contains(&Breakpoint::Function(frame.function.clone()))
+            })
+    }
+
+    fn write(&self, text: &str) {
+        let _ = self.output.write_line(text);
+    }
+
+    fn source_line(&self, line: usize) -> &str {
+        self.source.get(line - 1).map_or("", |text| text.trim())
+    }
+
+    /// Carry out one command. Returns whether execution should resume.
+    /// `quit` returns an error that stops the program.
+    pub fn execute(&mut self, command: DebugCommand, evaluator: &mut Evaluator) -> Result<bool, String> {
+        let location = self.last.unwrap_or(Location { line: 1, depth: 0 });
+        match command {
+            DebugCommand::Continue => {
+                self.mode = StepMode::Continue;
+                return Ok(true);
+            }
+            DebugCommand::StepInto => {
+                self.mode = StepMode::Into;
+                return Ok(true);
+            }
+            DebugCommand::StepOver => {
+                self.mode = StepMode::Over(location.depth);
+                return Ok(true);
+            }
+            DebugCommand::StepOut => {
+                self.mode = StepMode::Out(location.depth);
+                return Ok(true);
+            }
+            DebugCommand::Break(breakpoint) => {
+                self.write(&format!("Breakpoint set at {}", breakpoint));
+                self.breakpoints.insert(breakpoint);
+            }
+            DebugCommand::Delete(breakpoint) => {
+                if self.breakpoints.remove(&breakpoint) {
+                    self.write(&format!("Removed breakpoint at {}", breakpoint));
+                } else {
+                    self.write(&format!("No breakpoint at {}", breakpoint));
+                }
+            }
+            DebugCommand::Breakpoints => {
+                if self.breakpoints.is_empty() {
+                    self.write("No breakpoints");
+                }
+                for breakpoint in &self.breakpoints {
+                    self.write(&format!("  {}", breakpoint));
+                }
+            }
+            DebugCommand::Stack => {
+                for (index, frame) in evaluator.call_stack().iter().enumerate() {
+                    match frame.line {
+                        Some(line) => self.write(&format!("#{} {} at line {}", index, frame.function, line)),
+                        None => self.write(&format!("#{} {}", index, frame.function)),
+                    }
+                }
+            }
+            DebugCommand::Locals => {
+                let variables = evaluator.visible_variables();
+                if variables.is_empty() {
+                    self.write("No variables");
+                }
+                for (name, value) in variables {
+                    self.write(&format!("~{} = {}", name, display_value(&value)));
+                }
+            }
+            DebugCommand::Print(expression) => match evaluate(evaluator, &expression) {
+                Ok(value) => self.write(&display_value(&value)),
+                Err(e) => self.write(&format!("Error: {}", e)),
+            },
+            DebugCommand::Set { variable, value } => {
+                let code = format!("~{} is {}", variable, value);
+                match evaluate(evaluator, &code) {
+                    Ok(value) => self.write(&format!("~{} = {}", variable, display_value(&value))),
+                    Err(e) => self.write(&format!("Error: {}", e)),
+                }
+            }
+            DebugCommand::Eval(code) => match evaluate(evaluator, &code) {
+                Ok(Value::Null) => {}
+                Ok(value) => self.write(&display_value(&value)),
+                Err(e) => self.write(&format!("Error: {}", e)),
+            },
+            DebugCommand::List => {
+                let first = location.line.saturating_sub(2).max(1);
+                let last = (location.line + 2).min(self.source.len());
+                for line in first..=last {
+                    let marker = if line == location.line { ">" } else { " " };
+                    self.write(&format!("{} {:>4} | {}", marker, line, self.source[line - 1]));
+                }
+            }
+            DebugCommand::Help => self.write(HELP),
+            DebugCommand::Quit => return Err("Debugging stopped".to_string()),
+        }
+        Ok(false)
+    }
+
+    /// Read and run commands until one resumes execution
+    fn pause(&mut self, evaluator: &mut Evaluator, location: Location) -> Result<(), String> {
+        let function = evaluator
+            .call_stack()
+            .first()
+            .map(|frame| frame.function.clone())
+            .unwrap_or_default();
+        self.write(&format!(
+            "Paused at line {} in {}: {}",
+            location.line,
+            function,
+            self.source_line(location.line)
+        ));
+
+        loop {
+            let _ = self.output.write("(debug) ");
+            let Some(line) = self.input.read_line("(debug) ")? else {
+                // Out of commands: let the program finish
+                self.mode = StepMode::Continue;
+                self.breakpoints.clear();
+                return Ok(());
+            };
+            if !self.input.echoes() {
+                self.write(&line);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match DebugCommand::parse(&line) {
+                Ok(command) => {
+                    if self.execute(command, evaluator)? {
+                        return Ok(());
+                    }
+                }
+                Err(e) => self.write(&e),
+            }
+        }
+    }
+}
+
+impl DebugHook for Debugger {
+    fn before_statement(&mut self, evaluator: &mut Evaluator, location: Location) -> Result<(), String> {
+        let pause = self.should_pause(evaluator, location);
+        self.last = Some(location);
+        if pause {
+            self.pause(evaluator, location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run code at the paused point, in the current scope
+fn evaluate(evaluator: &mut Evaluator, code: &str) -> Result<Value, String> {
+    let program = Parser::new(code).parse()?;
+    evaluator.eval_in_place(program)
+}
+
+/// Quote strings so `"1"` and `1` look different
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(DebugCommand::parse("n").unwrap(), DebugCommand::StepOver);
+        assert_eq!(
+            DebugCommand::parse("break 12").unwrap(),
+            DebugCommand::Break(Breakpoint::Line(12))
+        );
+        assert_eq!(
+            DebugCommand::parse("b *double").unwrap(),
+            DebugCommand::Break(Breakpoint::Function("double".to_string()))
+        );
+        assert_eq!(
+            DebugCommand::parse("set ~x ~y + 1").unwrap(),
+            DebugCommand::Set {
+                variable: "x".to_string(),
+                value: "~y + 1".to_string()
+            }
+        );
+        assert_eq!(
+            DebugCommand::parse("p ~items.0").unwrap(),
+            DebugCommand::Print("~items.0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(DebugCommand::parse("break").is_err());
+        assert!(DebugCommand::parse("break 0").is_err());
+        assert!(DebugCommand::parse("set x 1").is_err());
+        assert!(DebugCommand::parse("print").is_err());
+        assert!(DebugCommand::parse("jump 3").is_err());
+    }
+}
@@ -0,0 +1,483 @@
+//! Reference documentation: `tilde doc`.
+//!
+//! A `##` comment directly above a `function` is its doc comment, written in
+//! Markdown. `tilde doc` turns the functions in a library into a reference
+//! page in Markdown or HTML, and `tilde doc --stdlib` does the same for the
+//! stdlib from `stdlib::metadata`. Fenced code blocks in doc comments are
+//! examples, and `tilde doc --test` runs each one as a test, after the rest
+//! of the file, so examples can call the functions they document. Blocks
+//! marked with a language other than `tilde`, like ```` ```text ````, are
+//! shown but not run.
+
+use crate::ast::Statement;
+use crate::evaluator::Evaluator;
+use crate::io::MemoryOutput;
+use crate::parser::Parser;
+use crate::stdlib::metadata::{self, CATEGORIES};
+use crate::testing::TestResult;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// What `tilde doc` writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocFormat {
+    #[default]
+    Markdown,
+    /// A standalone HTML page
+    Html,
+}
+
+impl DocFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "markdown" | "md" => Some(DocFormat::Markdown),
+            "html" => Some(DocFormat::Html),
+            _ => None,
+        }
+    }
+
+    /// The file extension for pages in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+}
+
+/// A reference page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub title: String,
+    /// Markdown shown before the first section
+    pub intro: Option<String>,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub items: Vec<Item>,
+}
+
+/// One documented function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub name: String,
+    /// How a call looks, e.g. `*greet ~name` or `slice list start [end]`
+    pub signature: String,
+    /// Markdown, empty if the function isn't documented
+    pub description: String,
+    /// An example shown after the description
+    pub example: Option<String>,
+}
+
+/// The reference page for a library: its top-level functions, in the order
+/// they're defined
+pub fn library_page(source: &str, title: &str) -> Result<Page, String> {
+    let program = Parser::new(source).parse()?;
+    let items = program
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::FunctionDefinition {
+                name, params, doc, ..
+            } => Some(Item {
+                name: name.clone(),
+                signature: std::iter::once(format!("*{}", name))
+                    .chain(params.iter().map(|param| format!("~{}", param)))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                description: doc.clone().unwrap_or_default(),
+                example: None,
+            }),
+            _ => None,
+        })
+        .collect();
+    Ok(Page {
+        title: title.to_string(),
+        intro: None,
+        sections: vec![Section {
+            title: "Functions".to_string(),
+            items,
+        }],
+    })
+}
+
+/// The stdlib reference, from the function table in `stdlib::metadata`
+pub fn stdlib_page() -> Page {
+    let sections = CATEGORIES
+        .iter()
+        .map(|&category| Section {
+            title: category.to_string(),
+            items: metadata::stdlib_functions()
+                .iter()
+                .filter(|info| info.category == category)
+                .map(|info| Item {
+                    name: info.name.to_string(),
+                    signature: info.signature(),
+                    description: info.description.to_string(),
+                    example: Some(info.example.to_string()),
+                })
+                .collect(),
+        })
+        .collect();
+    Page {
+        title: "Tilde Standard Library Reference".to_string(),
+        intro: Some(
+            "Generated by `tilde doc --stdlib` from `src/stdlib/metadata.rs`; don't edit it by hand. \
+             In signatures, `[name]` is optional and `name...` takes any number of values."
+                .to_string(),
+        ),
+        sections,
+    }
+}
+
+impl Page {
+    pub fn render(&self, format: DocFormat) -> String {
+        match format {
+            DocFormat::Markdown => self.to_markdown(),
+            DocFormat::Html => self.to_html(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n", self.title);
+        if let Some(intro) = &self.intro {
+            out.push_str(&format!("\n{}\n", intro));
+        }
+        if self.sections.len() > 1 {
+            out.push('\n');
+            for section in &self.sections {
+                out.push_str(&format!(
+                    "- [{}](#{})\n",
+                    section.title,
+                    anchor(&section.title)
+                ));
+            }
+        }
+        for section in &self.sections {
+            out.push_str(&format!("\n## {}\n", section.title));
+            for item in &section.items {
+                out.push_str(&format!("\n### {}\n\n`{}`\n", item.name, item.signature));
+                if !item.description.is_empty() {
+                    out.push_str(&format!("\n{}\n", item.description));
+                }
+                if let Some(example) = &item.example {
+                    out.push_str(&format!("\n```tilde\n{}\n```\n", example));
+                }
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut body = format!("<h1>{}</h1>\n", escape(&self.title));
+        if let Some(intro) = &self.intro {
+            body.push_str(&markdown_to_html(intro));
+        }
+        if self.sections.len() > 1 {
+            body.push_str("<nav>\n<ul>\n");
+            for section in &self.sections {
+                body.push_str(&format!(
+                    "<li><a href=\"#{}\">{}</a></li>\n",
+                    anchor(&section.title),
+                    escape(&section.title)
+                ));
+            }
+            body.push_str("</ul>\n</nav>\n");
+        }
+        for section in &self.sections {
+            body.push_str(&format!(
+                "<h2 id=\"{}\">{}</h2>\n",
+                anchor(&section.title),
+                escape(&section.title)
+            ));
+            for item in &section.items {
+                body.push_str(&format!(
+                    "<h3 id=\"{}\">{}</h3>\n<p><code>{}</code></p>\n",
+                    anchor(&item.name),
+                    escape(&item.name),
+                    escape(&item.signature)
+                ));
+                body.push_str(&markdown_to_html(&item.description));
+                if let Some(example) = &item.example {
+                    body.push_str(&code_block_html(example, "tilde"));
+                }
+            }
+        }
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape(&self.title),
+            STYLE,
+            body
+        )
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:48em;margin:2em auto;padding:0 1em;line-height:1.5}\
+code,pre{font-family:monospace;background:#f4f4f4}pre{padding:.5em 1em;overflow-x:auto}h3{margin-bottom:0}";
+
+/// Run the examples in a library's doc comments as tests. Each runs in a
+/// fresh evaluator after the file's top-level code. Fails only if the file or
+/// an example doesn't parse.
+pub fn run_doctests(source: &str, file: &str) -> Result<Vec<TestResult>, String> {
+    let program = Parser::new(source).parse()?;
+    let prelude: Vec<Statement> = program
+        .iter()
+        .filter(|statement| {
+            !matches!(
+                statement,
+                Statement::Test { .. } | Statement::Setup { .. } | Statement::Teardown { .. }
+            )
+        })
+        .cloned()
+        .collect();
+
+    let mut results = Vec::new();
+    for statement in &program {
+        let Statement::FunctionDefinition {
+            name,
+            doc: Some(doc),
+            ..
+        } = statement
+        else {
+            continue;
+        };
+        let examples: Vec<String> = code_blocks(doc)
+            .into_iter()
+            .filter(|(info, _)| runs(info))
+            .map(|(_, code)| code)
+            .collect();
+        for (i, example) in examples.iter().enumerate() {
+            let test_name = if examples.len() == 1 {
+                format!("*{} example", name)
+            } else {
+                format!("*{} example {}", name, i + 1)
+            };
+            let example = Parser::new(example)
+                .parse()
+                .map_err(|e| format!("{}: {}", test_name, e))?;
+
+            let output = Arc::new(MemoryOutput::new());
+            let mut evaluator = Evaluator::new();
+            evaluator.set_output(output.clone());
+            let start = Instant::now();
+            let result = evaluator
+                .eval_program(prelude.clone())
+                .and_then(|_| evaluator.eval_program(example));
+            results.push(TestResult {
+                file: file.to_string(),
+                name: test_name,
+                failure: result.err(),
+                output: output.contents(),
+                duration: start.elapsed(),
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Whether a fenced block with this info string is Tilde code to run
+fn runs(info: &str) -> bool {
+    matches!(info, "" | "tilde")
+}
+
+/// The fenced code blocks in Markdown, as (info string, code) pairs
+fn code_blocks(markdown: &str) -> Vec<(String, String)> {
+    let mut blocks = Vec::new();
+    let mut lines = markdown.lines();
+    while let Some(line) = lines.next() {
+        if let Some(info) = line.trim().strip_prefix("```") {
+            let code: Vec<&str> = lines
+                .by_ref()
+                .take_while(|line| line.trim() != "```")
+                .collect();
+            blocks.push((info.trim().to_string(), code.join("\n")));
+        }
+    }
+    blocks
+}
+
+/// A heading's id, as GitHub makes them
+fn anchor(title: &str) -> String {
+    title
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn code_block_html(code: &str, language: &str) -> String {
+    format!(
+        "<pre><code class=\"language-{}\">{}</code></pre>\n",
+        escape(language),
+        escape(code)
+    )
+}
+
+/// The Markdown that doc comments use: paragraphs, `- ` lists, fenced code
+/// blocks and `inline code`
+fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut in_list = false;
+    let mut lines = markdown.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let item = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "));
+        if trimmed.starts_with("```") || trimmed.is_empty() || item.is_some() {
+            end_paragraph(&mut html, &mut paragraph);
+        }
+        if in_list && item.is_none() {
+            html.push_str("</ul>\n");
+            in_list = false;
+        }
+
+        if let Some(info) = trimmed.strip_prefix("```") {
+            let code: Vec<&str> = lines
+                .by_ref()
+                .take_while(|line| line.trim() != "```")
+                .collect();
+            let language = if runs(info.trim()) {
+                "tilde"
+            } else {
+                info.trim()
+            };
+            html.push_str(&code_block_html(&code.join("\n"), language));
+        } else if let Some(item) = item {
+            if !in_list {
+                html.push_str("<ul>\n");
+                in_list = true;
+            }
+            html.push_str(&format!("<li>{}</li>\n", inline_html(item)));
+        } else if !trimmed.is_empty() {
+            paragraph.push(trimmed);
+        }
+    }
+    end_paragraph(&mut html, &mut paragraph);
+    if in_list {
+        html.push_str("</ul>\n");
+    }
+    html
+}
+
+fn end_paragraph(html: &mut String, paragraph: &mut Vec<&str>) {
+    if !paragraph.is_empty() {
+        html.push_str(&format!("<p>{}</p>\n", inline_html(&paragraph.join(" "))));
+        paragraph.clear();
+    }
+}
+
+/// Escape text, turning `backticks` into code
+fn inline_html(text: &str) -> String {
+    text.split('`')
+        .enumerate()
+        .map(|(i, part)| {
+            if i % 2 == 1 {
+                format!("<code>{}</code>", escape(part))
+            } else {
+                escape(part)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY: &str = r#"# Helpers for greetings
+
+## Greets someone by name.
+##
+## ```
+## expect (*greet "Ada") == "Hello, Ada"
+## ```
+function greet ~name (
+    give "Hello, " + ~name
+)
+
+# Not documented
+function shout ~text ~times (
+    give uppercase ~text
+)
+"#;
+
+    #[test]
+    fn test_library_page() {
+        let page = library_page(LIBRARY, "greetings").unwrap();
+        let items = &page.sections[0].items;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].signature, "*greet ~name");
+        assert!(
+            items[0]
+                .description
+                .starts_with("Greets someone by name.\n\n```")
+        );
+        assert_eq!(items[1].signature, "*shout ~text ~times");
+        assert_eq!(items[1].description, "");
+
+        let markdown = page.to_markdown();
+        assert!(
+            markdown.starts_with("# greetings\n\n## Functions\n\n### greet\n\n`*greet ~name`\n")
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        let html =
+            markdown_to_html("Joins `a <b>` and\nmore.\n\n- one\n- two\n\n```\n~x is 1 < 2\n```");
+        assert_eq!(
+            html,
+            "<p>Joins <code>a &lt;b&gt;</code> and more.</p>\n<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n\
+             <pre><code class=\"language-tilde\">~x is 1 &lt; 2</code></pre>\n"
+        );
+        let page = stdlib_page().to_html();
+        assert!(page.contains("<h2 id=\"json-encoding-and-hashing\">"));
+        assert!(
+            page.contains(
+                "<h3 id=\"slice\">slice</h3>\n<p><code>slice list start [end]</code></p>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_doctests() {
+        let results = run_doctests(LIBRARY, "greetings.tde").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "*greet example");
+        assert!(results[0].passed(), "{:?}", results[0].failure);
+
+        let failing = LIBRARY.replace("Hello, Ada", "Hi, Ada");
+        let results = run_doctests(&failing, "greetings.tde").unwrap();
+        assert!(
+            results[0]
+                .failure
+                .as_ref()
+                .unwrap()
+                .contains("Expectation failed")
+        );
+    }
+
+    #[test]
+    fn test_doc_comments_must_be_directly_above() {
+        let source = "## Not about f\n~x is 1\n\n## About f\n# but interrupted\nfunction f (\n    give 1\n)\n## About g\n\nfunction g (\n    give 2\n)\n";
+        let page = library_page(source, "t").unwrap();
+        assert_eq!(page.sections[0].items[0].description, "");
+        assert_eq!(page.sections[0].items[1].description, "About g");
+    }
+}
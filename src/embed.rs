@@ -0,0 +1,589 @@
+//! Embedding API for Rust hosts.
+//!
+//! Hosts register native functions with `Evaluator::register_fn` or group them
+//! under a block with `Evaluator::namespace`:
+//!
+//! ```
+//! use tilde::evaluator::Evaluator;
+//!
+//! let mut evaluator = Evaluator::new();
+//! evaluator.namespace("app").unwrap().register_fn("add", |args| {
+//!     let a: f64 = args.get(0)?;
+//!     let b: f64 = args.get(1)?;
+//!     Ok(a + b)
+//! });
+//!
+//! let program = evaluator.parser("~sum is app:add 2 3").parse().unwrap();
+//! evaluator.eval_program(program).unwrap();
+//! ```
+//!
+//! Arguments and results convert through `FromValue` and `IntoValue`. Rust
+//! values that scripts should hold but not look inside are wrapped in a
+//! `HostHandle`, and serde types cross the boundary with `Serde<T>`,
+//! `to_value` and `from_value`.
+
+use crate::value::Value;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_HANDLE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A host-owned Rust value that scripts can store and pass back, but not inspect
+#[derive(Clone)]
+pub struct HostHandle {
+    id: u64,
+    type_name: &'static str,
+    inner: Arc<dyn Any + Send + Sync>,
+}
+
+impl HostHandle {
+    pub fn new<T: Any + Send + Sync>(value: T) -> Self {
+        HostHandle {
+            id: NEXT_HANDLE_ID.fetch_add(1, Ordering::Relaxed),
+            type_name: std::any::type_name::<T>(),
+            inner: Arc::new(value),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Short name of the wrapped Rust type, e.g. `Connection`
+    pub fn type_name(&self) -> &'static str {
+        let path = self.type_name.split('<').next().unwrap_or(self.type_name);
+        path.rsplit("::").next().unwrap_or(path)
+    }
+
+    pub fn is<T: Any + Send + Sync>(&self) -> bool {
+        self.inner.is::<T>()
+    }
+
+    /// Get the wrapped value back if it has type `T`
+    pub fn downcast<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.inner.clone().downcast::<T>().ok()
+    }
+}
+
+impl PartialEq for HostHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl fmt::Debug for HostHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostHandle")
+            .field("id", &self.id)
+            .field("type", &self.type_name)
+            .finish()
+    }
+}
+
+/// Name of a value's type as scripts see it, used in conversion errors
+pub fn type_name_of(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Boolean(_) => "boolean",
+        Value::List(_) => "list",
+        Value::Object(_) => "object",
+        Value::Date(_) => "date",
+        Value::Error(_) => "error",
+        Value::Pattern(_) => "pattern",
+        Value::Task(_) => "task",
+        Value::Channel(_) => "channel",
+        Value::Handle(_) => "handle",
+        Value::Null => "null",
+    }
+}
+
+fn mismatch(expected: &str, value: &Value) -> String {
+    format!("expected {}, got {}", expected, type_name_of(value))
+}
+
+/// Convert a script value into a Rust type
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, String>;
+}
+
+/// Convert a Rust type into a script value
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            _ => Err(mismatch("number", value)),
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        f64::from_value(value).map(|n| n as f32)
+    }
+}
+
+macro_rules! integer_conversions {
+    ($($int:ty),*) => {
+        $(
+            impl FromValue for $int {
+                fn from_value(value: &Value) -> Result<Self, String> {
+                    match value {
+                        Value::Number(n)
+                            if n.fract() == 0.0
+                                && *n >= <$int>::MIN as f64
+                                && *n <= <$int>::MAX as f64 =>
+                        {
+                            Ok(*n as $int)
+                        }
+                        Value::Number(n) => Err(format!(
+                            "expected {}, got {}",
+                            stringify!($int),
+                            n
+                        )),
+                        _ => Err(mismatch("whole number", value)),
+                    }
+                }
+            }
+
+            impl IntoValue for $int {
+                fn into_value(self) -> Value {
+                    Value::Number(self as f64)
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i32, i64, u32, u64, usize);
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl IntoValue for f32 {
+    fn into_value(self) -> Value {
+        Value::Number(self as f64)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Boolean(b) => Ok(*b),
+            _ => Err(mismatch("boolean", value)),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(mismatch("string", value)),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+}
+
+impl FromValue for DateTime<Utc> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Date(date) => Ok(*date),
+            _ => Err(mismatch("date", value)),
+        }
+    }
+}
+
+impl IntoValue for DateTime<Utc> {
+    fn into_value(self) -> Value {
+        Value::Date(self)
+    }
+}
+
+impl FromValue for HostHandle {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Handle(handle) => Ok(handle.clone()),
+            _ => Err(mismatch("handle", value)),
+        }
+    }
+}
+
+impl IntoValue for HostHandle {
+    fn into_value(self) -> Value {
+        Value::Handle(self)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Null => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Null, IntoValue::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::List(items) => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    T::from_value(item).map_err(|e| format!("item {}: {}", index, e))
+                })
+                .collect(),
+            _ => Err(mismatch("list", value)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(key, item)| {
+                    T::from_value(item)
+                        .map(|converted| (key.clone(), converted))
+                        .map_err(|e| format!("key '{}': {}", key, e))
+                })
+                .collect(),
+            _ => Err(mismatch("object", value)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::Object(
+            self.into_iter()
+                .map(|(key, item)| (key, item.into_value()))
+                .collect(),
+        )
+    }
+}
+
+/// Convert any serde-serializable Rust value into a script value
+pub fn to_value<T: Serialize>(value: &T) -> Result<Value, String> {
+    let json = serde_json::to_value(value).map_err(|e| e.to_string())?;
+    crate::stdlib::json::json_value_to_tilde_value(json)
+}
+
+/// Convert a script value into any serde-deserializable Rust type
+pub fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, String> {
+    let mut json = crate::stdlib::json::tilde_value_to_json_value(value)?;
+    whole_numbers_as_integers(&mut json);
+    serde_json::from_value(json).map_err(|e| e.to_string())
+}
+
+/// Script numbers are all f64; present whole ones as integers so they
+/// deserialize into integer fields
+fn whole_numbers_as_integers(json: &mut serde_json::Value) {
+    match json {
+        serde_json::Value::Number(number) => {
+            if let Some(n) = number.as_f64()
+                && n.fract() == 0.0
+                && n.abs() < i64::MAX as f64
+            {
+                *number = serde_json::Number::from(n as i64);
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(whole_numbers_as_integers),
+        serde_json::Value::Object(map) => map.values_mut().for_each(whole_numbers_as_integers),
+        _ => {}
+    }
+}
+
+/// Wrapper that converts through serde, for use as a typed argument:
+/// `let Serde(user): Serde<User> = args.get(0)?;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Serde<T>(pub T);
+
+impl<T: DeserializeOwned> FromValue for Serde<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        from_value(value).map(Serde)
+    }
+}
+
+/// Evaluated arguments passed to a native function
+#[derive(Debug, Clone)]
+pub struct Args {
+    function: String,
+    values: Vec<Value>,
+}
+
+impl Args {
+    pub fn new(function: impl Into<String>, values: Vec<Value>) -> Self {
+        Args {
+            function: function.into(),
+            values,
+        }
+    }
+
+    /// Name the function was called by, e.g. `app:lookup-user`
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Convert the argument at `index`, failing if it is missing or has the wrong type
+    pub fn get<T: FromValue>(&self, index: usize) -> Result<T, String> {
+        match self.values.get(index) {
+            Some(value) => T::from_value(value).map_err(|e| self.argument_error(index, &e)),
+            None => Err(format!(
+                "{} requires at least {} argument{}",
+                self.function,
+                index + 1,
+                if index == 0 { "" } else { "s" }
+            )),
+        }
+    }
+
+    /// Convert the argument at `index`, or `None` if it was not given or is null
+    pub fn optional<T: FromValue>(&self, index: usize) -> Result<Option<T>, String> {
+        match self.values.get(index) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => T::from_value(value)
+                .map(Some)
+                .map_err(|e| self.argument_error(index, &e)),
+        }
+    }
+
+    /// Get the host value behind a handle argument
+    pub fn handle<T: Any + Send + Sync>(&self, index: usize) -> Result<Arc<T>, String> {
+        let handle: HostHandle = self.get(index)?;
+        handle.downcast::<T>().ok_or_else(|| {
+            self.argument_error(
+                index,
+                &format!(
+                    "expected {} handle, got {} handle",
+                    std::any::type_name::<T>().rsplit("::").next().unwrap_or("host"),
+                    handle.type_name()
+                ),
+            )
+        })
+    }
+
+    /// Fail unless exactly `count` arguments were given
+    pub fn expect_len(&self, count: usize) -> Result<(), String> {
+        if self.values.len() == count {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} requires exactly {} argument{}, got {}",
+                self.function,
+                count,
+                if count == 1 { "" } else { "s" },
+                self.values.len()
+            ))
+        }
+    }
+
+    fn argument_error(&self, index: usize, message: &str) -> String {
+        format!("{} argument {}: {}", self.function, index + 1, message)
+    }
+}
+
+type NativeCallback = dyn Fn(&Args) -> Result<Value, String> + Send + Sync;
+
+/// A host function callable from scripts
+#[derive(Clone)]
+pub struct NativeFunction {
+    callback: Arc<NativeCallback>,
+}
+
+impl NativeFunction {
+    pub fn new<F, R>(callback: F) -> Self
+    where
+        F: Fn(&Args) -> Result<R, String> + Send + Sync + 'static,
+        R: IntoValue,
+    {
+        NativeFunction {
+            callback: Arc::new(move |args| callback(args).map(IntoValue::into_value)),
+        }
+    }
+
+    pub fn call(&self, args: &Args) -> Result<Value, String> {
+        (self.callback)(args)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NativeFunction")
+    }
+}
+
+/// Registers functions under a block name so scripts call them as `name:function`
+pub struct Namespace<'a> {
+    name: String,
+    functions: &'a mut HashMap<String, NativeFunction>,
+}
+
+impl<'a> Namespace<'a> {
+    pub(crate) fn new(name: &str, functions: &'a mut HashMap<String, NativeFunction>) -> Self {
+        Namespace {
+            name: name.to_string(),
+            functions,
+        }
+    }
+
+    pub fn register_fn<F, R>(&mut self, name: &str, callback: F) -> &mut Self
+    where
+        F: Fn(&Args) -> Result<R, String> + Send + Sync + 'static,
+        R: IntoValue,
+    {
+        self.functions.insert(
+            format!("{}:{}", self.name, name),
+            NativeFunction::new(callback),
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct User {
+        name: String,
+        age: u32,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_typed_conversions() {
+        assert_eq!(i64::from_value(&Value::Number(42.0)), Ok(42));
+        assert!(i64::from_value(&Value::Number(4.5)).is_err());
+        assert!(u32::from_value(&Value::Number(-1.0)).is_err());
+        assert_eq!(
+            String::from_value(&Value::Number(1.0)),
+            Err("expected string, got number".to_string())
+        );
+        assert_eq!(Option::<f64>::from_value(&Value::Null), Ok(None));
+        assert_eq!(
+            Vec::<f64>::from_value(&Value::List(vec![Value::Number(1.0)])),
+            Ok(vec![1.0])
+        );
+        assert_eq!(
+            vec!["a", "b"].into_value(),
+            Value::List(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let user = User {
+            name: "Ada".to_string(),
+            age: 36,
+            tags: vec!["admin".to_string()],
+        };
+        let value = to_value(&user).unwrap();
+        assert!(matches!(&value, Value::Object(map) if map.len() == 3));
+        assert_eq!(from_value::<User>(&value).unwrap(), user);
+    }
+
+    #[test]
+    fn test_handle_downcast() {
+        let handle = HostHandle::new(vec![1u8, 2, 3]);
+        assert!(handle.is::<Vec<u8>>());
+        assert_eq!(handle.type_name(), "Vec");
+        assert_eq!(*handle.downcast::<Vec<u8>>().unwrap(), vec![1, 2, 3]);
+        assert!(handle.downcast::<String>().is_none());
+        assert_eq!(handle.clone(), handle);
+        assert_ne!(HostHandle::new(1u8), HostHandle::new(1u8));
+    }
+
+    #[test]
+    fn test_args_errors_name_the_function() {
+        let args = Args::new("app:lookup", vec![Value::String("x".to_string())]);
+        assert_eq!(
+            args.get::<f64>(0),
+            Err("app:lookup argument 1: expected number, got string".to_string())
+        );
+        assert_eq!(
+            args.get::<f64>(1),
+            Err("app:lookup requires at least 2 arguments".to_string())
+        );
+        assert_eq!(args.optional::<f64>(1), Ok(None));
+        assert!(args.expect_len(2).is_err());
+    }
+}
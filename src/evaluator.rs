@@ -1,8 +1,22 @@
 use crate::ast::*;
+use crate::bundle::Bundle;
+use crate::concurrency::{TaskContext, TaskHandle, TaskPool, TaskResult};
+use crate::debugger::{DebugHook, Location, StackFrame};
+use crate::embed::{Args, IntoValue, Namespace, NativeFunction};
 use crate::http::{HttpClient, HttpRequest, parse_http_options};
-use crate::value::Value;
+use crate::io::{InputStream, IoStreams, OutputStream};
+use crate::limits::{self, Limit, ResourceUsage, Stopwatch};
+use crate::package::{self, DiskFiles, ImportFiles};
+use crate::profiler::{CallKind, Profile, Profiler};
+use crate::sandbox::{Capabilities, EvaluatorConfig};
+use crate::testing;
+use crate::trace::{TraceEvent, TraceKind, Tracer, describe_statement};
+use crate::value::{ErrorValue, ObjectMap, Value};
 use crate::music::MusicEngine;
-use std::collections::HashMap;
+use crate::parser::Parser;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug, PartialEq)]
 enum ControlFlow {
@@ -17,19 +31,57 @@ type EvalResult = Result<(Value, ControlFlow), String>;
 pub struct Function {
     pub params: Vec<String>,
     pub body: Vec<Statement>,
+    /// The `##` doc comment above the definition, shown by `help`
+    pub doc: Option<String>,
 }
 
+/// The variable holding a script's command-line arguments
+pub const ARGS_VARIABLE: &str = "args";
+
 // Legacy scheduler code removed - now using modular MusicEngine
 
+/// A user function call, recorded while a debug hook is installed so the
+/// call stack can name the scopes in `scope_stack`
+#[derive(Debug)]
+struct CallFrame {
+    function: String,
+    call_line: Option<usize>,
+    depth: usize,
+}
+
 #[derive(Debug)]
 pub struct Evaluator {
     pub variables: HashMap<String, Value>,
     pub functions: HashMap<String, Function>,
+    pub(crate) native_functions: HashMap<String, NativeFunction>,
+    /// Block names registered with `namespace`, which scripts parsed by
+    /// `parser` lex as blocks
+    pub(crate) host_blocks: HashSet<String>,
     scope_stack: Vec<HashMap<String, Value>>,
-    max_call_depth: usize,
     pub output_buffer: Vec<String>,
+    streams: IoStreams,
     last_error: Option<Value>,
     pub music_engine: Option<MusicEngine>,
+    task_pool: Option<Arc<TaskPool>>,
+    task_limit: usize,
+    config: EvaluatorConfig,
+    usage: ResourceUsage,
+    current_line: Option<usize>,
+    active_lines: Vec<usize>,
+    debug_hook: Option<Box<dyn DebugHook>>,
+    call_frames: Vec<CallFrame>,
+    profiler: Option<Profiler>,
+    tracer: Option<Tracer>,
+    /// Set by `exit`, which stops the run in a way attempt/rescue can't catch
+    exit_code: Option<i32>,
+    /// The script and the modules it's importing, innermost last, so imports
+    /// resolve relative to the file they're in
+    files: Vec<PathBuf>,
+    /// Modules already run by `import`
+    imported: HashSet<PathBuf>,
+    /// The files built into a standalone executable, which `read` and
+    /// `import` see instead of the filesystem
+    bundle: Option<Arc<Bundle>>,
 }
 
 impl Default for Evaluator {
@@ -40,15 +92,461 @@ impl Default for Evaluator {
 
 impl Evaluator {
     pub fn new() -> Self {
+        Self::with_config(EvaluatorConfig::default())
+    }
+
+    /// Create an evaluator with the given config, e.g. a sandbox for untrusted scripts
+    pub fn with_config(config: EvaluatorConfig) -> Self {
         Evaluator {
             variables: HashMap::new(),
             functions: HashMap::new(),
+            native_functions: HashMap::new(),
+            host_blocks: HashSet::new(),
             scope_stack: Vec::new(),
-            max_call_depth: 100,
             output_buffer: Vec::new(),
+            streams: IoStreams::default(),
             last_error: None,
             music_engine: None,
+            task_pool: None,
+            task_limit: TaskPool::default_size(),
+            config,
+            usage: ResourceUsage::new(),
+            current_line: None,
+            active_lines: Vec::new(),
+            debug_hook: None,
+            call_frames: Vec::new(),
+            profiler: None,
+            tracer: None,
+            exit_code: None,
+            files: Vec::new(),
+            imported: HashSet::new(),
+            bundle: None,
+        }
+    }
+
+    /// Register a native Rust function that scripts call with `*name args`
+    ///
+    /// Native functions take precedence over stdlib functions of the same name,
+    /// but not over functions defined in the script.
+    pub fn register_fn<F, R>(&mut self, name: &str, callback: F) -> &mut Self
+    where
+        F: Fn(&Args) -> Result<R, String> + Send + Sync + 'static,
+        R: IntoValue,
+    {
+        self.native_functions
+            .insert(name.to_string(), NativeFunction::new(callback));
+        self
+    }
+
+    /// Register native functions under a block, called as `name:function args`
+    ///
+    /// Parse scripts that use the block with `parser`, since the lexer needs
+    /// to know the block name. Fails if `name` is a built-in block such as
+    /// `core`.
+    pub fn namespace(&mut self, name: &str) -> Result<Namespace<'_>, String> {
+        if crate::lexer::BUILTIN_BLOCKS.contains(&name) {
+            return Err(format!(
+                "cannot register host functions in the built-in '{}' block",
+                name
+            ));
+        }
+        self.host_blocks.insert(name.to_string());
+        Ok(Namespace::new(name, &mut self.native_functions))
+    }
+
+    /// A parser for `source` that knows this evaluator's host blocks
+    pub fn parser(&self, source: &str) -> Parser {
+        Parser::new_with_blocks(source, &self.host_blocks)
+    }
+
+    pub fn has_native_function(&self, name: &str) -> bool {
+        self.native_functions.contains_key(name)
+    }
+
+    fn call_native_function(
+        &mut self,
+        name: &str,
+        function: NativeFunction,
+        args: Vec<Expression>,
+    ) -> Result<Value, String> {
+        self.profile_call(CallKind::Native, name, |this| {
+            let mut values = Vec::with_capacity(args.len());
+            for arg in args {
+                values.push(this.eval_expression(arg)?);
+            }
+            function.call(&Args::new(name, values))
+        })
+    }
+
+    /// Call a native function with already-evaluated arguments, or `None` if
+    /// no native function has that name
+    pub(crate) fn call_native(&self, name: &str, values: Vec<Value>) -> Option<Result<Value, String>> {
+        self.native_functions
+            .get(name)
+            .map(|function| function.call(&Args::new(name, values)))
+    }
+
+    pub fn config(&self) -> &EvaluatorConfig {
+        &self.config
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.config.capabilities
+    }
+
+    /// Give the script its command-line arguments as the `~args` list
+    pub fn set_args(&mut self, args: &[String]) -> &mut Self {
+        let args = args.iter().cloned().map(Value::String).collect();
+        self.variables.insert(ARGS_VARIABLE.to_string(), Value::List(args));
+        self
+    }
+
+    /// Tell the evaluator which file the script is in, so its imports resolve
+    /// relative to it rather than the working directory
+    pub fn set_script_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        let path = path.into();
+        self.imported
+            .insert(path.canonicalize().unwrap_or_else(|_| path.clone()));
+        self.files = vec![path];
+        self
+    }
+
+    /// Give the script the files built into a standalone executable
+    pub fn set_bundle(&mut self, bundle: Arc<Bundle>) -> &mut Self {
+        self.bundle = Some(bundle);
+        self
+    }
+
+    /// The files built into the executable running the script, if any
+    pub fn bundle(&self) -> Option<&Bundle> {
+        self.bundle.as_deref()
+    }
+
+    /// The status code passed to `exit`, if the last run called it
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Stop the run with a status code for `exit`
+    pub(crate) fn exit(&mut self, code: i32) -> Result<Value, String> {
+        self.exit_code = Some(code);
+        Err(format!("exit {}", code))
+    }
+
+    pub fn streams(&self) -> &IoStreams {
+        &self.streams
+    }
+
+    /// Replace all three streams at once, e.g. with ones shared by another evaluator
+    pub fn set_streams(&mut self, streams: IoStreams) -> &mut Self {
+        self.streams = streams;
+        self
+    }
+
+    /// Send `say` and `clear` somewhere other than stdout
+    pub fn set_output(&mut self, output: Arc<dyn OutputStream>) -> &mut Self {
+        self.streams.output = output;
+        self
+    }
+
+    /// Send diagnostics somewhere other than stderr
+    pub fn set_error_output(&mut self, error_output: Arc<dyn OutputStream>) -> &mut Self {
+        self.streams.error_output = error_output;
+        self
+    }
+
+    /// Read `ask` answers from somewhere other than stdin
+    pub fn set_input(&mut self, input: Arc<dyn InputStream>) -> &mut Self {
+        self.streams.input = input;
+        self
+    }
+
+    /// Call `hook` before each statement parsed with `Parser::with_locations`,
+    /// once per line when statements are nested on one line
+    pub fn set_debug_hook(&mut self, hook: Box<dyn DebugHook>) -> &mut Self {
+        self.debug_hook = Some(hook);
+        self
+    }
+
+    pub fn take_debug_hook(&mut self) -> Option<Box<dyn DebugHook>> {
+        self.call_frames.clear();
+        self.debug_hook.take()
+    }
+
+    /// The source line of the statement running now, if the program has locations
+    pub fn current_line(&self) -> Option<usize> {
+        self.current_line
+    }
+
+    fn run_debug_hook(&mut self, line: usize) -> Result<(), String> {
+        // Take the hook out while it runs, so code it evaluates isn't debugged too
+        if let Some(mut hook) = self.debug_hook.take() {
+            let location = Location {
+                line,
+                depth: self.scope_stack.len(),
+            };
+            let result = hook.before_statement(self, location);
+            self.debug_hook = Some(hook);
+            result?;
+        }
+        Ok(())
+    }
+
+    fn push_call_frame(&mut self, function: Option<&str>) {
+        // Frames left behind by calls that failed no longer have a scope
+        let depth = self.scope_stack.len();
+        self.call_frames.retain(|frame| frame.depth < depth);
+        self.call_frames.push(CallFrame {
+            function: function.unwrap_or("<anonymous>").to_string(),
+            call_line: self.current_line,
+            depth,
+        });
+    }
+
+    /// The active calls, innermost first, ending with the top-level script
+    pub fn call_stack(&self) -> Vec<StackFrame> {
+        let mut stack = Vec::with_capacity(self.scope_stack.len() + 1);
+        let mut line = self.current_line;
+        for depth in (1..=self.scope_stack.len()).rev() {
+            let frame = self.call_frames.iter().find(|frame| frame.depth == depth);
+            stack.push(StackFrame {
+                function: frame.map_or("<anonymous>", |frame| &frame.function).to_string(),
+                line,
+            });
+            line = frame.and_then(|frame| frame.call_line);
+        }
+        stack.push(StackFrame {
+            function: "<script>".to_string(),
+            line,
+        });
+        stack
+    }
+
+    /// Variables of the innermost scope, or the globals at the top level, sorted by name
+    pub fn visible_variables(&self) -> Vec<(String, Value)> {
+        let scope = self.scope_stack.last().unwrap_or(&self.variables);
+        let mut variables: Vec<(String, Value)> = scope
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        variables.sort_by(|a, b| a.0.cmp(&b.0));
+        variables
+    }
+
+    /// Run statements in the current scope without starting a new run, e.g.
+    /// from a debugger while the program is paused
+    pub fn eval_in_place(&mut self, program: Program) -> Result<Value, String> {
+        let mut last_value = Value::Null;
+        for statement in program {
+            last_value = self.eval_statement(statement)?;
         }
+        Ok(last_value)
+    }
+
+    /// Time function calls and count created values until `take_profile`
+    pub fn enable_profiling(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    /// Stop profiling and return what was recorded
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profiler.take().map(Profiler::finish)
+    }
+
+    /// Record execution events to `tracer`; spawned tasks share it
+    pub fn set_tracer(&mut self, tracer: Tracer) -> &mut Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// Whether events of this kind are being traced, to skip building them otherwise
+    pub(crate) fn is_tracing(&self, kind: TraceKind) -> bool {
+        self.tracer.as_ref().is_some_and(|tracer| tracer.traces(kind))
+    }
+
+    pub(crate) fn trace(&self, event: TraceEvent) {
+        if let Some(tracer) = &self.tracer {
+            let line = event.line.or(self.current_line);
+            tracer.record(&event.with_line(line));
+        }
+    }
+
+    fn profile_call<F>(&mut self, kind: CallKind, name: &str, call: F) -> Result<Value, String>
+    where
+        F: FnOnce(&mut Self) -> Result<Value, String>,
+    {
+        let Some(profiler) = self.profiler.as_mut() else {
+            return call(self);
+        };
+        profiler.enter(kind, name);
+        let result = call(self);
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.exit();
+        }
+        result
+    }
+
+    /// Run a module for `import` in the current scope, so its functions and
+    /// variables become visible. Each module runs once, however often it's
+    /// imported, which also stops import cycles.
+    fn eval_import(&mut self, args: Vec<Expression>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(
+                "import requires exactly 1 argument: a package name or file path".to_string(),
+            );
+        }
+        let spec = match self.eval_expression(args[0].clone())? {
+            Value::String(spec) => spec,
+            _ => return Err("import argument must be a string".to_string()),
+        };
+        let from = self.files.last().map(PathBuf::as_path);
+        let path = match &self.bundle {
+            Some(bundle) => package::resolve_import_in(&spec, from, bundle.as_ref())?,
+            None => package::resolve_import(&spec, from)?,
+        };
+        let path_check = self
+            .capabilities()
+            .check_path("import", &path.to_string_lossy());
+        self.permit(path_check)?;
+        if !self
+            .imported
+            .insert(path.canonicalize().unwrap_or_else(|_| path.clone()))
+        {
+            return Ok(Value::Null);
+        }
+
+        let source = match &self.bundle {
+            Some(bundle) => bundle.read(&path)?,
+            None => DiskFiles.read(&path)?,
+        };
+        let program = self
+            .parser(&source)
+            .parse()
+            .map_err(|e| format!("Parse error in {}: {}", path.display(), e))?;
+        self.files.push(path);
+        let result = program
+            .into_iter()
+            .try_for_each(|statement| self.eval_statement(statement).map(|_| ()));
+        self.files.pop();
+        result.map(|_| Value::Null)
+    }
+
+    /// Write the prompt, read a line and parse it as a number if possible
+    fn eval_ask(&mut self, args: Vec<Expression>) -> Result<Value, String> {
+        let mut prompt_values = Vec::new();
+        for arg in args {
+            prompt_values.push(self.eval_expression(arg)?);
+        }
+        let prompt = prompt_values
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        if !prompt.is_empty() {
+            self.streams.output.write(&prompt)?;
+        }
+        let input = self.streams.input.read_line(&prompt)?.unwrap_or_default();
+        if !self.streams.input.echoes() {
+            self.streams.output.write_line(&input)?;
+        }
+
+        let input = input.trim().to_string();
+
+        // Try to parse as number first, fall back to string
+        if let Ok(num) = input.parse::<f64>() {
+            Ok(Value::Number(num))
+        } else {
+            Ok(Value::String(input))
+        }
+    }
+
+    /// Raise a `limit_exceeded` error. Unless limits are catchable, the error is
+    /// marked fatal so attempt/rescue can't swallow it.
+    fn exceed_limit(&mut self, limit: Limit, max: f64) -> Result<(), String> {
+        let error = limits::limit_exceeded(limit, max);
+        let message = error.message.clone();
+        if self.config.limits.catchable {
+            self.usage.renew(limit);
+        } else {
+            self.usage.shared.set_fatal();
+        }
+        self.last_error = Some(Value::Error(error));
+        Err(message)
+    }
+
+    /// Count one step of execution against the step and time limits
+    fn check_step(&mut self) -> Result<(), String> {
+        let steps = self.usage.shared.add_step();
+        if let Some(max_steps) = self.config.limits.max_steps
+            && steps > max_steps
+        {
+            return self.exceed_limit(Limit::Steps, max_steps as f64);
+        }
+        if let Some(timeout) = self.config.limits.timeout
+            && self.usage.shared.elapsed() > timeout
+        {
+            return self.exceed_limit(Limit::Time, timeout.as_secs_f64());
+        }
+        Ok(())
+    }
+
+    /// Check an assignment against the memory limit. Global variables count
+    /// towards the running total; a local value only has to fit on its own.
+    fn check_assignment(&mut self, variable: &str, value: &Value) -> Result<(), String> {
+        if self.config.limits.max_memory.is_none() {
+            return Ok(());
+        }
+        self.check_assignment_size(variable, limits::approximate_size(value))
+    }
+
+    /// Like `check_assignment`, for a value of `size` bytes that hasn't been
+    /// built yet, such as a list about to be filled out to a far-off index
+    fn check_assignment_size(&mut self, variable: &str, size: usize) -> Result<(), String> {
+        let Some(max_memory) = self.config.limits.max_memory else {
+            return Ok(());
+        };
+
+        let projected = if self.scope_stack.is_empty() {
+            let replaced = self
+                .variables
+                .get(variable)
+                .map_or(0, limits::approximate_size);
+            self.usage.memory_bytes.saturating_sub(replaced) + size
+        } else {
+            self.usage.memory_bytes + size
+        };
+
+        if projected > max_memory {
+            return self.exceed_limit(Limit::Memory, max_memory as f64);
+        }
+        if self.scope_stack.is_empty() {
+            self.usage.memory_bytes = projected;
+        }
+        Ok(())
+    }
+
+    /// Count bytes written by `say` against the output limit
+    fn check_output(&mut self, message: &str) -> Result<(), String> {
+        let max_output = self.config.limits.max_output;
+        if !self.usage.shared.add_output(message.len() + 1, max_output) {
+            let max_output = max_output.unwrap_or_default();
+            return self.exceed_limit(Limit::Output, max_output as f64);
+        }
+        Ok(())
+    }
+
+    /// Turn a failed capability check into a rescuable `permission_denied` error
+    pub(crate) fn permit(&mut self, check: Result<(), ErrorValue>) -> Result<(), String> {
+        check.map_err(|error| {
+            let message = error.message.clone();
+            self.last_error = Some(Value::Error(error));
+            message
+        })
     }
 
     pub fn is_truthy(&self, value: &Value) -> bool {
@@ -61,6 +559,9 @@ impl Evaluator {
             Value::Date(_) => true,   // Dates are always truthy
             Value::Error(_) => false, // Errors are falsy
             Value::Pattern(p) => !p.events().is_empty(), // Patterns with events are truthy
+            Value::Task(_) => true,
+            Value::Channel(_) => true,
+            Value::Handle(_) => true,
             Value::Null => false,
         }
     }
@@ -112,6 +613,8 @@ impl Evaluator {
     }
 
     pub fn eval_program(&mut self, program: Program) -> Result<Value, String> {
+        self.usage.start_run();
+        self.exit_code = None;
         let mut last_value = Value::Null;
 
         for statement in program {
@@ -127,9 +630,14 @@ impl Evaluator {
     }
 
     fn eval_statement_with_control(&mut self, stmt: Statement) -> EvalResult {
+        // A location tag wraps the statement that counts as the step
+        if !matches!(stmt, Statement::Located { .. }) {
+            self.check_step()?;
+        }
         match stmt {
             Statement::Assignment { variable, value } => {
                 let val = self.eval_expression(value)?;
+                self.check_assignment(&variable, &val)?;
                 // Check if we're in a local scope
                 if let Some(scope) = self.scope_stack.last_mut() {
                     scope.insert(variable, val.clone());
@@ -153,12 +661,17 @@ impl Evaluator {
                         match &mut obj_value {
                             Value::Object(map) => {
                                 map.insert(property.clone(), val.clone());
+                                self.check_assignment(var_name, &obj_value)?;
                                 self.update_variable(var_name.clone(), obj_value)?;
                                 Ok((val, ControlFlow::Continue))
                             }
                             Value::List(list) => {
                                 // Try to parse property as numeric index
                                 if let Ok(index) = property.parse::<usize>() {
+                                    // Check before filling, so a far-off index
+                                    // can't allocate past the memory limit
+                                    let size = limits::assigned_list_size(list, index, &val);
+                                    self.check_assignment_size(var_name, size)?;
                                     // Expand list if necessary (fill with nulls)
                                     while list.len() <= index {
                                         list.push(Value::Null);
@@ -182,6 +695,8 @@ impl Evaluator {
                         // Create a new object or list if variable doesn't exist
                         if let Ok(index) = property.parse::<usize>() {
                             // Creating a new list
+                            let size = limits::assigned_list_size(&[], index, &val);
+                            self.check_assignment_size(var_name, size)?;
                             let mut list = Vec::new();
                             // Expand list if necessary (fill with nulls)
                             while list.len() <= index {
@@ -197,13 +712,15 @@ impl Evaluator {
                             Ok((val, ControlFlow::Continue))
                         } else {
                             // Creating a new object
-                            let mut map = std::collections::HashMap::new();
+                            let mut map = ObjectMap::new();
                             map.insert(property.clone(), val.clone());
+                            let object = Value::Object(map);
+                            self.check_assignment(var_name, &object)?;
                             // Use scope-aware assignment for new variables
                             if let Some(scope) = self.scope_stack.last_mut() {
-                                scope.insert(var_name.clone(), Value::Object(map));
+                                scope.insert(var_name.clone(), object);
                             } else {
-                                self.variables.insert(var_name.clone(), Value::Object(map));
+                                self.variables.insert(var_name.clone(), object);
                             }
                             Ok((val, ControlFlow::Continue))
                         }
@@ -243,6 +760,8 @@ impl Evaluator {
                 }
             }
             Statement::Loop { body } => loop {
+                // Count each iteration so an empty loop still hits the step limit
+                self.check_step()?;
                 for stmt in &body {
                     let (_, control) = self.eval_statement_with_control(stmt.clone())?;
                     if control == ControlFlow::BreakLoop {
@@ -412,17 +931,28 @@ impl Evaluator {
                 Ok((last_value, ControlFlow::Continue))
             }
             Statement::Breakloop => Ok((Value::Null, ControlFlow::BreakLoop)),
+            Statement::Trivia(_) => Ok((Value::Null, ControlFlow::Continue)),
+            Statement::Error(message) => Err(message),
+            // Only `tilde test` runs tests, with their setup and teardown
+            Statement::Test { .. } | Statement::Setup { .. } | Statement::Teardown { .. } => {
+                Ok((Value::Null, ControlFlow::Continue))
+            }
+            Statement::Expect(condition) => {
+                self.eval_expect(condition)?;
+                Ok((Value::Null, ControlFlow::Continue))
+            }
             Statement::Open(_) => Ok((
                 Value::String("open not implemented yet".to_string()),
                 ControlFlow::Continue,
             )),
-            Statement::FunctionDefinition { name, params, body } => {
+            Statement::FunctionDefinition { name, params, body, doc } => {
                 // Store the action definition
                 self.functions.insert(
                     name.clone(),
                     Function {
                         params: params.clone(),
                         body: body.clone(),
+                        doc: doc.clone(),
                     },
                 );
                 Ok((Value::Null, ControlFlow::Continue))
@@ -436,8 +966,27 @@ impl Evaluator {
                 rescue_var,
                 rescue_body,
             } => self.eval_attempt_rescue(attempt_body, rescue_var, rescue_body),
+            Statement::Located { line, statement } => {
+                // Statements nested on one line, like `if ~x (say ~x)`, get the hook once
+                let nested = self.active_lines.last() == Some(&line);
+                self.current_line = Some(line);
+                if self.debug_hook.is_some() && !nested {
+                    self.run_debug_hook(line)?;
+                }
+                if !nested && self.is_tracing(TraceKind::Statement) {
+                    self.trace(
+                        TraceEvent::new(TraceKind::Statement)
+                            .with_field("statement", describe_statement(&statement)),
+                    );
+                }
+                self.active_lines.push(line);
+                let result = self.eval_statement_with_control(*statement);
+                self.active_lines.pop();
+                result
+            }
             Statement::FunctionChain { variable, steps } => {
                 let result = self.eval_function_chain(&steps)?;
+                self.check_assignment(&variable, &result)?;
                 // Store result in variable like a regular assignment
                 if let Some(scope) = self.scope_stack.last_mut() {
                     scope.insert(variable, result.clone());
@@ -499,7 +1048,11 @@ impl Evaluator {
     }
 
     pub fn eval_expression(&mut self, expr: Expression) -> Result<Value, String> {
-        self.eval_expression_with_context(expr, false, None)
+        let result = self.eval_expression_with_context(expr, false, None);
+        if let (Some(profiler), Ok(value)) = (self.profiler.as_mut(), &result) {
+            profiler.count_value(value);
+        }
+        result
     }
 
     fn eval_expression_with_context(
@@ -565,60 +1118,7 @@ impl Evaluator {
                         let left_val = self.eval_expression(*left)?;
                         let right_val = self.eval_expression(*right)?;
 
-                        match (left_val, right_val, op) {
-                            (Value::Number(l), Value::Number(r), BinaryOperator::Add) => {
-                                Ok(Value::Number(l + r))
-                            }
-                            (Value::Number(l), Value::Number(r), BinaryOperator::Subtract) => {
-                                Ok(Value::Number(l - r))
-                            }
-                            (Value::Number(l), Value::Number(r), BinaryOperator::Multiply) => {
-                                Ok(Value::Number(l * r))
-                            }
-                            (Value::Number(l), Value::Number(r), BinaryOperator::Divide) => {
-                                if r == 0.0 {
-                                    Err("Division by zero".to_string())
-                                } else {
-                                    Ok(Value::Number(l / r))
-                                }
-                            }
-                            (Value::Number(l), Value::Number(r), BinaryOperator::IntegerDivide) => {
-                                if r == 0.0 {
-                                    Err("Division by zero".to_string())
-                                } else {
-                                    Ok(Value::Number((l / r).floor()))
-                                }
-                            }
-                            (Value::Number(l), Value::Number(r), BinaryOperator::Modulo) => {
-                                if r == 0.0 {
-                                    Err("Modulo by zero".to_string())
-                                } else {
-                                    Ok(Value::Number(l % r))
-                                }
-                            }
-                            (Value::Number(l), Value::Number(r), BinaryOperator::LessThan) => {
-                                Ok(Value::Boolean(l < r))
-                            }
-                            (
-                                Value::Number(l),
-                                Value::Number(r),
-                                BinaryOperator::LessThanOrEqual,
-                            ) => Ok(Value::Boolean(l <= r)),
-                            (Value::Number(l), Value::Number(r), BinaryOperator::GreaterThan) => {
-                                Ok(Value::Boolean(l > r))
-                            }
-                            (
-                                Value::Number(l),
-                                Value::Number(r),
-                                BinaryOperator::GreaterThanOrEqual,
-                            ) => Ok(Value::Boolean(l >= r)),
-                            (l, r, BinaryOperator::Equal) => Ok(Value::Boolean(l == r)),
-                            (l, r, BinaryOperator::NotEqual) => Ok(Value::Boolean(l != r)),
-                            (Value::String(l), Value::String(r), BinaryOperator::Add) => {
-                                Ok(Value::String(format!("{}{}", l, r)))
-                            }
-                            _ => Err("Invalid operation".to_string()),
-                        }
+                        apply_binary_op(left_val, right_val, op)
                     }
                 }
             }
@@ -631,83 +1131,22 @@ impl Evaluator {
                             output.push(value.to_string());
                         }
                         let message = output.join("");
+                        self.check_output(&message)?;
+                        self.streams.output.write_line(&message)?;
 
                         #[cfg(target_arch = "wasm32")]
                         {
                             self.output_buffer.push(message.clone());
                         }
 
-                        #[cfg(not(target_arch = "wasm32"))]
-                        {
-                            println!("{}", message);
-                        }
-
                         Ok(Value::String(message))
                     }
                     "get" | "post" | "put" | "delete" | "patch" | "http" | "run" | "wait"
-                    | "read" | "write" | "clear" => self.eval_positional_function(&name, args),
-                    "ask" => {
-                        #[cfg(target_arch = "wasm32")]
-                        {
-                            // For WASM, return a placeholder for now
-                            // In a real implementation, this would use a JavaScript prompt
-                            let prompt = if !args.is_empty() {
-                                let mut prompt_values = Vec::new();
-                                for arg in args {
-                                    prompt_values.push(self.eval_expression(arg)?);
-                                }
-                                prompt_values
-                                    .iter()
-                                    .map(|v| v.to_string())
-                                    .collect::<Vec<String>>()
-                                    .join(" ")
-                            } else {
-                                String::new()
-                            };
-
-                            // Call JavaScript prompt function
-                            use crate::wasm::prompt_user;
-                            let result = prompt_user(&prompt);
-                            Ok(Value::String(result))
-                        }
-
-                        #[cfg(not(target_arch = "wasm32"))]
-                        {
-                            use std::io::{self, Write};
-
-                            // If there are prompt arguments, evaluate and display them
-                            if !args.is_empty() {
-                                let mut prompt_values = Vec::new();
-                                for arg in args {
-                                    prompt_values.push(self.eval_expression(arg)?);
-                                }
-
-                                let prompt = prompt_values
-                                    .iter()
-                                    .map(|v| v.to_string())
-                                    .collect::<Vec<String>>()
-                                    .join(" ");
-
-                                print!("{}", prompt);
-                                io::stdout().flush().unwrap();
-                            }
-
-                            // Read user input
-                            let mut input = String::new();
-                            io::stdin()
-                                .read_line(&mut input)
-                                .map_err(|e| format!("Failed to read input: {}", e))?;
-
-                            let input = input.trim().to_string();
-
-                            // Try to parse as number first, fall back to string
-                            if let Ok(num) = input.parse::<f64>() {
-                                Ok(Value::Number(num))
-                            } else {
-                                Ok(Value::String(input))
-                            }
-                        }
-                    }
+                    | "read" | "write" | "clear" => self.profile_call(CallKind::Stdlib, &name, |this| {
+                        this.eval_positional_function(&name, args)
+                    }),
+                    "ask" => self.eval_ask(args),
+                    "import" => self.eval_import(args),
                     _ => {
                         // Check for block syntax first (e.g., core:is-even)
                         if name.contains(':') {
@@ -724,12 +1163,28 @@ impl Evaluator {
                                         if let Some(func) =
                                             crate::stdlib::get_stdlib_function(func_name)
                                         {
-                                            func(args, self)
+                                            self.profile_call(CallKind::Stdlib, func_name, |this| {
+                                                func(args, this)
+                                            })
                                         } else {
                                             Err(format!("Unknown core function: {}", func_name))
                                         }
                                     }
-                                    _ => Err(format!("Unknown block: {}", block_name)),
+                                    _ => match self.native_functions.get(&name).cloned() {
+                                        Some(function) => {
+                                            self.call_native_function(&name, function, args)
+                                        }
+                                        None if self.native_functions.keys().any(|key| {
+                                            key.starts_with(&format!("{}:", block_name))
+                                        }) =>
+                                        {
+                                            Err(format!(
+                                                "Unknown {} function: {}",
+                                                block_name, func_name
+                                            ))
+                                        }
+                                        None => Err(format!("Unknown block: {}", block_name)),
+                                    },
                                 }
                             } else {
                                 Err(format!("Invalid block syntax: {}", name))
@@ -738,10 +1193,14 @@ impl Evaluator {
                             // Check if this is a user-defined function
                             let function = if let Some(function) = self.functions.get(&name) {
                                 function.clone()
+                            } else if let Some(native) = self.native_functions.get(&name).cloned() {
+                                return self.call_native_function(&name, native, args);
                             } else {
                                 // Function not found, try stdlib
                                 if let Some(func) = crate::stdlib::get_stdlib_function(&name) {
-                                    return func(args, self);
+                                    return self.profile_call(CallKind::Stdlib, &name, |this| {
+                                        func(args, this)
+                                    });
                                 } else {
                                     return Err(format!("Unknown function: {}", name));
                                 }
@@ -843,7 +1302,7 @@ impl Evaluator {
                                 Some(source) => Ok(Value::String(source.clone())),
                                 None => Ok(Value::Null),
                             },
-                            "context" => Ok(Value::Object(err.context.clone())),
+                            "context" => Ok(Value::Object(*err.context.clone())),
                             _ => Ok(Value::Null), // Return null for non-existent properties
                         }
                     }
@@ -854,7 +1313,7 @@ impl Evaluator {
                 }
             }
             Expression::ObjectLiteral { pairs } => {
-                let mut map = std::collections::HashMap::new();
+                let mut map = ObjectMap::new();
                 for (key, expr) in pairs {
                     let value = self.eval_expression(expr)?;
                     map.insert(key, value);
@@ -895,9 +1354,10 @@ impl Evaluator {
                         // Get or create the nested object
                         let nested_obj = map
                             .entry(nested_prop.to_string())
-                            .or_insert_with(|| Value::Object(std::collections::HashMap::new()));
+                            .or_insert_with(|| Value::Object(ObjectMap::new()));
                         if let Value::Object(nested_map) = nested_obj {
                             nested_map.insert(final_prop.to_string(), value);
+                            self.check_assignment(var_name, &obj_value)?;
                             self.update_variable(var_name.clone(), obj_value)?;
                             Ok(())
                         } else {
@@ -945,14 +1405,15 @@ impl Evaluator {
                         // Get or create the current property's object
                         let current_obj = map
                             .entry(current_prop.to_string())
-                            .or_insert_with(|| Value::Object(std::collections::HashMap::new()));
+                            .or_insert_with(|| Value::Object(ObjectMap::new()));
                         if let Value::Object(current_map) = current_obj {
                             // Get or create the nested object
                             let nested_obj = current_map
                                 .entry(nested_prop.to_string())
-                                .or_insert_with(|| Value::Object(std::collections::HashMap::new()));
+                                .or_insert_with(|| Value::Object(ObjectMap::new()));
                             if let Value::Object(nested_map) = nested_obj {
                                 nested_map.insert(final_prop.to_string(), value);
+                                self.check_assignment(var_name, &obj_value)?;
                                 self.update_variable(var_name.clone(), obj_value)?;
                                 Ok(())
                             } else {
@@ -1010,7 +1471,10 @@ impl Evaluator {
             .with_headers(headers)
             .with_timeout(timeout_ms);
 
-        match HttpClient::execute(request) {
+        let url_check = self.capabilities().check_url("get", &final_url);
+        self.permit(url_check)?;
+
+        match self.execute_http(request) {
             Ok(response) => Ok(response.to_tails_value()),
             Err(error_value) => {
                 self.last_error = Some(error_value.clone());
@@ -1048,7 +1512,10 @@ impl Evaluator {
             request = request.with_body(body_str);
         }
 
-        match HttpClient::execute(request) {
+        let url_check = self.capabilities().check_url("post", &final_url);
+        self.permit(url_check)?;
+
+        match self.execute_http(request) {
             Ok(response) => Ok(response.to_tails_value()),
             Err(error_value) => {
                 self.last_error = Some(error_value.clone());
@@ -1085,7 +1552,10 @@ impl Evaluator {
             request = request.with_body(body_str);
         }
 
-        match HttpClient::execute(request) {
+        let url_check = self.capabilities().check_url("put", &final_url);
+        self.permit(url_check)?;
+
+        match self.execute_http(request) {
             Ok(response) => Ok(response.to_tails_value()),
             Err(error_value) => {
                 self.last_error = Some(error_value.clone());
@@ -1118,7 +1588,10 @@ impl Evaluator {
             .with_headers(headers)
             .with_timeout(timeout_ms);
 
-        match HttpClient::execute(request) {
+        let url_check = self.capabilities().check_url("delete", &final_url);
+        self.permit(url_check)?;
+
+        match self.execute_http(request) {
             Ok(response) => Ok(response.to_tails_value()),
             Err(error_value) => {
                 self.last_error = Some(error_value.clone());
@@ -1155,7 +1628,10 @@ impl Evaluator {
             request = request.with_body(body_str);
         }
 
-        match HttpClient::execute(request) {
+        let url_check = self.capabilities().check_url("patch", &final_url);
+        self.permit(url_check)?;
+
+        match self.execute_http(request) {
             Ok(response) => Ok(response.to_tails_value()),
             Err(error_value) => {
                 self.last_error = Some(error_value.clone());
@@ -1198,7 +1674,10 @@ impl Evaluator {
             request = request.with_body(body_str);
         }
 
-        match HttpClient::execute(request) {
+        let url_check = self.capabilities().check_url("http", &final_url);
+        self.permit(url_check)?;
+
+        match self.execute_http(request) {
             Ok(response) => Ok(response.to_tails_value()),
             Err(error_value) => {
                 self.last_error = Some(error_value.clone());
@@ -1207,6 +1686,25 @@ impl Evaluator {
         }
     }
 
+    /// Send a request, tracing it if HTTP events are traced
+    fn execute_http(&self, request: HttpRequest) -> Result<crate::http::HttpResponse, Value> {
+        if !self.is_tracing(TraceKind::Http) {
+            return HttpClient::execute(request);
+        }
+
+        let event = TraceEvent::new(TraceKind::Http)
+            .with_field("method", request.method.as_str())
+            .with_field("url", request.url.as_str());
+        let started = Stopwatch::start();
+        let result = HttpClient::execute(request);
+        let event = event.with_duration(started.elapsed());
+        self.trace(match &result {
+            Ok(response) => event.with_field("status", response.status),
+            Err(error) => event.with_field("error", Self::error_value_to_string(error)),
+        });
+        result
+    }
+
     // Convert error value to string for Rust error propagation
     fn error_value_to_string(error_value: &Value) -> String {
         if let Value::Error(err) = error_value {
@@ -1227,12 +1725,25 @@ impl Evaluator {
             _ => return Err("run command must be a string".to_string()),
         };
 
+        let process_check = self.capabilities().check_process("run", &command);
+        self.permit(process_check)?;
+
         // Execute the shell command
+        let event = TraceEvent::new(TraceKind::Process).with_field("command", command.as_str());
+        let started = Stopwatch::start();
         let output = std::process::Command::new("sh")
             .arg("-c")
             .arg(&command)
             .output()
-            .map_err(|e| format!("Failed to execute command: {}", e))?;
+            .map_err(|e| format!("Failed to execute command: {}", e));
+        if self.is_tracing(TraceKind::Process) {
+            let event = event.with_duration(started.elapsed());
+            self.trace(match &output {
+                Ok(output) => event.with_field("exit_code", output.status.code()),
+                Err(error) => event.with_field("error", error.as_str()),
+            });
+        }
+        let output = output?;
 
         // Combine stdout and stderr
         let mut result = String::from_utf8_lossy(&output.stdout).to_string();
@@ -1244,7 +1755,7 @@ impl Evaluator {
         }
 
         // Create result object
-        let mut result_map = std::collections::HashMap::new();
+        let mut result_map = ObjectMap::new();
         result_map.insert("output".to_string(), Value::String(result));
 
         Ok(Value::Object(result_map))
@@ -1280,13 +1791,9 @@ impl Evaluator {
         let pattern = self.eval_expression(args[0].clone())?;
         match pattern {
             Value::Pattern(ref pattern_value) => {
-                let engine = self.ensure_music_engine();
-                let pattern_name = format!("pattern_{}", engine.get_pattern_names().len());
-                
-                engine.add_pattern_value(pattern_name, pattern_value)?;
-                if !engine.is_playing() {
-                    engine.start()?;
-                }
+                let audio_check = self.capabilities().check_audio("play");
+                self.permit(audio_check)?;
+                self.ensure_music_engine().play_pattern_value(pattern_value)?;
                 Ok(Value::String("Pattern added to scheduler".to_string()))
             }
             _ => Err("play argument must be a pattern".to_string()),
@@ -1315,6 +1822,8 @@ impl Evaluator {
                 if cpm <= 0.0 {
                     return Err("tempo must be positive".to_string());
                 }
+                let audio_check = self.capabilities().check_audio("tempo");
+                self.permit(audio_check)?;
                 let engine = self.ensure_music_engine();
                 engine.set_tempo(cpm);
                 Ok(Value::String(format!("Tempo set to {} CPM", cpm)))
@@ -1344,11 +1853,17 @@ impl Evaluator {
             // Try to create audio engine first, fall back to debug if audio fails
             let engine = match MusicEngine::with_audio_output() {
                 Ok(audio_engine) => {
-                    println!("🎵 Audio output initialized successfully");
+                    let _ = self
+                        .streams
+                        .output
+                        .write_line("🎵 Audio output initialized successfully");
                     audio_engine
                 }
                 Err(audio_error) => {
-                    println!("⚠️  Audio output failed ({}), using debug output", audio_error);
+                    let _ = self.streams.error_output.write_line(&format!(
+                        "⚠️  Audio output failed ({}), using debug output",
+                        audio_error
+                    ));
                     MusicEngine::with_debug_output()
                 }
             };
@@ -1357,6 +1872,96 @@ impl Evaluator {
         self.music_engine.as_mut().unwrap()
     }
 
+    // Record a structured error so attempt/rescue binds it instead of the plain message
+    pub(crate) fn set_last_error(&mut self, error: Value) {
+        self.last_error = Some(error);
+    }
+
+    pub(crate) fn take_last_error(&mut self) -> Option<Value> {
+        self.last_error.take()
+    }
+
+    /// Snapshot the visible variables, functions and config for a spawned task.
+    /// Local scopes are flattened over globals so tasks see what the caller sees.
+    /// Call `ensure_task_pool` first so the task's own tasks share the pool.
+    pub fn task_context(&self) -> TaskContext {
+        let mut variables = self.variables.clone();
+        for scope in &self.scope_stack {
+            for (name, value) in scope {
+                variables.insert(name.clone(), value.clone());
+            }
+        }
+        TaskContext {
+            variables,
+            functions: self.functions.clone(),
+            native_functions: self.native_functions.clone(),
+            host_blocks: self.host_blocks.clone(),
+            config: self.config.clone(),
+            streams: self.streams.clone(),
+            tracer: self.tracer.clone(),
+            pool: self.task_pool.clone(),
+            usage: Some(self.usage.clone()),
+        }
+    }
+
+    /// Run as a task of another evaluator: nested tasks go on its pool and
+    /// every step counts against its budget
+    pub(crate) fn join_task_tree(
+        &mut self,
+        pool: Option<Arc<TaskPool>>,
+        usage: Option<ResourceUsage>,
+    ) {
+        if let Some(pool) = pool {
+            self.task_limit = pool.size();
+            self.task_pool = Some(pool);
+        }
+        if let Some(usage) = usage {
+            self.usage = usage;
+        }
+    }
+
+    pub fn task_limit(&self) -> usize {
+        self.task_limit
+    }
+
+    /// Set the maximum number of tasks that run at once.
+    /// Tasks already queued on the previous pool still run to completion.
+    pub fn set_task_limit(&mut self, limit: usize) {
+        let limit = limit.max(1);
+        if limit != self.task_limit {
+            self.task_limit = limit;
+            self.task_pool = None;
+        }
+    }
+
+    // Helper method to ensure the worker pool is started
+    pub fn ensure_task_pool(&mut self) -> &TaskPool {
+        if self.task_pool.is_none() {
+            self.task_pool = Some(Arc::new(TaskPool::new(self.task_limit)));
+        }
+        self.task_pool.as_ref().unwrap()
+    }
+
+    /// The worker pool, shared with the tasks it runs, started if need be
+    pub(crate) fn shared_task_pool(&mut self) -> Arc<TaskPool> {
+        self.ensure_task_pool();
+        self.task_pool.clone().unwrap()
+    }
+
+    /// Wait for a task through the pool, so a task awaiting its own tasks
+    /// keeps its worker busy
+    pub(crate) fn wait_for_task(&self, task: &TaskHandle) -> TaskResult {
+        match &self.task_pool {
+            Some(pool) => pool.wait(task),
+            None => task.wait(),
+        }
+    }
+
+    /// Whether an uncatchable limit was hit by this run or any of its tasks
+    pub(crate) fn limit_is_fatal(&self) -> bool {
+        self.usage.shared.is_fatal()
+    }
+
     // Hidden debug functions for TDD - prefixed with __ to indicate internal use
     fn eval_scheduler_debug(&mut self, args: Vec<Expression>) -> Result<Value, String> {
         if !args.is_empty() {
@@ -1416,14 +2021,49 @@ impl Evaluator {
         function: Function,
         args: Vec<Expression>,
         function_name: Option<String>,
+    ) -> Result<Value, String> {
+        if self.profiler.is_none() && self.tracer.is_none() {
+            return self.run_function(function, args, function_name);
+        }
+
+        let name = function_name.clone().unwrap_or_else(|| "<anonymous>".to_string());
+        let started = Stopwatch::start();
+        let depth = self.scope_stack.len() + 1;
+        if self.is_tracing(TraceKind::Call) {
+            self.trace(
+                TraceEvent::new(TraceKind::Call)
+                    .with_field("function", name.as_str())
+                    .with_field("depth", depth),
+            );
+        }
+        let result = self.profile_call(CallKind::User, &name, |this| {
+            this.run_function(function, args, function_name)
+        });
+        if self.is_tracing(TraceKind::Return) {
+            let event = TraceEvent::new(TraceKind::Return)
+                .with_duration(started.elapsed())
+                .with_field("function", name.as_str())
+                .with_field("depth", depth);
+            self.trace(match &result {
+                Ok(value) => event.with_field("value", value.to_string()),
+                Err(error) => event.with_field("error", error.as_str()),
+            });
+        }
+        result
+    }
+
+    fn run_function(
+        &mut self,
+        function: Function,
+        args: Vec<Expression>,
+        function_name: Option<String>,
     ) -> Result<Value, String> {
         // Check call depth to prevent stack overflow
-        if self.scope_stack.len() >= self.max_call_depth {
-            return Err(format!(
-                "Maximum call depth ({}) exceeded",
-                self.max_call_depth
-            ));
+        let max_call_depth = self.config.limits.max_call_depth;
+        if self.scope_stack.len() >= max_call_depth {
+            self.exceed_limit(Limit::CallDepth, max_call_depth as f64)?;
         }
+        self.check_step()?;
 
         // Check argument count
         if args.len() != function.params.len() {
@@ -1448,6 +2088,9 @@ impl Evaluator {
 
         // Push the pre-populated scope
         self.scope_stack.push(local_scope);
+        if self.debug_hook.is_some() {
+            self.push_call_frame(function_name.as_deref());
+        }
 
         // Tail call optimization loop
         let mut found_tail_call = false;
@@ -1467,31 +2110,30 @@ impl Evaluator {
             let mut last_value = Value::Null;
 
             for stmt in &function.body {
-                if std::env::var("TILDE_DEBUG_TAIL_CALL").is_ok() {
-                    eprintln!("Executing statement: {:?}", stmt);
-                }
                 let (value, control) = self.eval_statement_with_control(stmt.clone())?;
-                if std::env::var("TILDE_DEBUG_TAIL_CALL").is_ok() {
-                    eprintln!("Statement result: value={:?}, control={:?}", value, control);
-                }
 
                 match control {
                     ControlFlow::Give(return_value) => {
                         // Check if this is a tail call to the same function
-                        if let Some(ref fn_name) = function_name {
-                            if std::env::var("TILDE_DEBUG_TAIL_CALL").is_ok() {
-                                eprintln!("Checking statement for tail call: {:?}", stmt);
+                        if let Some(ref fn_name) = function_name
+                            && let Some(tail_args) = self.detect_tail_call(stmt, fn_name)
+                        {
+                            if self.is_tracing(TraceKind::Call) {
+                                self.trace(
+                                    TraceEvent::new(TraceKind::Call)
+                                        .with_field("function", fn_name.as_str())
+                                        .with_field("depth", self.scope_stack.len())
+                                        .with_field("tail_call", true),
+                                );
                             }
-                            if let Some(tail_args) = self.detect_tail_call(stmt, fn_name) {
-                                // Evaluate new arguments for tail call
-                                let mut new_args = Vec::new();
-                                for arg_expr in tail_args {
-                                    new_args.push(self.eval_expression(arg_expr)?);
-                                }
-                                current_args = new_args;
-                                found_tail_call = true;
-                                break; // Break inner loop to restart with new args
+                            // Evaluate new arguments for tail call
+                            let mut new_args = Vec::new();
+                            for arg_expr in tail_args {
+                                new_args.push(self.eval_expression(arg_expr)?);
                             }
+                            current_args = new_args;
+                            found_tail_call = true;
+                            break; // Break inner loop to restart with new args
                         }
 
                         // Not a tail call, cache result and return
@@ -1536,9 +2178,6 @@ impl Evaluator {
             // Direct function call in give statement
             Statement::Give(Expression::FunctionCall { name, args }) => {
                 if name == function_name {
-                    if std::env::var("TILDE_DEBUG_TAIL_CALL").is_ok() {
-                        eprintln!("TAIL CALL DETECTED: {} with {} args", name, args.len());
-                    }
                     Some(args.clone())
                 } else {
                     None
@@ -1550,12 +2189,6 @@ impl Evaluator {
                 then_stmt,
                 else_stmt,
             } => {
-                if std::env::var("TILDE_DEBUG_TAIL_CALL").is_ok() {
-                    eprintln!(
-                        "Checking if statement: then={:?}, else={:?}",
-                        then_stmt, else_stmt
-                    );
-                }
                 // Check the then branch
                 if let Some(tail_args) = self.find_tail_call_in_statement(then_stmt, function_name)
                 {
@@ -1570,6 +2203,9 @@ impl Evaluator {
                 }
                 None
             }
+            Statement::Located { statement, .. } => {
+                self.find_tail_call_in_statement(statement, function_name)
+            }
             // Check block statements (look at the last statement)
             Statement::Block { body } => {
                 if let Some(last_stmt) = body.last() {
@@ -1606,6 +2242,32 @@ impl Evaluator {
     }
 
     /// Create a hash key from function arguments for memoization
+    /// Check an `expect`. For comparisons each side is evaluated once and
+    /// both are shown if it fails.
+    fn eval_expect(&mut self, condition: Expression) -> Result<(), String> {
+        let source = crate::formatter::format_expression(&condition);
+        match condition {
+            Expression::BinaryOp { left, op, right } if testing::is_comparison(&op) => {
+                let actual = self.eval_expression(*left)?;
+                let expected = self.eval_expression(*right)?;
+                let holds = apply_binary_op(actual.clone(), expected.clone(), op.clone())?;
+                if self.is_truthy(&holds) {
+                    Ok(())
+                } else {
+                    Err(testing::comparison_failure(&source, &op, &actual, &expected))
+                }
+            }
+            condition => {
+                let value = self.eval_expression(condition)?;
+                if self.is_truthy(&value) {
+                    Ok(())
+                } else {
+                    Err(format!("Expectation failed: expect {}", source))
+                }
+            }
+        }
+    }
+
     fn eval_attempt_rescue(
         &mut self,
         attempt_body: Vec<Statement>,
@@ -1625,6 +2287,11 @@ impl Evaluator {
                     }
                 }
                 Err(error_msg) => {
+                    // Uncatchable limits and `exit` stop the whole run
+                    if self.usage.shared.is_fatal() || self.exit_code.is_some() {
+                        return Err(error_msg);
+                    }
+
                     // Check if we have a structured error from HTTP or use simple error
                     let error_value = if let Some(structured_error) = self.last_error.take() {
                         structured_error
@@ -1733,6 +2400,52 @@ impl Evaluator {
     }
 }
 
+/// Apply an arithmetic or comparison operator to two evaluated operands
+fn apply_binary_op(left: Value, right: Value, op: BinaryOperator) -> Result<Value, String> {
+    match (left, right, op) {
+        (Value::Number(l), Value::Number(r), BinaryOperator::Add) => Ok(Value::Number(l + r)),
+        (Value::Number(l), Value::Number(r), BinaryOperator::Subtract) => Ok(Value::Number(l - r)),
+        (Value::Number(l), Value::Number(r), BinaryOperator::Multiply) => Ok(Value::Number(l * r)),
+        (Value::Number(l), Value::Number(r), BinaryOperator::Divide) => {
+            if r == 0.0 {
+                Err("Division by zero".to_string())
+            } else {
+                Ok(Value::Number(l / r))
+            }
+        }
+        (Value::Number(l), Value::Number(r), BinaryOperator::IntegerDivide) => {
+            if r == 0.0 {
+                Err("Division by zero".to_string())
+            } else {
+                Ok(Value::Number((l / r).floor()))
+            }
+        }
+        (Value::Number(l), Value::Number(r), BinaryOperator::Modulo) => {
+            if r == 0.0 {
+                Err("Modulo by zero".to_string())
+            } else {
+                Ok(Value::Number(l % r))
+            }
+        }
+        (Value::Number(l), Value::Number(r), BinaryOperator::LessThan) => Ok(Value::Boolean(l < r)),
+        (Value::Number(l), Value::Number(r), BinaryOperator::LessThanOrEqual) => {
+            Ok(Value::Boolean(l <= r))
+        }
+        (Value::Number(l), Value::Number(r), BinaryOperator::GreaterThan) => {
+            Ok(Value::Boolean(l > r))
+        }
+        (Value::Number(l), Value::Number(r), BinaryOperator::GreaterThanOrEqual) => {
+            Ok(Value::Boolean(l >= r))
+        }
+        (l, r, BinaryOperator::Equal) => Ok(Value::Boolean(l == r)),
+        (l, r, BinaryOperator::NotEqual) => Ok(Value::Boolean(l != r)),
+        (Value::String(l), Value::String(r), BinaryOperator::Add) => {
+            Ok(Value::String(format!("{}{}", l, r)))
+        }
+        _ => Err("Invalid operation".to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,7 +1,30 @@
 use crate::ast::Expression;
 use crate::evaluator::Evaluator;
-use crate::value::Value;
-use std::collections::HashMap;
+use crate::limits::Stopwatch;
+use crate::trace::{TraceEvent, TraceKind};
+use crate::value::{ObjectMap, Value};
+use std::path::Path;
+
+/// Trace a finished file operation, given the bytes moved or the error
+pub(crate) fn trace_file(
+    evaluator: &Evaluator,
+    operation: &str,
+    path: &str,
+    started: &Stopwatch,
+    outcome: Result<usize, &std::io::Error>,
+) {
+    if !evaluator.is_tracing(TraceKind::File) {
+        return;
+    }
+    let event = TraceEvent::new(TraceKind::File)
+        .with_duration(started.elapsed())
+        .with_field("operation", operation)
+        .with_field("path", path);
+    evaluator.trace(match outcome {
+        Ok(bytes) => event.with_field("bytes", bytes),
+        Err(error) => event.with_field("error", error.to_string()),
+    });
+}
 
 pub fn eval_read_positional(
     args: Vec<Expression>,
@@ -16,16 +39,36 @@ pub fn eval_read_positional(
         Value::String(path) => path,
         _ => return Err("read argument must be a string (file path)".to_string()),
     };
+    let path_check = evaluator.capabilities().check_path("read", &file_path);
+    evaluator.permit(path_check)?;
 
     // Attempt to read the file
-    let mut result = HashMap::new();
-
-    match std::fs::read_to_string(&file_path) {
+    let mut result = ObjectMap::new();
+
+    let started = Stopwatch::start();
+    // Files built into a standalone executable shadow the filesystem
+    let bundled = evaluator
+        .bundle()
+        .and_then(|bundle| bundle.file(Path::new(&file_path)));
+    let read = match bundled {
+        Some(contents) => String::from_utf8(contents.to_vec())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        None => std::fs::read_to_string(&file_path),
+    };
+    trace_file(
+        evaluator,
+        "read",
+        &file_path,
+        &started,
+        read.as_ref().map(String::len),
+    );
+
+    match read {
         Ok(content) => {
             // Calculate file size for metadata
             let size = match std::fs::metadata(&file_path) {
-                Ok(metadata) => metadata.len() as f64,
-                Err(_) => content.len() as f64, // Fallback to content length
+                Ok(metadata) if bundled.is_none() => metadata.len() as f64,
+                _ => content.len() as f64, // Fallback to content length
             };
 
             result.insert("content".to_string(), Value::String(content));
@@ -35,7 +78,7 @@ pub fn eval_read_positional(
         }
         Err(err) => {
             // File operation failed - populate error details
-            let exists = std::path::Path::new(&file_path).exists();
+            let exists = bundled.is_some() || Path::new(&file_path).exists();
             let error_message = if !exists {
                 "File not found".to_string()
             } else {
@@ -65,6 +108,8 @@ pub fn eval_write_positional(
         Value::String(path) => path,
         _ => return Err("write first argument must be a string (file path)".to_string()),
     };
+    let path_check = evaluator.capabilities().check_path("write", &file_path);
+    evaluator.permit(path_check)?;
 
     let content_val = evaluator.eval_expression(args[1].clone())?;
     let content = match content_val {
@@ -77,12 +122,25 @@ pub fn eval_write_positional(
         Value::Date(_) => content_val.to_string(),
         Value::Error(_) => content_val.to_string(),
         Value::Pattern(_) => content_val.to_string(),
+        Value::Task(_) => content_val.to_string(),
+        Value::Channel(_) => content_val.to_string(),
+        Value::Handle(_) => content_val.to_string(),
     };
 
     // Attempt to write the file
-    let mut result = HashMap::new();
-
-    match std::fs::write(&file_path, &content) {
+    let mut result = ObjectMap::new();
+
+    let started = Stopwatch::start();
+    let written = std::fs::write(&file_path, &content);
+    trace_file(
+        evaluator,
+        "write",
+        &file_path,
+        &started,
+        written.as_ref().map(|()| content.len()),
+    );
+
+    match written {
         Ok(()) => {
             // Successfully wrote the file
             result.insert("success".to_string(), Value::Boolean(true));
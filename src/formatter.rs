@@ -0,0 +1,666 @@
+//! Canonical formatting for `tilde fmt`.
+//!
+//! `format_source` parses a script along with its comments and prints it
+//! back in one standard layout: four-space indents, one statement per line,
+//! blocks opened at the end of their header line, single spaces around
+//! operators and at most one blank line in a row. Formatting never changes
+//! what a script does, since the output parses back to the same program, and
+//! formatting formatted code changes nothing.
+//!
+//! ```
+//! use tilde::formatter::format_source;
+//!
+//! let formatted = format_source("~x   is 1+2 # three\nif ~x>2 (\nsay \"big\")").unwrap();
+//! assert_eq!(formatted, "~x is 1 + 2 # three\nif ~x > 2 (\n    say \"big\"\n)\n");
+//! ```
+
+use crate::ast::*;
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use std::collections::HashSet;
+
+const INDENT: &str = "    ";
+
+/// Lists and objects wider than this are split one item per line
+const MAX_WIDTH: usize = 80;
+
+/// Calls written with a keyword, whose arguments are whole expressions
+const KEYWORD_CALLS: &[&str] = &[
+    "say", "ask", "get", "post", "put", "delete", "patch", "http", "run", "wait", "random", "read",
+    "write", "clear",
+];
+
+/// Format a script, keeping its comments and blank lines
+pub fn format_source(source: &str) -> Result<String, String> {
+    let program = Parser::new(source).with_comments().parse()?;
+    Ok(format_program(&program))
+}
+
+/// Print a parsed program in canonical form. Comments only appear if the
+/// program was parsed with `Parser::with_comments`.
+pub fn format_program(program: &[Statement]) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        functions: HashSet::new(),
+    };
+    printer.collect_functions(program);
+    printer.body(program, 0);
+    printer.out
+}
+
+/// Print one expression in canonical form, such as for an `expect` failure
+pub fn format_expression(expr: &Expression) -> String {
+    let printer = Printer {
+        out: String::new(),
+        functions: HashSet::new(),
+    };
+    printer.expression(expr, 0)
+}
+
+struct Printer {
+    out: String,
+    /// Functions the script defines. Calls to them keep their `*` even if a
+    /// stdlib function has the same name.
+    functions: HashSet<String>,
+}
+
+impl Printer {
+    fn collect_functions(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            match unlocated(statement) {
+                Statement::FunctionDefinition { name, body, .. } => {
+                    self.functions.insert(name.clone());
+                    self.collect_functions(body);
+                }
+                Statement::Loop { body }
+                | Statement::Block { body }
+                | Statement::ForEach { body, .. }
+                | Statement::Test { body, .. }
+                | Statement::Setup { body }
+                | Statement::Teardown { body } => self.collect_functions(body),
+                Statement::If {
+                    then_stmt,
+                    else_stmt,
+                    ..
+                } => {
+                    self.collect_functions(std::slice::from_ref(then_stmt.as_ref()));
+                    if let Some(else_stmt) = else_stmt {
+                        self.collect_functions(std::slice::from_ref(else_stmt.as_ref()));
+                    }
+                }
+                Statement::AttemptRescue {
+                    attempt_body,
+                    rescue_body,
+                    ..
+                } => {
+                    self.collect_functions(attempt_body);
+                    self.collect_functions(rescue_body);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn body(&mut self, statements: &[Statement], indent: usize) {
+        let mut printed = false;
+        let mut blank_line = false;
+        for statement in statements {
+            match statement {
+                Statement::Trivia(Trivia::BlankLine) => blank_line = printed,
+                // Goes on the end of the line before, even if that's the
+                // line opening this body
+                Statement::Trivia(Trivia::TrailingComment(text)) if self.out.ends_with('\n') => {
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out.push_str(text);
+                    self.out.push('\n');
+                }
+                statement => {
+                    if std::mem::take(&mut blank_line) {
+                        self.out.push('\n');
+                    }
+                    self.out.push_str(&INDENT.repeat(indent));
+                    self.statement(statement, indent);
+                    printed = true;
+                }
+            }
+        }
+    }
+
+    /// Print a statement from the current position to the end of its line
+    fn statement(&mut self, statement: &Statement, indent: usize) {
+        match statement {
+            Statement::Assignment { variable, value } => self.line(format!(
+                "~{} is {}",
+                variable,
+                self.expression(value, indent)
+            )),
+            Statement::PropertyAssignment {
+                object,
+                property,
+                value,
+            } => self.line(format!(
+                "{}.{} is {}",
+                self.expression(object, indent),
+                property,
+                self.expression(value, indent)
+            )),
+            Statement::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                self.out
+                    .push_str(&format!("if {} ", self.guarded(condition, indent)));
+                self.statement(then_stmt, indent);
+                if let Some(else_stmt) = else_stmt {
+                    // A chain runs to the end of its last line, so `else`
+                    // can't follow it there
+                    if matches!(unlocated(then_stmt), Statement::FunctionChain { .. }) {
+                        self.out.push_str(&INDENT.repeat(indent));
+                    } else {
+                        self.out.pop();
+                        self.out.push(' ');
+                    }
+                    self.out.push_str("else ");
+                    self.statement(else_stmt, indent);
+                }
+            }
+            Statement::Loop { body } => {
+                self.out.push_str("loop ");
+                self.block(body, indent);
+            }
+            Statement::ForEach {
+                variables,
+                iterable,
+                body,
+            } => {
+                self.out.push_str(&format!(
+                    "for-each {} in {} ",
+                    variable_list(variables),
+                    self.guarded(iterable, indent)
+                ));
+                self.block(body, indent);
+            }
+            Statement::Increment { variable, amount } => self.line(format!(
+                "~{} up {}",
+                variable,
+                self.expression(amount, indent)
+            )),
+            Statement::Decrement { variable, amount } => self.line(format!(
+                "~{} down {}",
+                variable,
+                self.expression(amount, indent)
+            )),
+            Statement::Block { body } => self.block(body, indent),
+            Statement::Breakloop => self.line("break-loop".to_string()),
+            Statement::Open(path) => self.line(format!("open {}", self.expression(path, indent))),
+            Statement::FunctionDefinition {
+                name, params, body, ..
+            } => {
+                self.out.push_str("function ");
+                self.out.push_str(name);
+                if !params.is_empty() {
+                    self.out.push(' ');
+                    self.out.push_str(&variable_list(params));
+                }
+                self.out.push(' ');
+                self.block(body, indent);
+            }
+            Statement::Give(value) => self.line(format!("give {}", self.expression(value, indent))),
+            Statement::AttemptRescue {
+                attempt_body,
+                rescue_var,
+                rescue_body,
+            } => {
+                self.out.push_str("attempt ");
+                self.block(attempt_body, indent);
+                self.out.pop();
+                self.out.push_str(" rescue ");
+                if let Some(variable) = rescue_var {
+                    self.out.push_str(&format!("~{} ", variable));
+                }
+                self.block(rescue_body, indent);
+            }
+            Statement::FunctionChain { variable, steps } => {
+                self.line(format!("~{}:", variable));
+                for step in steps {
+                    self.out.push_str(&INDENT.repeat(indent + 1));
+                    let line = match (step.function_name.as_str(), step.args.as_slice()) {
+                        ("@@initial-value", [initial]) => self.expression(initial, indent + 1),
+                        (name, args) => std::iter::once(name.to_string())
+                            .chain(args.iter().map(|arg| self.expression(arg, indent + 1)))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    };
+                    self.line(line);
+                }
+            }
+            Statement::Expression(expr) => self.line(self.expression(expr, indent)),
+            Statement::Test { name, body } => {
+                self.out.push_str(&format!("test {} ", string(name)));
+                self.block(body, indent);
+            }
+            Statement::Setup { body } => {
+                self.out.push_str("setup ");
+                self.block(body, indent);
+            }
+            Statement::Teardown { body } => {
+                self.out.push_str("teardown ");
+                self.block(body, indent);
+            }
+            Statement::Expect(condition) => {
+                self.line(format!("expect {}", self.expression(condition, indent)))
+            }
+            Statement::Located { statement, .. } => self.statement(statement, indent),
+            Statement::Trivia(Trivia::Comment(text) | Trivia::TrailingComment(text)) => {
+                self.line(text.clone())
+            }
+            Statement::Trivia(Trivia::BlankLine) => self.line(String::new()),
+            // Only scripts that parse are formatted
+            Statement::Error(_) => {}
+        }
+    }
+
+    fn line(&mut self, text: String) {
+        self.out.push_str(&text);
+        self.out.push('\n');
+    }
+
+    /// Print `( ... )` with the body indented one level
+    fn block(&mut self, body: &[Statement], indent: usize) {
+        if body
+            .iter()
+            .all(|statement| *statement == Statement::Trivia(Trivia::BlankLine))
+        {
+            self.line("()".to_string());
+            return;
+        }
+        self.line("(".to_string());
+        self.body(body, indent + 1);
+        self.out.push_str(&INDENT.repeat(indent));
+        self.line(")".to_string());
+    }
+}
+
+impl Printer {
+    /// Print an expression. `indent` is the level of the line it starts on, for
+    /// lists and objects that don't fit on one line.
+    fn expression(&self, expr: &Expression, indent: usize) -> String {
+        match expr {
+            Expression::Number(value, was_float) => number(*value, *was_float),
+            Expression::String(text) => string(text),
+            Expression::InterpolatedString(parts) => {
+                let mut text = String::from("\"");
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(part) => text.push_str(&escape(part, '"')),
+                        InterpolationPart::Variable(name) => text.push_str(&format!("`~{}`", name)),
+                        InterpolationPart::Expression(expr) => {
+                            text.push_str(&format!("`{}`", self.expression(expr, indent)))
+                        }
+                    }
+                }
+                text.push('"');
+                text
+            }
+            Expression::Boolean(value) => value.to_string(),
+            // `.name` refers to a stdlib function
+            Expression::Variable(name) if name.starts_with('.') => name.clone(),
+            Expression::Variable(name) => format!("~{}", name),
+            Expression::List(items) => {
+                let items: Vec<_> = items
+                    .iter()
+                    .map(|item| self.guarded(item, indent + 1))
+                    .collect();
+                collection('[', items, ']', indent)
+            }
+            Expression::BinaryOp { left, op, right } => format!(
+                "{} {} {}",
+                self.operand(left, op, false, indent),
+                operator(op),
+                self.operand(right, op, true, indent)
+            ),
+            Expression::FunctionCall { name, args } => self.call(name, args, indent),
+            Expression::PropertyAccess { object, property } => {
+                format!("{}.{}", self.expression(object, indent), property)
+            }
+            Expression::ObjectLiteral { pairs } => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = if is_plain_key(key) {
+                            key.clone()
+                        } else {
+                            string(key)
+                        };
+                        format!("{}: {}", key, self.guarded(value, indent + 1))
+                    })
+                    .collect();
+                collection('{', pairs, '}', indent)
+            }
+            Expression::AnonymousFunction { params, body } => {
+                format!(
+                    "|{} ({})|",
+                    variable_list(params),
+                    self.expression(body, indent)
+                )
+            }
+            Expression::FunctionChainExpression { steps, input } => {
+                // There's no syntax for a chain inside an expression, so print
+                // the calls it makes, each step taking the previous result first
+                let calls = steps.iter().fold(input.as_ref().clone(), |input, step| {
+                    if step.function_name == "@@initial-value" {
+                        return input;
+                    }
+                    Expression::FunctionCall {
+                        name: step.function_name.clone(),
+                        args: std::iter::once(input).chain(step.args.clone()).collect(),
+                    }
+                });
+                self.expression(&calls, indent)
+            }
+        }
+    }
+
+    /// Print an expression, in parentheses if it ends in a call that would
+    /// otherwise take whatever follows it as more arguments
+    fn guarded(&self, expr: &Expression, indent: usize) -> String {
+        if ends_in_call(expr) {
+            format!("({})", self.expression(expr, indent))
+        } else {
+            self.expression(expr, indent)
+        }
+    }
+
+    fn call(&self, name: &str, args: &[Expression], indent: usize) -> String {
+        // Keyword calls, block calls and functions passed by name are written
+        // as they are. Stdlib functions go without the `*`, unless the
+        // script has its own function of that name.
+        let bare = KEYWORD_CALLS.contains(&name)
+            || name.contains(':')
+            || name.starts_with('*')
+            || (crate::stdlib::get_stdlib_function_names().contains(&name)
+                && !self.functions.contains(name)
+                && is_identifier(name));
+        let mut text = if bare {
+            name.to_string()
+        } else {
+            format!("*{}", name)
+        };
+
+        for (i, arg) in args.iter().enumerate() {
+            text.push(' ');
+            // `~a .b` would read as a property of `~a`
+            if i > 0 && matches!(arg, Expression::Variable(name) if name.starts_with('.')) {
+                text.push_str(&format!("({})", self.expression(arg, indent)));
+            } else if KEYWORD_CALLS.contains(&name) {
+                // Each argument runs as far as an expression can, so only a
+                // call needs closing off. Calls with arguments are closed off
+                // anyway, to read as one argument.
+                let bare_name =
+                    matches!(arg, Expression::FunctionCall { args, .. } if args.is_empty());
+                if i + 1 < args.len() || !bare_name {
+                    text.push_str(&self.guarded(arg, indent));
+                } else {
+                    text.push_str(&self.expression(arg, indent));
+                }
+            } else {
+                text.push_str(&self.argument(arg, indent));
+            }
+        }
+        text
+    }
+
+    /// Print an argument to a `*function`, stdlib or block call. These take
+    /// single values, so anything bigger goes in parentheses.
+    fn argument(&self, arg: &Expression, indent: usize) -> String {
+        match arg {
+            // A function passed by name, like `map ~list *double`
+            Expression::FunctionCall { name, args }
+                if args.is_empty() && (name.contains(':') || is_identifier(name)) =>
+            {
+                name.clone()
+            }
+            Expression::Number(..)
+            | Expression::String(_)
+            | Expression::InterpolatedString(_)
+            | Expression::Boolean(_)
+            | Expression::Variable(_)
+            | Expression::PropertyAccess { .. }
+            | Expression::List(_)
+            | Expression::ObjectLiteral { .. }
+            | Expression::AnonymousFunction { .. } => self.expression(arg, indent),
+            _ => format!("({})", self.expression(arg, indent)),
+        }
+    }
+
+    fn operand(
+        &self,
+        expr: &Expression,
+        parent: &BinaryOperator,
+        right: bool,
+        indent: usize,
+    ) -> String {
+        if needs_parens(expr, parent, right) {
+            format!("({})", self.expression(expr, indent))
+        } else {
+            self.expression(expr, indent)
+        }
+    }
+}
+
+fn unlocated(statement: &Statement) -> &Statement {
+    match statement {
+        Statement::Located { statement, .. } => unlocated(statement),
+        statement => statement,
+    }
+}
+
+fn variable_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| format!("~{}", name))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn ends_in_call(expr: &Expression) -> bool {
+    match expr {
+        Expression::FunctionCall { .. } | Expression::FunctionChainExpression { .. } => true,
+        Expression::BinaryOp { op, right, .. } => {
+            !needs_parens(right, op, true) && ends_in_call(right)
+        }
+        _ => false,
+    }
+}
+
+fn needs_parens(expr: &Expression, parent: &BinaryOperator, right: bool) -> bool {
+    match expr {
+        // Operators are left-associative, so an equal one on the right was
+        // grouped on purpose
+        Expression::BinaryOp { op, .. } => {
+            precedence(op) < precedence(parent) || (right && precedence(op) == precedence(parent))
+        }
+        // Keyword calls would take the rest of the expression as arguments.
+        // Other calls wouldn't, but read more clearly in parentheses.
+        Expression::FunctionCall { name, args } => {
+            !args.is_empty() || KEYWORD_CALLS.contains(&name.as_str())
+        }
+        Expression::FunctionChainExpression { .. } => true,
+        _ => false,
+    }
+}
+
+fn precedence(op: &BinaryOperator) -> u8 {
+    match op {
+        BinaryOperator::Or => 1,
+        BinaryOperator::And => 2,
+        BinaryOperator::LessThan
+        | BinaryOperator::LessThanOrEqual
+        | BinaryOperator::GreaterThan
+        | BinaryOperator::GreaterThanOrEqual
+        | BinaryOperator::Equal
+        | BinaryOperator::NotEqual => 3,
+        BinaryOperator::Add | BinaryOperator::Subtract => 4,
+        BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::IntegerDivide
+        | BinaryOperator::Modulo => 5,
+    }
+}
+
+fn operator(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::IntegerDivide => "\\",
+        BinaryOperator::Modulo => "%",
+        BinaryOperator::LessThan => "<",
+        BinaryOperator::LessThanOrEqual => "<=",
+        BinaryOperator::GreaterThan => ">",
+        BinaryOperator::GreaterThanOrEqual => ">=",
+        BinaryOperator::Equal => "==",
+        BinaryOperator::NotEqual => "!=",
+        BinaryOperator::And => "and",
+        BinaryOperator::Or => "or",
+    }
+}
+
+/// `[a, b]` or `{a: 1}` on one line if it fits, otherwise one item per line
+fn collection(open: char, items: Vec<String>, close: char, indent: usize) -> String {
+    let inline = format!("{}{}{}", open, items.join(", "), close);
+    if items.is_empty()
+        || (INDENT.len() * indent + inline.len() <= MAX_WIDTH && !inline.contains('\n'))
+    {
+        return inline;
+    }
+    let inner = INDENT.repeat(indent + 1);
+    format!(
+        "{}\n{}{}\n{}{}",
+        open,
+        inner,
+        items.join(&format!(",\n{}", inner)),
+        INDENT.repeat(indent),
+        close
+    )
+}
+
+fn number(value: f64, was_float: bool) -> String {
+    if was_float && value.fract() == 0.0 {
+        format!("{:.1}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn string(text: &str) -> String {
+    // Double-quoted strings with a backtick are interpolated
+    if text.contains('`') {
+        format!("'{}'", escape(text, '\''))
+    } else {
+        format!("\"{}\"", escape(text, '"'))
+    }
+}
+
+fn escape(text: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            ch if ch == quote => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Whether `word` reads back as a plain identifier rather than a keyword
+fn is_identifier(word: &str) -> bool {
+    matches!(
+        Lexer::new(word).tokenize().as_slice(),
+        [Token::Identifier(found), Token::Eof] if found == word
+    )
+}
+
+/// Whether an object key can be written without quotes
+fn is_plain_key(key: &str) -> bool {
+    matches!(
+        Lexer::new(&format!("{}:", key)).tokenize().as_slice(),
+        [Token::Identifier(found), Token::Colon, Token::Eof] if found == key
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        format_source(source).unwrap()
+    }
+
+    #[test]
+    fn test_normalizes_spacing_and_indentation() {
+        assert_eq!(
+            format("~x   is 1+2*3\nloop (\n  if ~x>10 break-loop\n        ~x up 1\n)"),
+            "~x is 1 + 2 * 3\nloop (\n    if ~x > 10 break-loop\n    ~x up 1\n)\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_needed_parentheses() {
+        assert_eq!(format("~x is (1 + 2) * 3"), "~x is (1 + 2) * 3\n");
+        assert_eq!(format("~x is 10 - (4 - 3)"), "~x is 10 - (4 - 3)\n");
+        assert_eq!(format("~x is ((10 - 4)) - 3"), "~x is 10 - 4 - 3\n");
+        assert_eq!(
+            format("say \"n\" (*twice 2) \"!\""),
+            "say \"n\" (*twice 2) \"!\"\n"
+        );
+        assert_eq!(format("if (*is-big ~x) say 1"), "if (*is-big ~x) say 1\n");
+    }
+
+    #[test]
+    fn test_calls() {
+        assert_eq!(
+            format("~x is map ~list *double\n~y is *length ~x"),
+            "~x is map ~list *double\n~y is length ~x\n"
+        );
+        // A script's own `add` shadows the stdlib one
+        assert_eq!(
+            format("function add ~a ~b (give ~a)\n~z is *add 1 2"),
+            "function add ~a ~b (\n    give ~a\n)\n~z is *add 1 2\n"
+        );
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let source = "# header\n\n\n~x is 1   # one\n~y is 2\n\n# about z\n~z is 3\n\n";
+        assert_eq!(
+            format(source),
+            "# header\n\n~x is 1 # one\n~y is 2\n\n# about z\n~z is 3\n"
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            format(r#"~x is [1.0, -2, 'a`b', "q\"t", {name: "Ada", "two words": true}]"#),
+            "~x is [1.0, -2, 'a`b', \"q\\\"t\", {name: \"Ada\", \"two words\": true}]\n"
+        );
+    }
+
+    #[test]
+    fn test_long_lists_split() {
+        let formatted = format(&format!("~x is [{}]", vec!["\"item\""; 12].join(", ")));
+        assert!(formatted.starts_with("~x is [\n    \"item\",\n"));
+        assert!(formatted.ends_with("    \"item\"\n]\n"));
+    }
+}
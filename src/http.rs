@@ -1,4 +1,4 @@
-use crate::value::{ErrorValue, Value};
+use crate::value::{ErrorValue, ObjectMap, Value};
 use std::collections::HashMap;
 
 /// HTTP response object containing status, headers, and body
@@ -14,7 +14,7 @@ pub struct HttpResponse {
 
 impl HttpResponse {
     pub fn to_tails_value(&self) -> Value {
-        let mut response_map = HashMap::new();
+        let mut response_map = ObjectMap::new();
 
         response_map.insert("status".to_string(), Value::Number(self.status as f64));
         response_map.insert(
@@ -28,7 +28,7 @@ impl HttpResponse {
         );
 
         // Convert headers to Tilde object
-        let mut headers_map = HashMap::new();
+        let mut headers_map = ObjectMap::new();
         for (key, value) in &self.headers {
             headers_map.insert(key.clone(), Value::String(value.clone()));
         }
@@ -77,7 +77,7 @@ impl HttpResponse {
                 Value::List(tails_list)
             }
             serde_json::Value::Object(obj) => {
-                let mut tails_map = HashMap::new();
+                let mut tails_map = ObjectMap::new();
                 for (key, value) in obj {
                     tails_map.insert(key, Self::json_to_tails_value(value));
                 }
@@ -182,6 +182,9 @@ impl HttpRequest {
             )),
             Value::Error(_) => Err("Cannot serialize Error values to JSON".to_string()),
             Value::Pattern(pattern) => Ok(serde_json::Value::String(pattern.notation())),
+            Value::Task(_) => Err("Cannot serialize task handles to JSON".to_string()),
+            Value::Channel(_) => Err("Cannot serialize channels to JSON".to_string()),
+            Value::Handle(_) => Err("Cannot serialize host handles to JSON".to_string()),
         }
     }
 }
@@ -369,7 +372,7 @@ impl HttpClient {
                     "Invalid URL format".to_string(),
                     Some("invalid_url".to_string()),
                     Some(request.url),
-                    HashMap::new(),
+                    ObjectMap::new(),
                 ));
             }
             _ => {
@@ -469,7 +472,7 @@ impl HttpClient {
                     format!("Unsupported HTTP method: {}", request.method),
                     Some("unsupported_method".to_string()),
                     Some(request.url.clone()),
-                    HashMap::new(),
+                    ObjectMap::new(),
                 ));
             }
         };
@@ -523,7 +526,7 @@ impl HttpClient {
 
                 // Check if this is a client or server error (4xx or 5xx)
                 if status >= 400 {
-                    let mut error_context = HashMap::new();
+                    let mut error_context = ObjectMap::new();
                     error_context.insert("status".to_string(), Value::Number(status as f64));
                     error_context.insert("status_text".to_string(), Value::String(status_text));
                     error_context.insert(
@@ -536,7 +539,7 @@ impl HttpClient {
                     );
 
                     // Include headers in error context
-                    let mut headers_map = HashMap::new();
+                    let mut headers_map = ObjectMap::new();
                     for (key, value) in &http_response.headers {
                         headers_map.insert(key.clone(), Value::String(value.clone()));
                     }
@@ -554,7 +557,7 @@ impl HttpClient {
             }
             Err(error) => {
                 // Handle ureq errors
-                let mut error_context = HashMap::new();
+                let mut error_context = ObjectMap::new();
                 error_context.insert(
                     "response_time_ms".to_string(),
                     Value::Number(response_time_ms as f64),
@@ -599,7 +602,7 @@ impl HttpClient {
             "WASM HTTP client not yet implemented".to_string(),
             Some("not_implemented".to_string()),
             None,
-            HashMap::new(),
+            ObjectMap::new(),
         ))
     }
 
@@ -607,13 +610,13 @@ impl HttpClient {
         message: String,
         code: Option<String>,
         source: Option<String>,
-        context: HashMap<String, Value>,
+        context: ObjectMap,
     ) -> Value {
         Value::Error(ErrorValue {
             message,
             code,
             source,
-            context,
+            context: Box::new(context),
         })
     }
 }
@@ -0,0 +1,283 @@
+//! Where `say`, `ask` and `clear` read and write.
+//!
+//! Every `Evaluator` holds an `IoStreams`: an output stream, an error stream
+//! and an input stream. By default these are the process's stdout, stderr and
+//! stdin. Hosts that need to capture or script a run swap in `MemoryOutput`
+//! and `MemoryInput`, or their own implementations:
+//!
+//! ```
+//! use std::sync::Arc;
+//! use tilde::evaluator::Evaluator;
+//! use tilde::io::{MemoryInput, MemoryOutput};
+//! use tilde::parser::Parser;
+//!
+//! let output = Arc::new(MemoryOutput::new());
+//! let mut evaluator = Evaluator::new();
+//! evaluator.set_output(output.clone());
+//! evaluator.set_input(Arc::new(MemoryInput::new(["Ada"])));
+//!
+//! let program = Parser::new(r#"~name is ask "Name? "
+//! say "Hello, " ~name"#).parse().unwrap();
+//! evaluator.eval_program(program).unwrap();
+//!
+//! assert_eq!(output.lines(), vec!["Name? Ada", "Hello, Ada"]);
+//! ```
+//!
+//! Streams are shared with spawned tasks, so they must be `Send + Sync` and
+//! take `&self`.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, Seek, Write};
+use std::sync::{Arc, Mutex};
+
+/// Somewhere text can be written, such as a terminal or a buffer
+pub trait OutputStream: Send + Sync {
+    /// Write text as-is, without adding a newline
+    fn write(&self, text: &str) -> Result<(), String>;
+
+    /// Write a line of text followed by a newline
+    fn write_line(&self, line: &str) -> Result<(), String> {
+        self.write(&format!("{}\n", line))
+    }
+
+    /// Clear everything written so far, like clearing a screen
+    fn clear(&self) -> Result<(), String>;
+}
+
+/// Somewhere lines of text can be read from
+pub trait InputStream: Send + Sync {
+    /// Read one line without its line ending, or `None` at the end of input.
+    ///
+    /// `ask` has already written `prompt` to the output stream. It is passed
+    /// along for inputs that show their own prompt, like a browser dialog.
+    fn read_line(&self, prompt: &str) -> Result<Option<String>, String>;
+
+    /// Whether the answer already appears after the prompt, as it does when
+    /// someone types into a terminal. If not, `ask` writes it to the output
+    /// so the transcript reads the same.
+    fn echoes(&self) -> bool {
+        false
+    }
+}
+
+/// The streams an evaluator reads from and writes to
+#[derive(Clone)]
+pub struct IoStreams {
+    /// Where `say` writes and `clear` clears
+    pub output: Arc<dyn OutputStream>,
+    /// Where diagnostics meant for the user, not the program's output, go
+    pub error_output: Arc<dyn OutputStream>,
+    /// Where `ask` reads answers from
+    pub input: Arc<dyn InputStream>,
+}
+
+impl Default for IoStreams {
+    fn default() -> Self {
+        IoStreams {
+            output: Arc::new(StdoutStream),
+            error_output: Arc::new(StderrStream),
+            input: Arc::new(StdinStream),
+        }
+    }
+}
+
+impl fmt::Debug for IoStreams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoStreams").finish_non_exhaustive()
+    }
+}
+
+/// The process's standard output. `clear` sends ANSI escape codes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutStream;
+
+impl OutputStream for StdoutStream {
+    fn write(&self, text: &str) -> Result<(), String> {
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(text.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| format!("Failed to write output: {}", e))
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        // ESC c resets the screen, ESC [H moves the cursor to the top-left
+        self.write("\x1bc\x1b[H")
+    }
+}
+
+/// The process's standard error. Clearing it does nothing, since the screen
+/// is cleared through stdout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrStream;
+
+impl OutputStream for StderrStream {
+    fn write(&self, text: &str) -> Result<(), String> {
+        io::stderr()
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("Failed to write error output: {}", e))
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A file, truncated when opened. Clearing it empties the file.
+#[derive(Debug)]
+pub struct FileOutput {
+    file: Mutex<std::fs::File>,
+}
+
+impl FileOutput {
+    pub fn create(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path)
+            .map_err(|e| format!("Failed to create '{}': {}", path.display(), e))?;
+        Ok(FileOutput {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl OutputStream for FileOutput {
+    fn write(&self, text: &str) -> Result<(), String> {
+        let mut file = self.file.lock().unwrap();
+        file.write_all(text.as_bytes())
+            .map_err(|e| format!("Failed to write file: {}", e))
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        let mut file = self.file.lock().unwrap();
+        file.set_len(0)
+            .and_then(|_| file.rewind())
+            .map_err(|e| format!("Failed to clear file: {}", e))
+    }
+}
+
+/// The process's standard input
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdinStream;
+
+impl InputStream for StdinStream {
+    fn read_line(&self, _prompt: &str) -> Result<Option<String>, String> {
+        let mut line = String::new();
+        let read = io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+        if read == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+
+    fn echoes(&self) -> bool {
+        true
+    }
+}
+
+/// Output collected in memory, for tests and embedding hosts
+#[derive(Debug, Default)]
+pub struct MemoryOutput {
+    contents: Mutex<String>,
+}
+
+impl MemoryOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written since the last clear
+    pub fn contents(&self) -> String {
+        self.contents.lock().unwrap().clone()
+    }
+
+    /// The written text split into lines, without line endings
+    pub fn lines(&self) -> Vec<String> {
+        self.contents().lines().map(str::to_string).collect()
+    }
+
+    /// Return everything written so far and empty the buffer
+    pub fn take(&self) -> String {
+        std::mem::take(&mut *self.contents.lock().unwrap())
+    }
+}
+
+impl OutputStream for MemoryOutput {
+    fn write(&self, text: &str) -> Result<(), String> {
+        self.contents.lock().unwrap().push_str(text);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.contents.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+/// Scripted input, answered one line per `ask`. Runs out with `None`.
+#[derive(Debug, Default)]
+pub struct MemoryInput {
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl MemoryInput {
+    pub fn new<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        MemoryInput {
+            lines: Mutex::new(lines.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Queue another line to be read
+    pub fn push_line(&self, line: impl Into<String>) {
+        self.lines.lock().unwrap().push_back(line.into());
+    }
+}
+
+impl InputStream for MemoryInput {
+    fn read_line(&self, _prompt: &str) -> Result<Option<String>, String> {
+        Ok(self.lines.lock().unwrap().pop_front())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_output_collects_lines() {
+        let output = MemoryOutput::new();
+        output.write("Name? ").unwrap();
+        output.write_line("Ada").unwrap();
+        output.write_line("done").unwrap();
+
+        assert_eq!(output.contents(), "Name? Ada\ndone\n");
+        assert_eq!(output.lines(), vec!["Name? Ada", "done"]);
+        assert_eq!(output.take(), "Name? Ada\ndone\n");
+        assert_eq!(output.contents(), "");
+    }
+
+    #[test]
+    fn test_memory_output_clear() {
+        let output = MemoryOutput::new();
+        output.write_line("before").unwrap();
+        output.clear().unwrap();
+        output.write_line("after").unwrap();
+        assert_eq!(output.lines(), vec!["after"]);
+    }
+
+    #[test]
+    fn test_memory_input_runs_out() {
+        let input = MemoryInput::new(["one"]);
+        input.push_line("two");
+        assert_eq!(input.read_line("").unwrap(), Some("one".to_string()));
+        assert_eq!(input.read_line("").unwrap(), Some("two".to_string()));
+        assert_eq!(input.read_line("").unwrap(), None);
+    }
+}
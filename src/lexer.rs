@@ -1,4 +1,9 @@
 use crate::ast::{Expression, InterpolationPart};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Built-in block names that hosts can't register
+pub const BUILTIN_BLOCKS: &[&str] = &["core", "math"];
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -80,11 +85,40 @@ pub enum Token {
     Eof,
 }
 
+/// A `#` comment. The lexer skips comments but keeps them here so tools like
+/// `tilde fmt` can put them back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The comment from its `#`, without trailing whitespace
+    pub text: String,
+    /// Whether the comment is alone on its line rather than after code
+    pub own_line: bool,
+    /// Index of the token that follows the comment, set by `tokenize_with_lines`
+    pub before_token: usize,
+}
+
+impl Comment {
+    /// The text of a `##` doc comment, without the `##` and the space after
+    /// it. Doc comments sit on their own lines above what they document.
+    pub fn doc(&self) -> Option<&str> {
+        let text = self.text.strip_prefix("##")?;
+        if !self.own_line || text.starts_with('#') {
+            return None;
+        }
+        Some(text.strip_prefix(' ').unwrap_or(text))
+    }
+}
+
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
     after_block: bool,
+    comments: Vec<Comment>,
+    /// Where the token being read starts
+    token_start: usize,
+    /// Block names registered by the embedding host, besides the built-in ones
+    host_blocks: HashSet<String>,
 }
 
 impl Lexer {
@@ -101,9 +135,24 @@ impl Lexer {
             position: 0,
             current_char,
             after_block: false,
+            comments: Vec::new(),
+            token_start: 0,
+            host_blocks: HashSet::new(),
         }
     }
 
+    /// Also lex `name:` as a block for each of the host's block names, so
+    /// scripts can call host functions as `name:function`
+    pub fn with_blocks(mut self, blocks: &HashSet<String>) -> Self {
+        self.host_blocks = blocks.clone();
+        self
+    }
+
+    /// The comments skipped so far, in source order
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        std::mem::take(&mut self.comments)
+    }
+
     fn advance(&mut self) {
         self.position += 1;
         self.current_char = if self.position < self.input.len() {
@@ -234,9 +283,14 @@ impl Lexer {
             }
 
             if ch == '`' {
-                // Save any accumulated text
+                // Save any accumulated text, joining it to text before a
+                // backtick that didn't hold a variable
                 if !current_text.is_empty() {
-                    parts.push(InterpolationPart::Text(current_text.clone()));
+                    if let Some(InterpolationPart::Text(text)) = parts.last_mut() {
+                        text.push_str(&current_text);
+                    } else {
+                        parts.push(InterpolationPart::Text(current_text.clone()));
+                    }
                     current_text.clear();
                 }
 
@@ -286,7 +340,11 @@ impl Lexer {
 
         // Add any remaining text
         if !current_text.is_empty() {
-            parts.push(InterpolationPart::Text(current_text));
+            if let Some(InterpolationPart::Text(text)) = parts.last_mut() {
+                text.push_str(&current_text);
+            } else {
+                parts.push(InterpolationPart::Text(current_text));
+            }
         }
 
         parts
@@ -313,20 +371,32 @@ impl Lexer {
     }
 
     fn skip_comment(&mut self) {
-        // Skip the '#' character
-        self.advance();
+        let own_line = self.input[..self.position]
+            .iter()
+            .rev()
+            .find(|&&c| !matches!(c, ' ' | '\t' | '\r'))
+            .is_none_or(|&c| c == '\n');
+        let mut text = String::new();
 
         // Skip everything until newline or end of file
         while let Some(ch) = self.current_char {
             if ch == '\n' {
                 break; // Stop at newline, but don't consume it
             }
+            text.push(ch);
             self.advance();
         }
+
+        self.comments.push(Comment {
+            text: text.trim_end().to_string(),
+            own_line,
+            before_token: 0,
+        });
     }
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.token_start = self.position.min(self.input.len());
 
         // Reset after_block flag for most tokens (set to true only when we produce a Block token)
         let was_after_block = self.after_block;
@@ -579,8 +649,55 @@ impl Lexer {
         tokens
     }
 
+    /// Tokenize, also returning the 1-based line each token starts on
+    pub fn tokenize_with_lines(&mut self) -> (Vec<Token>, Vec<usize>) {
+        let mut tokens = Vec::new();
+        let mut lines = Vec::new();
+        let mut line = 1;
+        let mut counted = 0;
+
+        loop {
+            self.skip_whitespace();
+            let start = self.position.min(self.input.len());
+            line += self.input[counted..start].iter().filter(|&&c| c == '\n').count();
+            counted = start;
+
+            let skipped = self.comments.len();
+            let token = self.next_token();
+            for comment in &mut self.comments[skipped..] {
+                comment.before_token = tokens.len();
+            }
+            let done = token == Token::Eof;
+            tokens.push(token);
+            lines.push(line);
+            if done {
+                break;
+            }
+        }
+
+        (tokens, lines)
+    }
+
+    /// Tokenize, also returning the range of characters (not bytes) each
+    /// token covers, for tools like the language server
+    pub fn tokenize_with_spans(&mut self) -> Vec<(Token, Range<usize>)> {
+        let mut tokens = Vec::new();
+
+        loop {
+            let token = self.next_token();
+            let span = self.token_start..self.position.min(self.input.len());
+            let done = token == Token::Eof;
+            tokens.push((token, span));
+            if done {
+                break;
+            }
+        }
+
+        tokens
+    }
+
     fn is_known_block(&self, ident: &str) -> bool {
-        matches!(ident, "core" | "math")
+        BUILTIN_BLOCKS.contains(&ident) || self.host_blocks.contains(ident)
     }
 }
 
@@ -746,6 +863,17 @@ mod tests {
         assert_eq!(tokens[4], Token::Number(20.0, false));
     }
 
+    #[test]
+    fn test_token_spans() {
+        let mut lexer = Lexer::new("~é is *f  # note\n\"hi\"");
+        let spans: Vec<_> = lexer
+            .tokenize_with_spans()
+            .into_iter()
+            .map(|(_, span)| span)
+            .collect();
+        assert_eq!(spans, vec![0..2, 3..5, 6..8, 16..17, 17..21, 21..21]);
+    }
+
     #[test]
     fn test_comment_with_special_characters() {
         let mut lexer =
@@ -759,6 +887,21 @@ mod tests {
         assert_eq!(tokens[3], Token::Boolean(true));
     }
 
+    #[test]
+    fn test_doc_comments() {
+        let mut lexer = Lexer::new("## Adds one\n##\n### banner\n# plain\n~x is 1 ## trailing\n");
+        lexer.tokenize_with_lines();
+        let docs: Vec<_> = lexer
+            .take_comments()
+            .iter()
+            .map(|comment| comment.doc().map(str::to_string))
+            .collect();
+        assert_eq!(
+            docs,
+            vec![Some("Adds one".to_string()), Some(String::new()), None, None, None]
+        );
+    }
+
     #[test]
     fn test_interpolated_string_with_property_access() {
         let mut lexer = Lexer::new("\"Hello `~user.name`!\"");
@@ -923,4 +1066,16 @@ mod tests {
         assert_eq!(tokens[2], Token::String("hello world".to_string()));
         assert_eq!(tokens[3], Token::Eof);
     }
+
+    #[test]
+    fn test_tokenize_with_lines() {
+        let mut lexer = Lexer::new("~a is 1\n# note\n\n~b is \"x\ny\"\nsay ~b");
+        let (tokens, lines) = lexer.tokenize_with_lines();
+
+        assert_eq!(tokens.len(), lines.len());
+        let line_of = |wanted: Token| lines[tokens.iter().position(|t| *t == wanted).unwrap()];
+        assert_eq!(line_of(Token::Variable("a".to_string())), 1);
+        assert_eq!(line_of(Token::Variable("b".to_string())), 4);
+        assert_eq!(line_of(Token::Say), 6);
+    }
 }
@@ -1,15 +1,34 @@
 pub mod ast;
+pub mod bundle;
+pub mod concurrency;
+pub mod debugger;
+pub mod doc;
+pub mod embed;
 pub mod evaluator;
 pub mod file_io;
+pub mod formatter;
 pub mod http;
 pub mod intern;
+pub mod io;
 pub mod lexer;
+pub mod limits;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod line_editor;
+pub mod lint;
+pub mod lsp;
 pub mod music;
+pub mod package;
 pub mod parser;
+pub mod profiler;
 pub mod random;
+pub mod repl;
+pub mod sandbox;
 pub mod stdlib;
 pub mod terminal;
+pub mod testing;
+pub mod trace;
 pub mod value;
+pub mod watch;
 
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
@@ -0,0 +1,333 @@
+//! Resource limits that stop runaway scripts.
+//!
+//! Limits are part of `EvaluatorConfig`. Hitting one raises a `limit_exceeded`
+//! error. By default that error is uncatchable: attempt/rescue lets it through
+//! so a script can't swallow it and keep running.
+
+use crate::value::{ErrorValue, Value};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Call depth used when no other limit is configured
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 100;
+
+/// Upper bounds on what a single run of a script may use
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceLimits {
+    /// Statements, loop iterations and function calls executed
+    pub max_steps: Option<u64>,
+    /// Wall-clock time for one run
+    pub timeout: Option<Duration>,
+    /// Approximate bytes held in global variables, and in any single assigned value
+    pub max_memory: Option<usize>,
+    /// Bytes written by `say`
+    pub max_output: Option<usize>,
+    /// Nested function calls
+    pub max_call_depth: usize,
+    /// Whether attempt/rescue can catch `limit_exceeded` errors. A caught step
+    /// or time limit starts a fresh budget.
+    pub catchable: bool,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            max_steps: None,
+            timeout: None,
+            max_memory: None,
+            max_output: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            catchable: false,
+        }
+    }
+}
+
+impl ResourceLimits {
+    pub fn with_max_steps(mut self, steps: u64) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    pub fn with_max_output(mut self, bytes: usize) -> Self {
+        self.max_output = Some(bytes);
+        self
+    }
+
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = depth;
+        self
+    }
+
+    pub fn catchable(mut self, catchable: bool) -> Self {
+        self.catchable = catchable;
+        self
+    }
+}
+
+/// A limit that can be exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps,
+    Time,
+    Memory,
+    Output,
+    CallDepth,
+}
+
+impl Limit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Limit::Steps => "steps",
+            Limit::Time => "time",
+            Limit::Memory => "memory",
+            Limit::Output => "output",
+            Limit::CallDepth => "call_depth",
+        }
+    }
+}
+
+/// Build the structured error for an exceeded limit
+pub fn limit_exceeded(limit: Limit, max: f64) -> ErrorValue {
+    let message = match limit {
+        Limit::Steps => format!("Step limit exceeded (max {} steps)", max),
+        Limit::Time => format!("Time limit exceeded (max {} seconds)", max),
+        Limit::Memory => format!("Memory limit exceeded (max {} bytes)", max),
+        Limit::Output => format!("Output limit exceeded (max {} bytes)", max),
+        Limit::CallDepth => format!("Maximum call depth ({}) exceeded", max),
+    };
+    ErrorValue::new(message)
+        .with_code("limit_exceeded")
+        .with_source(limit.name())
+        .with_context("limit", Value::String(limit.name().to_string()))
+        .with_context("max", Value::Number(max))
+}
+
+/// What the current run has used so far. Steps, time and output are shared
+/// with every task the run spawns, so a tree of tasks has one budget; a
+/// clone draws on the same budget and starts from the same memory total.
+#[derive(Debug, Clone)]
+pub(crate) struct ResourceUsage {
+    pub shared: Arc<SharedUsage>,
+    pub memory_bytes: usize,
+}
+
+impl ResourceUsage {
+    pub fn new() -> Self {
+        ResourceUsage {
+            shared: Arc::new(SharedUsage::new()),
+            memory_bytes: 0,
+        }
+    }
+
+    /// Reset the per-run counters. Memory is kept because variables outlive a run.
+    pub fn start_run(&mut self) {
+        self.shared = Arc::new(SharedUsage::new());
+    }
+
+    /// Give a fresh budget after a catchable limit was raised
+    pub fn renew(&self, limit: Limit) {
+        match limit {
+            Limit::Steps => self.shared.steps.store(0, Ordering::Relaxed),
+            Limit::Time => {
+                let elapsed = self.shared.started.elapsed().as_nanos() as u64;
+                self.shared.renewed_at.store(elapsed, Ordering::Relaxed);
+            }
+            Limit::Memory | Limit::Output | Limit::CallDepth => {}
+        }
+    }
+}
+
+/// The part of a run's usage that its tasks update from other threads
+#[derive(Debug)]
+pub(crate) struct SharedUsage {
+    steps: AtomicU64,
+    output_bytes: AtomicUsize,
+    started: Stopwatch,
+    /// Nanoseconds after `started` that a caught time limit restarted the clock
+    renewed_at: AtomicU64,
+    /// Set once an uncatchable limit is hit so attempt/rescue lets the error through
+    fatal: AtomicBool,
+}
+
+impl SharedUsage {
+    fn new() -> Self {
+        SharedUsage {
+            steps: AtomicU64::new(0),
+            output_bytes: AtomicUsize::new(0),
+            started: Stopwatch::start(),
+            renewed_at: AtomicU64::new(0),
+            fatal: AtomicBool::new(false),
+        }
+    }
+
+    /// Count one step and return the total so far
+    pub fn add_step(&self) -> u64 {
+        self.steps.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        let renewed_at = Duration::from_nanos(self.renewed_at.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(renewed_at)
+    }
+
+    /// Count output bytes unless they would take the total past `max`
+    pub fn add_output(&self, bytes: usize, max: Option<usize>) -> bool {
+        let written = self.output_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if max.is_some_and(|max| written > max) {
+            self.output_bytes.fetch_sub(bytes, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        self.fatal.load(Ordering::Relaxed)
+    }
+
+    pub fn set_fatal(&self) {
+        self.fatal.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Elapsed-time measurement that also works in the browser, where
+/// `std::time::Instant` is unavailable
+#[derive(Debug)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start_ms: f64,
+}
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Stopwatch {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            start_ms: js_sys::Date::now(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.start.elapsed()
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            Duration::from_secs_f64((js_sys::Date::now() - self.start_ms).max(0.0) / 1000.0)
+        }
+    }
+}
+
+/// Rough number of bytes a value occupies, counting nested items
+pub fn approximate_size(value: &Value) -> usize {
+    let heap = match value {
+        Value::String(s) => s.len(),
+        Value::List(items) => items.iter().map(approximate_size).sum(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, item)| key.len() + approximate_size(item))
+            .sum(),
+        Value::Error(error) => error.message.len(),
+        Value::Pattern(pattern) => pattern.notation().len(),
+        Value::Number(_)
+        | Value::Boolean(_)
+        | Value::Date(_)
+        | Value::Task(_)
+        | Value::Channel(_)
+        | Value::Handle(_)
+        | Value::Null => 0,
+    };
+    std::mem::size_of::<Value>() + heap
+}
+
+/// Rough size of a list after `items[index] = value`, counting the nulls
+/// that fill the gap when `index` is past the end
+pub fn assigned_list_size(items: &[Value], index: usize, value: &Value) -> usize {
+    let null = std::mem::size_of::<Value>();
+    let kept: usize = items
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != index)
+        .map(|(_, item)| approximate_size(item))
+        .sum();
+    let filled = index.saturating_sub(items.len()) * null;
+    null + kept + filled + approximate_size(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ObjectMap;
+
+    #[test]
+    fn test_approximate_size_counts_nested_values() {
+        let base = std::mem::size_of::<Value>();
+        let text = Value::String("hello".to_string());
+        assert_eq!(approximate_size(&text), base + 5);
+
+        let list = Value::List(vec![text.clone(), Value::Number(1.0)]);
+        assert_eq!(approximate_size(&list), 3 * base + 5);
+
+        let mut map = ObjectMap::new();
+        map.insert("key".to_string(), text);
+        assert_eq!(approximate_size(&Value::Object(map)), 2 * base + 8);
+    }
+
+    #[test]
+    fn test_assigned_list_size_counts_the_filled_gap() {
+        let base = std::mem::size_of::<Value>();
+        let one = Value::Number(1.0);
+        assert_eq!(assigned_list_size(&[], 0, &one), 2 * base);
+        assert_eq!(assigned_list_size(&[], 9, &one), 11 * base);
+        let items = vec![Value::String("hello".to_string()), Value::Null];
+        assert_eq!(assigned_list_size(&items, 0, &one), 3 * base);
+    }
+
+    #[test]
+    fn test_limit_error_is_structured() {
+        let error = limit_exceeded(Limit::Steps, 1000.0);
+        assert_eq!(error.code.as_deref(), Some("limit_exceeded"));
+        assert_eq!(
+            error.context.get("limit"),
+            Some(&Value::String("steps".to_string()))
+        );
+        assert_eq!(error.message, "Step limit exceeded (max 1000 steps)");
+    }
+
+    #[test]
+    fn test_renew_resets_only_the_tripped_budget() {
+        let usage = ResourceUsage::new();
+        for _ in 0..50 {
+            usage.shared.add_step();
+        }
+        assert!(usage.shared.add_output(10, None));
+        usage.renew(Limit::Steps);
+        assert_eq!(usage.shared.add_step(), 1);
+        assert!(!usage.shared.add_output(1, Some(10)));
+    }
+
+    #[test]
+    fn test_tasks_share_the_run_budget() {
+        let usage = ResourceUsage::new();
+        usage.shared.add_step();
+        let task = usage.clone();
+        assert_eq!(task.shared.add_step(), 2);
+        task.shared.set_fatal();
+        assert!(usage.shared.is_fatal());
+    }
+}
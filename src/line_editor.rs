@@ -0,0 +1,460 @@
+//! Line editing for the REPL.
+//!
+//! On a terminal, input is read in raw mode so the arrow keys move through
+//! the line and through history, Tab completes, and the usual Emacs-style
+//! control keys work. When input is piped in, or on platforms without raw
+//! mode, lines are read as they are.
+
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+
+/// The most history lines kept in the history file
+const MAX_HISTORY: usize = 1000;
+
+/// What reading a line gave
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadLine {
+    Line(String),
+    /// Ctrl-C: the line was abandoned
+    Interrupted,
+    /// Ctrl-D on an empty line, or the end of piped input
+    Eof,
+}
+
+/// Finds completions for the text before the cursor. Returns where the
+/// completed word starts and the candidates to replace it with.
+pub type Completer<'a> = dyn Fn(&str, usize) -> (usize, Vec<String>) + 'a;
+
+/// A key press, decoded from the bytes a terminal sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Interrupt,
+    EndOfInput,
+    KillToEnd,
+    KillToStart,
+    DeleteWord,
+    ClearScreen,
+    Ignored,
+}
+
+/// Read one key from a terminal's bytes, or `None` once they run out
+fn read_key(bytes: &mut impl Iterator<Item = u8>) -> Option<Key> {
+    let byte = bytes.next()?;
+    Some(match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 => Key::Interrupt,
+        0x04 => Key::EndOfInput,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x0b => Key::KillToEnd,
+        0x0c => Key::ClearScreen,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x15 => Key::KillToStart,
+        0x17 => Key::DeleteWord,
+        0x1b => read_escape(bytes),
+        byte if byte < 0x20 => Key::Ignored,
+        byte => {
+            // The rest of a UTF-8 sequence follows its first byte
+            let length = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut encoded = vec![byte];
+            encoded.extend(bytes.take(length - 1));
+            match std::str::from_utf8(&encoded)
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => Key::Char(c),
+                None => Key::Ignored,
+            }
+        }
+    })
+}
+
+/// Decode the rest of an escape sequence such as `ESC [ A`
+fn read_escape(bytes: &mut impl Iterator<Item = u8>) -> Key {
+    match bytes.next() {
+        Some(b'[') => {
+            let mut parameters = String::new();
+            for byte in bytes.by_ref() {
+                if (0x40..=0x7e).contains(&byte) {
+                    return match (byte, parameters.as_str()) {
+                        (b'A', _) => Key::Up,
+                        (b'B', _) => Key::Down,
+                        (b'C', _) => Key::Right,
+                        (b'D', _) => Key::Left,
+                        (b'H', _) | (b'~', "1" | "7") => Key::Home,
+                        (b'F', _) | (b'~', "4" | "8") => Key::End,
+                        (b'~', "3") => Key::Delete,
+                        _ => Key::Ignored,
+                    };
+                }
+                parameters.push(byte as char);
+            }
+            Key::Ignored
+        }
+        Some(b'O') => match bytes.next() {
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Ignored,
+        },
+        _ => Key::Ignored,
+    }
+}
+
+/// The line being edited
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Buffer {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl Buffer {
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    /// Apply an editing key. Keys that aren't edits are left to the caller.
+    fn edit(&mut self, key: Key) {
+        match key {
+            Key::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.chars.remove(self.cursor);
+            }
+            Key::Delete | Key::EndOfInput if self.cursor < self.chars.len() => {
+                self.chars.remove(self.cursor);
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.chars.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.chars.len(),
+            Key::KillToEnd => self.chars.truncate(self.cursor),
+            Key::KillToStart => {
+                self.chars.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::DeleteWord => {
+                let mut start = self.cursor;
+                while start > 0 && self.chars[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.chars[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.chars.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            _ => {}
+        }
+    }
+
+    /// Replace the word from `start` to the cursor
+    fn replace_word(&mut self, start: usize, replacement: &str) {
+        let start = start.min(self.cursor);
+        self.chars
+            .splice(start..self.cursor, replacement.chars())
+            .for_each(drop);
+        self.cursor = start + replacement.chars().count();
+    }
+}
+
+/// The longest text every candidate starts with
+fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+    let mut prefix: Vec<char> = first.chars().collect();
+    for candidate in &candidates[1..] {
+        let shared = prefix
+            .iter()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        prefix.truncate(shared);
+    }
+    prefix.into_iter().collect()
+}
+
+pub struct LineEditor {
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+}
+
+impl LineEditor {
+    /// An editor keeping its history in `history_file`, if given
+    pub fn new(history_file: Option<PathBuf>) -> Self {
+        let history = history_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        LineEditor {
+            history,
+            history_file,
+        }
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Remember a line, saving it to the history file. Blank lines and
+    /// repeats of the line before aren't kept.
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.drain(..self.history.len() - MAX_HISTORY);
+        }
+        if let Some(path) = &self.history_file {
+            // History is a convenience, so failing to save it isn't an error
+            let _ = fs::write(path, self.history.join("\n") + "\n");
+        }
+    }
+
+    /// Read a line, editing it in place on a terminal
+    pub fn read_line(&mut self, prompt: &str, complete: &Completer) -> io::Result<ReadLine> {
+        #[cfg(unix)]
+        if let Some(raw) = raw_mode::RawMode::enable() {
+            let result = self.edit_line(prompt, complete);
+            drop(raw);
+            return result;
+        }
+
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(ReadLine::Eof);
+        }
+        Ok(ReadLine::Line(
+            line.trim_end_matches(['\n', '\r']).to_string(),
+        ))
+    }
+
+    fn edit_line(&mut self, prompt: &str, complete: &Completer) -> io::Result<ReadLine> {
+        let mut stdout = io::stdout();
+        let mut bytes = io::stdin().lock().bytes().map_while(Result::ok);
+        let mut buffer = Buffer::default();
+        // Where we are in history while browsing it, and the line we left
+        let mut browsing: Option<usize> = None;
+        let mut unsaved = String::new();
+        let mut last_was_tab = false;
+
+        redraw(&mut stdout, prompt, &buffer)?;
+        loop {
+            let Some(key) = read_key(&mut bytes) else {
+                return Ok(ReadLine::Eof);
+            };
+            let tab = key == Key::Tab;
+            match key {
+                Key::Enter => {
+                    write!(stdout, "\r\n")?;
+                    return Ok(ReadLine::Line(buffer.text()));
+                }
+                Key::Interrupt => {
+                    write!(stdout, "^C\r\n")?;
+                    return Ok(ReadLine::Interrupted);
+                }
+                Key::EndOfInput if buffer.chars.is_empty() => {
+                    write!(stdout, "\r\n")?;
+                    return Ok(ReadLine::Eof);
+                }
+                Key::Up | Key::Down => {
+                    let index = match (key, browsing) {
+                        (Key::Up, None) if !self.history.is_empty() => {
+                            unsaved = buffer.text();
+                            Some(self.history.len() - 1)
+                        }
+                        (Key::Up, Some(index)) => Some(index.saturating_sub(1)),
+                        (Key::Down, Some(index)) if index + 1 < self.history.len() => {
+                            Some(index + 1)
+                        }
+                        (Key::Down, Some(_)) => {
+                            buffer.set(&unsaved);
+                            None
+                        }
+                        _ => browsing,
+                    };
+                    if let Some(index) = index {
+                        buffer.set(&self.history[index]);
+                    }
+                    browsing = index;
+                }
+                Key::Tab => {
+                    let text = buffer.text();
+                    let before: String = buffer.chars[..buffer.cursor].iter().collect();
+                    let (start, candidates) = complete(&text, before.len());
+                    let start = before[..start.min(before.len())].chars().count();
+                    let prefix = common_prefix(&candidates);
+                    let typed: String = buffer.chars[start..buffer.cursor].iter().collect();
+                    if candidates.len() == 1 || prefix.chars().count() > typed.chars().count() {
+                        buffer.replace_word(start, &prefix);
+                    } else if candidates.len() > 1 && last_was_tab {
+                        // A second Tab lists what the word could be
+                        write!(stdout, "\r\n{}\r\n", candidates.join("  "))?;
+                    }
+                }
+                Key::ClearScreen => write!(stdout, "\x1b[2J\x1b[H")?,
+                key => buffer.edit(key),
+            }
+            last_was_tab = tab;
+            redraw(&mut stdout, prompt, &buffer)?;
+        }
+    }
+}
+
+/// Draw the prompt and line, and put the cursor in place
+fn redraw(out: &mut impl Write, prompt: &str, buffer: &Buffer) -> io::Result<()> {
+    write!(out, "\r{}{}\x1b[K", prompt, buffer.text())?;
+    let column = prompt.chars().count() + buffer.cursor;
+    write!(out, "\r")?;
+    if column > 0 {
+        write!(out, "\x1b[{}C", column)?;
+    }
+    out.flush()
+}
+
+#[cfg(unix)]
+mod raw_mode {
+    /// Puts the terminal in raw mode until dropped
+    pub struct RawMode {
+        original: libc::termios,
+    }
+
+    impl RawMode {
+        /// `None` if standard input isn't a terminal
+        pub fn enable() -> Option<Self> {
+            // SAFETY: termios is plain data, and tcgetattr fills it in
+            unsafe {
+                if libc::isatty(libc::STDIN_FILENO) == 0 || libc::isatty(libc::STDOUT_FILENO) == 0 {
+                    return None;
+                }
+                let mut original: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                    return None;
+                }
+                let mut raw = original;
+                raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+                raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) != 0 {
+                    return None;
+                }
+                Some(RawMode { original })
+            }
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            // SAFETY: restores the settings tcgetattr gave us
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(input: &[u8]) -> Vec<Key> {
+        let mut bytes = input.iter().copied();
+        std::iter::from_fn(|| read_key(&mut bytes)).collect()
+    }
+
+    #[test]
+    fn test_decodes_keys() {
+        assert_eq!(
+            keys(b"a\x1b[A\x1b[D\x1b[3~\x1bOH\x7f\t\r"),
+            vec![
+                Key::Char('a'),
+                Key::Up,
+                Key::Left,
+                Key::Delete,
+                Key::Home,
+                Key::Backspace,
+                Key::Tab,
+                Key::Enter,
+            ]
+        );
+        assert_eq!(keys("é".as_bytes()), vec![Key::Char('é')]);
+    }
+
+    #[test]
+    fn test_edits_buffer() {
+        let mut buffer = Buffer::default();
+        for key in "say ~x".chars().map(Key::Char) {
+            buffer.edit(key);
+        }
+        buffer.edit(Key::Home);
+        buffer.edit(Key::Delete);
+        buffer.edit(Key::Char('S'));
+        assert_eq!(buffer.text(), "Say ~x");
+        buffer.edit(Key::End);
+        buffer.edit(Key::DeleteWord);
+        assert_eq!(buffer.text(), "Say ");
+        buffer.replace_word(0, "ask");
+        assert_eq!((buffer.text(), buffer.cursor), ("ask".to_string(), 3));
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let candidates = vec![
+            "~total".to_string(),
+            "~totals".to_string(),
+            "~top".to_string(),
+        ];
+        assert_eq!(common_prefix(&candidates), "~to");
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn test_history_file() {
+        let path = std::env::temp_dir().join("tilde_line_editor_history");
+        let _ = fs::remove_file(&path);
+        let mut editor = LineEditor::new(Some(path.clone()));
+        editor.add_history("~x is 1");
+        editor.add_history("~x is 1");
+        editor.add_history("   ");
+        editor.add_history("say ~x");
+
+        let reloaded = LineEditor::new(Some(path.clone()));
+        assert_eq!(reloaded.history(), ["~x is 1", "say ~x"]);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,811 @@
+//! Static checks for `tilde lint`.
+//!
+//! The linter walks a parsed program without running it and reports likely
+//! mistakes, each under a rule ID such as `unused-variable`. Functions can see
+//! every global variable, so a variable counts as defined if it is assigned
+//! anywhere it could be seen from, not only above the line that reads it.
+//!
+//! A `# lint-ignore` comment silences every rule on its line, or on the next
+//! line when the comment has a line to itself. Follow it with rule IDs to
+//! silence only those: `# lint-ignore unused-variable, unknown-function`.
+
+use crate::ast::{ChainStep, Expression, InterpolationPart, Program, Statement};
+use crate::evaluator::ARGS_VARIABLE;
+use crate::parser::{ParseError, Parser};
+use crate::stdlib::get_stdlib_function_names;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Functions the evaluator handles itself instead of through the stdlib
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "say", "ask", "get", "post", "put", "delete", "patch", "http", "run", "wait", "read", "write",
+    "clear",
+];
+
+/// Chain step the parser inserts for a chain's starting value
+const CHAIN_INITIAL_VALUE: &str = "@@initial-value";
+
+/// What a diagnostic is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedVariable,
+    UnusedFunction,
+    UndefinedVariable,
+    UnknownFunction,
+    ShadowedStdlib,
+    UnreachableCode,
+    BreakOutsideLoop,
+    ArityMismatch,
+    InfiniteLoop,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 9] = [
+        Rule::UnusedVariable,
+        Rule::UnusedFunction,
+        Rule::UndefinedVariable,
+        Rule::UnknownFunction,
+        Rule::ShadowedStdlib,
+        Rule::UnreachableCode,
+        Rule::BreakOutsideLoop,
+        Rule::ArityMismatch,
+        Rule::InfiniteLoop,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnusedFunction => "unused-function",
+            Rule::UndefinedVariable => "undefined-variable",
+            Rule::UnknownFunction => "unknown-function",
+            Rule::ShadowedStdlib => "shadowed-stdlib",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::BreakOutsideLoop => "break-outside-loop",
+            Rule::ArityMismatch => "arity-mismatch",
+            Rule::InfiniteLoop => "infinite-loop",
+        }
+    }
+
+    pub fn parse(id: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.id() == id)
+    }
+
+    /// Errors are problems that fail at runtime; warnings are suspicious but run
+    pub fn severity(&self) -> Severity {
+        match self {
+            Rule::UndefinedVariable
+            | Rule::UnknownFunction
+            | Rule::BreakOutsideLoop
+            | Rule::ArityMismatch => Severity::Error,
+            Rule::UnusedVariable
+            | Rule::UnusedFunction
+            | Rule::ShadowedStdlib
+            | Rule::UnreachableCode
+            | Rule::InfiniteLoop => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// One problem found by the linter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    /// The line of the statement the problem is in, when the program was
+    /// parsed with `Parser::with_locations`
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.rule.severity()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "line": self.line,
+            "rule": self.rule.id(),
+            "severity": self.severity().name(),
+            "message": self.message,
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}",
+            self.severity().name(),
+            self.rule.id(),
+            self.message
+        )
+    }
+}
+
+/// Parse and lint a script, leaving out diagnostics silenced by
+/// `# lint-ignore` comments. A script that doesn't parse gives all of its
+/// syntax errors instead.
+pub fn lint_source(source: &str) -> Result<Vec<Diagnostic>, Vec<ParseError>> {
+    let (program, errors) = Parser::new(source).with_locations().parse_recovering();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let suppressions = Suppressions::parse(source);
+    Ok(lint_program(&program)
+        .into_iter()
+        .filter(|diagnostic| !suppressions.silences(diagnostic))
+        .collect())
+}
+
+/// Lint a parsed program, ordered by line
+pub fn lint_program(program: &Program) -> Vec<Diagnostic> {
+    let mut linter = Linter::default();
+    linter.collect(program, false);
+    linter.line = None;
+    linter.check_body(program);
+    linter.finish()
+}
+
+/// Rules silenced by `# lint-ignore` comments, by line
+#[derive(Debug, Default)]
+struct Suppressions {
+    /// An empty list silences every rule
+    lines: HashMap<usize, Vec<String>>,
+}
+
+impl Suppressions {
+    fn parse(source: &str) -> Self {
+        let mut suppressions = Suppressions::default();
+        for (index, line) in source.lines().enumerate() {
+            let Some(comment) = comment_in(line) else {
+                continue;
+            };
+            let Some(rules) = comment.trim().strip_prefix("lint-ignore") else {
+                continue;
+            };
+            if !rules.is_empty() && !rules.starts_with(char::is_whitespace) {
+                continue;
+            }
+
+            let own_line = line.trim_start().starts_with('#');
+            let target = if own_line { index + 2 } else { index + 1 };
+            suppressions.lines.entry(target).or_default().extend(
+                rules
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|rule| !rule.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        suppressions
+    }
+
+    fn silences(&self, diagnostic: &Diagnostic) -> bool {
+        let Some(rules) = diagnostic.line.and_then(|line| self.lines.get(&line)) else {
+            return false;
+        };
+        rules.is_empty() || rules.iter().any(|rule| rule == diagnostic.rule.id())
+    }
+}
+
+/// The text after a line's `#`, if it has a comment outside a string
+fn comment_in(line: &str) -> Option<&str> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return Some(&line[index + 1..]),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Variables a user function can see of its own
+#[derive(Debug, Default)]
+struct FunctionScope {
+    params: HashSet<String>,
+    /// Variables assigned in the body, with the line of the first assignment
+    locals: HashMap<String, Option<usize>>,
+    reads: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct Linter {
+    /// User functions: parameter count and definition line
+    functions: HashMap<String, (usize, Option<usize>)>,
+    /// Variables assigned outside functions, with the line of the first assignment
+    globals: HashMap<String, Option<usize>>,
+    /// Other names visible everywhere: top-level loop variables and rescue variables
+    other_globals: HashSet<String>,
+    /// Every variable read anywhere, since functions see global variables
+    reads: HashSet<String>,
+    /// Every function called or passed by name
+    called: HashSet<String>,
+    /// Enclosing user functions, innermost last
+    scopes: Vec<FunctionScope>,
+    /// Loop variables and anonymous function parameters in scope
+    bindings: Vec<String>,
+    /// Whether the script imports modules, whose functions only exist at runtime
+    imports: bool,
+    line: Option<usize>,
+    loop_depth: usize,
+    reported: HashSet<(Rule, String, Option<usize>)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn report(&mut self, rule: Rule, message: String) {
+        self.report_at(rule, self.line, message);
+    }
+
+    fn report_at(&mut self, rule: Rule, line: Option<usize>, message: String) {
+        if self.reported.insert((rule, message.clone(), line)) {
+            self.diagnostics.push(Diagnostic {
+                rule,
+                line,
+                message,
+            });
+        }
+    }
+
+    /// First pass: find every function and global variable, so the checks
+    /// don't depend on definition order
+    fn collect(&mut self, body: &[Statement], in_function: bool) {
+        for statement in body {
+            self.collect_statement(statement, in_function);
+        }
+    }
+
+    fn collect_statement(&mut self, statement: &Statement, in_function: bool) {
+        match statement {
+            Statement::Located { line, statement } => {
+                self.line = Some(*line);
+                self.collect_statement(statement, in_function);
+            }
+            Statement::Expression(Expression::FunctionCall { name, .. }) if name == "import" => {
+                self.imports = true;
+            }
+            Statement::Assignment { variable, .. } | Statement::FunctionChain { variable, .. } => {
+                if !in_function {
+                    self.globals.entry(variable.clone()).or_insert(self.line);
+                }
+            }
+            Statement::FunctionDefinition {
+                name, params, body, ..
+            } => {
+                self.functions
+                    .entry(name.clone())
+                    .or_insert((params.len(), self.line));
+                self.collect(body, true);
+            }
+            Statement::ForEach {
+                variables, body, ..
+            } => {
+                if !in_function {
+                    self.other_globals.extend(variables.iter().cloned());
+                }
+                self.collect(body, in_function);
+            }
+            Statement::AttemptRescue {
+                attempt_body,
+                rescue_var,
+                rescue_body,
+            } => {
+                // The error is always bound as a global
+                self.other_globals.extend(rescue_var.iter().cloned());
+                self.collect(attempt_body, in_function);
+                self.collect(rescue_body, in_function);
+            }
+            Statement::If {
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                self.collect_statement(then_stmt, in_function);
+                if let Some(else_stmt) = else_stmt {
+                    self.collect_statement(else_stmt, in_function);
+                }
+            }
+            Statement::Loop { body }
+            | Statement::Block { body }
+            | Statement::Test { body, .. }
+            | Statement::Setup { body }
+            | Statement::Teardown { body } => self.collect(body, in_function),
+            Statement::PropertyAssignment { .. }
+            | Statement::Increment { .. }
+            | Statement::Decrement { .. }
+            | Statement::Breakloop
+            | Statement::Open(_)
+            | Statement::Give(_)
+            | Statement::Expression(_)
+            | Statement::Expect(_)
+            | Statement::Trivia(_)
+            | Statement::Error(_) => {}
+        }
+    }
+
+    fn check_body(&mut self, body: &[Statement]) {
+        let mut exited = false;
+        let mut reported = false;
+        for statement in body {
+            if exited && !reported {
+                let line = located_line(statement).or(self.line);
+                self.report_at(
+                    Rule::UnreachableCode,
+                    line,
+                    "this code never runs".to_string(),
+                );
+                reported = true;
+            }
+            self.check_statement(statement);
+            exited |= always_exits(statement, self.loop_depth > 0);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Located { line, statement } => {
+                let outer = self.line.replace(*line);
+                self.check_statement(statement);
+                self.line = outer;
+            }
+            Statement::Assignment { value, .. } => self.check_expression(value, false),
+            Statement::PropertyAssignment { object, value, .. } => {
+                self.check_expression(object, false);
+                self.check_expression(value, false);
+            }
+            Statement::If {
+                condition,
+                then_stmt,
+                else_stmt,
+            } => {
+                self.check_expression(condition, false);
+                self.check_statement(then_stmt);
+                if let Some(else_stmt) = else_stmt {
+                    self.check_statement(else_stmt);
+                }
+            }
+            Statement::Loop { body } => {
+                if !can_exit(body) {
+                    self.report(
+                        Rule::InfiniteLoop,
+                        "loop has no break-loop or give, so it never ends".to_string(),
+                    );
+                }
+                self.check_loop_body(body);
+            }
+            Statement::ForEach {
+                variables,
+                iterable,
+                body,
+            } => {
+                self.check_expression(iterable, false);
+                let outer = self.bindings.len();
+                self.bindings.extend(variables.iter().cloned());
+                self.check_loop_body(body);
+                self.bindings.truncate(outer);
+            }
+            Statement::Increment { variable, amount }
+            | Statement::Decrement { variable, amount } => {
+                self.check_expression(amount, false);
+                self.require_variable(variable);
+            }
+            Statement::Block { body }
+            | Statement::Test { body, .. }
+            | Statement::Setup { body }
+            | Statement::Teardown { body } => self.check_body(body),
+            Statement::Breakloop => {
+                if self.loop_depth == 0 {
+                    self.report(
+                        Rule::BreakOutsideLoop,
+                        "break-loop is not inside a loop".to_string(),
+                    );
+                }
+            }
+            Statement::Trivia(_) | Statement::Error(_) => {}
+            Statement::Open(expression)
+            | Statement::Give(expression)
+            | Statement::Expect(expression)
+            | Statement::Expression(expression) => self.check_expression(expression, false),
+            Statement::FunctionDefinition {
+                name, params, body, ..
+            } => self.check_function(name, params, body),
+            Statement::AttemptRescue {
+                attempt_body,
+                rescue_body,
+                ..
+            } => {
+                self.check_body(attempt_body);
+                self.check_body(rescue_body);
+            }
+            Statement::FunctionChain { steps, .. } => self.check_chain(steps),
+        }
+    }
+
+    fn check_loop_body(&mut self, body: &[Statement]) {
+        self.loop_depth += 1;
+        self.check_body(body);
+        self.loop_depth -= 1;
+    }
+
+    fn check_function(&mut self, name: &str, params: &[String], body: &[Statement]) {
+        if is_builtin(name) {
+            self.report(
+                Rule::ShadowedStdlib,
+                format!("function {} hides the standard library's {}", name, name),
+            );
+        }
+
+        let mut scope = FunctionScope {
+            params: params.iter().cloned().collect(),
+            ..FunctionScope::default()
+        };
+        let line = self.line;
+        collect_locals(body, line, &mut scope.locals);
+        self.scopes.push(scope);
+        // A loop around the definition isn't around the body when it runs
+        let outer_depth = std::mem::take(&mut self.loop_depth);
+        let outer_bindings = std::mem::take(&mut self.bindings);
+
+        self.check_body(body);
+
+        self.loop_depth = outer_depth;
+        self.bindings = outer_bindings;
+        self.line = line;
+        let scope = self.scopes.pop().unwrap_or_default();
+        let mut unused: Vec<_> = scope
+            .locals
+            .into_iter()
+            .filter(|(local, _)| !scope.reads.contains(local) && !scope.params.contains(local))
+            .collect();
+        unused.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        for (local, line) in unused {
+            self.report_at(
+                Rule::UnusedVariable,
+                line,
+                format!("~{} is assigned but never used", local),
+            );
+        }
+    }
+
+    fn check_chain(&mut self, steps: &[ChainStep]) {
+        for step in steps {
+            if step.function_name != CHAIN_INITIAL_VALUE {
+                self.reference_function(&step.function_name);
+            }
+            for arg in &step.args {
+                self.check_expression(arg, true);
+            }
+        }
+    }
+
+    /// `argument` is set for arguments of a call, where a bare name can be a
+    /// function passed by name, as in `map ~items uppercase`
+    fn check_expression(&mut self, expression: &Expression, argument: bool) {
+        match expression {
+            Expression::Number(..) | Expression::String(_) | Expression::Boolean(_) => {}
+            Expression::InterpolatedString(parts) => {
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(_) => {}
+                        InterpolationPart::Variable(name) => {
+                            let root = name.split('.').next().unwrap_or(name);
+                            self.read_variable(root);
+                        }
+                        InterpolationPart::Expression(expression) => {
+                            self.check_expression(expression, false)
+                        }
+                    }
+                }
+            }
+            Expression::Variable(name) => {
+                if let Some(function) = name.strip_prefix('.') {
+                    self.reference_function(function);
+                } else if argument && !self.is_visible(name) && self.is_function(name) {
+                    self.reference_function(name);
+                } else {
+                    self.read_variable(name);
+                }
+            }
+            Expression::List(items) => {
+                for item in items {
+                    self.check_expression(item, false);
+                }
+            }
+            Expression::BinaryOp { left, right, .. } => {
+                self.check_expression(left, false);
+                self.check_expression(right, false);
+            }
+            Expression::FunctionCall { name, args } => {
+                if argument && args.is_empty() {
+                    self.reference_function(name);
+                } else {
+                    self.call_function(name, args.len());
+                }
+                for arg in args {
+                    self.check_expression(arg, true);
+                }
+            }
+            Expression::PropertyAccess { object, property } => {
+                self.check_expression(object, false);
+                if let Some(variable) = property.strip_prefix('~') {
+                    self.read_variable(variable);
+                }
+            }
+            Expression::ObjectLiteral { pairs } => {
+                for (_, value) in pairs {
+                    self.check_expression(value, false);
+                }
+            }
+            Expression::AnonymousFunction { params, body } => {
+                let outer = self.bindings.len();
+                self.bindings.extend(params.iter().cloned());
+                self.check_expression(body, false);
+                self.bindings.truncate(outer);
+            }
+            Expression::FunctionChainExpression { steps, input } => {
+                self.check_expression(input, false);
+                self.check_chain(steps);
+            }
+        }
+    }
+
+    fn is_visible(&self, name: &str) -> bool {
+        self.bindings.iter().any(|bound| bound == name)
+            || self
+                .scopes
+                .last()
+                .is_some_and(|scope| scope.params.contains(name) || scope.locals.contains_key(name))
+            || self.globals.contains_key(name)
+            || self.other_globals.contains(name)
+            || name == ARGS_VARIABLE
+    }
+
+    fn read_variable(&mut self, name: &str) {
+        self.reads.insert(name.to_string());
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.reads.insert(name.to_string());
+        }
+        self.require_variable(name);
+    }
+
+    fn require_variable(&mut self, name: &str) {
+        if !self.is_visible(name) {
+            self.report(
+                Rule::UndefinedVariable,
+                format!("~{} is never assigned", name),
+            );
+        }
+    }
+
+    fn is_function(&self, name: &str) -> bool {
+        let name = name.strip_prefix('*').unwrap_or(name);
+        match name.split_once(':') {
+            Some(("core", function)) => is_builtin(function),
+            // Other blocks hold host functions, which only exist at runtime
+            Some(_) => true,
+            None => is_builtin(name) || self.functions.contains_key(name) || self.imports,
+        }
+    }
+
+    /// A function passed by name rather than called
+    fn reference_function(&mut self, name: &str) {
+        let name = name.strip_prefix('*').unwrap_or(name);
+        self.called.insert(name.to_string());
+        if !self.is_function(name) {
+            self.report(Rule::UnknownFunction, format!("unknown function {}", name));
+        }
+    }
+
+    fn call_function(&mut self, name: &str, arg_count: usize) {
+        self.reference_function(name);
+        if let Some(&(params, _)) = self.functions.get(name)
+            && params != arg_count
+        {
+            self.report(
+                Rule::ArityMismatch,
+                format!(
+                    "*{} takes {} but is given {}",
+                    name,
+                    plural(params, "argument"),
+                    arg_count
+                ),
+            );
+        }
+    }
+
+    fn finish(mut self) -> Vec<Diagnostic> {
+        let mut unused: Vec<_> = self
+            .globals
+            .iter()
+            .filter(|(name, _)| !self.reads.contains(*name))
+            .map(|(name, line)| {
+                let message = format!("~{} is assigned but never used", name);
+                (*line, message, Rule::UnusedVariable)
+            })
+            .collect();
+        unused.extend(
+            self.functions
+                .iter()
+                .filter(|(name, _)| !self.called.contains(*name))
+                .map(|(name, (_, line))| {
+                    let message = format!("function {} is never called", name);
+                    (*line, message, Rule::UnusedFunction)
+                }),
+        );
+        unused.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+        for (line, message, rule) in unused {
+            self.report_at(rule, line, message);
+        }
+
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        self.diagnostics
+    }
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTIN_FUNCTIONS.contains(&name) || get_stdlib_function_names().contains(&name)
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+fn located_line(statement: &Statement) -> Option<usize> {
+    match statement {
+        Statement::Located { line, .. } => Some(*line),
+        _ => None,
+    }
+}
+
+/// Variables a function body assigns, leaving out nested function definitions
+fn collect_locals(
+    body: &[Statement],
+    line: Option<usize>,
+    locals: &mut HashMap<String, Option<usize>>,
+) {
+    for statement in body {
+        collect_locals_in(statement, line, locals);
+    }
+}
+
+fn collect_locals_in(
+    statement: &Statement,
+    line: Option<usize>,
+    locals: &mut HashMap<String, Option<usize>>,
+) {
+    match statement {
+        Statement::Located { line, statement } => collect_locals_in(statement, Some(*line), locals),
+        Statement::Assignment { variable, .. } | Statement::FunctionChain { variable, .. } => {
+            locals.entry(variable.clone()).or_insert(line);
+        }
+        Statement::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            collect_locals_in(then_stmt, line, locals);
+            if let Some(else_stmt) = else_stmt {
+                collect_locals_in(else_stmt, line, locals);
+            }
+        }
+        Statement::Loop { body } | Statement::Block { body } | Statement::ForEach { body, .. } => {
+            collect_locals(body, line, locals)
+        }
+        Statement::AttemptRescue {
+            attempt_body,
+            rescue_body,
+            ..
+        } => {
+            collect_locals(attempt_body, line, locals);
+            collect_locals(rescue_body, line, locals);
+        }
+        _ => {}
+    }
+}
+
+/// Whether running this statement always leaves the enclosing body. Outside
+/// loops, `break-loop` is reported on its own and doesn't count.
+fn always_exits(statement: &Statement, in_loop: bool) -> bool {
+    match statement {
+        Statement::Give(_) => true,
+        Statement::Breakloop => in_loop,
+        Statement::Located { statement, .. } => always_exits(statement, in_loop),
+        Statement::Block { body } => body
+            .iter()
+            .any(|statement| always_exits(statement, in_loop)),
+        Statement::If {
+            then_stmt,
+            else_stmt: Some(else_stmt),
+            ..
+        } => always_exits(then_stmt, in_loop) && always_exits(else_stmt, in_loop),
+        _ => false,
+    }
+}
+
+/// Whether a loop body has a `break-loop` or `give` that leaves the loop
+fn can_exit(body: &[Statement]) -> bool {
+    body.iter().any(|statement| match statement {
+        Statement::Give(_) | Statement::Breakloop => true,
+        Statement::Located { statement, .. } => can_exit(std::slice::from_ref(statement)),
+        Statement::Block { body } => can_exit(body),
+        Statement::If {
+            then_stmt,
+            else_stmt,
+            ..
+        } => {
+            can_exit(std::slice::from_ref(then_stmt))
+                || else_stmt
+                    .as_ref()
+                    .is_some_and(|else_stmt| can_exit(std::slice::from_ref(else_stmt)))
+        }
+        Statement::AttemptRescue {
+            attempt_body,
+            rescue_body,
+            ..
+        } => can_exit(attempt_body) || can_exit(rescue_body),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_in_ignores_strings() {
+        assert_eq!(
+            comment_in(r##"say "# not a comment" # lint-ignore"##),
+            Some(" lint-ignore")
+        );
+        assert_eq!(comment_in(r#"say "a \" # b""#), None);
+        assert_eq!(comment_in("~x is 1"), None);
+    }
+
+    #[test]
+    fn test_suppressions() {
+        let suppressions = Suppressions::parse(
+            "~a is 1 # lint-ignore\n# lint-ignore unused-variable, infinite-loop\n~b is 2\n~c is 3 # lint-ignored",
+        );
+        let diagnostic = |rule, line| Diagnostic {
+            rule,
+            line: Some(line),
+            message: String::new(),
+        };
+        assert!(suppressions.silences(&diagnostic(Rule::UnknownFunction, 1)));
+        assert!(suppressions.silences(&diagnostic(Rule::UnusedVariable, 3)));
+        assert!(!suppressions.silences(&diagnostic(Rule::UnknownFunction, 3)));
+        assert!(!suppressions.silences(&diagnostic(Rule::UnusedVariable, 4)));
+    }
+
+    #[test]
+    fn test_rule_ids_round_trip() {
+        for rule in Rule::ALL {
+            assert_eq!(Rule::parse(rule.id()), Some(rule));
+        }
+    }
+}
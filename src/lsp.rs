@@ -0,0 +1,746 @@
+//! A Language Server Protocol server for `tilde lsp`.
+//!
+//! Editors start `tilde lsp` and talk JSON-RPC to it over stdin and stdout.
+//! The server keeps the text of each open script and answers from its tokens
+//! and parse: parse and lint diagnostics, completion of `~variables`,
+//! functions and stdlib names, hover docs, go-to-definition, document
+//! symbols and rename. Positions follow the protocol: zero-based lines and
+//! UTF-16 columns.
+
+use crate::lexer::{Lexer, Token};
+use crate::lint::{self, Severity};
+use crate::parser::Parser;
+use crate::stdlib::metadata;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+
+// Numbers the protocol uses
+const ERROR_METHOD_NOT_FOUND: i64 = -32601;
+const ERROR_INVALID_PARAMS: i64 = -32602;
+const SYNC_FULL: i64 = 1;
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+const SYMBOL_FUNCTION: i64 = 12;
+
+/// Read one message, or `None` once the input is closed
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>, String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        let read = reader
+            .read_line(&mut header)
+            .map_err(|e| format!("Failed to read message: {}", e))?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid Content-Length: {}", value.trim()))?,
+            );
+        }
+    }
+
+    let length = length.ok_or("Message has no Content-Length header")?;
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| format!("Failed to read message: {}", e))?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| format!("Invalid message: {}", e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<(), String> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Failed to write message: {}", e))
+}
+
+/// Answer messages from `input` until the client sends `exit`. Fails if the
+/// client exits or disconnects without asking to shut down first.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> Result<(), String> {
+    let mut server = LanguageServer::new();
+    while let Some(message) = read_message(&mut input)? {
+        if message["method"] == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    if server.shutting_down {
+        Ok(())
+    } else {
+        Err("Client exited without shutting down".to_string())
+    }
+}
+
+/// The server's state: the open documents, by URI
+#[derive(Debug, Default)]
+pub struct LanguageServer {
+    documents: HashMap<String, Document>,
+    shutting_down: bool,
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle one request or notification, returning the messages to send
+    /// back: a response for a request, plus any notifications
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            return self.notify(method, params);
+        };
+        if method.is_empty() {
+            // A response to something we sent; we never send requests
+            return Vec::new();
+        }
+
+        let result = match method {
+            "initialize" => Ok(initialize_result()),
+            "shutdown" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/completion" => self.with_position(params, Document::completion),
+            "textDocument/hover" => self.with_position(params, Document::hover),
+            "textDocument/definition" => self.with_position(params, Document::definition),
+            "textDocument/documentSymbol" => match self.document(params) {
+                Some((uri, document)) => Ok(document.symbols(uri)),
+                None => Err((ERROR_INVALID_PARAMS, "Unknown document".to_string())),
+            },
+            "textDocument/rename" => {
+                let new_name = params["newName"].as_str().unwrap_or_default().to_string();
+                self.with_position(params, |document, uri, offset| {
+                    document.rename(uri, offset, &new_name)
+                })
+            }
+            _ => Err((
+                ERROR_METHOD_NOT_FOUND,
+                format!("Method not found: {}", method),
+            )),
+        };
+
+        let response = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message},
+            }),
+        };
+        vec![response]
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // We ask for full syncs, so the last change holds the whole text
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        };
+        let Some(text) = text else {
+            return Vec::new();
+        };
+
+        let document = Document::new(text);
+        let diagnostics = document.diagnostics();
+        self.documents.insert(uri.clone(), document);
+        vec![publish_diagnostics(&uri, diagnostics)]
+    }
+
+    fn document<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        Some((uri, self.documents.get(uri)?))
+    }
+
+    /// Answer a request about a position in a document
+    fn with_position(
+        &self,
+        params: &Value,
+        answer: impl FnOnce(&Document, &str, usize) -> Result<Value, String>,
+    ) -> Result<Value, (i64, String)> {
+        let (uri, document) = self
+            .document(params)
+            .ok_or((ERROR_INVALID_PARAMS, "Unknown document".to_string()))?;
+        let position = &params["position"];
+        let offset = document.offset(
+            position["line"].as_u64().unwrap_or(0) as usize,
+            position["character"].as_u64().unwrap_or(0) as usize,
+        );
+        answer(document, uri, offset).map_err(|message| (ERROR_INVALID_PARAMS, message))
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": SYNC_FULL,
+            "completionProvider": {"triggerCharacters": ["~", "*"]},
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "documentSymbolProvider": true,
+            "renameProvider": true,
+        },
+        "serverInfo": {"name": "tilde", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymbolKind {
+    Variable,
+    Function,
+}
+
+/// A place a variable or user function is named
+#[derive(Debug, Clone)]
+struct Occurrence {
+    kind: SymbolKind,
+    name: String,
+    /// The name itself, without its `~` or `*`
+    span: Range<usize>,
+    /// Whether this assigns or binds the name rather than using it
+    defines: bool,
+    /// The innermost function it appears in, as an index into `functions`
+    scope: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct FunctionInfo {
+    name: String,
+    params: Vec<String>,
+    name_span: Range<usize>,
+    /// From `function` to the closing `)`
+    span: Range<usize>,
+    /// The function this one is defined inside
+    parent: Option<usize>,
+}
+
+/// An open script and what's been worked out about it. Offsets count
+/// characters, as the lexer does.
+#[derive(Debug)]
+struct Document {
+    text: String,
+    chars: Vec<char>,
+    line_starts: Vec<usize>,
+    tokens: Vec<(Token, Range<usize>)>,
+    functions: Vec<FunctionInfo>,
+    occurrences: Vec<Occurrence>,
+}
+
+impl Document {
+    fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let line_starts = std::iter::once(0)
+            .chain(
+                chars
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        let mut document = Document {
+            text: text.to_string(),
+            chars,
+            line_starts,
+            tokens: Lexer::new(text).tokenize_with_spans(),
+            functions: Vec::new(),
+            occurrences: Vec::new(),
+        };
+        document.find_functions();
+        document.find_occurrences();
+        document
+    }
+
+    /// The character offset of a protocol position
+    fn offset(&self, line: usize, character: usize) -> usize {
+        let Some(&start) = self.line_starts.get(line) else {
+            return self.chars.len();
+        };
+        let mut units = 0;
+        let mut offset = start;
+        while offset < self.chars.len() && self.chars[offset] != '\n' && units < character {
+            units += self.chars[offset].len_utf16();
+            offset += 1;
+        }
+        offset
+    }
+
+    /// The protocol position of a character offset
+    fn position(&self, offset: usize) -> Value {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let character: usize = self.chars[self.line_starts[line]..offset]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum();
+        json!({"line": line, "character": character})
+    }
+
+    fn range(&self, span: &Range<usize>) -> Value {
+        json!({"start": self.position(span.start), "end": self.position(span.end)})
+    }
+
+    /// The span of a 1-based line, without its indentation
+    fn line_span(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[(line.max(1) - 1).min(self.line_starts.len() - 1)];
+        let end = self.chars[start..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(self.chars.len(), |length| start + length);
+        let indent = self.chars[start..end]
+            .iter()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        start + indent..end
+    }
+
+    fn find_functions(&mut self) {
+        for (i, (token, span)) in self.tokens.iter().enumerate() {
+            let (Token::Function, Some((Token::Identifier(name), name_span))) =
+                (token, self.tokens.get(i + 1))
+            else {
+                continue;
+            };
+            let params: Vec<String> = self.tokens[i + 2..]
+                .iter()
+                .map_while(|(token, _)| match token {
+                    Token::Variable(param) => Some(param.clone()),
+                    _ => None,
+                })
+                .collect();
+            let end = self
+                .closing_paren(i + 2 + params.len())
+                .map_or(self.chars.len(), |close| self.tokens[close].1.end);
+            self.functions.push(FunctionInfo {
+                name: name.clone(),
+                params,
+                name_span: name_span.clone(),
+                span: span.start..end,
+                parent: None,
+            });
+        }
+
+        for i in 0..self.functions.len() {
+            let span = self.functions[i].span.clone();
+            self.functions[i].parent = self.scope_at(span.start, Some(i));
+        }
+    }
+
+    /// The index of the `)` matching the `(` at token `open`
+    fn closing_paren(&self, open: usize) -> Option<usize> {
+        if self.tokens.get(open).map(|(token, _)| token) != Some(&Token::LeftParen) {
+            return None;
+        }
+        let mut depth = 0;
+        for (i, (token, _)) in self.tokens.iter().enumerate().skip(open) {
+            match token {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The innermost function around an offset, other than `except`
+    fn scope_at(&self, offset: usize, except: Option<usize>) -> Option<usize> {
+        self.functions
+            .iter()
+            .enumerate()
+            .filter(|(i, function)| Some(*i) != except && function.span.contains(&offset))
+            .min_by_key(|(_, function)| function.span.len())
+            .map(|(i, _)| i)
+    }
+
+    fn find_occurrences(&mut self) {
+        let mut binding = false;
+        let mut occurrences = Vec::new();
+        for (i, (token, span)) in self.tokens.iter().enumerate() {
+            let next = self.tokens.get(i + 1).map(|(token, _)| token);
+            match token {
+                Token::Variable(name) => {
+                    // `~a is`, `~a:`, or a parameter or loop variable
+                    let defines = binding || matches!(next, Some(Token::Is | Token::Colon));
+                    occurrences.push(Occurrence {
+                        kind: SymbolKind::Variable,
+                        name: name.clone(),
+                        span: span.start + 1..span.end,
+                        defines,
+                        scope: self.scope_at(span.start, None),
+                    });
+                    continue;
+                }
+                Token::InterpolatedString(_) => {
+                    occurrences.extend(self.interpolated_variables(span));
+                }
+                Token::Identifier(name) => {
+                    let after_function =
+                        i > 0 && matches!(self.tokens[i - 1].0, Token::Function | Token::Block(_));
+                    let (name, span) = match name.strip_prefix('*') {
+                        Some(name) => (name, span.start + 1..span.end),
+                        None => (name.as_str(), span.clone()),
+                    };
+                    // Bare names are only functions if the script defines
+                    // them; otherwise they're stdlib calls or object keys
+                    if after_function || self.functions.iter().any(|function| function.name == name)
+                    {
+                        occurrences.push(Occurrence {
+                            kind: SymbolKind::Function,
+                            name: name.to_string(),
+                            span: span.clone(),
+                            defines: after_function
+                                && !matches!(self.tokens[i - 1].0, Token::Block(_)),
+                            scope: self.scope_at(span.start, None),
+                        });
+                    }
+                }
+                _ => {}
+            }
+            // The variables after these tokens are bound, not used
+            binding = match token {
+                Token::ForEach | Token::Rescue => true,
+                Token::Identifier(_) => i > 0 && self.tokens[i - 1].0 == Token::Function,
+                Token::Pipe => self.tokens[i + 1..]
+                    .iter()
+                    .find(|(token, _)| !matches!(token, Token::Variable(_)))
+                    .is_some_and(|(token, _)| *token == Token::LeftParen),
+                _ => false,
+            };
+        }
+        self.occurrences = occurrences;
+    }
+
+    /// The `` `~name` `` references inside an interpolated string token
+    fn interpolated_variables(&self, span: &Range<usize>) -> Vec<Occurrence> {
+        let mut found = Vec::new();
+        let mut i = span.start;
+        while i + 1 < span.end {
+            if self.chars[i] == '`' && self.chars[i + 1] == '~' {
+                let start = i + 2;
+                let mut end = start;
+                while end < span.end
+                    && (self.chars[end].is_alphanumeric() || matches!(self.chars[end], '-' | '_'))
+                {
+                    end += 1;
+                }
+                found.push(Occurrence {
+                    kind: SymbolKind::Variable,
+                    name: self.chars[start..end].iter().collect(),
+                    span: start..end,
+                    defines: false,
+                    scope: self.scope_at(start, None),
+                });
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+        found
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        let (_, errors) = Parser::new(&self.text).parse_recovering();
+        if !errors.is_empty() {
+            return errors
+                .iter()
+                .map(|error| {
+                    let span = self.line_span(error.line.unwrap_or(1));
+                    json!({
+                        "range": self.range(&span),
+                        "severity": 1,
+                        "source": "tilde",
+                        "message": error.message,
+                    })
+                })
+                .collect();
+        }
+
+        lint::lint_source(&self.text)
+            .unwrap_or_default()
+            .iter()
+            .map(|diagnostic| {
+                let span = self.line_span(diagnostic.line.unwrap_or(1));
+                json!({
+                    "range": self.range(&span),
+                    "severity": if diagnostic.severity() == Severity::Error { 1 } else { 2 },
+                    "code": diagnostic.rule.id(),
+                    "source": "tilde lint",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect()
+    }
+
+    /// The variable or function named at an offset, including just after it
+    fn occurrence_at(&self, offset: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.span.start <= offset && offset <= occurrence.span.end)
+    }
+
+    /// The function that a variable occurrence's name is bound in, or `None`
+    /// for a global
+    fn binding_scope(&self, occurrence: &Occurrence) -> Option<usize> {
+        let mut scope = occurrence.scope;
+        while let Some(function) = scope {
+            let bound_here = self.occurrences.iter().any(|other| {
+                other.kind == SymbolKind::Variable
+                    && other.defines
+                    && other.name == occurrence.name
+                    && other.scope == Some(function)
+            });
+            if bound_here {
+                return Some(function);
+            }
+            scope = self.functions[function].parent;
+        }
+        None
+    }
+
+    /// Every occurrence of the same variable or function
+    fn references(&self, target: &Occurrence) -> Vec<&Occurrence> {
+        let scope = self.binding_scope(target);
+        self.occurrences
+            .iter()
+            .filter(|other| other.kind == target.kind && other.name == target.name)
+            .filter(|other| {
+                target.kind == SymbolKind::Function || self.binding_scope(other) == scope
+            })
+            .collect()
+    }
+
+    fn completion(&self, _uri: &str, offset: usize) -> Result<Value, String> {
+        // Replace what's been typed of the name, including any `~` or `*`
+        let mut start = offset;
+        while start > 0 && is_name_char(self.chars[start - 1]) {
+            start -= 1;
+        }
+        let sigil = start
+            .checked_sub(1)
+            .map(|before| self.chars[before])
+            .filter(|c| matches!(c, '~' | '*'));
+        if sigil.is_some() {
+            start -= 1;
+        }
+        let range = self.range(&(start..offset));
+        let item = |label: String, kind: i64, detail: &str| {
+            json!({
+                "label": label,
+                "kind": kind,
+                "detail": detail,
+                "filterText": label,
+                "textEdit": {"range": range, "newText": label},
+            })
+        };
+
+        let mut items = Vec::new();
+        if sigil != Some('*') {
+            let mut variables: Vec<&str> = self
+                .occurrences
+                .iter()
+                .filter(|occurrence| {
+                    occurrence.kind == SymbolKind::Variable && occurrence.span.end != offset
+                })
+                .map(|occurrence| occurrence.name.as_str())
+                .collect();
+            variables.sort_unstable();
+            variables.dedup();
+            items.extend(
+                variables
+                    .into_iter()
+                    .map(|name| item(format!("~{}", name), COMPLETION_VARIABLE, "variable")),
+            );
+        }
+        if sigil != Some('~') {
+            items.extend(self.functions.iter().map(|function| {
+                item(
+                    format!("*{}", function.name),
+                    COMPLETION_FUNCTION,
+                    &signature(function),
+                )
+            }));
+        }
+        if sigil.is_none() {
+            items.extend(metadata::stdlib_functions().iter().map(|info| {
+                item(
+                    info.name.to_string(),
+                    COMPLETION_FUNCTION,
+                    &info.signature(),
+                )
+            }));
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn hover(&self, _uri: &str, offset: usize) -> Result<Value, String> {
+        let (contents, span) = if let Some(occurrence) = self.occurrence_at(offset) {
+            let text = match occurrence.kind {
+                SymbolKind::Function => self
+                    .functions
+                    .iter()
+                    .find(|function| function.name == occurrence.name)
+                    .map(|function| format!("```tilde\n{}\n```", signature(function))),
+                SymbolKind::Variable => None,
+            };
+            (text, occurrence.span.clone())
+        } else {
+            // A stdlib function called by name
+            let found = self.tokens.iter().find_map(|(token, span)| match token {
+                Token::Identifier(name) if span.start <= offset && offset <= span.end => {
+                    Some((name.trim_start_matches('*'), span.clone()))
+                }
+                _ => None,
+            });
+            match found.and_then(|(name, span)| Some((metadata::lookup(name)?, span))) {
+                Some((info, span)) => (Some(info.markdown()), span),
+                None => (None, 0..0),
+            }
+        };
+
+        Ok(match contents {
+            Some(value) => json!({
+                "contents": {"kind": "markdown", "value": value},
+                "range": self.range(&span),
+            }),
+            None => Value::Null,
+        })
+    }
+
+    fn definition(&self, uri: &str, offset: usize) -> Result<Value, String> {
+        let Some(target) = self.occurrence_at(offset) else {
+            return Ok(Value::Null);
+        };
+        let definition = self
+            .references(target)
+            .into_iter()
+            .find(|occurrence| occurrence.defines);
+        Ok(match definition {
+            Some(definition) => json!({"uri": uri, "range": self.range(&definition.span)}),
+            None => Value::Null,
+        })
+    }
+
+    fn symbols(&self, _uri: &str) -> Value {
+        self.functions
+            .iter()
+            .map(|function| {
+                json!({
+                    "name": function.name,
+                    "detail": signature(function),
+                    "kind": SYMBOL_FUNCTION,
+                    "range": self.range(&function.span),
+                    "selectionRange": self.range(&function.name_span),
+                })
+            })
+            .collect()
+    }
+
+    fn rename(&self, uri: &str, offset: usize, new_name: &str) -> Result<Value, String> {
+        let target = self
+            .occurrence_at(offset)
+            .ok_or("There's no variable or function here to rename")?;
+        let new_name = new_name.trim_start_matches(['~', '*']);
+        if new_name.is_empty()
+            || !new_name.chars().next().is_some_and(char::is_alphabetic)
+            || !new_name.chars().all(is_name_char)
+        {
+            return Err(format!("'{}' isn't a valid name", new_name));
+        }
+
+        let edits: Vec<Value> = self
+            .references(target)
+            .into_iter()
+            .map(|occurrence| json!({"range": self.range(&occurrence.span), "newText": new_name}))
+            .collect();
+        Ok(json!({"changes": {uri: edits}}))
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+fn signature(function: &FunctionInfo) -> String {
+    std::iter::once(format!("function {}", function.name))
+        .chain(function.params.iter().map(|param| format!("~{}", param)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> Document {
+        Document::new(
+            "~total is 0\nfunction add-to ~n (\n    ~total is ~total + ~n\n    give ~n\n)\nsay \"`~total`\" (*add-to 1)\n",
+        )
+    }
+
+    #[test]
+    fn test_positions_count_utf16_units() {
+        let document = Document::new("say \"é🎉\" ~x\n~y is 1");
+        let offset = document.offset(0, 10);
+        assert_eq!(document.chars[offset], '~');
+        assert_eq!(
+            document.position(offset),
+            json!({"line": 0, "character": 10})
+        );
+        assert_eq!(document.offset(1, 0), 12);
+    }
+
+    #[test]
+    fn test_finds_functions_and_scopes() {
+        let document = document();
+        assert_eq!(document.functions.len(), 1);
+        assert_eq!(document.functions[0].params, vec!["n"]);
+
+        // `~total` inside the function is assigned there, so it's a local
+        let inner = document.occurrence_at(document.offset(2, 6)).unwrap();
+        assert_eq!(document.binding_scope(inner), Some(0));
+        let outer = document.occurrence_at(document.offset(0, 2)).unwrap();
+        assert_eq!(document.binding_scope(outer), None);
+        assert_eq!(document.references(outer).len(), 2);
+    }
+}
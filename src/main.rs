@@ -1,178 +1,831 @@
 use std::env;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use tilde::bundle::Bundle;
+use tilde::debugger::Debugger;
+use tilde::doc::{self, DocFormat};
+use tilde::line_editor::{LineEditor, ReadLine};
+use tilde::repl::{self, Repl};
+use tilde::testing::{self, TestFormat};
+use tilde::{formatter, lint, lsp};
+use tilde::io::{FileOutput, OutputStream, StderrStream, StdinStream, StdoutStream};
+use tilde::package::{self, Manifest};
+use tilde::sandbox::{Capabilities, EvaluatorConfig};
+use tilde::trace::{TraceFormat, TraceKind, Tracer};
+use tilde::watch::{self, FileWatcher, WatchSession};
 use tilde::{evaluator::Evaluator, parser::Parser};
 
 fn version_string() -> String {
     format!("~tilde v{}", env!("CARGO_PKG_VERSION"))
 }
 
+/// Options given as flags before the script name
+#[derive(Debug, Default)]
+struct RunOptions {
+    config: EvaluatorConfig,
+    profile: bool,
+    /// Where to write collapsed stacks for flamegraph tools
+    profile_stacks: Option<String>,
+    /// Which events `--trace` records, if tracing
+    trace: Option<Vec<TraceKind>>,
+    trace_format: TraceFormat,
+    /// Where to write the trace instead of stderr
+    trace_file: Option<String>,
+    /// Re-run the script whenever it changes
+    watch: bool,
+    /// The files built into this executable, when it was made by `tilde build`
+    bundle: Option<Arc<Bundle>>,
+}
+
+impl RunOptions {
+    fn tracer(&self) -> Result<Option<Tracer>, String> {
+        let Some(kinds) = &self.trace else {
+            return Ok(None);
+        };
+        let output: Arc<dyn OutputStream> = match &self.trace_file {
+            Some(path) => Arc::new(FileOutput::create(path)?),
+            None => Arc::new(StderrStream),
+        };
+        Ok(Some(
+            Tracer::new(output)
+                .with_format(self.trace_format)
+                .with_kinds(kinds.iter().copied()),
+        ))
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // An executable made by `tilde build` runs its own script
+    match Bundle::from_current_exe() {
+        Ok(Some(bundle)) => {
+            let args: Vec<String> = env::args().skip(1).collect();
+            exit_with(run_bundle(bundle, &args));
+            return;
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    }
 
-    if args.len() > 1 {
-        match args[1].as_str() {
+    let (options, args) = match parse_flags(env::args().skip(1).collect()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    if options.watch {
+        match args.first() {
+            Some(filename) if !filename.starts_with('-') => {
+                run_watch(filename, &args[1..], options)
+            }
+            _ => {
+                eprintln!("Usage: tilde --watch <file> [args]");
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+
+    if let Some(first) = args.first() {
+        match first.as_str() {
             "--version" | "-v" => {
                 println!("{}", version_string());
             }
             "--help" | "-h" => {
                 print_help();
             }
+            "debug" => match args.get(1) {
+                Some(filename) => run_debug(filename, options.config),
+                None => {
+                    eprintln!("Usage: tilde debug <file>");
+                    std::process::exit(2);
+                }
+            },
+            "lint" => run_lint(&args[1..]),
+            "fmt" => run_fmt(&args[1..]),
+            "test" => run_tests(&args[1..]),
+            "doc" => run_doc(&args[1..]),
+            "lsp" => {
+                let stdin = io::stdin();
+                if let Err(e) = lsp::serve(stdin.lock(), io::stdout()) {
+                    eprintln!("tilde lsp: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            "-e" => match args.get(1) {
+                Some(code) => exit_with(run_source(code, None, &args[2..], options)),
+                None => {
+                    eprintln!("Usage: tilde -e <code> [args]...");
+                    std::process::exit(2);
+                }
+            },
+            "-" => {
+                let mut source = String::new();
+                if let Err(e) = io::stdin().read_to_string(&mut source) {
+                    eprintln!("Error reading script from stdin: {}", e);
+                    std::process::exit(2);
+                }
+                exit_with(run_source(&source, None, &args[1..], options));
+            }
+            "build" => run_build(&args[1..]),
+            "install" => run_install(&args[1..]),
+            "run" => run_project(&args[1..], options),
             _ => {
                 // File execution mode
-                run_file(&args[1]);
+                let source = match fs::read_to_string(first) {
+                    Ok(source) => source,
+                    Err(e) => {
+                        eprintln!("Error reading file '{}': {}", first, e);
+                        std::process::exit(2);
+                    }
+                };
+                let script = Some(Path::new(first));
+                exit_with(run_source(&source, script, &args[1..], options));
             }
         }
     } else {
         // REPL mode
-        run_repl();
+        run_repl(options.config);
     }
 }
 
-fn run_file(filename: &str) {
-    let contents = match fs::read_to_string(filename) {
-        Ok(contents) => contents,
+/// Split the flags given before the script name from the other arguments. Any of
+/// `--sandbox` and `--allow-*` turns on the sandbox: only the listed capabilities are granted.
+fn parse_flags(args: Vec<String>) -> Result<(RunOptions, Vec<String>), String> {
+    let mut options = RunOptions::default();
+    let mut capabilities = Capabilities::none();
+    let mut sandboxed = false;
+    let mut rest = Vec::new();
+
+    for arg in args {
+        if !rest.is_empty() {
+            rest.push(arg);
+        } else if arg == "--profile" {
+            options.profile = true;
+        } else if let Some(path) = arg.strip_prefix("--profile-stacks=") {
+            options.profile = true;
+            options.profile_stacks = Some(path.to_string());
+        } else if arg == "--trace" {
+            options.trace = Some(TraceKind::ALL.to_vec());
+        } else if let Some(kinds) = arg.strip_prefix("--trace=") {
+            options.trace = Some(TraceKind::parse_list(kinds)?);
+        } else if let Some(format) = arg.strip_prefix("--trace-format=") {
+            options.trace_format = match format {
+                "text" => TraceFormat::Text,
+                "json" => TraceFormat::Json,
+                _ => return Err(format!("Unknown trace format '{}' (expected text or json)", format)),
+            };
+        } else if let Some(path) = arg.strip_prefix("--trace-file=") {
+            options.trace_file = Some(path.to_string());
+        } else if arg == "--watch" {
+            options.watch = true;
+        } else if arg == "--sandbox" {
+            sandboxed = true;
+        } else if arg.starts_with("--allow-") {
+            if !capabilities.apply_flag(&arg)? {
+                return Err(format!("Unknown capability flag: {}", arg));
+            }
+            sandboxed = true;
+        } else {
+            rest.push(arg);
+        }
+    }
+
+    if sandboxed {
+        options.config = options.config.with_capabilities(capabilities);
+    }
+    Ok((options, rest))
+}
+
+/// Exit with `code` once output is flushed. A zero code just returns, so
+/// the program ends normally.
+fn exit_with(code: i32) {
+    if code != 0 {
+        let _ = io::stdout().flush();
+        std::process::exit(code);
+    }
+}
+
+/// Run a script from the file `script`, if it has one, with `script_args` as
+/// its `~args`. Gives the status code to exit with: the one passed to `exit`,
+/// 1 after an uncaught runtime error, or 2 if the script couldn't be parsed.
+fn run_source(
+    contents: &str,
+    script: Option<&Path>,
+    script_args: &[String],
+    options: RunOptions,
+) -> i32 {
+    let tracer = match options.tracer() {
+        Ok(tracer) => tracer,
         Err(e) => {
-            eprintln!("Error reading file '{}': {}", filename, e);
-            return;
+            eprintln!("Error: {}", e);
+            return 2;
         }
     };
 
-    let mut parser = Parser::new(&contents);
-    let program = match parser.parse() {
-        Ok(program) => program,
+    // Statement events need line numbers
+    let mut parser = Parser::new(contents);
+    if tracer.is_some() {
+        parser = parser.with_locations();
+    }
+    let (program, errors) = parser.parse_recovering();
+    if !errors.is_empty() {
+        for error in errors {
+            eprintln!("Parse error: {}", error);
+        }
+        return 2;
+    }
+
+    let mut evaluator = Evaluator::with_config(options.config);
+    evaluator.set_args(script_args);
+    if let Some(script) = script {
+        evaluator.set_script_path(script);
+    }
+    if let Some(bundle) = options.bundle {
+        evaluator.set_bundle(bundle);
+    }
+    if let Some(tracer) = tracer {
+        evaluator.set_tracer(tracer);
+    }
+    if options.profile {
+        evaluator.enable_profiling();
+    }
+    let result = evaluator.eval_program(program);
+    let code = match (evaluator.exit_code(), result) {
+        (Some(code), _) => code,
+        (None, Ok(_)) => 0,
+        (None, Err(e)) => {
+            eprintln!("Runtime error: {}", e);
+            1
+        }
+    };
+
+    if let Some(profile) = evaluator.take_profile() {
+        eprint!("\n{}", profile.report());
+        if let Some(path) = options.profile_stacks
+            && let Err(e) = fs::write(&path, profile.collapsed_stacks())
+        {
+            eprintln!("Error writing profile stacks to '{}': {}", path, e);
+        }
+    }
+    code
+}
+
+/// Run the script built into this executable, with all of `args` as its
+/// `~args`: flags belong to the script, not to Tilde
+fn run_bundle(bundle: Bundle, args: &[String]) -> i32 {
+    let source = match bundle.source() {
+        Ok(source) => source,
         Err(e) => {
-            eprintln!("Parse error: {}", e);
-            return;
+            eprintln!("Error: {}", e);
+            return 2;
         }
     };
+    let script = PathBuf::from(bundle.entry());
+    let options = RunOptions {
+        bundle: Some(Arc::new(bundle)),
+        ..RunOptions::default()
+    };
+    run_source(&source, Some(&script), args, options)
+}
 
-    let mut evaluator = Evaluator::new();
-    if let Err(e) = evaluator.eval_program(program) {
-        eprintln!("Runtime error: {}", e);
+/// Build a standalone executable that runs a script. Exits with 2 if the
+/// script or a file it needs couldn't be read or parsed, or 1 if the
+/// executable couldn't be written.
+fn run_build(args: &[String]) {
+    let usage = || {
+        eprintln!("Usage: tilde build <file> [-o <output>] [--include=<path>]...");
+        std::process::exit(2);
+    };
+    let mut script = None;
+    let mut output = None;
+    let mut includes = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => usage(),
+            }
+        } else if let Some(path) = arg.strip_prefix("--include=") {
+            includes.push(PathBuf::from(path));
+        } else if arg.starts_with('-') {
+            eprintln!("Unknown build option: {}", arg);
+            usage();
+        } else if script.is_none() {
+            script = Some(PathBuf::from(arg));
+        } else {
+            usage();
+        }
+    }
+    let Some(script) = script else {
+        return usage();
+    };
+    let output = output.unwrap_or_else(|| {
+        let stem = script.file_stem().unwrap_or(script.as_os_str());
+        PathBuf::from(format!("{}{}", stem.to_string_lossy(), env::consts::EXE_SUFFIX))
+    });
+    if output.canonicalize().ok() == Some(&script).and_then(|script| script.canonicalize().ok()) {
+        eprintln!("tilde build: the output would overwrite {}; pass -o <output>", script.display());
+        std::process::exit(2);
+    }
+
+    let bundle = match Bundle::build(&script, &includes) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("tilde build: {}", e);
+            std::process::exit(2);
+        }
+    };
+    let written = env::current_exe()
+        .map_err(|e| format!("Can't find the tilde executable: {}", e))
+        .and_then(|interpreter| bundle.write_executable(&interpreter, &output));
+    if let Err(e) = written {
+        eprintln!("tilde build: {}", e);
+        std::process::exit(1);
     }
+    let count = bundle.paths().count();
+    println!(
+        "Built {} with {} embedded file{}",
+        output.display(),
+        count,
+        if count == 1 { "" } else { "s" }
+    );
 }
 
-fn run_repl() {
-    println!("{}", version_string());
-    println!("Type 'exit' to quit\n");
+/// The project the working directory is in, or exit with a usage error
+fn current_project() -> PathBuf {
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    match package::find_project_root(&cwd) {
+        Some(root) => root,
+        None => {
+            eprintln!(
+                "No {} found in this directory or its parents",
+                package::MANIFEST_FILE
+            );
+            std::process::exit(2);
+        }
+    }
+}
 
-    let mut evaluator = Evaluator::new();
-    let mut input_buffer = String::new();
-    let mut is_multiline = false;
+/// Install the current project's dependencies. Exits with 1 if they couldn't
+/// be installed.
+fn run_install(args: &[String]) {
+    if let Some(arg) = args.first() {
+        eprintln!("Unknown install option: {}", arg);
+        eprintln!("Usage: tilde install");
+        std::process::exit(2);
+    }
+    let root = current_project();
+    match package::install(&root) {
+        Ok(lockfile) => {
+            for locked in &lockfile.packages {
+                match &locked.commit {
+                    Some(commit) => println!(
+                        "  {} ({} at {})",
+                        locked.name,
+                        locked.source,
+                        &commit[..commit.len().min(10)]
+                    ),
+                    None => println!("  {} ({})", locked.name, locked.source),
+                }
+            }
+            let count = lockfile.packages.len();
+            println!(
+                "Installed {} package{} into {}",
+                count,
+                if count == 1 { "" } else { "s" },
+                package::MODULES_DIR
+            );
+        }
+        Err(e) => {
+            eprintln!("tilde install: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
+/// Run the current project's entry script, with `args` as its `~args`
+fn run_project(args: &[String], options: RunOptions) {
+    let root = current_project();
+    let checked = Manifest::load(&root).and_then(|manifest| {
+        manifest.check_tilde_version()?;
+        package::check_installed(&root, &manifest)?;
+        Ok(manifest)
+    });
+    let manifest = match checked {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("tilde run: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let entry = root.join(&manifest.entry);
+    let source = match fs::read_to_string(&entry) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file '{}': {}", entry.display(), e);
+            std::process::exit(2);
+        }
+    };
+    exit_with(run_source(&source, Some(&entry), args, options));
+}
+
+/// Run a script, then run it again each time it's saved, until interrupted
+fn run_watch(filename: &str, script_args: &[String], options: RunOptions) {
+    let mut watcher = FileWatcher::new(filename);
+    let mut session =
+        WatchSession::new(options.config, script_args.to_vec()).with_script_path(filename);
+    if !watcher.changed() {
+        eprintln!("Error reading file '{}': file not found", filename);
+        std::process::exit(2);
+    }
+    eprintln!("Watching {} for changes (Ctrl-C to stop)", filename);
+
+    let mut changed = true;
+    let mut last_poll = Instant::now();
     loop {
-        // Show appropriate prompt
-        if is_multiline {
-            print!("... ");
+        if changed {
+            match fs::read_to_string(filename) {
+                Ok(source) => {
+                    if let Err(e) = session.run(&source) {
+                        eprintln!("{}", e);
+                    }
+                }
+                Err(e) => eprintln!("Error reading file '{}': {}", filename, e),
+            }
+            let _ = io::stdout().flush();
+        }
+
+        session.tick();
+        thread::sleep(if session.is_playing() {
+            watch::TICK_INTERVAL
         } else {
-            print!("> ");
+            watch::POLL_INTERVAL
+        });
+        changed = false;
+        if last_poll.elapsed() >= watch::POLL_INTERVAL {
+            last_poll = Instant::now();
+            changed = watcher.changed();
+            if changed {
+                eprintln!("\n{} changed, re-running", filename);
+            }
         }
-        io::stdout().flush().unwrap();
+    }
+}
 
-        let mut line = String::new();
-        match io::stdin().read_line(&mut line) {
-            Ok(_) => {}
+/// Lint each file without running it. Exits with 1 if anything was found,
+/// or 2 if a file couldn't be read or parsed.
+fn run_lint(args: &[String]) {
+    let mut json = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--format=json" => json = true,
+            "--format=text" => json = false,
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown lint option: {}", arg);
+                std::process::exit(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("Usage: tilde lint [--format=json] <file>...");
+        std::process::exit(2);
+    }
+
+    let mut failed = false;
+    let mut found = Vec::new();
+    for filename in files {
+        let result = fs::read_to_string(filename)
+            .map_err(|e| format!("Error reading file '{}': {}", filename, e))
+            .and_then(|contents| {
+                lint::lint_source(&contents).map_err(|errors| {
+                    errors
+                        .iter()
+                        .map(|error| match error.line {
+                            Some(line) => {
+                                format!("{}:{}: parse error: {}", filename, line, error.message)
+                            }
+                            None => format!("{}: parse error: {}", filename, error.message),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+            });
+        match result {
+            Ok(diagnostics) => {
+                found.extend(diagnostics.into_iter().map(|diagnostic| (filename, diagnostic)))
+            }
             Err(e) => {
-                eprintln!("Error reading input: {}", e);
-                continue;
+                eprintln!("{}", e);
+                failed = true;
             }
         }
+    }
 
-        let line = line.trim();
+    if json {
+        let report: Vec<_> = found
+            .iter()
+            .map(|(filename, diagnostic)| {
+                let mut entry = diagnostic.to_json();
+                entry["file"] = filename.as_str().into();
+                entry
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(report));
+    } else {
+        for (filename, diagnostic) in &found {
+            match diagnostic.line {
+                Some(line) => println!("{}:{}: {}", filename, line, diagnostic),
+                None => println!("{}: {}", filename, diagnostic),
+            }
+        }
+    }
 
-        // Handle exit command
-        if line == "exit" && input_buffer.is_empty() {
-            break;
+    if failed {
+        std::process::exit(2);
+    }
+    if !found.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Rewrite each file in canonical form. With `--check`, list the files that
+/// aren't formatted instead and exit with 1 if there are any. Exits with 2 if
+/// a file couldn't be read, parsed or written.
+fn run_fmt(args: &[String]) {
+    let mut check = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown fmt option: {}", arg);
+                std::process::exit(2);
+            }
+            _ => files.push(arg),
         }
+    }
+    if files.is_empty() {
+        eprintln!("Usage: tilde fmt [--check] <file>...");
+        std::process::exit(2);
+    }
 
-        // Handle empty lines
-        if line.is_empty() {
-            if is_multiline {
-                // Empty line in multiline mode - try to execute what we have
-                if should_execute_buffer(&input_buffer) {
-                    execute_buffer(&mut evaluator, &input_buffer);
-                    input_buffer.clear();
-                    is_multiline = false;
+    let mut failed = false;
+    let mut unformatted = false;
+    for filename in files {
+        let result = fs::read_to_string(filename)
+            .map_err(|e| format!("Error reading file '{}': {}", filename, e))
+            .and_then(|contents| {
+                formatter::format_source(&contents)
+                    .map(|formatted| (contents, formatted))
+                    .map_err(|e| format!("{}: parse error: {}", filename, e))
+            });
+        match result {
+            Ok((contents, formatted)) if contents == formatted => {}
+            Ok(_) if check => {
+                println!("{}", filename);
+                unformatted = true;
+            }
+            Ok((_, formatted)) => {
+                if let Err(e) = fs::write(filename, formatted) {
+                    eprintln!("Error writing file '{}': {}", filename, e);
+                    failed = true;
                 }
-                // Otherwise continue collecting input
             }
-            continue;
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+            }
         }
+    }
 
-        // Add line to buffer
-        if !input_buffer.is_empty() {
-            input_buffer.push('\n');
+    if failed {
+        std::process::exit(2);
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+/// Run the tests in `*_test.tde` files, exiting with 1 if any fail
+fn run_tests(args: &[String]) {
+    let mut format = TestFormat::Text;
+    let mut filter = None;
+    let mut paths = Vec::new();
+    for arg in args {
+        if let Some(name) = arg.strip_prefix("--format=") {
+            format = TestFormat::parse(name).unwrap_or_else(|| {
+                eprintln!("Unknown test format: {} (expected text, tap or junit)", name);
+                std::process::exit(2);
+            });
+        } else if let Some(text) = arg.strip_prefix("--filter=") {
+            filter = Some(text);
+        } else if arg.starts_with("--") {
+            eprintln!("Unknown test option: {}", arg);
+            std::process::exit(2);
+        } else {
+            paths.push(arg.clone());
         }
-        input_buffer.push_str(line);
+    }
+    if paths.is_empty() {
+        paths.push(".".to_string());
+    }
 
-        // Check if we need to continue collecting input
-        if needs_more_input(&input_buffer) {
-            is_multiline = true;
-            continue;
+    let files = testing::find_test_files(&paths).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    let mut failed = false;
+    let mut results = Vec::new();
+    for file in files {
+        match testing::run_file(&file, filter) {
+            Ok(file_results) => results.extend(file_results),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+            }
         }
+    }
 
-        // Execute the complete input
-        execute_buffer(&mut evaluator, &input_buffer);
-        input_buffer.clear();
-        is_multiline = false;
+    print!("{}", format.report(&results));
+    if failed {
+        std::process::exit(2);
+    }
+    if results.iter().any(|result| !result.passed()) {
+        std::process::exit(1);
     }
 }
 
-fn needs_more_input(input: &str) -> bool {
-    let mut paren_count = 0;
-    let mut brace_count = 0;
-    let mut in_string = false;
-    let mut escape_next = false;
-    let mut string_char = '\0';
-
-    for ch in input.chars() {
-        if escape_next {
-            escape_next = false;
-            continue;
+/// Write reference pages for libraries, or for the stdlib with `--stdlib`,
+/// to stdout or into `--output`. With `--test`, run the examples in the
+/// libraries' doc comments instead, exiting with 1 if any fail. Exits with 2
+/// if a file couldn't be read, parsed or written.
+fn run_doc(args: &[String]) {
+    let mut format = DocFormat::Markdown;
+    let mut output = None;
+    let mut stdlib = false;
+    let mut test = false;
+    let mut files = Vec::new();
+    for arg in args {
+        if let Some(name) = arg.strip_prefix("--format=") {
+            format = DocFormat::parse(name).unwrap_or_else(|| {
+                eprintln!("Unknown doc format: {} (expected markdown or html)", name);
+                std::process::exit(2);
+            });
+        } else if let Some(dir) = arg.strip_prefix("--output=") {
+            output = Some(PathBuf::from(dir));
+        } else if arg == "--stdlib" {
+            stdlib = true;
+        } else if arg == "--test" {
+            test = true;
+        } else if arg.starts_with("--") {
+            eprintln!("Unknown doc option: {}", arg);
+            std::process::exit(2);
+        } else {
+            files.push(arg);
         }
+    }
+    if (files.is_empty() && !stdlib) || (test && stdlib) {
+        eprintln!("Usage: tilde doc [--format=markdown|html] [--output=DIR] (--stdlib | <file>...)");
+        eprintln!("       tilde doc --test <file>...");
+        std::process::exit(2);
+    }
 
-        if ch == '\\' && in_string {
-            escape_next = true;
-            continue;
+    let mut failed = false;
+    let mut pages = Vec::new();
+    let mut results = Vec::new();
+    if stdlib {
+        pages.push(("stdlib".to_string(), doc::stdlib_page()));
+    }
+    for filename in files {
+        let name = Path::new(filename)
+            .file_stem()
+            .map_or_else(|| filename.clone(), |stem| stem.to_string_lossy().into_owned());
+        let result = fs::read_to_string(filename)
+            .map_err(|e| format!("Error reading file '{}': {}", filename, e))
+            .and_then(|contents| {
+                if test {
+                    doc::run_doctests(&contents, filename).map(|found| results.extend(found))
+                } else {
+                    doc::library_page(&contents, &name).map(|page| pages.push((name, page)))
+                }
+                .map_err(|e| format!("{}: parse error: {}", filename, e))
+            });
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            failed = true;
         }
+    }
 
-        if ch == '"' && !in_string {
-            in_string = true;
-            string_char = ch;
+    if test {
+        print!("{}", TestFormat::Text.report(&results));
+    }
+    for (name, page) in pages {
+        let rendered = page.render(format);
+        let Some(dir) = &output else {
+            print!("{}", rendered);
             continue;
+        };
+        let path = dir.join(format!("{}.{}", name, format.extension()));
+        match fs::create_dir_all(dir).and_then(|_| fs::write(&path, rendered)) {
+            Ok(()) => println!("Wrote {}", path.display()),
+            Err(e) => {
+                eprintln!("Error writing '{}': {}", path.display(), e);
+                failed = true;
+            }
         }
+    }
 
-        if ch == string_char && in_string {
-            in_string = false;
-            continue;
-        }
+    if failed {
+        std::process::exit(2);
+    }
+    if results.iter().any(|result| !result.passed()) {
+        std::process::exit(1);
+    }
+}
 
-        if in_string {
-            continue;
+/// Run a script under the interactive debugger, paused before the first statement
+fn run_debug(filename: &str, config: EvaluatorConfig) {
+    let contents = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Error reading file '{}': {}", filename, e);
+            return;
         }
+    };
 
-        match ch {
-            '(' => paren_count += 1,
-            ')' => paren_count -= 1,
-            '{' => brace_count += 1,
-            '}' => brace_count -= 1,
-            _ => {}
+    let program = match Parser::new(&contents).with_locations().parse() {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            return;
         }
-    }
+    };
 
-    // Need more input if we have unclosed brackets/parens
-    paren_count > 0 || brace_count > 0
+    println!("Debugging {}. Type 'help' for commands.", filename);
+    let debugger = Debugger::new(&contents, Arc::new(StdinStream), Arc::new(StdoutStream));
+    let mut evaluator = Evaluator::with_config(config);
+    evaluator.set_debug_hook(Box::new(debugger));
+    match evaluator.eval_program(program) {
+        Ok(_) => println!("Program finished"),
+        Err(e) => eprintln!("Runtime error: {}", e),
+    }
 }
 
-fn should_execute_buffer(buffer: &str) -> bool {
-    // If buffer is just whitespace, don't execute
-    if buffer.trim().is_empty() {
-        return false;
-    }
+fn run_repl(config: EvaluatorConfig) {
+    println!("{}", version_string());
+    println!("Type :help for commands, exit to quit\n");
+
+    let mut repl = Repl::new(config, Arc::new(StdoutStream));
+    let mut editor = LineEditor::new(repl::history_file());
+    let mut entry = String::new();
+
+    loop {
+        let prompt = if entry.is_empty() { "> " } else { "... " };
+        let complete = |line: &str, cursor: usize| repl.complete(line, cursor);
+        let line = match editor.read_line(prompt, &complete) {
+            Ok(ReadLine::Line(line)) => line,
+            // Ctrl-C abandons the entry being typed
+            Ok(ReadLine::Interrupted) => {
+                entry.clear();
+                continue;
+            }
+            Ok(ReadLine::Eof) => break,
+            Err(e) => {
+                eprintln!("Error reading input: {}", e);
+                break;
+            }
+        };
+        editor.add_history(&line);
+
+        if !entry.is_empty() {
+            entry.push('\n');
+        }
+        entry.push_str(&line);
+        if repl::needs_more_input(&entry) {
+            continue;
+        }
 
-    // If we still need more input, don't execute
-    !needs_more_input(buffer)
+        let keep_going = repl.run_entry(&entry);
+        entry.clear();
+        if !keep_going {
+            break;
+        }
+    }
+    exit_with(repl.exit_code().unwrap_or(0));
 }
 
 fn print_help() {
@@ -180,14 +833,61 @@ fn print_help() {
     println!("A simple, readable scripting language");
     println!();
     println!("USAGE:");
-    println!("  tilde                  Start interactive REPL");
-    println!("  tilde <file>          Run a Tilde script file");
+    println!("  tilde                 Start the interactive REPL (:help lists its commands)");
+    println!("  tilde <file> [args]   Run a Tilde script file; its arguments are in ~args");
+    println!("  tilde -e <code> [args]  Run code given on the command line");
+    println!("  tilde - [args]        Run a script read from stdin");
+    println!("  tilde debug <file>    Run a script in the interactive debugger");
+    println!("  tilde lint <file>...  Check scripts for mistakes without running them");
+    println!("                        (--format=json for CI; exits 1 if anything is found)");
+    println!("  tilde fmt <file>...   Rewrite scripts in the standard layout, keeping comments");
+    println!("                        (--check lists unformatted files and exits 1 instead)");
+    println!("  tilde test [path]...  Run the tests in *_test.tde files (default: this directory)");
+    println!("                        (--filter=TEXT, --format=tap|junit; exits 1 if any fail)");
+    println!("  tilde doc <file>...   Write reference pages from the ## comments above functions");
+    println!("                        (--format=html, --output=DIR, --stdlib for the stdlib;");
+    println!("                        --test runs their examples and exits 1 if any fail)");
+    println!("  tilde lsp             Start the language server on stdin/stdout, for editors");
+    println!("  tilde build <file>    Make a standalone executable that runs the script");
+    println!("                        (-o <output>; --include=<path> embeds files for `read`)");
+    println!("  tilde install         Install the dependencies in tilde.toml into tilde_modules");
+    println!("  tilde run [args]      Run the project's entry script from tilde.toml");
     println!("  tilde --version       Show version information");
     println!("  tilde --help          Show this help message");
     println!();
+    println!("EXIT STATUS:");
+    println!("  The code passed to `exit`, else 0 on success, 1 after an uncaught runtime");
+    println!("  error, or 2 if the script couldn't be read or parsed.");
+    println!();
+    println!("PROFILING:");
+    println!("  --profile             Print per-function timings after the run (to stderr)");
+    println!("  --profile-stacks=FILE Also write collapsed stacks for flamegraph tools");
+    println!();
+    println!("WATCHING:");
+    println!("  --watch               Re-run the script each time it's saved. Playing patterns");
+    println!("                        are swapped in place; a version that doesn't parse keeps");
+    println!("                        the last good one playing");
+    println!();
+    println!("TRACING:");
+    println!("  --trace[=events]      Log events as they happen (to stderr). Events:");
+    println!("                        statement, call, return, http, run, file (default: all)");
+    println!("  --trace-format=FORMAT text (default) or json, one object per line");
+    println!("  --trace-file=FILE     Write the trace to FILE instead of stderr");
+    println!();
+    println!("SANDBOX:");
+    println!("  --sandbox             Run with no capabilities");
+    println!("  --allow-process       Allow running shell commands");
+    println!("  --allow-fs[=paths]    Allow file access (optionally only below paths)");
+    println!("  --allow-net[=hosts]   Allow HTTP requests (optionally only to hosts)");
+    println!("  --allow-env           Allow reading environment variables");
+    println!("  --allow-audio         Allow audio playback");
+    println!("  --allow-all           Allow everything");
+    println!("  Any of these flags enables the sandbox with only the listed capabilities.");
+    println!();
     println!("EXAMPLES:");
     println!("  tilde                 # Start REPL");
     println!("  tilde hello.tde     # Run hello.tde");
+    println!("  tilde --allow-fs=./data script.tde  # Only allow files under ./data");
     println!();
     println!("LANGUAGE FEATURES:");
     println!("  Variables:    ~name is \"Hello\"");
@@ -196,14 +896,3 @@ fn print_help() {
     println!("  Objects:      ~user is {{name: \"Alice\", age: 30}}");
     println!("  Control:      if ~age > 18 then say \"Adult\" else say \"Minor\"");
 }
-
-fn execute_buffer(evaluator: &mut Evaluator, buffer: &str) {
-    let mut parser = Parser::new(buffer);
-    match parser.parse() {
-        Ok(program) => match evaluator.eval_program(program) {
-            Ok(_) => {}
-            Err(e) => println!("Runtime error: {}", e),
-        },
-        Err(e) => println!("Parse error: {}", e),
-    }
-}
@@ -17,6 +17,8 @@ pub struct MusicEngine {
     audio_thread: Option<thread::JoinHandle<()>>,
     stop_sender: Option<Sender<()>>,
     tick_receiver: Option<Receiver<()>>,
+    /// Patterns played since `begin_reload`, while a watched script re-runs
+    reloaded: Option<usize>,
 }
 
 impl MusicEngine {
@@ -29,6 +31,7 @@ impl MusicEngine {
             audio_thread: None,
             stop_sender: None,
             tick_receiver: None,
+            reloaded: None,
         }
     }
     
@@ -134,6 +137,59 @@ impl MusicEngine {
         self.add_pattern(name, pattern)
     }
     
+    /// Play a pattern from `play`, starting playback if needed. Patterns are
+    /// named by the order they're played in, so a reloaded script's patterns
+    /// replace the ones in the same position.
+    pub fn play_pattern_value(&mut self, pattern_value: &PatternValue) -> Result<(), String> {
+        let index = match &mut self.reloaded {
+            Some(played) => {
+                *played += 1;
+                *played - 1
+            }
+            None => self.get_pattern_names().len(),
+        };
+        let name = format!("pattern_{}", index);
+        let pattern = self.convert_legacy_pattern_value(pattern_value)?;
+        if !self.update_pattern(&name, pattern.clone()) {
+            self.add_pattern(name, pattern)?;
+        }
+        if !self.is_playing() {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Start re-running a script: patterns it plays swap out the current
+    /// ones in place, without resetting scheduler time
+    pub fn begin_reload(&mut self) {
+        self.reloaded = Some(0);
+    }
+
+    /// Finish a reload, dropping the patterns the new version no longer plays
+    pub fn finish_reload(&mut self) {
+        let Some(played) = self.reloaded.take() else {
+            return;
+        };
+        let unplayed: Vec<String> = self
+            .get_pattern_names()
+            .into_iter()
+            .filter(|name| {
+                name.strip_prefix("pattern_")
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .is_some_and(|index| index >= played)
+            })
+            .cloned()
+            .collect();
+        for name in unplayed {
+            self.remove_pattern(&name);
+        }
+    }
+
+    /// Give up on a reload that failed, keeping every pattern playing
+    pub fn abandon_reload(&mut self) {
+        self.reloaded = None;
+    }
+
     /// Convert legacy PatternValue from value.rs to new modular Pattern
     fn convert_legacy_pattern_value(&self, legacy: &PatternValue) -> Result<Pattern, String> {
         
@@ -507,6 +563,32 @@ mod tests {
         assert!(engine.has_pattern("test"));
     }
 
+    #[test]
+    fn test_reload_swaps_patterns_in_place() {
+        let pattern_value = |pitch: &str| PatternValue::Simple {
+            notation: pitch.to_string(),
+            events: vec![Event::new(0.0, EventData::Note {
+                pitch: pitch.to_string(),
+                velocity: 1.0,
+                duration: 0.1,
+            })],
+        };
+        let mut engine = MusicEngine::with_debug_output();
+        engine.play_pattern_value(&pattern_value("c3")).unwrap();
+        engine.play_pattern_value(&pattern_value("d3")).unwrap();
+        assert!(engine.is_playing());
+
+        engine.begin_reload();
+        engine.play_pattern_value(&pattern_value("e3")).unwrap();
+        engine.finish_reload();
+
+        assert!(engine.is_playing());
+        assert_eq!(engine.get_pattern_names(), vec!["pattern_0"]);
+        let events = engine.tick_at_time(0.0);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].starts_with("♪ pattern_0 e3"));
+    }
+
     #[test]
     fn test_engine_stats() {
         let mut engine = MusicEngine::with_debug_output();
@@ -0,0 +1,255 @@
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::lockfile::{LockedPackage, Lockfile};
+use super::manifest::{Manifest, Source};
+use super::{MANIFEST_FILE, MODULES_DIR};
+
+/// Install a project's dependencies, and theirs, into `tilde_modules/` and
+/// write `tilde.lock`. Git dependencies already in the lockfile with the same
+/// source are checked out at their locked commit, so installs repeat exactly.
+/// Nothing in `tilde_modules/` changes unless every package installs.
+pub fn install(root: &Path) -> Result<Lockfile, String> {
+    let manifest = Manifest::load(root)?;
+    manifest.check_tilde_version()?;
+    let previous = Lockfile::load(root)?.unwrap_or_default();
+
+    let staging = root.join(format!("{}.partial", MODULES_DIR));
+    remove_dir(&staging)?;
+    fs::create_dir_all(&staging)
+        .map_err(|e| format!("Error creating '{}': {}", staging.display(), e))?;
+    let installed = install_all(root, &manifest, &previous, &staging);
+    let lockfile = match installed {
+        Ok(lockfile) => lockfile,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+
+    let modules = root.join(MODULES_DIR);
+    remove_dir(&modules)?;
+    fs::rename(&staging, &modules)
+        .map_err(|e| format!("Error moving packages into '{}': {}", modules.display(), e))?;
+    lockfile.save(root)?;
+    Ok(lockfile)
+}
+
+fn install_all(
+    root: &Path,
+    manifest: &Manifest,
+    previous: &Lockfile,
+    staging: &Path,
+) -> Result<Lockfile, String> {
+    // Dependencies still to install, with the directory their paths are relative to
+    let mut queue: VecDeque<(String, Source, PathBuf)> = manifest
+        .dependencies
+        .iter()
+        .map(|(name, source)| (name.clone(), source.clone(), root.to_path_buf()))
+        .collect();
+    let mut lockfile = Lockfile::default();
+
+    while let Some((name, source, base)) = queue.pop_front() {
+        let described = source.to_string();
+        if let Some(existing) = lockfile.find(&name) {
+            if existing.source == described {
+                continue;
+            }
+            return Err(format!(
+                "Two dependencies are named '{}' but come from different places: {} and {}",
+                name, existing.source, described
+            ));
+        }
+
+        let target = staging.join(&name);
+        let (commit, package_base) = match &source {
+            Source::Path(path) => {
+                let from = base.join(path);
+                if !from.is_dir() {
+                    return Err(format!(
+                        "Dependency '{}': '{}' is not a directory",
+                        name,
+                        from.display()
+                    ));
+                }
+                copy_package(&from, &target)?;
+                (None, from)
+            }
+            Source::Git { url, rev } => {
+                let locked = previous
+                    .find(&name)
+                    .filter(|locked| locked.source == described)
+                    .and_then(|locked| locked.commit.clone());
+                let commit =
+                    clone_package(&name, &git_url(url, &base), locked.or(rev.clone()), &target)?;
+                (Some(commit), target.clone())
+            }
+        };
+
+        if target.join(MANIFEST_FILE).exists() {
+            let package = Manifest::load(&target)?;
+            package
+                .check_tilde_version()
+                .map_err(|e| format!("Dependency '{}': {}", name, e))?;
+            for (dependency, source) in package.dependencies {
+                queue.push_back((dependency, source, package_base.clone()));
+            }
+        }
+
+        lockfile.packages.push(LockedPackage {
+            name,
+            source: described,
+            commit,
+            checksum: checksum(&target)?,
+        });
+    }
+
+    lockfile.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(lockfile)
+}
+
+/// Check that `tilde_modules/` holds what the manifest and lockfile say
+pub fn check_installed(root: &Path, manifest: &Manifest) -> Result<(), String> {
+    if manifest.dependencies.is_empty() {
+        return Ok(());
+    }
+    let stale = |reason: String| Err(format!("{}; run `tilde install`", reason));
+    let Some(lockfile) = Lockfile::load(root)? else {
+        return stale("Dependencies aren't installed".to_string());
+    };
+    for (name, source) in &manifest.dependencies {
+        if lockfile
+            .find(name)
+            .is_none_or(|locked| locked.source != source.to_string())
+        {
+            return stale(format!("'{}' in tilde.toml isn't installed", name));
+        }
+    }
+    for package in &lockfile.packages {
+        let dir = root.join(MODULES_DIR).join(&package.name);
+        if !dir.is_dir() || checksum(&dir)? != package.checksum {
+            return stale(format!(
+                "'{}' in {} doesn't match tilde.lock",
+                package.name, MODULES_DIR
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Local repository paths are relative to the project, like path dependencies
+fn git_url(url: &str, base: &Path) -> String {
+    let remote = url.contains("://") || url.contains('@') || Path::new(url).is_absolute();
+    if remote {
+        url.to_string()
+    } else {
+        base.join(url).display().to_string()
+    }
+}
+
+/// Clone a git dependency at `rev`, or the default branch, and give the
+/// commit it's at. The package is installed without its history.
+fn clone_package(
+    name: &str,
+    url: &str,
+    rev: Option<String>,
+    target: &Path,
+) -> Result<String, String> {
+    let target_arg = target.display().to_string();
+    git(&["clone", "--quiet", url, &target_arg], None)
+        .map_err(|e| format!("Dependency '{}': {}", name, e))?;
+    if let Some(rev) = rev {
+        git(&["checkout", "--quiet", &rev], Some(target))
+            .map_err(|e| format!("Dependency '{}': {}", name, e))?;
+    }
+    let commit = git(&["rev-parse", "HEAD"], Some(target))?;
+    remove_dir(&target.join(".git"))?;
+    Ok(commit)
+}
+
+fn git(args: &[&str], dir: Option<&Path>) -> Result<String, String> {
+    let mut command = Command::new("git");
+    command.args(args).env("GIT_TERMINAL_PROMPT", "0");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("Couldn't run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Copy a package's files, leaving out version control and installed packages
+fn copy_package(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("Error creating '{}': {}", to.display(), e))?;
+    for path in package_files(from)? {
+        let relative = path.strip_prefix(from).expect("listed below the package");
+        let destination = to.join(relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Error creating '{}': {}", parent.display(), e))?;
+        }
+        fs::copy(&path, &destination)
+            .map_err(|e| format!("Error copying '{}': {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Every file in a package, sorted, skipping `.git` and `tilde_modules`
+fn package_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Error reading '{}': {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Error reading '{}': {}", dir.display(), e))?;
+            let path = entry.path();
+            let name = entry.file_name();
+            if name == ".git" || name == MODULES_DIR {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// SHA-256 over each file's path and contents
+fn checksum(dir: &Path) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    for path in package_files(dir)? {
+        let relative = path.strip_prefix(dir).expect("listed below the package");
+        let contents =
+            fs::read(&path).map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+        hasher.update(relative.to_string_lossy().replace('\\', "/").as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(format!("sha256:{}", hex::encode(hasher.finalize())))
+}
+
+fn remove_dir(dir: &Path) -> Result<(), String> {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Error removing '{}': {}", dir.display(), e)),
+    }
+}
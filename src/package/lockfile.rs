@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::LOCKFILE;
+
+const HEADER: &str =
+    "# Written by `tilde install` to record exactly what it installed. Don't edit by hand.\n\n";
+
+/// The packages `tilde install` put in `tilde_modules/`, from `tilde.lock`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(rename = "package", default)]
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    /// The source as the manifest gave it, e.g. `git+https://...#v1.0`
+    pub source: String,
+    /// The commit a git dependency was checked out at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Hash of the installed files
+    pub checksum: String,
+}
+
+impl Lockfile {
+    pub fn parse(source: &str) -> Result<Lockfile, String> {
+        toml::from_str(source).map_err(|e| format!("{}: {}", LOCKFILE, e.message()))
+    }
+
+    /// Read a project's lockfile, if it has one
+    pub fn load(dir: &Path) -> Result<Option<Lockfile>, String> {
+        let path = dir.join(LOCKFILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+        Lockfile::parse(&source).map(Some)
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let path = dir.join(LOCKFILE);
+        fs::write(&path, self.to_toml())
+            .map_err(|e| format!("Error writing '{}': {}", path.display(), e))
+    }
+
+    pub fn to_toml(&self) -> String {
+        let body = toml::to_string(self).expect("lockfiles always serialize");
+        format!("{}{}", HEADER, body)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+}
@@ -0,0 +1,170 @@
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::MANIFEST_FILE;
+
+/// Entry script used when a manifest doesn't name one
+pub const DEFAULT_ENTRY: &str = "main.tde";
+
+/// A project's `tilde.toml`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    /// Script run by `tilde run`, and by `import "name"` in other projects
+    pub entry: String,
+    /// Tilde versions the project works with
+    pub tilde: Option<VersionReq>,
+    /// Dependencies by the name scripts import them as
+    pub dependencies: BTreeMap<String, Source>,
+}
+
+/// Where a dependency comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A directory, relative to the project that depends on it
+    Path(PathBuf),
+    /// A git repository, at a branch, tag or commit if given
+    Git { url: String, rev: Option<String> },
+}
+
+impl fmt::Display for Source {
+    /// How the source is written in the lockfile
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Path(path) => write!(f, "path+{}", path.display()),
+            Source::Git { url, rev: None } => write!(f, "git+{}", url),
+            Source::Git {
+                url,
+                rev: Some(rev),
+            } => write!(f, "git+{}#{}", url, rev),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    project: RawProject,
+    #[serde(default)]
+    dependencies: BTreeMap<String, RawDependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProject {
+    name: String,
+    entry: Option<String>,
+    tilde: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDependency {
+    path: Option<String>,
+    git: Option<String>,
+    rev: Option<String>,
+}
+
+impl Manifest {
+    pub fn parse(source: &str) -> Result<Manifest, String> {
+        let raw: RawManifest = toml::from_str(source).map_err(|e| e.message().to_string())?;
+        check_name(&raw.project.name, "Project")?;
+        let tilde = raw
+            .project
+            .tilde
+            .map(|requirement| {
+                VersionReq::parse(&requirement).map_err(|e| {
+                    format!("Invalid tilde version requirement '{}': {}", requirement, e)
+                })
+            })
+            .transpose()?;
+
+        let mut dependencies = BTreeMap::new();
+        for (name, dependency) in raw.dependencies {
+            check_name(&name, "Dependency")?;
+            let source = match dependency {
+                RawDependency {
+                    path: Some(path),
+                    git: None,
+                    rev: None,
+                } => Source::Path(PathBuf::from(path)),
+                RawDependency {
+                    path: None,
+                    git: Some(url),
+                    rev,
+                } => Source::Git { url, rev },
+                RawDependency {
+                    path: Some(_),
+                    rev: Some(_),
+                    ..
+                } => {
+                    return Err(format!(
+                        "Dependency '{}': rev only applies to git dependencies",
+                        name
+                    ));
+                }
+                _ => {
+                    return Err(format!(
+                        "Dependency '{}' needs exactly one of path or git",
+                        name
+                    ));
+                }
+            };
+            dependencies.insert(name, source);
+        }
+
+        Ok(Manifest {
+            name: raw.project.name,
+            entry: raw
+                .project
+                .entry
+                .unwrap_or_else(|| DEFAULT_ENTRY.to_string()),
+            tilde,
+            dependencies,
+        })
+    }
+
+    /// Read the manifest in a project directory
+    pub fn load(dir: &Path) -> Result<Manifest, String> {
+        let path = dir.join(MANIFEST_FILE);
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+        Manifest::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Fail unless this version of Tilde meets the project's requirement
+    pub fn check_tilde_version(&self) -> Result<(), String> {
+        let Some(requirement) = &self.tilde else {
+            return Ok(());
+        };
+        let current = Version::parse(env!("CARGO_PKG_VERSION")).expect("crate version is semver");
+        if requirement.matches(&current) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} needs Tilde {}, but this is Tilde {}",
+                self.name, requirement, current
+            ))
+        }
+    }
+}
+
+/// Names are used as directory names and in `import`, so keep them simple
+fn check_name(name: &str, kind: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} name '{}' may only use letters, digits, '-' and '_'",
+            kind, name
+        ))
+    }
+}
@@ -0,0 +1,204 @@
+//! Projects: the `tilde.toml` manifest, dependencies and imports.
+//!
+//! A project is a directory whose `tilde.toml` names the project, its entry
+//! script, the Tilde versions it works with, and its dependencies, each from
+//! a local path or a git URL. `tilde install` copies the dependencies into
+//! `tilde_modules/` and records exactly what it installed in `tilde.lock`.
+//! Scripts load them with `import "name"`, which runs the package's entry
+//! script, or `import "name/file"` for another of its files.
+
+mod install;
+mod lockfile;
+mod manifest;
+
+pub use install::{check_installed, install};
+pub use lockfile::{LockedPackage, Lockfile};
+pub use manifest::{DEFAULT_ENTRY, Manifest, Source};
+
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "tilde.toml";
+pub const LOCKFILE: &str = "tilde.lock";
+/// Where installed dependencies live, one directory each
+pub const MODULES_DIR: &str = "tilde_modules";
+
+/// The nearest directory at or above `start` with a `tilde.toml`
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(MANIFEST_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Where `import` looks for files: the real filesystem, or the files built
+/// into a standalone executable
+pub trait ImportFiles {
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn read(&self, path: &Path) -> Result<String, String>;
+}
+
+/// Imports from the real filesystem
+pub struct DiskFiles;
+
+impl ImportFiles for DiskFiles {
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read(&self, path: &Path) -> Result<String, String> {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading '{}': {}", path.display(), e))
+    }
+}
+
+/// The file `import spec` loads. Paths starting with `.` or `/`, or ending
+/// in `.tde`, are files relative to the importing script (`from`, or the
+/// working directory); anything else names an installed package. Packages
+/// are found in the nearest `tilde_modules` above the importing script, so
+/// packages can import the other packages installed alongside them.
+pub fn resolve_import(spec: &str, from: Option<&Path>) -> Result<PathBuf, String> {
+    resolve_import_in(spec, from, &DiskFiles)
+}
+
+/// Like `resolve_import`, looking for files in `files`
+pub fn resolve_import_in(
+    spec: &str,
+    from: Option<&Path>,
+    files: &dyn ImportFiles,
+) -> Result<PathBuf, String> {
+    let base = from.and_then(Path::parent).unwrap_or(Path::new(""));
+
+    if is_file_spec(spec) {
+        let path = with_extension(base.join(spec));
+        return if files.is_file(&path) {
+            Ok(path)
+        } else {
+            Err(format!(
+                "Can't import '{}': {} not found",
+                spec,
+                path.display()
+            ))
+        };
+    }
+
+    let (name, file) = match spec.split_once('/') {
+        Some((name, file)) => (name, Some(file)),
+        None => (spec, None),
+    };
+    let Some(package) = base
+        .ancestors()
+        .map(|dir| dir.join(MODULES_DIR).join(name))
+        .find(|dir| files.is_dir(dir))
+    else {
+        return Err(format!(
+            "Can't import '{}': no package named '{}' is installed (add it to tilde.toml and run `tilde install`)",
+            spec, name
+        ));
+    };
+
+    let manifest = package.join(MANIFEST_FILE);
+    let path = match file {
+        Some(file) => with_extension(package.join(file)),
+        None if files.is_file(&manifest) => {
+            let entry = Manifest::parse(&files.read(&manifest)?)
+                .map_err(|e| format!("{}: {}", manifest.display(), e))?
+                .entry;
+            package.join(entry)
+        }
+        None => package.join(DEFAULT_ENTRY),
+    };
+    if files.is_file(&path) {
+        Ok(path)
+    } else {
+        Err(format!(
+            "Can't import '{}': package '{}' has no {}",
+            spec,
+            name,
+            path.strip_prefix(&package).unwrap_or(&path).display()
+        ))
+    }
+}
+
+/// Whether `import spec` names a file rather than a package
+fn is_file_spec(spec: &str) -> bool {
+    spec.starts_with('.') || spec.starts_with('/') || spec.ends_with(".tde")
+}
+
+fn with_extension(path: PathBuf) -> PathBuf {
+    if path.extension().is_some() {
+        path
+    } else {
+        path.with_extension("tde")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            r#"
+[project]
+name = "weather-bot"
+tilde = ">=0.6"
+
+[dependencies]
+strings = { path = "../strings" }
+colors = { git = "https://example.com/colors.git", rev = "v1.0" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.entry, "main.tde");
+        assert_eq!(
+            manifest.dependencies["strings"],
+            Source::Path(PathBuf::from("../strings"))
+        );
+        assert_eq!(
+            manifest.dependencies["colors"].to_string(),
+            "git+https://example.com/colors.git#v1.0"
+        );
+        assert!(manifest.check_tilde_version().is_ok());
+
+        let too_new = Manifest::parse("[project]\nname = \"x\"\ntilde = \">=99\"\n").unwrap();
+        assert!(
+            too_new
+                .check_tilde_version()
+                .unwrap_err()
+                .contains("needs Tilde >=99")
+        );
+    }
+
+    #[test]
+    fn test_manifest_errors() {
+        let error = |source: &str| Manifest::parse(source).unwrap_err();
+        assert!(error("[project]\nname = \"a b\"\n").contains("may only use"));
+        assert!(error("[project]\nname = \"a\"\n[dependencies]\nx = {}\n").contains("exactly one"));
+        assert!(
+            error("[project]\nname = \"a\"\n[dependencies]\nx = { path = \"y\", rev = \"z\" }\n")
+                .contains("rev only applies")
+        );
+        assert!(error("[project]\nname = \"a\"\nversion = 1\n").contains("unknown field"));
+    }
+
+    #[test]
+    fn test_lockfile_round_trip() {
+        let lockfile = Lockfile {
+            packages: vec![LockedPackage {
+                name: "colors".to_string(),
+                source: "git+../colors.git".to_string(),
+                commit: Some("abc123".to_string()),
+                checksum: "sha256:00".to_string(),
+            }],
+        };
+        let written = lockfile.to_toml();
+        assert!(written.starts_with("# Written by `tilde install`"));
+        assert_eq!(Lockfile::parse(&written).unwrap(), lockfile);
+    }
+}
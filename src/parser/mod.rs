@@ -1,33 +1,100 @@
 use crate::ast::*;
-use crate::lexer::{Lexer, Token};
+use crate::lexer::{Comment, Lexer, Token};
+use std::collections::HashSet;
 
 mod expressions;
 mod literals;
 mod statements;
 
+/// A syntax error found by `Parser::parse_recovering`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// The 1-based line the parser had reached, if line numbers are known
+    pub line: Option<usize>,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
+    lines: Vec<usize>,
     position: usize,
+    locate: bool,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    keep_comments: bool,
+    recover: bool,
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
     pub fn new(input: &str) -> Self {
-        let mut lexer = Lexer::new(input);
-        let tokens = lexer.tokenize();
+        Self::new_with_blocks(input, &HashSet::new())
+    }
+
+    /// Parse `input` with the embedding host's block names, as returned by
+    /// `Evaluator::parser`
+    pub fn new_with_blocks(input: &str, blocks: &HashSet<String>) -> Self {
+        let mut lexer = Lexer::new(input).with_blocks(blocks);
+        let (tokens, lines) = lexer.tokenize_with_lines();
 
         Parser {
             tokens,
+            lines,
             position: 0,
+            locate: false,
+            comments: lexer.take_comments(),
+            next_comment: 0,
+            keep_comments: false,
+            recover: false,
+            errors: Vec::new(),
         }
     }
 
     pub fn new_from_tokens(tokens: Vec<Token>) -> Self {
         Parser {
             tokens,
+            lines: Vec::new(),
             position: 0,
+            locate: false,
+            comments: Vec::new(),
+            next_comment: 0,
+            keep_comments: false,
+            recover: false,
+            errors: Vec::new(),
         }
     }
 
+    /// Wrap every statement, including nested ones, in `Statement::Located`
+    /// so tools can map execution back to source lines
+    pub fn with_locations(mut self) -> Self {
+        self.locate = true;
+        self
+    }
+
+    /// Keep comments and blank lines between statements as `Statement::Trivia`,
+    /// so tools like `tilde fmt` can reproduce them
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    /// The 1-based line of the current token, if line numbers are known
+    pub fn current_line(&self) -> Option<usize> {
+        self.lines
+            .get(self.position)
+            .or_else(|| self.lines.last())
+            .copied()
+    }
+
     pub(crate) fn current_token(&self) -> &Token {
         self.tokens.get(self.position).unwrap_or(&Token::Eof)
     }
@@ -82,6 +149,133 @@ impl Parser {
         }
     }
 
+    /// Skip the newlines between statements. With `with_comments`, comments
+    /// and blank lines passed on the way are added to `statements` as trivia.
+    pub(crate) fn skip_trivia(&mut self, statements: &mut Vec<Statement>) {
+        if !self.keep_comments {
+            self.skip_newlines();
+            return;
+        }
+
+        let mut newlines = 0;
+        loop {
+            while let Some(comment) = self.comments.get(self.next_comment)
+                && comment.before_token <= self.position
+            {
+                let trivia = if comment.own_line {
+                    if newlines > 1 {
+                        statements.push(Statement::Trivia(Trivia::BlankLine));
+                    }
+                    Trivia::Comment(comment.text.clone())
+                } else {
+                    Trivia::TrailingComment(comment.text.clone())
+                };
+                statements.push(Statement::Trivia(trivia));
+                self.next_comment += 1;
+                newlines = 0;
+            }
+            if *self.current_token() != Token::Newline {
+                break;
+            }
+            newlines += 1;
+            self.advance();
+        }
+        if newlines > 1 {
+            statements.push(Statement::Trivia(Trivia::BlankLine));
+        }
+    }
+
+    /// The `##` comment lines directly above the current token, joined with
+    /// newlines. Blank lines may separate them from the token; an ordinary
+    /// comment may not.
+    pub(crate) fn doc_comment(&self) -> Option<String> {
+        let mut start = self.position;
+        while start > 0 && self.tokens[start - 1] == Token::Newline {
+            start -= 1;
+        }
+        let above = self.comments.iter().filter(|comment| {
+            comment.own_line && (start..=self.position).contains(&comment.before_token)
+        });
+        let mut lines = Vec::new();
+        for comment in above {
+            match comment.doc() {
+                Some(line) => lines.push(line),
+                None => lines.clear(),
+            }
+        }
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    /// Parse the statements of a `( ... )` body, stopping before the `)`
+    pub(crate) fn parse_body(&mut self) -> Result<Vec<Statement>, String> {
+        let mut body = Vec::new();
+        loop {
+            self.skip_trivia(&mut body);
+            if matches!(self.current_token(), Token::RightParen | Token::Eof) {
+                break;
+            }
+            body.push(self.parse_statement_or_recover(true)?);
+        }
+        Ok(body)
+    }
+
+    /// Parse a statement. While recovering, a statement that fails is
+    /// recorded, skipped up to where the next one can start, and stood in
+    /// for by `Statement::Error`.
+    fn parse_statement_or_recover(&mut self, in_body: bool) -> Result<Statement, String> {
+        let start = self.position;
+        let line = self.current_line();
+        let message = match self.parse_statement() {
+            Err(message) if self.recover => message,
+            result => return result,
+        };
+
+        self.errors.push(ParseError {
+            message: message.clone(),
+            line: self.current_line(),
+        });
+        self.synchronize(start, in_body);
+        let statement = Statement::Error(message);
+        match line {
+            Some(line) if self.locate => Ok(Statement::Located {
+                line,
+                statement: Box::new(statement),
+            }),
+            _ => Ok(statement),
+        }
+    }
+
+    /// Skip past a statement that failed to parse: to the end of its line,
+    /// or before the `)` closing the body it is in. A `)` closing a
+    /// parenthesis the statement opened is skipped with it. Bodies recover
+    /// their own statements, so the skip never needs to cross a line.
+    fn synchronize(&mut self, start: usize, in_body: bool) {
+        let mut depth = 0usize;
+        for token in &self.tokens[start..self.position] {
+            match token {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+
+        loop {
+            match self.current_token() {
+                Token::Eof => return,
+                Token::Newline => return,
+                Token::RightParen if depth == 0 && in_body => return,
+                Token::RightParen => depth = depth.saturating_sub(1),
+                Token::LeftParen => depth += 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
     pub(crate) fn is_binary_operator(&self, token: &Token) -> bool {
         matches!(
             token,
@@ -105,19 +299,27 @@ impl Parser {
     pub fn parse(&mut self) -> Result<Program, String> {
         let mut statements = Vec::new();
 
-        while *self.current_token() != Token::Eof {
-            self.skip_newlines();
+        loop {
+            self.skip_trivia(&mut statements);
             if *self.current_token() == Token::Eof {
                 break;
             }
-
-            let stmt = self.parse_statement()?;
-            statements.push(stmt);
-            self.skip_newlines();
+            statements.push(self.parse_statement_or_recover(false)?);
         }
 
         Ok(statements)
     }
+
+    /// Parse the whole program, carrying on past syntax errors. Each
+    /// statement that fails becomes a `Statement::Error` in the program,
+    /// and every error is returned, in source order.
+    pub fn parse_recovering(&mut self) -> (Program, Vec<ParseError>) {
+        self.recover = true;
+        let program = self
+            .parse()
+            .expect("a recovering parse records errors rather than returning them");
+        (program, std::mem::take(&mut self.errors))
+    }
 }
 
 #[cfg(test)]
@@ -139,6 +341,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_with_locations() {
+        let program = Parser::new("~x is 1\n\nloop (\n    ~x up 1\n)")
+            .with_locations()
+            .parse()
+            .unwrap();
+
+        assert_eq!(program.len(), 2);
+        match &program[1] {
+            Statement::Located { line: 3, statement } => match statement.as_ref() {
+                Statement::Loop { body } => {
+                    assert!(matches!(body[0], Statement::Located { line: 4, .. }))
+                }
+                other => panic!("Expected loop, got {:?}", other),
+            },
+            other => panic!("Expected located loop, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_test_blocks() {
+        let program = Parser::new(
+            "setup (\n    ~x is 1\n)\ntest \"adds\" (\n    expect ~x == 1\n)\nteardown ()\n~y: trim\nexpect ~y",
+        )
+        .parse()
+        .unwrap();
+
+        assert!(matches!(&program[0], Statement::Setup { body } if body.len() == 1));
+        match &program[1] {
+            Statement::Test { name, body } => {
+                assert_eq!(name, "adds");
+                assert!(matches!(
+                    &body[0],
+                    Statement::Expect(Expression::BinaryOp {
+                        op: BinaryOperator::Equal,
+                        ..
+                    })
+                ));
+            }
+            other => panic!("Expected test, got {:?}", other),
+        }
+        assert!(matches!(&program[2], Statement::Teardown { body } if body.is_empty()));
+        // A chain ends before an `expect`
+        assert!(matches!(&program[3], Statement::FunctionChain { steps, .. } if steps.len() == 1));
+        assert!(matches!(&program[4], Statement::Expect(_)));
+    }
+
     #[test]
     fn test_parse_expression() {
         let mut parser = Parser::new("~result is ~x + ~y");
@@ -787,6 +1036,72 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_recovering_collects_every_error() {
+        let mut parser = Parser::new("~a is\nsay \"ok\"\n~b is (1 +\n~c is 3\n");
+        let (program, errors) = parser.parse_recovering();
+
+        let lines: Vec<Option<usize>> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![Some(1), Some(3)]);
+        assert_eq!(errors[0].to_string(), "line 1: Unexpected token: Newline");
+        assert_eq!(program.len(), 4);
+        assert!(matches!(program[0], Statement::Error(_)));
+        assert!(matches!(program[1], Statement::Expression(_)));
+        assert!(matches!(program[2], Statement::Error(_)));
+        assert!(matches!(program[3], Statement::Assignment { .. }));
+
+        // parse still stops at the first error
+        assert!(Parser::new("~a is\nsay \"ok\"").parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_recovering_inside_bodies() {
+        let source = "loop (\n    ~x is (1 + )\n    break-loop\n)\nsay \"after\"\n)\n";
+        let (program, errors) = Parser::new(source).with_locations().parse_recovering();
+
+        // The `)` in the bad statement is its own; the stray one at the end is skipped
+        let lines: Vec<Option<usize>> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![Some(2), Some(6)]);
+        assert_eq!(program.len(), 3);
+        match &program[0] {
+            Statement::Located { statement, .. } => match statement.as_ref() {
+                Statement::Loop { body } => {
+                    assert_eq!(body.len(), 2);
+                    match &body[0] {
+                        Statement::Located { line, statement } => {
+                            assert_eq!(*line, 2);
+                            assert!(matches!(**statement, Statement::Error(_)));
+                        }
+                        other => panic!("Expected located error, got {:?}", other),
+                    }
+                }
+                other => panic!("Expected loop, got {:?}", other),
+            },
+            other => panic!("Expected located loop, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_leading_paren() {
+        // Blocks: a statement inside, or more than one line
+        let blocks = ["(~x is 1)", "(break-loop)", "(say 1\nsay 2)", "(\nsay [1,\n2]\n)"];
+        for source in blocks {
+            let program = Parser::new(source).parse().unwrap();
+            assert!(
+                matches!(program[0], Statement::Block { .. }),
+                "{} should be a block",
+                source
+            );
+        }
+
+        let program = Parser::new("(1 + 2) * 3").parse().unwrap();
+        assert!(matches!(program[0], Statement::Expression(_)));
+
+        // An expression's own error is reported, not one from parsing it as a block
+        let error = Parser::new("(1 + ) * 3").parse().unwrap_err();
+        assert_eq!(error, "Unexpected token: RightParen");
+    }
+
     #[test]
     fn test_parse_anonymous_function_errors() {
         // Missing closing pipe
@@ -4,6 +4,19 @@ use crate::lexer::Token;
 
 impl Parser {
     pub fn parse_statement(&mut self) -> Result<Statement, String> {
+        if self.locate
+            && let Some(line) = self.current_line()
+        {
+            let statement = self.parse_unlocated_statement()?;
+            return Ok(Statement::Located {
+                line,
+                statement: Box::new(statement),
+            });
+        }
+        self.parse_unlocated_statement()
+    }
+
+    fn parse_unlocated_statement(&mut self) -> Result<Statement, String> {
         match self.current_token() {
             Token::Variable(name) => {
                 let var_name = name.clone();
@@ -118,18 +131,7 @@ impl Parser {
                 let path = self.parse_expression()?;
                 Ok(Statement::Open(path))
             }
-            Token::LeftParen => {
-                // Try parsing as a regular expression statement first (which handles parentheses)
-                let saved_pos = self.position;
-                match self.parse_expression() {
-                    Ok(expr) => Ok(Statement::Expression(expr)),
-                    Err(_) => {
-                        // Restore position and try as block
-                        self.position = saved_pos;
-                        self.parse_block()
-                    }
-                }
-            }
+            Token::LeftParen if self.is_at_block() => self.parse_block(),
             Token::Function => self.parse_function_definition(),
             Token::Give => {
                 self.advance();
@@ -137,6 +139,7 @@ impl Parser {
                 Ok(Statement::Give(expr))
             }
             Token::Attempt => self.parse_attempt_rescue(),
+            Token::Identifier(_) if self.is_at_test_statement() => self.parse_test_statement(),
             _ => {
                 let expr = self.parse_expression()?;
                 Ok(Statement::Expression(expr))
@@ -150,13 +153,16 @@ impl Parser {
         let condition = self.parse_expression()?;
         let then_stmt = Box::new(self.parse_statement()?);
 
-        // Skip newlines before checking for else clause
+        // Skip newlines before checking for else clause, leaving them for
+        // the enclosing body if there isn't one
+        let after_then = self.position;
         self.skip_newlines();
 
         let else_stmt = if *self.current_token() == Token::Else {
             self.advance();
             Some(Box::new(self.parse_statement()?))
         } else {
+            self.position = after_then;
             None
         };
 
@@ -170,19 +176,7 @@ impl Parser {
     pub fn parse_loop(&mut self) -> Result<Statement, String> {
         self.expect(Token::Loop)?;
         self.expect(Token::LeftParen)?;
-        self.skip_newlines();
-
-        let mut body = Vec::new();
-
-        while *self.current_token() != Token::RightParen && *self.current_token() != Token::Eof {
-            self.skip_newlines();
-            if *self.current_token() == Token::RightParen {
-                break;
-            }
-            body.push(self.parse_statement()?);
-            self.skip_newlines();
-        }
-
+        let body = self.parse_body()?;
         self.expect(Token::RightParen)?;
 
         Ok(Statement::Loop { body })
@@ -221,19 +215,7 @@ impl Parser {
 
         // Parse body
         self.expect(Token::LeftParen)?;
-        self.skip_newlines();
-
-        let mut body = Vec::new();
-
-        while *self.current_token() != Token::RightParen && *self.current_token() != Token::Eof {
-            self.skip_newlines();
-            if *self.current_token() == Token::RightParen {
-                break;
-            }
-            body.push(self.parse_statement()?);
-            self.skip_newlines();
-        }
-
+        let body = self.parse_body()?;
         self.expect(Token::RightParen)?;
 
         Ok(Statement::ForEach {
@@ -245,25 +227,14 @@ impl Parser {
 
     pub fn parse_block(&mut self) -> Result<Statement, String> {
         self.expect(Token::LeftParen)?;
-        self.skip_newlines();
-
-        let mut body = Vec::new();
-
-        while *self.current_token() != Token::RightParen && *self.current_token() != Token::Eof {
-            self.skip_newlines();
-            if *self.current_token() == Token::RightParen {
-                break;
-            }
-            body.push(self.parse_statement()?);
-            self.skip_newlines();
-        }
-
+        let body = self.parse_body()?;
         self.expect(Token::RightParen)?;
 
         Ok(Statement::Block { body })
     }
 
     pub fn parse_function_definition(&mut self) -> Result<Statement, String> {
+        let doc = self.doc_comment();
         self.expect(Token::Function)?;
 
         // Parse function name
@@ -282,39 +253,23 @@ impl Parser {
 
         // Parse body block
         self.expect(Token::LeftParen)?;
-        self.skip_newlines();
-
-        let mut body = Vec::new();
-        while *self.current_token() != Token::RightParen && *self.current_token() != Token::Eof {
-            self.skip_newlines();
-            if *self.current_token() == Token::RightParen {
-                break;
-            }
-            body.push(self.parse_statement()?);
-            self.skip_newlines();
-        }
-
+        let body = self.parse_body()?;
         self.expect(Token::RightParen)?;
 
-        Ok(Statement::FunctionDefinition { name, params, body })
+        Ok(Statement::FunctionDefinition {
+            name,
+            params,
+            body,
+            doc,
+        })
     }
 
     pub fn parse_attempt_rescue(&mut self) -> Result<Statement, String> {
         self.expect(Token::Attempt)?;
         self.expect(Token::LeftParen)?;
-        self.skip_newlines();
 
         // Parse attempt body
-        let mut attempt_body = Vec::new();
-        while *self.current_token() != Token::RightParen && *self.current_token() != Token::Eof {
-            self.skip_newlines();
-            if *self.current_token() == Token::RightParen {
-                break;
-            }
-            attempt_body.push(self.parse_statement()?);
-            self.skip_newlines();
-        }
-
+        let attempt_body = self.parse_body()?;
         self.expect(Token::RightParen)?;
         self.expect(Token::Rescue)?;
 
@@ -328,19 +283,9 @@ impl Parser {
         };
 
         self.expect(Token::LeftParen)?;
-        self.skip_newlines();
 
         // Parse rescue body
-        let mut rescue_body = Vec::new();
-        while *self.current_token() != Token::RightParen && *self.current_token() != Token::Eof {
-            self.skip_newlines();
-            if *self.current_token() == Token::RightParen {
-                break;
-            }
-            rescue_body.push(self.parse_statement()?);
-            self.skip_newlines();
-        }
-
+        let rescue_body = self.parse_body()?;
         self.expect(Token::RightParen)?;
 
         Ok(Statement::AttemptRescue {
@@ -350,6 +295,102 @@ impl Parser {
         })
     }
 
+    /// Whether the `(` at the current token opens a block rather than a
+    /// parenthesized expression: its contents start with a statement, or
+    /// run over more than one line
+    fn is_at_block(&self) -> bool {
+        let starts_statement = match self.peek_token() {
+            Token::If
+            | Token::Loop
+            | Token::ForEach
+            | Token::Breakloop
+            | Token::Open
+            | Token::Function
+            | Token::Give
+            | Token::Attempt => true,
+            Token::Identifier(name) => {
+                matches!(name.as_str(), "test" | "setup" | "teardown" | "expect")
+            }
+            Token::Variable(_) => {
+                // ~var is/up/down/: and ~var.property is
+                let mut index = self.position + 2;
+                while self.tokens.get(index) == Some(&Token::Dot) {
+                    index += 2;
+                }
+                matches!(
+                    self.tokens.get(index),
+                    Some(Token::Is | Token::Up | Token::Down | Token::Colon)
+                )
+            }
+            _ => false,
+        };
+        if starts_statement {
+            return true;
+        }
+
+        // Newlines inside list and object literals don't count
+        let mut depth = 0usize;
+        for token in &self.tokens[self.position..] {
+            match token {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightBracket | Token::RightBrace => depth = depth.saturating_sub(1),
+                Token::RightParen if depth == 1 => return false,
+                Token::RightParen => depth = depth.saturating_sub(1),
+                Token::Newline if depth == 1 => return true,
+                Token::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// `test`, `setup`, `teardown` and `expect` aren't keywords, so they can
+    /// still name keys and properties; they only start a statement in the
+    /// shapes below
+    fn is_at_test_statement(&self) -> bool {
+        let Token::Identifier(name) = self.current_token() else {
+            return false;
+        };
+        match name.as_str() {
+            "test" => matches!(self.peek_token(), Token::String(_)),
+            "setup" | "teardown" => *self.peek_token() == Token::LeftParen,
+            "expect" => !matches!(
+                self.peek_token(),
+                Token::Newline | Token::Eof | Token::RightParen
+            ),
+            _ => false,
+        }
+    }
+
+    pub fn parse_test_statement(&mut self) -> Result<Statement, String> {
+        let Token::Identifier(keyword) = self.current_token().clone() else {
+            return Err("Expected test, setup, teardown or expect".to_string());
+        };
+        self.advance();
+
+        if keyword == "expect" {
+            return Ok(Statement::Expect(self.parse_expression()?));
+        }
+        let name = match (keyword.as_str(), self.current_token()) {
+            ("test", Token::String(name)) => {
+                let name = name.clone();
+                self.advance();
+                name
+            }
+            _ => String::new(),
+        };
+
+        self.expect(Token::LeftParen)?;
+        let body = self.parse_body()?;
+        self.expect(Token::RightParen)?;
+
+        Ok(match keyword.as_str() {
+            "setup" => Statement::Setup { body },
+            "teardown" => Statement::Teardown { body },
+            _ => Statement::Test { name, body },
+        })
+    }
+
     pub fn parse_chain_steps(&mut self) -> Result<Vec<ChainStep>, String> {
         let mut steps = Vec::new();
 
@@ -423,9 +464,13 @@ impl Parser {
 
             // Skip to next line
             if *self.current_token() == Token::Newline {
-                self.advance();
-                // Skip any additional whitespace/newlines before next step
+                // Skip any additional whitespace/newlines before next step,
+                // leaving them for the enclosing body if the chain ends here
+                let line_end = self.position;
                 self.skip_newlines();
+                if !matches!(self.current_token(), Token::Identifier(_) | Token::Block(_)) {
+                    self.position = line_end;
+                }
             }
         }
 
@@ -457,6 +502,7 @@ impl Parser {
             Token::Give => true,      // Next give statement
             Token::Open => true,      // Next open statement
             Token::LeftParen => true, // Next block
+            Token::Identifier(_) => self.is_at_test_statement(),
             _ => false,
         }
     }
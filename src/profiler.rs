@@ -0,0 +1,284 @@
+//! Per-function timings for `tilde --profile`.
+//!
+//! While profiling is on, the evaluator times every user, stdlib and native
+//! function call. It also counts the values each expression produces by
+//! kind. Inclusive time covers everything a call did. Exclusive ("self") time
+//! leaves out time spent in the functions it called. Spawned tasks are not
+//! profiled.
+
+use crate::embed::type_name_of;
+use crate::limits::Stopwatch;
+use crate::value::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Name used for time spent at the top level of the script
+pub const SCRIPT_FRAME: &str = "<script>";
+
+/// Where a profiled function comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CallKind {
+    User,
+    Stdlib,
+    Native,
+}
+
+impl CallKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CallKind::User => "user",
+            CallKind::Stdlib => "stdlib",
+            CallKind::Native => "native",
+        }
+    }
+}
+
+/// Timings for one function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionStats {
+    pub kind: CallKind,
+    pub name: String,
+    pub calls: u64,
+    /// Time from entry to exit, counted once for recursive calls
+    pub inclusive: Duration,
+    /// Inclusive time minus time spent in called functions
+    pub exclusive: Duration,
+}
+
+/// The results of a profiled run
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// Sorted by exclusive time, slowest first
+    pub functions: Vec<FunctionStats>,
+    /// Values produced by expressions, by kind, most common first
+    pub values: Vec<(&'static str, u64)>,
+    /// Exclusive time per call stack, with frames joined by `;`
+    pub stacks: Vec<(String, Duration)>,
+    pub total: Duration,
+}
+
+impl Profile {
+    /// A table of function timings and value counts
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Profile: {:.3} ms total", millis(self.total));
+        let _ = writeln!(
+            out,
+            "{:>10} {:>12} {:>12}  function",
+            "calls", "total ms", "self ms"
+        );
+        for stats in &self.functions {
+            let _ = writeln!(
+                out,
+                "{:>10} {:>12.3} {:>12.3}  {}{}",
+                stats.calls,
+                millis(stats.inclusive),
+                millis(stats.exclusive),
+                stats.name,
+                match stats.kind {
+                    CallKind::User => "",
+                    CallKind::Stdlib => " (stdlib)",
+                    CallKind::Native => " (native)",
+                }
+            );
+        }
+
+        if !self.values.is_empty() {
+            let _ = writeln!(out, "\nValues created:");
+            for (kind, count) in &self.values {
+                let _ = writeln!(out, "{:>10}  {}", count, kind);
+            }
+        }
+        out
+    }
+
+    /// Collapsed stacks (`a;b;c <microseconds>` per line), the input format
+    /// of flamegraph.pl, inferno and speedscope
+    pub fn collapsed_stacks(&self) -> String {
+        let mut out = String::new();
+        for (stack, time) in &self.stacks {
+            let micros = time.as_micros();
+            if micros > 0 {
+                let _ = writeln!(out, "{} {}", stack, micros);
+            }
+        }
+        out
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[derive(Debug)]
+struct Frame {
+    kind: CallKind,
+    name: String,
+    started: Stopwatch,
+    children: Duration,
+}
+
+/// Collects timings while a run is profiled
+#[derive(Debug)]
+pub(crate) struct Profiler {
+    started: Stopwatch,
+    stack: Vec<Frame>,
+    functions: HashMap<(CallKind, String), FunctionStats>,
+    stacks: HashMap<String, Duration>,
+    values: HashMap<&'static str, u64>,
+    /// Time spent in top-level calls, to work out the script's own time
+    top_level: Duration,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            started: Stopwatch::start(),
+            stack: Vec::new(),
+            functions: HashMap::new(),
+            stacks: HashMap::new(),
+            values: HashMap::new(),
+            top_level: Duration::ZERO,
+        }
+    }
+
+    pub fn enter(&mut self, kind: CallKind, name: &str) {
+        self.stack.push(Frame {
+            kind,
+            name: name.to_string(),
+            started: Stopwatch::start(),
+            children: Duration::ZERO,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let elapsed = frame.started.elapsed();
+        let exclusive = elapsed.saturating_sub(frame.children);
+        let recursive = self
+            .stack
+            .iter()
+            .any(|outer| outer.kind == frame.kind && outer.name == frame.name);
+
+        let path = self.stack_path(&frame.name);
+        *self.stacks.entry(path).or_default() += exclusive;
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.children += elapsed,
+            None => self.top_level += elapsed,
+        }
+
+        let stats = self
+            .functions
+            .entry((frame.kind, frame.name))
+            .or_insert_with_key(|(kind, name)| FunctionStats {
+                kind: *kind,
+                name: name.clone(),
+                calls: 0,
+                inclusive: Duration::ZERO,
+                exclusive: Duration::ZERO,
+            });
+        stats.calls += 1;
+        stats.exclusive += exclusive;
+        if !recursive {
+            stats.inclusive += elapsed;
+        }
+    }
+
+    fn stack_path(&self, innermost: &str) -> String {
+        let mut path = String::from(SCRIPT_FRAME);
+        for frame in &self.stack {
+            path.push(';');
+            path.push_str(&frame.name);
+        }
+        path.push(';');
+        path.push_str(innermost);
+        path
+    }
+
+    pub fn count_value(&mut self, value: &Value) {
+        *self.values.entry(type_name_of(value)).or_default() += 1;
+    }
+
+    pub fn finish(mut self) -> Profile {
+        // Close calls cut short by an error
+        while !self.stack.is_empty() {
+            self.exit();
+        }
+
+        let total = self.started.elapsed();
+        let script_time = total.saturating_sub(self.top_level);
+        *self.stacks.entry(SCRIPT_FRAME.to_string()).or_default() += script_time;
+
+        let mut functions: Vec<FunctionStats> = self.functions.into_values().collect();
+        functions.sort_by(|a, b| {
+            b.exclusive
+                .cmp(&a.exclusive)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let mut values: Vec<(&'static str, u64)> = self.values.into_iter().collect();
+        values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        let mut stacks: Vec<(String, Duration)> = self.stacks.into_iter().collect();
+        stacks.sort();
+
+        Profile {
+            functions,
+            values,
+            stacks,
+            total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exclusive_time_leaves_out_children() {
+        let mut profiler = Profiler::new();
+        profiler.enter(CallKind::User, "outer");
+        profiler.enter(CallKind::Stdlib, "map");
+        std::thread::sleep(Duration::from_millis(5));
+        profiler.exit();
+        profiler.exit();
+        let profile = profiler.finish();
+
+        let outer = profile.functions.iter().find(|f| f.name == "outer").unwrap();
+        let map = profile.functions.iter().find(|f| f.name == "map").unwrap();
+        assert!(map.exclusive >= Duration::from_millis(5));
+        assert!(outer.inclusive >= map.inclusive);
+        assert!(outer.exclusive < map.exclusive);
+        assert!(profile.stacks.iter().any(|(stack, _)| stack == "<script>;outer;map"));
+    }
+
+    #[test]
+    fn test_recursive_calls_count_inclusive_time_once() {
+        let mut profiler = Profiler::new();
+        profiler.enter(CallKind::User, "fib");
+        profiler.enter(CallKind::User, "fib");
+        std::thread::sleep(Duration::from_millis(5));
+        profiler.exit();
+        profiler.exit();
+        let profile = profiler.finish();
+
+        let fib = &profile.functions[0];
+        assert_eq!(fib.calls, 2);
+        assert!(fib.inclusive < Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_counts_values_by_kind() {
+        let mut profiler = Profiler::new();
+        profiler.count_value(&Value::Number(1.0));
+        profiler.count_value(&Value::Number(2.0));
+        profiler.count_value(&Value::String("a".to_string()));
+        let profile = profiler.finish();
+        assert_eq!(profile.values, vec![("number", 2), ("string", 1)]);
+    }
+}
@@ -0,0 +1,393 @@
+//! The interactive REPL that `tilde` starts with no arguments.
+//!
+//! `Repl` runs entries one at a time in one evaluator, echoing the value of
+//! any entry that prints nothing, as the browser REPL does. Entries starting
+//! with `:` are meta-commands for looking around the session; `:help` lists
+//! them. Reading lines is left to `line_editor`.
+
+use crate::embed::type_name_of;
+use crate::evaluator::Evaluator;
+use crate::io::OutputStream;
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use crate::sandbox::EvaluatorConfig;
+use crate::stdlib::{introspection, metadata};
+use crate::value::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// Meta-commands and what they do, for `:help` and completion
+const COMMANDS: &[(&str, &str)] = &[
+    (
+        ":help [name]",
+        "Show this list, or the documentation for a function",
+    ),
+    (":vars", "List variables and their values"),
+    (":funcs", "List the functions defined so far"),
+    (":type <expr>", "Show the type of an expression's value"),
+    (":ast <code>", "Show how code parses"),
+    (":time <code>", "Run code and show how long it took"),
+    (":load <file>", "Run a file in this session"),
+    (
+        ":save <file>",
+        "Save the code entered this session to a file",
+    ),
+    (":reset", "Forget all variables and functions"),
+    (":quit", "Leave the REPL (so do exit and Ctrl-D)"),
+];
+
+/// Words completed at the start of a word, besides stdlib names
+const KEYWORDS: &[&str] = &[
+    "is",
+    "if",
+    "else",
+    "loop",
+    "for-each",
+    "break-loop",
+    "in",
+    "say",
+    "ask",
+    "function",
+    "give",
+    "attempt",
+    "rescue",
+    "and",
+    "or",
+    "true",
+    "false",
+    "up",
+    "down",
+    "test",
+    "expect",
+];
+
+/// Where history is kept: `$TILDE_HISTORY`, or `~/.tilde_history`
+pub fn history_file() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("TILDE_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".tilde_history"))
+}
+
+/// Whether an entry is unfinished: it has unclosed brackets, or ends with
+/// the `:` that starts a chain
+pub fn needs_more_input(input: &str) -> bool {
+    let tokens = Lexer::new(input).tokenize();
+    let mut depth = 0;
+    for token in &tokens {
+        match token {
+            Token::LeftParen | Token::LeftBrace | Token::LeftBracket => depth += 1,
+            Token::RightParen | Token::RightBrace | Token::RightBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    let last = tokens
+        .iter()
+        .rev()
+        .find(|token| !matches!(token, Token::Newline | Token::Eof));
+    depth > 0 || (last == Some(&Token::Colon) && !input.trim_start().starts_with(':'))
+}
+
+/// An output stream that notes whether anything was written to it
+struct WatchedOutput {
+    inner: Arc<dyn OutputStream>,
+    written: AtomicBool,
+}
+
+impl OutputStream for WatchedOutput {
+    fn write(&self, text: &str) -> Result<(), String> {
+        self.written.store(true, Ordering::Relaxed);
+        self.inner.write(text)
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.inner.clear()
+    }
+}
+
+pub struct Repl {
+    evaluator: Evaluator,
+    config: EvaluatorConfig,
+    output: Arc<dyn OutputStream>,
+    watched: Arc<WatchedOutput>,
+    /// The code entered this session that ran, for `:save`
+    session: Vec<String>,
+}
+
+impl Repl {
+    /// A session running with `config`, writing everything to `output`
+    pub fn new(config: EvaluatorConfig, output: Arc<dyn OutputStream>) -> Self {
+        let watched = Arc::new(WatchedOutput {
+            inner: output.clone(),
+            written: AtomicBool::new(false),
+        });
+        let mut evaluator = Evaluator::with_config(config.clone());
+        evaluator.set_output(watched.clone());
+        Repl {
+            evaluator,
+            config,
+            output,
+            watched,
+            session: Vec::new(),
+        }
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    /// Run one complete entry, code or a meta-command, writing its output,
+    /// value and any error. Returns false if the entry asks to quit.
+    pub fn run_entry(&mut self, entry: &str) -> bool {
+        let entry = entry.trim();
+        if entry == "exit" {
+            return false;
+        }
+        if let Some(command) = entry.strip_prefix(':') {
+            return self.run_command(command) && self.exit_code().is_none();
+        }
+        if !entry.is_empty() && self.run_code(entry) {
+            self.session.push(entry.to_string());
+        }
+        self.exit_code().is_none()
+    }
+
+    /// The status code passed to `exit`, which ends the session
+    pub fn exit_code(&self) -> Option<i32> {
+        self.evaluator.exit_code()
+    }
+
+    /// Completions for the word before `cursor`, a byte offset into `line`:
+    /// where the word starts and what it could be
+    pub fn complete(&self, line: &str, cursor: usize) -> (usize, Vec<String>) {
+        let before = &line[..cursor];
+        let mut start = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || matches!(c, '-' | '_'))
+            .last()
+            .map_or(cursor, |(i, _)| i);
+        let sigil = before[..start]
+            .chars()
+            .next_back()
+            .filter(|c| matches!(c, '~' | '*') || (*c == ':' && start == 1));
+        if sigil.is_some() {
+            start -= 1;
+        }
+        let word = &before[start..];
+
+        let mut candidates: Vec<String> = match sigil {
+            Some('~') => self
+                .evaluator
+                .variables
+                .keys()
+                .map(|name| format!("~{}", name))
+                .collect(),
+            Some('*') => self.function_names(),
+            Some(_) => COMMANDS
+                .iter()
+                .map(|(usage, _)| usage.split(' ').next().unwrap_or(usage).to_string())
+                .collect(),
+            None => metadata::stdlib_functions()
+                .iter()
+                .map(|info| info.name)
+                .chain(KEYWORDS.iter().copied())
+                .map(|name| name.to_string())
+                .chain(self.function_names())
+                .collect(),
+        };
+        candidates.retain(|candidate| candidate.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+        (start, candidates)
+    }
+
+    fn function_names(&self) -> Vec<String> {
+        self.evaluator
+            .functions
+            .keys()
+            .map(|name| format!("*{}", name))
+            .collect()
+    }
+
+    fn write(&self, text: &str) {
+        // Nowhere is left to report a failed write to
+        let _ = self.output.write_line(text);
+    }
+
+    /// Run code, echoing its value if it printed nothing. Returns whether it
+    /// ran without an error.
+    fn run_code(&mut self, code: &str) -> bool {
+        match self.evaluate(code) {
+            Ok((value, printed)) => {
+                if !printed && value != Value::Null {
+                    self.write(&value.inspect());
+                }
+                true
+            }
+            Err(e) => {
+                if self.exit_code().is_none() {
+                    self.write(&e);
+                }
+                false
+            }
+        }
+    }
+
+    /// Run code, giving its value and whether it printed anything
+    fn evaluate(&mut self, code: &str) -> Result<(Value, bool), String> {
+        let program = Parser::new(code)
+            .parse()
+            .map_err(|e| format!("Parse error: {}", e))?;
+        self.watched.written.store(false, Ordering::Relaxed);
+        let value = self
+            .evaluator
+            .eval_program(program)
+            .map_err(|e| format!("Runtime error: {}", e))?;
+        Ok((value, self.watched.written.load(Ordering::Relaxed)))
+    }
+
+    fn run_command(&mut self, command: &str) -> bool {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+        let needs_argument = COMMANDS
+            .iter()
+            .find(|(usage, _)| usage.starts_with(&format!(":{} <", name)));
+        if let Some((usage, _)) = needs_argument
+            && argument.is_empty()
+        {
+            self.write(&format!("Usage: {}", usage));
+            return true;
+        }
+
+        match name {
+            "help" if !argument.is_empty() => {
+                match introspection::help_text(argument, &self.evaluator) {
+                    Ok(text) | Err(text) => self.write(&text),
+                }
+            }
+            "help" => {
+                let width = COMMANDS.iter().map(|(usage, _)| usage.len()).max();
+                for (usage, description) in COMMANDS {
+                    self.write(&format!(
+                        "{:width$}  {}",
+                        usage,
+                        description,
+                        width = width.unwrap_or(0)
+                    ));
+                }
+                self.write("Run `help` to list the stdlib functions by block");
+            }
+            "vars" => {
+                let variables = self.evaluator.visible_variables();
+                if variables.is_empty() {
+                    self.write("No variables yet");
+                }
+                for (name, value) in variables {
+                    self.write(&format!("~{} is {}", name, value.inspect()));
+                }
+            }
+            "funcs" => {
+                let mut functions: Vec<_> = self.evaluator.functions.iter().collect();
+                functions.sort_by_key(|(name, _)| name.as_str());
+                if functions.is_empty() {
+                    self.write("No functions yet");
+                }
+                for (name, function) in functions {
+                    let params: String = function
+                        .params
+                        .iter()
+                        .map(|param| format!(" ~{}", param))
+                        .collect();
+                    self.write(&format!("function {}{}", name, params));
+                }
+            }
+            "type" => match self.evaluate(argument) {
+                Ok((value, _)) => self.write(type_name_of(&value)),
+                Err(e) => self.write(&e),
+            },
+            "ast" => match Parser::new(argument).parse() {
+                Ok(program) => {
+                    for statement in program {
+                        self.write(&format!("{:#?}", statement));
+                    }
+                }
+                Err(e) => self.write(&format!("Parse error: {}", e)),
+            },
+            "time" => {
+                let start = Instant::now();
+                let ran = self.run_code(argument);
+                let elapsed = start.elapsed();
+                self.write(&format!("Took {:.3}ms", elapsed.as_secs_f64() * 1000.0));
+                if ran {
+                    self.session.push(argument.to_string());
+                }
+            }
+            "load" => {
+                let loaded = self
+                    .evaluator
+                    .capabilities()
+                    .check_path("load", argument)
+                    .map_err(|e| e.message)
+                    .and_then(|_| {
+                        fs::read_to_string(argument)
+                            .map_err(|e| format!("Error reading file '{}': {}", argument, e))
+                    });
+                match loaded {
+                    Ok(code) => {
+                        if self.run_code(&code) {
+                            self.session.push(code.trim_end().to_string());
+                        }
+                    }
+                    Err(e) => self.write(&e),
+                }
+            }
+            "save" => {
+                let mut code = self.session.join("\n");
+                code.push('\n');
+                match fs::write(argument, code) {
+                    Ok(_) => self.write(&format!(
+                        "Saved {} entries to {}",
+                        self.session.len(),
+                        argument
+                    )),
+                    Err(e) => self.write(&format!("Error writing file '{}': {}", argument, e)),
+                }
+            }
+            "reset" => {
+                let mut evaluator = Evaluator::with_config(self.config.clone());
+                evaluator.set_output(self.watched.clone());
+                self.evaluator = evaluator;
+                self.session.clear();
+                self.write("Cleared all variables and functions");
+            }
+            "quit" | "exit" | "q" => return false,
+            _ => self.write(&format!(
+                "Unknown command :{} (type :help for a list)",
+                name
+            )),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_more_input() {
+        assert!(needs_more_input("function f ("));
+        assert!(needs_more_input("~x is [1,\n2"));
+        assert!(needs_more_input("~x:"));
+        assert!(!needs_more_input("say \"(\""));
+        assert!(!needs_more_input("say '(' # ("));
+        assert!(!needs_more_input("loop (\n    break-loop\n)"));
+        assert!(!needs_more_input(":ast ~x:"));
+    }
+}
@@ -0,0 +1,429 @@
+//! Capabilities that control what a script may do outside the interpreter.
+//!
+//! An `Evaluator` created with `Evaluator::new()` can do everything. Embedders
+//! running untrusted scripts pass an `EvaluatorConfig` with a restricted
+//! `Capabilities` set instead; denied calls fail with a `permission_denied`
+//! error that attempt/rescue can catch.
+
+use crate::limits::ResourceLimits;
+use crate::value::{ErrorValue, Value};
+use std::path::{Component, Path, PathBuf};
+
+/// Which resources of one kind a script may use
+#[derive(Debug, Clone, PartialEq)]
+pub enum Access<T> {
+    Denied,
+    Only(Vec<T>),
+    All,
+}
+
+impl<T> Access<T> {
+    fn grant(&mut self, item: T) {
+        match self {
+            Access::All => {}
+            Access::Only(items) => items.push(item),
+            Access::Denied => *self = Access::Only(vec![item]),
+        }
+    }
+}
+
+/// A capability a script can be granted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Process,
+    Filesystem,
+    Network,
+    Env,
+    Audio,
+}
+
+impl Capability {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Process => "process",
+            Capability::Filesystem => "filesystem",
+            Capability::Network => "network",
+            Capability::Env => "env",
+            Capability::Audio => "audio",
+        }
+    }
+
+    /// The CLI flag that grants this capability
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Capability::Process => "--allow-process",
+            Capability::Filesystem => "--allow-fs",
+            Capability::Network => "--allow-net",
+            Capability::Env => "--allow-env",
+            Capability::Audio => "--allow-audio",
+        }
+    }
+}
+
+/// The set of capabilities granted to a script
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// Running shell commands with `run`
+    pub process: bool,
+    /// Reading and writing files, limited to the given root directories
+    pub filesystem: Access<PathBuf>,
+    /// HTTP requests, limited to the given hosts (`*.example.com` matches subdomains)
+    pub network: Access<String>,
+    /// Reading environment variables with `env`
+    pub env: bool,
+    /// Starting the audio scheduler with `play` and `tempo`
+    pub audio: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Capabilities {
+    /// Every capability granted, the default for trusted scripts
+    pub fn all() -> Self {
+        Capabilities {
+            process: true,
+            filesystem: Access::All,
+            network: Access::All,
+            env: true,
+            audio: true,
+        }
+    }
+
+    /// No capabilities granted
+    pub fn none() -> Self {
+        Capabilities {
+            process: false,
+            filesystem: Access::Denied,
+            network: Access::Denied,
+            env: false,
+            audio: false,
+        }
+    }
+
+    pub fn allow_process(mut self) -> Self {
+        self.process = true;
+        self
+    }
+
+    pub fn allow_env(mut self) -> Self {
+        self.env = true;
+        self
+    }
+
+    pub fn allow_audio(mut self) -> Self {
+        self.audio = true;
+        self
+    }
+
+    /// Allow file access anywhere below `root`
+    pub fn allow_path(mut self, root: impl Into<PathBuf>) -> Self {
+        self.filesystem.grant(root.into());
+        self
+    }
+
+    /// Allow HTTP requests to `host`
+    pub fn allow_host(mut self, host: impl Into<String>) -> Self {
+        self.network.grant(host.into().to_lowercase());
+        self
+    }
+
+    /// Apply a CLI flag such as `--allow-fs=./data,/tmp` or `--allow-net=api.example.com`.
+    ///
+    /// Returns `Ok(false)` if the flag is not a capability flag. `--allow-fs`
+    /// and `--allow-net` without a value grant unrestricted access.
+    pub fn apply_flag(&mut self, flag: &str) -> Result<bool, String> {
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (flag, None),
+        };
+
+        let no_value = |capabilities: &mut Capabilities, grant: fn(&mut Capabilities)| {
+            match value {
+                None => {
+                    grant(capabilities);
+                    Ok(true)
+                }
+                Some(_) => Err(format!("{} does not take a value", name)),
+            }
+        };
+
+        match name {
+            "--allow-all" => no_value(self, |c| *c = Capabilities::all()),
+            "--allow-process" => no_value(self, |c| c.process = true),
+            "--allow-env" => no_value(self, |c| c.env = true),
+            "--allow-audio" => no_value(self, |c| c.audio = true),
+            "--allow-fs" => {
+                match split_list(value) {
+                    None => self.filesystem = Access::All,
+                    Some(roots) => roots
+                        .into_iter()
+                        .for_each(|root| self.filesystem.grant(PathBuf::from(root))),
+                }
+                Ok(true)
+            }
+            "--allow-net" => {
+                match split_list(value) {
+                    None => self.network = Access::All,
+                    Some(hosts) => hosts
+                        .into_iter()
+                        .for_each(|host| self.network.grant(host.to_lowercase())),
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub fn check_process(&self, function: &str, command: &str) -> Result<(), ErrorValue> {
+        if self.process {
+            Ok(())
+        } else {
+            Err(permission_denied(Capability::Process, function, command))
+        }
+    }
+
+    pub fn check_env(&self, function: &str, name: &str) -> Result<(), ErrorValue> {
+        if self.env {
+            Ok(())
+        } else {
+            Err(permission_denied(Capability::Env, function, name))
+        }
+    }
+
+    pub fn check_audio(&self, function: &str) -> Result<(), ErrorValue> {
+        if self.audio {
+            Ok(())
+        } else {
+            Err(permission_denied(Capability::Audio, function, function))
+        }
+    }
+
+    /// Check that `path` lies inside one of the allowed roots.
+    /// Symlinks and `..` are resolved first so they can't escape a root.
+    pub fn check_path(&self, function: &str, path: &str) -> Result<(), ErrorValue> {
+        let allowed = match &self.filesystem {
+            Access::All => true,
+            Access::Denied => false,
+            Access::Only(roots) => {
+                let target = resolve_path(Path::new(path));
+                roots
+                    .iter()
+                    .any(|root| target.starts_with(resolve_path(root)))
+            }
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(permission_denied(Capability::Filesystem, function, path))
+        }
+    }
+
+    /// Check that the host of `url` is on the network allowlist
+    pub fn check_url(&self, function: &str, url: &str) -> Result<(), ErrorValue> {
+        let allowed = match &self.network {
+            Access::All => true,
+            Access::Denied => false,
+            Access::Only(hosts) => {
+                let host = url_host(url);
+                hosts.iter().any(|allowed| host_matches(allowed, &host))
+            }
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(permission_denied(Capability::Network, function, url))
+        }
+    }
+}
+
+/// Options that control how an `Evaluator` runs scripts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvaluatorConfig {
+    pub capabilities: Capabilities,
+    pub limits: ResourceLimits,
+}
+
+impl EvaluatorConfig {
+    /// A config with no capabilities, for untrusted scripts
+    pub fn sandboxed() -> Self {
+        EvaluatorConfig {
+            capabilities: Capabilities::none(),
+            limits: ResourceLimits::default(),
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+fn permission_denied(capability: Capability, function: &str, target: &str) -> ErrorValue {
+    ErrorValue::new(format!(
+        "Permission denied: {} needs the {} capability (grant it with {})",
+        function,
+        capability.name(),
+        capability.flag()
+    ))
+    .with_code("permission_denied")
+    .with_source(target)
+    .with_context("capability", Value::String(capability.name().to_string()))
+    .with_context("function", Value::String(function.to_string()))
+}
+
+fn split_list(value: Option<&str>) -> Option<Vec<&str>> {
+    value
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .filter(|items| !items.is_empty())
+}
+
+/// Make a path absolute, remove `.` and `..`, and resolve symlinks in the
+/// longest prefix that exists (the file itself may not exist yet for writes)
+fn resolve_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    let mut missing = Vec::new();
+    let mut existing = normalized.as_path();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+/// Extract the lowercase host from a URL, without userinfo or port
+fn url_host(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = if let Some(bracketed) = host_port.strip_prefix('[') {
+        bracketed.split(']').next().unwrap_or("")
+    } else {
+        host_port.split(':').next().unwrap_or("")
+    };
+    host.to_lowercase()
+}
+
+fn host_matches(allowed: &str, host: &str) -> bool {
+    match allowed.strip_prefix("*.") {
+        Some(domain) => host.len() > domain.len() && host.ends_with(&format!(".{}", domain)),
+        None => allowed == host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_allows_everything() {
+        let capabilities = Capabilities::default();
+        assert!(capabilities.check_process("run", "ls").is_ok());
+        assert!(capabilities.check_path("write", "/etc/passwd").is_ok());
+        assert!(capabilities.check_url("get", "https://example.com").is_ok());
+        assert!(capabilities.check_env("env", "HOME").is_ok());
+        assert!(capabilities.check_audio("play").is_ok());
+    }
+
+    #[test]
+    fn test_denied_error_is_structured() {
+        let error = Capabilities::none()
+            .check_process("run", "rm -rf /")
+            .unwrap_err();
+        assert_eq!(error.code.as_deref(), Some("permission_denied"));
+        assert_eq!(error.source.as_deref(), Some("rm -rf /"));
+        assert_eq!(
+            error.context.get("capability"),
+            Some(&Value::String("process".to_string()))
+        );
+        assert!(error.message.contains("--allow-process"));
+    }
+
+    #[test]
+    fn test_path_roots() {
+        let root = std::env::temp_dir().join("tilde_sandbox_roots");
+        std::fs::create_dir_all(&root).unwrap();
+        let capabilities = Capabilities::none().allow_path(&root);
+        let inside = root.join("new_file.txt");
+        let escape = root.join("..").join("outside.txt");
+
+        assert!(capabilities.check_path("write", inside.to_str().unwrap()).is_ok());
+        assert!(capabilities.check_path("write", escape.to_str().unwrap()).is_err());
+        assert!(capabilities.check_path("read", "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_host_allowlist() {
+        let capabilities = Capabilities::none()
+            .allow_host("api.example.com")
+            .allow_host("*.trusted.org");
+
+        assert!(capabilities.check_url("get", "https://api.example.com/users").is_ok());
+        assert!(capabilities.check_url("get", "http://API.example.com:8080").is_ok());
+        assert!(capabilities.check_url("get", "https://cdn.trusted.org/x").is_ok());
+        assert!(capabilities.check_url("get", "https://trusted.org").is_err());
+        assert!(capabilities.check_url("get", "https://example.com").is_err());
+        assert!(capabilities
+            .check_url("get", "https://api.example.com@evil.com/")
+            .is_err());
+    }
+
+    #[test]
+    fn test_apply_flags() {
+        let mut capabilities = Capabilities::none();
+        assert_eq!(capabilities.apply_flag("--allow-env"), Ok(true));
+        assert_eq!(capabilities.apply_flag("--allow-net=a.com, b.com"), Ok(true));
+        assert_eq!(capabilities.apply_flag("--allow-fs"), Ok(true));
+        assert_eq!(capabilities.apply_flag("--verbose"), Ok(false));
+        assert!(capabilities.apply_flag("--allow-env=yes").is_err());
+
+        assert!(capabilities.env);
+        assert!(!capabilities.process);
+        assert_eq!(
+            capabilities.network,
+            Access::Only(vec!["a.com".to_string(), "b.com".to_string()])
+        );
+        assert_eq!(capabilities.filesystem, Access::All);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::ast::Expression;
+use crate::concurrency::{self, ChannelError, ChannelHandle};
+use crate::evaluator::Evaluator;
+use crate::value::{ErrorValue, ObjectMap, Value};
+use std::time::Duration;
+
+fn extract_channel(value: Value, function_name: &str) -> Result<ChannelHandle, String> {
+    match value {
+        Value::Channel(channel) => Ok(channel),
+        _ => Err(format!("{} argument must be a channel", function_name)),
+    }
+}
+
+fn extract_seconds(value: Value, function_name: &str) -> Result<Duration, String> {
+    match value {
+        Value::Number(n) if n >= 0.0 => Ok(Duration::from_secs_f64(n)),
+        Value::Number(_) => Err(format!("{} timeout cannot be negative", function_name)),
+        _ => Err(format!("{} timeout must be a number of seconds", function_name)),
+    }
+}
+
+/// A received value, or null on timeout or when the channel is closed and drained
+fn received(result: Result<Value, ChannelError>, function: &str) -> Result<Value, String> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(ChannelError::WouldBlock) => Err(would_block(function)),
+        Err(ChannelError::Timeout) | Err(ChannelError::Closed) => Ok(Value::Null),
+    }
+}
+
+fn would_block(function: &str) -> String {
+    format!(
+        "{} would wait forever: no message is waiting, and none can arrive while the browser waits",
+        function
+    )
+}
+
+/// Create a channel for passing values between tasks
+///
+/// With no argument the channel is unbounded. With a capacity, `send` blocks
+/// while that many messages are waiting (0 makes every send wait for a receiver).
+///
+/// # Examples
+/// ```tilde
+/// ~jobs is channel 100
+/// ~events is channel
+/// ```
+pub fn eval_channel(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    match args.len() {
+        0 => Ok(Value::Channel(ChannelHandle::new(None))),
+        1 => match evaluator.eval_expression(args[0].clone())? {
+            Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => {
+                Ok(Value::Channel(ChannelHandle::new(Some(n as usize))))
+            }
+            _ => Err("channel capacity must be a non-negative whole number".to_string()),
+        },
+        _ => Err("channel takes at most 1 argument (capacity)".to_string()),
+    }
+}
+
+/// Send a value on a channel, waiting while a bounded channel is full
+///
+/// Sending on a closed channel raises an error with code `channel_closed`.
+pub fn eval_send(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("send requires exactly 2 arguments (channel, value)".to_string());
+    }
+
+    let channel = extract_channel(evaluator.eval_expression(args[0].clone())?, "send")?;
+    let value = evaluator.eval_expression(args[1].clone())?;
+    match channel.send(value) {
+        Ok(()) => Ok(Value::Null),
+        Err(_) => {
+            let error = ErrorValue::new("Cannot send on a closed channel")
+                .with_code("channel_closed")
+                .with_source("channel")
+                .with_context("channel", Value::Number(channel.id() as f64));
+            evaluator.set_last_error(Value::Error(error.clone()));
+            Err(error.message)
+        }
+    }
+}
+
+/// Receive the next value from a channel, waiting until one arrives
+///
+/// Returns null once the channel is closed and every pending message has been received.
+pub fn eval_receive(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("receive requires exactly 1 argument (channel)".to_string());
+    }
+
+    let channel = extract_channel(evaluator.eval_expression(args[0].clone())?, "receive")?;
+    received(channel.receive(), "receive")
+}
+
+/// Receive the next value, giving up after a number of seconds
+///
+/// Returns null on timeout or when the channel is closed and drained;
+/// use `is-closed` to tell the two apart.
+pub fn eval_receive_timeout(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("receive-timeout requires exactly 2 arguments (channel, seconds)".to_string());
+    }
+
+    let channel = extract_channel(
+        evaluator.eval_expression(args[0].clone())?,
+        "receive-timeout",
+    )?;
+    let timeout = extract_seconds(
+        evaluator.eval_expression(args[1].clone())?,
+        "receive-timeout",
+    )?;
+    received(channel.receive_timeout(timeout), "receive-timeout")
+}
+
+/// Close a channel so no more values can be sent
+///
+/// Messages already sent can still be received. Returns false if the channel
+/// was already closed.
+pub fn eval_close(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("close requires exactly 1 argument (channel)".to_string());
+    }
+
+    let channel = extract_channel(evaluator.eval_expression(args[0].clone())?, "close")?;
+    Ok(Value::Boolean(channel.close()))
+}
+
+/// Check whether a channel has been closed
+pub fn eval_is_closed(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("is-closed requires exactly 1 argument (channel)".to_string());
+    }
+
+    let channel = extract_channel(evaluator.eval_expression(args[0].clone())?, "is-closed")?;
+    Ok(Value::Boolean(channel.is_closed()))
+}
+
+/// Wait for a value from whichever channel is ready first
+///
+/// Returns an object `{index: n, value: v}` where `index` is the position of
+/// the channel in the list. Closed channels are skipped. Returns null when the
+/// optional timeout (in seconds) expires or every channel is closed.
+///
+/// # Examples
+/// ```tilde
+/// ~ready is select [~results, ~errors] 5
+/// if ~ready.index == 0 say "result: " ~ready.value
+/// ```
+pub fn eval_select(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("select requires 1 or 2 arguments (channels, [seconds])".to_string());
+    }
+
+    let channels = match evaluator.eval_expression(args[0].clone())? {
+        Value::List(items) => items
+            .into_iter()
+            .map(|item| extract_channel(item, "select"))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err("select first argument must be a list of channels".to_string()),
+    };
+    if channels.is_empty() {
+        return Err("select requires at least one channel".to_string());
+    }
+
+    let timeout = if args.len() == 2 {
+        Some(extract_seconds(
+            evaluator.eval_expression(args[1].clone())?,
+            "select",
+        )?)
+    } else {
+        None
+    };
+
+    match concurrency::select(&channels, timeout) {
+        Ok((index, value)) => {
+            let mut result = ObjectMap::new();
+            result.insert("index".to_string(), Value::Number(index as f64));
+            result.insert("value".to_string(), value);
+            Ok(Value::Object(result))
+        }
+        Err(ChannelError::WouldBlock) => Err(would_block("select")),
+        Err(ChannelError::Timeout) | Err(ChannelError::Closed) => Ok(Value::Null),
+    }
+}
+
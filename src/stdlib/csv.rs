@@ -0,0 +1,528 @@
+use super::date::{format_date_text, parse_date_text};
+use super::list::eval_function_expression_on_item;
+use super::utils::*;
+use crate::ast::Expression;
+use crate::evaluator::Evaluator;
+use crate::file_io::trace_file;
+use crate::limits::Stopwatch;
+use crate::value::{ObjectMap, Value};
+use indexmap::IndexSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Write};
+use std::path::Path;
+
+/// Parse CSV text into a list of objects, or of lists with `{header: false}`
+///
+/// # Examples
+/// ```tilde
+/// ~people is csv-parse "name,age\nAda,36"
+/// ~rows is csv-parse ~text {delimiter: ";", header: false}
+/// ```
+pub fn eval_csv_parse(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("csv-parse requires 1 or 2 arguments: text, [options]".to_string());
+    }
+    let text = match evaluator.eval_expression(args[0].clone())? {
+        Value::String(text) => text,
+        _ => return Err("csv-parse text must be a string".to_string()),
+    };
+    let options = CsvOptions::from_arg(args.get(1), evaluator, "csv-parse")?;
+
+    let mut reader =
+        CsvReader::new(text.as_bytes(), &options).map_err(|e| format!("csv-parse: {}", e))?;
+    let mut rows = Vec::new();
+    while let Some(row) = reader.next_row().map_err(|e| format!("csv-parse: {}", e))? {
+        rows.push(row);
+    }
+    Ok(Value::List(rows))
+}
+
+/// Write a list of objects or lists as CSV text
+///
+/// # Examples
+/// ```tilde
+/// ~text is csv-stringify ~people {columns: ["name", "age"]}
+/// ```
+pub fn eval_csv_stringify(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err("csv-stringify requires 1 or 2 arguments: rows, [options]".to_string());
+    }
+    let rows = match evaluator.eval_expression(args[0].clone())? {
+        Value::List(rows) => rows,
+        _ => return Err("csv-stringify rows must be a list".to_string()),
+    };
+    let options = CsvOptions::from_arg(args.get(1), evaluator, "csv-stringify")?;
+
+    let mut output = Vec::new();
+    write_rows(&mut output, &rows, &options).map_err(|e| format!("csv-stringify: {}", e))?;
+    String::from_utf8(output)
+        .map(Value::String)
+        .map_err(|e| format!("csv-stringify: {}", e))
+}
+
+/// Read a CSV file a row at a time. Given a function, returns what it
+/// returns for each row, leaving out nulls, so the whole file is never in
+/// memory at once.
+///
+/// # Examples
+/// ```tilde
+/// ~orders is csv-read-file "orders.csv"
+/// ~emails is csv-read-file "users.csv" |~row (~row.email)|
+/// ```
+pub fn eval_csv_read_file(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(
+            "csv-read-file requires 1 to 3 arguments: path, [options], [function]".to_string(),
+        );
+    }
+    let path = match evaluator.eval_expression(args[0].clone())? {
+        Value::String(path) => path,
+        _ => return Err("csv-read-file path must be a string".to_string()),
+    };
+    let (options, each) = match &args[1..] {
+        [each] if is_function_argument(each) => (None, Some(each)),
+        [options] => (Some(options), None),
+        [options, each] if is_function_argument(each) => (Some(options), Some(each)),
+        [_, _] => return Err("csv-read-file third argument must be a function".to_string()),
+        _ => (None, None),
+    };
+    let options = CsvOptions::from_arg(options, evaluator, "csv-read-file")?;
+
+    let path_check = evaluator.capabilities().check_path("csv-read-file", &path);
+    evaluator.permit(path_check)?;
+
+    // Files built into a standalone executable shadow the filesystem
+    let input: Box<dyn BufRead> = match evaluator.bundle().and_then(|b| b.file(Path::new(&path))) {
+        Some(contents) => Box::new(Cursor::new(contents.to_vec())),
+        None => Box::new(BufReader::new(
+            File::open(&path).map_err(|e| format!("csv-read-file: {}: {}", path, e))?,
+        )),
+    };
+
+    let started = Stopwatch::start();
+    let mut reader =
+        CsvReader::new(input, &options).map_err(|e| format!("csv-read-file: {} {}", path, e))?;
+    let mut rows = Vec::new();
+    let result = loop {
+        let row = match reader.next_row() {
+            Ok(Some(row)) => row,
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(format!("csv-read-file: {} {}", path, e)),
+        };
+        match each {
+            Some(function) => match eval_function_expression_on_item(function, &row, evaluator) {
+                Ok(Value::Null) => {}
+                Ok(value) => rows.push(value),
+                Err(e) => break Err(e),
+            },
+            None => rows.push(row),
+        }
+    };
+    trace_file(evaluator, "read", &path, &started, Ok(reader.bytes));
+    result.map(|()| Value::List(rows))
+}
+
+/// Write a list of objects or lists to a CSV file, returning how many rows
+/// were written
+///
+/// # Examples
+/// ```tilde
+/// csv-write-file "report.csv" ~rows {columns: ["date", "total"]}
+/// ```
+pub fn eval_csv_write_file(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err("csv-write-file requires 2 or 3 arguments: path, rows, [options]".to_string());
+    }
+    let path = match evaluator.eval_expression(args[0].clone())? {
+        Value::String(path) => path,
+        _ => return Err("csv-write-file path must be a string".to_string()),
+    };
+    let rows = match evaluator.eval_expression(args[1].clone())? {
+        Value::List(rows) => rows,
+        _ => return Err("csv-write-file rows must be a list".to_string()),
+    };
+    let options = CsvOptions::from_arg(args.get(2), evaluator, "csv-write-file")?;
+
+    let path_check = evaluator.capabilities().check_path("csv-write-file", &path);
+    evaluator.permit(path_check)?;
+
+    let started = Stopwatch::start();
+    let written = File::create(&path).and_then(|file| {
+        let mut output = BufWriter::new(file);
+        write_rows(&mut output, &rows, &options)?;
+        output.flush()?;
+        output
+            .get_ref()
+            .metadata()
+            .map(|metadata| metadata.len() as usize)
+    });
+    trace_file(
+        evaluator,
+        "write",
+        &path,
+        &started,
+        written.as_ref().copied(),
+    );
+    written.map_err(|e| format!("csv-write-file: {}: {}", path, e))?;
+    Ok(Value::Number(rows.len() as f64))
+}
+
+struct CsvOptions {
+    delimiter: char,
+    quote: char,
+    /// Whether the first row names the columns
+    header: bool,
+    /// Whether to turn fields into numbers, booleans, dates and nulls
+    types: bool,
+    columns: Option<Vec<String>>,
+}
+
+impl CsvOptions {
+    fn from_arg(
+        arg: Option<&Expression>,
+        evaluator: &mut Evaluator,
+        function_name: &str,
+    ) -> Result<Self, String> {
+        let mut options = CsvOptions {
+            delimiter: ',',
+            quote: '"',
+            header: true,
+            types: true,
+            columns: None,
+        };
+        let Some(arg) = arg else {
+            return Ok(options);
+        };
+        let map = match evaluator.eval_expression(arg.clone())? {
+            Value::Object(map) => map,
+            _ => return Err(format!("{} options must be an object", function_name)),
+        };
+
+        let character = |key: &str, value: &Value| match value {
+            Value::String(text) if text.chars().count() == 1 => Ok(text.chars().next().unwrap()),
+            _ => Err(format!(
+                "{} {} must be a single character",
+                function_name, key
+            )),
+        };
+        for (key, value) in &map {
+            match (key.as_str(), value) {
+                ("delimiter", _) => options.delimiter = character(key, value)?,
+                ("quote", _) => options.quote = character(key, value)?,
+                ("header", Value::Boolean(header)) => options.header = *header,
+                ("types", Value::Boolean(types)) => options.types = *types,
+                ("columns", Value::List(names)) => {
+                    let names = names
+                        .iter()
+                        .map(|name| match name {
+                            Value::String(name) => Ok(name.clone()),
+                            _ => Err(format!(
+                                "{} columns must be a list of strings",
+                                function_name
+                            )),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    options.columns = Some(names);
+                }
+                ("header" | "types", _) => {
+                    return Err(format!("{} {} must be true or false", function_name, key));
+                }
+                ("columns", _) => {
+                    return Err(format!(
+                        "{} columns must be a list of strings",
+                        function_name
+                    ));
+                }
+                _ => {
+                    return Err(format!(
+                        "{}: unknown option '{}' (expected delimiter, quote, header, types or columns)",
+                        function_name, key
+                    ));
+                }
+            }
+        }
+        if options.delimiter == options.quote {
+            return Err(format!(
+                "{} delimiter and quote must be different",
+                function_name
+            ));
+        }
+        Ok(options)
+    }
+}
+
+/// Reads CSV records, including quoted fields that span lines, and turns
+/// them into row values
+struct CsvReader<'a, R> {
+    input: R,
+    options: &'a CsvOptions,
+    /// The column names rows become objects with, if any
+    columns: Option<Vec<String>>,
+    line: usize,
+    bytes: usize,
+}
+
+impl<'a, R: BufRead> CsvReader<'a, R> {
+    /// Start reading, taking the header row if there is one
+    fn new(input: R, options: &'a CsvOptions) -> Result<Self, String> {
+        let mut reader = CsvReader {
+            input,
+            options,
+            columns: options.columns.clone(),
+            line: 0,
+            bytes: 0,
+        };
+        // The header line is skipped even when `columns` names the fields
+        if options.header
+            && let Some((line, names)) = reader.next_record()?
+            && reader.columns.is_none()
+        {
+            for (i, name) in names.iter().enumerate() {
+                if names[..i].contains(name) {
+                    return Err(format!(
+                        "line {}: column '{}' appears twice in the header",
+                        line, name
+                    ));
+                }
+            }
+            reader.columns = Some(names);
+        }
+        Ok(reader)
+    }
+
+    fn next_row(&mut self) -> Result<Option<Value>, String> {
+        let Some((line, fields)) = self.next_record()? else {
+            return Ok(None);
+        };
+        let types = self.options.types;
+        let value = |field: String| {
+            if types {
+                infer_type(field)
+            } else {
+                Value::String(field)
+            }
+        };
+
+        let Some(columns) = &self.columns else {
+            return Ok(Some(Value::List(fields.into_iter().map(value).collect())));
+        };
+        if fields.len() > columns.len() {
+            return Err(format!(
+                "line {} has {} fields but there are {} columns",
+                line,
+                fields.len(),
+                columns.len()
+            ));
+        }
+        // Short rows leave the last columns null
+        let mut fields = fields.into_iter();
+        let row: ObjectMap = columns
+            .iter()
+            .map(|column| (column.clone(), fields.next().map_or(Value::Null, value)))
+            .collect();
+        Ok(Some(Value::Object(row)))
+    }
+
+    /// The next record's fields and the line it starts on. Blank lines are
+    /// skipped.
+    fn next_record(&mut self) -> Result<Option<(usize, Vec<String>)>, String> {
+        let (delimiter, quote) = (self.options.delimiter, self.options.quote);
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut in_quotes = false;
+        let mut start = self.line + 1;
+        let mut text = String::new();
+
+        loop {
+            text.clear();
+            let read = self.input.read_line(&mut text).map_err(|e| e.to_string())?;
+            if read == 0 {
+                if in_quotes {
+                    return Err(format!("line {}: quoted field is never closed", start));
+                }
+                return Ok(None);
+            }
+            self.bytes += read;
+            self.line += 1;
+            if self.line == 1 && text.starts_with('\u{feff}') {
+                text.remove(0);
+            }
+            let content = text
+                .strip_suffix('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line))
+                .unwrap_or(&text);
+
+            if !in_quotes && content.is_empty() {
+                start = self.line + 1;
+                continue;
+            }
+
+            let mut chars = content.chars().peekable();
+            while let Some(c) = chars.next() {
+                if in_quotes {
+                    if c != quote {
+                        field.push(c);
+                    } else if chars.peek() == Some(&quote) {
+                        field.push(quote);
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                    }
+                } else if c == delimiter {
+                    fields.push(std::mem::take(&mut field));
+                    quoted = false;
+                } else if c == quote && field.is_empty() && !quoted {
+                    in_quotes = true;
+                    quoted = true;
+                } else {
+                    field.push(c);
+                }
+            }
+
+            if in_quotes {
+                field.push('\n');
+                continue;
+            }
+            fields.push(field);
+            return Ok(Some((start, fields)));
+        }
+    }
+}
+
+/// A field as the value it looks like: empty is null, then booleans,
+/// numbers and dates, and anything else stays text. Numbers with leading
+/// zeros, like ZIP codes, stay text too.
+pub(crate) fn infer_type(field: String) -> Value {
+    if field.is_empty() {
+        return Value::Null;
+    }
+    if field.eq_ignore_ascii_case("true") {
+        return Value::Boolean(true);
+    }
+    if field.eq_ignore_ascii_case("false") {
+        return Value::Boolean(false);
+    }
+
+    let digits = field.strip_prefix('-').unwrap_or(&field);
+    let numeric = digits.starts_with(|c: char| c.is_ascii_digit())
+        && !(digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0."))
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+'));
+    if numeric && let Ok(number) = field.parse::<f64>() {
+        return Value::Number(number);
+    }
+    if let Some(date) = parse_date_text(&field) {
+        return Value::Date(date);
+    }
+    Value::String(field)
+}
+
+/// Write rows, all objects or all lists, with a header row for objects
+fn write_rows(
+    output: &mut impl Write,
+    rows: &[Value],
+    options: &CsvOptions,
+) -> std::io::Result<()> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    let objects = matches!(rows.first(), Some(Value::Object(_)));
+    for (i, row) in rows.iter().enumerate() {
+        match row {
+            Value::Object(_) if objects => {}
+            Value::List(_) if !objects => {}
+            Value::Object(_) | Value::List(_) => {
+                return Err(invalid(format!(
+                    "row {} is {} but row 1 is {}",
+                    i + 1,
+                    if objects { "a list" } else { "an object" },
+                    if objects { "an object" } else { "a list" }
+                )));
+            }
+            other => {
+                return Err(invalid(format!(
+                    "rows must be objects or lists, but row {} is {}",
+                    i + 1,
+                    crate::embed::type_name_of(other)
+                )));
+            }
+        }
+    }
+
+    if !objects {
+        for row in rows {
+            if let Value::List(fields) = row {
+                write_record(output, fields.iter().map(field_text), options)?;
+            }
+        }
+        return Ok(());
+    }
+
+    // Columns come in the order keys first appear, unless given
+    let columns = options.columns.clone().unwrap_or_else(|| {
+        let names: IndexSet<&String> = rows
+            .iter()
+            .filter_map(|row| match row {
+                Value::Object(map) => Some(map.keys()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        names.into_iter().cloned().collect()
+    });
+    if options.header {
+        write_record(output, columns.iter().cloned(), options)?;
+    }
+    for row in rows {
+        if let Value::Object(map) = row {
+            let fields = columns
+                .iter()
+                .map(|column| map.get(column).map(field_text).unwrap_or_default());
+            write_record(output, fields, options)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write one line, quoting the fields that need it
+fn write_record(
+    output: &mut impl Write,
+    fields: impl Iterator<Item = String>,
+    options: &CsvOptions,
+) -> std::io::Result<()> {
+    let quote = options.quote.to_string();
+    let mut line = String::new();
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            line.push(options.delimiter);
+        }
+        if field.contains([options.delimiter, options.quote, '\n', '\r']) {
+            line.push_str(&quote);
+            line.push_str(&field.replace(&quote, &quote.repeat(2)));
+            line.push_str(&quote);
+        } else {
+            line.push_str(&field);
+        }
+    }
+    line.push('\n');
+    output.write_all(line.as_bytes())
+}
+
+/// A value as CSV text, with dates written the way they're read back
+fn field_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Date(date) => format_date_text(date),
+        other => other.to_string(),
+    }
+}
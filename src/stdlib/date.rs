@@ -1,6 +1,6 @@
 use crate::ast::Expression;
 use crate::evaluator::Evaluator;
-use crate::value::Value;
+use crate::value::{ObjectMap, Value};
 use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
 
 /// Returns the current UTC datetime
@@ -27,21 +27,34 @@ pub fn eval_date(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Val
         _ => return Err("date argument must be a string".to_string()),
     };
 
+    parse_date_text(&date_str).map(Value::Date).ok_or_else(|| {
+        format!(
+            "Invalid date format '{}'. Expected YYYY-MM-DD or ISO 8601 format",
+            date_str
+        )
+    })
+}
+
+/// Parse the text formats `date` accepts: ISO 8601 with a time, or YYYY-MM-DD
+pub(crate) fn parse_date_text(text: &str) -> Option<DateTime<Utc>> {
     // Try parsing as full datetime first (ISO 8601)
-    if let Ok(datetime) = DateTime::parse_from_rfc3339(&date_str) {
-        return Ok(Value::Date(datetime.with_timezone(&Utc)));
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.with_timezone(&Utc));
     }
 
     // Try parsing as date only (YYYY-MM-DD)
-    if let Ok(naive_date) = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d") {
-        let datetime = Utc.from_utc_datetime(&naive_date.and_hms_opt(0, 0, 0).unwrap());
-        return Ok(Value::Date(datetime));
-    }
+    let naive_date = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?;
+    Some(Utc.from_utc_datetime(&naive_date.and_hms_opt(0, 0, 0).unwrap()))
+}
 
-    Err(format!(
-        "Invalid date format '{}'. Expected YYYY-MM-DD or ISO 8601 format",
-        date_str
-    ))
+/// Write a date so `parse_date_text` reads it back: just YYYY-MM-DD at
+/// midnight, otherwise ISO 8601 in UTC
+pub(crate) fn format_date_text(date: &DateTime<Utc>) -> String {
+    if date.num_seconds_from_midnight() == 0 && date.nanosecond() == 0 {
+        date.format("%Y-%m-%d").to_string()
+    } else {
+        date.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string()
+    }
 }
 
 /// Adds a specified number of days to a date
@@ -106,7 +119,7 @@ pub fn eval_date_diff(args: Vec<Expression>, evaluator: &mut Evaluator) -> Resul
     let (date1, date2) = extract_two_dates(args, evaluator, "date-diff")?;
     let diff = date2.signed_duration_since(date1);
 
-    let mut result = std::collections::HashMap::new();
+    let mut result = ObjectMap::new();
     result.insert("days".to_string(), Value::Number(diff.num_days() as f64));
     result.insert("hours".to_string(), Value::Number(diff.num_hours() as f64));
     result.insert(
@@ -15,6 +15,8 @@ pub fn eval_file_exists(args: Vec<Expression>, evaluator: &mut Evaluator) -> Res
         Value::String(s) => s,
         _ => return Err("file-exists argument must be a string".to_string()),
     };
+    let path_check = evaluator.capabilities().check_path("file-exists", &path);
+    evaluator.permit(path_check)?;
 
     let exists = Path::new(&path).is_file();
     Ok(Value::Boolean(exists))
@@ -31,6 +33,8 @@ pub fn eval_dir_exists(args: Vec<Expression>, evaluator: &mut Evaluator) -> Resu
         Value::String(s) => s,
         _ => return Err("dir-exists argument must be a string".to_string()),
     };
+    let path_check = evaluator.capabilities().check_path("dir-exists", &path);
+    evaluator.permit(path_check)?;
 
     let exists = Path::new(&path).is_dir();
     Ok(Value::Boolean(exists))
@@ -47,6 +51,8 @@ pub fn eval_file_size(args: Vec<Expression>, evaluator: &mut Evaluator) -> Resul
         Value::String(s) => s,
         _ => return Err("file-size argument must be a string".to_string()),
     };
+    let path_check = evaluator.capabilities().check_path("file-size", &path);
+    evaluator.permit(path_check)?;
 
     match fs::metadata(&path) {
         Ok(metadata) => Ok(Value::Number(metadata.len() as f64)),
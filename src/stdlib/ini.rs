@@ -0,0 +1,268 @@
+use super::csv::infer_type;
+use super::date::format_date_text;
+use super::utils::number_text;
+use crate::ast::Expression;
+use crate::embed::type_name_of;
+use crate::evaluator::Evaluator;
+use crate::value::{ObjectMap, Value};
+
+/// Parse INI text into an object, with an object for each [section]
+///
+/// # Examples
+/// ```tilde
+/// ~file is read "settings.ini"
+/// ~config is from-ini ~file.content
+/// say ~config.database.port
+/// ```
+pub fn eval_from_ini(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("from-ini requires exactly 1 argument".to_string());
+    }
+    let text = match evaluator.eval_expression(args[0].clone())? {
+        Value::String(text) => text,
+        _ => return Err("from-ini argument must be a string".to_string()),
+    };
+
+    parse_ini(&text)
+        .map(Value::Object)
+        .map_err(|e| format!("from-ini: {}", e))
+}
+
+/// Write an object as INI: plain keys first, then a [section] for each
+/// nested object
+///
+/// # Examples
+/// ```tilde
+/// write "settings.ini" (to-ini ~config)
+/// ```
+pub fn eval_to_ini(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("to-ini requires exactly 1 argument".to_string());
+    }
+    let map = match evaluator.eval_expression(args[0].clone())? {
+        Value::Object(map) => map,
+        _ => return Err("to-ini argument must be an object".to_string()),
+    };
+
+    write_ini(&map)
+        .map(Value::String)
+        .map_err(|e| format!("to-ini: {}", e))
+}
+
+fn parse_ini(text: &str) -> Result<ObjectMap, String> {
+    let mut root = ObjectMap::new();
+    let mut section: Option<(String, ObjectMap)> = None;
+
+    for (index, line) in text
+        .strip_prefix('\u{feff}')
+        .unwrap_or(text)
+        .lines()
+        .enumerate()
+    {
+        let column = |byte: usize| line[..byte].chars().count() + 1;
+        let error = |byte: usize, message: String| {
+            format!("line {}, column {}: {}", index + 1, column(byte), message)
+        };
+        let start = line.len() - line.trim_start().len();
+        let content = line.trim();
+        if content.is_empty() || content.starts_with([';', '#']) {
+            continue;
+        }
+
+        if let Some(header) = content.strip_prefix('[') {
+            let Some(close) = header.find(']') else {
+                return Err(error(start, "[ is never closed with ]".to_string()));
+            };
+            let after = &header[close + 1..];
+            let rest = after.trim_start();
+            if !(rest.is_empty() || rest.starts_with([';', '#'])) {
+                let rest_start = start + close + 2 + (after.len() - rest.len());
+                return Err(error(rest_start, format!("unexpected '{}' after ]", rest)));
+            }
+            let name = header[..close].trim();
+            if name.is_empty() {
+                return Err(error(start, "section name is empty".to_string()));
+            }
+            if let Some((name, entries)) = section.take() {
+                root.insert(name, Value::Object(entries));
+            }
+            if root.contains_key(name) {
+                return Err(error(start, format!("[{}] is defined twice", name)));
+            }
+            section = Some((name.to_string(), ObjectMap::new()));
+            continue;
+        }
+
+        let Some(split) = content.find(['=', ':']) else {
+            return Err(error(
+                start,
+                format!("expected key = value or [section], found '{}'", content),
+            ));
+        };
+        let key = content[..split].trim_end();
+        if key.is_empty() {
+            return Err(error(
+                start,
+                format!("expected a key before '{}'", &content[split..split + 1]),
+            ));
+        }
+        let after = &content[split + 1..];
+        let value_start = start + split + 1 + (after.len() - after.trim_start().len());
+        let value = parse_value(after.trim()).map_err(|message| error(value_start, message))?;
+
+        let entries = match &mut section {
+            Some((_, entries)) => entries,
+            None => &mut root,
+        };
+        if entries.contains_key(key) {
+            return Err(error(start, format!("duplicate key '{}'", key)));
+        }
+        entries.insert(key.to_string(), value);
+    }
+
+    if let Some((name, entries)) = section {
+        root.insert(name, Value::Object(entries));
+    }
+    Ok(root)
+}
+
+/// A value's text: double-quoted values are taken as written, anything else
+/// loses its trailing comment and has its type inferred like a CSV field
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        loop {
+            match chars.next() {
+                None => return Err("quoted value is never closed".to_string()),
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    Some(c) => return Err(format!("unknown escape \\{}", c)),
+                    None => return Err("quoted value is never closed".to_string()),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+        let rest = chars.as_str().trim_start();
+        if !(rest.is_empty() || rest.starts_with([';', '#'])) {
+            return Err(format!("unexpected '{}' after the closing quote", rest));
+        }
+        return Ok(Value::String(value));
+    }
+
+    // A ; or # at the start or after whitespace begins a comment
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| matches!(c, ';' | '#') && (i == 0 || text[..i].ends_with([' ', '\t'])))
+        .map_or(text.len(), |(i, _)| i);
+    let text = text[..end].trim_end();
+    if text.is_empty() {
+        return Ok(Value::String(String::new()));
+    }
+    Ok(infer_type(text.to_string()))
+}
+
+fn write_ini(map: &ObjectMap) -> Result<String, String> {
+    let mut output = String::new();
+    for (key, value) in map {
+        if !matches!(value, Value::Object(_)) {
+            write_entry(&mut output, key, value, None)?;
+        }
+    }
+
+    for (name, value) in map {
+        let Value::Object(entries) = value else {
+            continue;
+        };
+        if name.is_empty() || name.contains([']', '\n', '\r']) || name != name.trim() {
+            return Err(format!("'{}' can't be a section name", name));
+        }
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format!("[{}]\n", name));
+        for (key, value) in entries {
+            write_entry(&mut output, key, value, Some(name))?;
+        }
+    }
+    Ok(output)
+}
+
+fn write_entry(
+    output: &mut String,
+    key: &str,
+    value: &Value,
+    section: Option<&str>,
+) -> Result<(), String> {
+    let place = || match section {
+        Some(section) => format!("[{}] {}", section, key),
+        None => key.to_string(),
+    };
+    if key.is_empty()
+        || key.contains(['=', ':', '\n', '\r'])
+        || key.starts_with(['[', ';', '#'])
+        || key != key.trim()
+    {
+        return Err(format!("'{}' can't be a key", key));
+    }
+
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(text) => ini_text(text),
+        Value::Number(n) => number_text(*n),
+        Value::Boolean(b) => b.to_string(),
+        Value::Date(date) => format_date_text(date),
+        Value::Object(_) => {
+            return Err(format!(
+                "{} is an object, but sections can't be nested",
+                place()
+            ));
+        }
+        other => {
+            return Err(format!(
+                "can't write {} ({}) as INI",
+                place(),
+                type_name_of(other)
+            ));
+        }
+    };
+    if text.is_empty() {
+        output.push_str(&format!("{} =\n", key));
+    } else {
+        output.push_str(&format!("{} = {}\n", key, text));
+    }
+    Ok(())
+}
+
+/// Text as written when it would read back as the same string, otherwise
+/// double-quoted
+fn ini_text(text: &str) -> String {
+    let plain = text == text.trim()
+        && !text.starts_with(['"', ';', '#'])
+        && !["\t;", "\t#", " ;", " #"]
+            .iter()
+            .any(|comment| text.contains(comment))
+        && !text.chars().any(char::is_control)
+        && (text.is_empty() || matches!(infer_type(text.to_string()), Value::String(_)));
+    if plain {
+        return text.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
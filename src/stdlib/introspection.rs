@@ -0,0 +1,221 @@
+use super::metadata::{self, CATEGORIES, FunctionInfo};
+use super::utils::*;
+use crate::ast::Expression;
+use crate::evaluator::{Evaluator, Function};
+use crate::value::Value;
+
+/// Show the documentation for a function, or list the stdlib with no name
+///
+/// # Examples
+/// ```tilde
+/// say help "date-format"
+/// say help "*my-function"
+/// ```
+pub fn eval_help(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    match args.len() {
+        0 => Ok(Value::String(overview(evaluator))),
+        1 => {
+            let name = extract_string_arg(&args, evaluator, "help")?;
+            help_text(&name, evaluator).map(Value::String)
+        }
+        _ => Err("help takes at most 1 argument (a function name)".to_string()),
+    }
+}
+
+/// List function names, optionally only the stdlib functions in one block
+///
+/// # Examples
+/// ```tilde
+/// ~math is functions-list "Math"
+/// ```
+pub fn eval_functions_list(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    let block = match args.len() {
+        0 => None,
+        1 => Some(extract_string_arg(&args, evaluator, "functions-list")?),
+        _ => return Err("functions-list takes at most 1 argument (a block name)".to_string()),
+    };
+
+    let names = match block {
+        Some(block) => {
+            let Some(category) = CATEGORIES
+                .iter()
+                .find(|category| category.eq_ignore_ascii_case(&block))
+            else {
+                return Err(format!(
+                    "functions-list: no block named '{}' (expected one of: {})",
+                    block,
+                    CATEGORIES.join(", ")
+                ));
+            };
+            stdlib_names(category)
+        }
+        None => CATEGORIES
+            .iter()
+            .flat_map(|category| stdlib_names(category))
+            .chain(user_function_names(evaluator))
+            .collect(),
+    };
+    Ok(Value::List(names.into_iter().map(Value::String).collect()))
+}
+
+/// How many arguments a function takes
+///
+/// # Examples
+/// ```tilde
+/// ~count is function-arity "my-fn"
+/// ```
+pub fn eval_function_arity(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("function-arity requires exactly 1 argument (a function name)".to_string());
+    }
+    let name = extract_string_arg(&args, evaluator, "function-arity")?;
+    match find_function(&name, evaluator) {
+        Some(Found::User(_, function)) => Ok(Value::Number(function.params.len() as f64)),
+        Some(Found::Stdlib(info)) => Ok(Value::Number(info.arity() as f64)),
+        None => Err(unknown_function("function-arity", &name)),
+    }
+}
+
+/// Describe a value's type and size
+///
+/// # Examples
+/// ```tilde
+/// say describe ~response
+/// ```
+pub fn eval_describe(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("describe requires exactly 1 argument".to_string());
+    }
+    let value = evaluator.eval_expression(args[0].clone())?;
+    Ok(Value::String(describe(&value)))
+}
+
+/// The documentation `help name` shows: a user function's parameters and
+/// doc comment, or a stdlib function's from the metadata
+pub fn help_text(name: &str, evaluator: &Evaluator) -> Result<String, String> {
+    let (name, function) = match find_function(name, evaluator) {
+        Some(Found::User(name, function)) => (name, function),
+        Some(Found::Stdlib(info)) => return Ok(info.help()),
+        None => return Err(unknown_function("help", name)),
+    };
+    let signature: String = function
+        .params
+        .iter()
+        .map(|param| format!(" ~{}", param))
+        .collect();
+    let doc = function.doc.as_deref().unwrap_or(
+        "No documentation. Add a ## comment on the lines above the function to describe it.",
+    );
+    Ok(format!("*{}{}\n\n{}", name, signature, doc))
+}
+
+/// What `help` shows with no name: the stdlib functions in each block, and
+/// the user's functions
+pub fn overview(evaluator: &Evaluator) -> String {
+    let mut lines: Vec<String> = CATEGORIES
+        .iter()
+        .map(|category| format!("{}: {}", category, stdlib_names(category).join(", ")))
+        .collect();
+    let user_functions = user_function_names(evaluator);
+    if !user_functions.is_empty() {
+        lines.push(format!("Yours: {}", user_functions.join(", ")));
+    }
+    lines.push(String::new());
+    lines.push("Use help \"name\" for the details of one function.".to_string());
+    lines.join("\n")
+}
+
+enum Found<'a> {
+    User(&'a str, &'a Function),
+    Stdlib(&'static FunctionInfo),
+}
+
+/// The function a call to `name` runs: the user's function of that name if
+/// there is one, else the stdlib's, as the evaluator looks them up.
+/// `core:name` always means the stdlib function.
+fn find_function<'a>(name: &'a str, evaluator: &'a Evaluator) -> Option<Found<'a>> {
+    if let Some(name) = name.strip_prefix("core:") {
+        return metadata::lookup(name).map(Found::Stdlib);
+    }
+    let name = name.trim_start_matches('*');
+    match evaluator.functions.get(name) {
+        Some(function) => Some(Found::User(name, function)),
+        None => metadata::lookup(name).map(Found::Stdlib),
+    }
+}
+
+fn stdlib_names(category: &str) -> Vec<String> {
+    metadata::stdlib_functions()
+        .iter()
+        .filter(|info| info.category == category)
+        .map(|info| info.name.to_string())
+        .collect()
+}
+
+fn user_function_names(evaluator: &Evaluator) -> Vec<String> {
+    let mut names: Vec<String> = evaluator
+        .functions
+        .keys()
+        .map(|name| format!("*{}", name))
+        .collect();
+    names.sort();
+    names
+}
+
+fn unknown_function(caller: &str, name: &str) -> String {
+    format!(
+        "{}: no function named '{}' (functions-list lists them all)",
+        caller, name
+    )
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Number(_) | Value::Boolean(_) | Value::Date(_) => {
+            format!("{} {}", crate::embed::type_name_of(value), value)
+        }
+        Value::String(text) => match text.chars().count() {
+            0 => "empty string".to_string(),
+            1 => "string of 1 character".to_string(),
+            count => format!("string of {} characters", count),
+        },
+        Value::List(items) => match items.len() {
+            0 => "empty list".to_string(),
+            1 => "list of 1 item".to_string(),
+            count => format!("list of {} items", count),
+        },
+        Value::Object(map) => {
+            let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+            keys.sort();
+            match keys.len() {
+                0 => "empty object".to_string(),
+                1..=10 => format!(
+                    "object with {} {}",
+                    if keys.len() == 1 { "key" } else { "keys" },
+                    keys.join(", ")
+                ),
+                count => format!("object with {} keys: {}, ...", count, keys[..10].join(", ")),
+            }
+        }
+        Value::Error(error) => match &error.code {
+            Some(code) => format!("error ({}): {}", code, error.message),
+            None => format!("error: {}", error.message),
+        },
+        Value::Pattern(pattern) => format!(
+            "pattern \"{}\" with {} events",
+            pattern.notation(),
+            pattern.events().len()
+        ),
+        Value::Task(_) => "task".to_string(),
+        Value::Channel(channel) if channel.is_closed() => "closed channel".to_string(),
+        Value::Channel(_) => "open channel".to_string(),
+        Value::Handle(handle) => format!("{} handle", handle.type_name()),
+        Value::Null => "null".to_string(),
+    }
+}
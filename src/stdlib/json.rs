@@ -1,7 +1,6 @@
 use crate::ast::Expression;
 use crate::evaluator::Evaluator;
-use crate::value::Value;
-use std::collections::HashMap;
+use crate::value::{ObjectMap, Value};
 
 /// Converts a Tilde value to JSON string
 pub fn eval_to_json(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
@@ -40,7 +39,7 @@ pub fn eval_from_json(args: Vec<Expression>, evaluator: &mut Evaluator) -> Resul
 }
 
 /// Convert a Tilde Value to a serde_json::Value
-fn tilde_value_to_json_value(value: &Value) -> Result<serde_json::Value, String> {
+pub(crate) fn tilde_value_to_json_value(value: &Value) -> Result<serde_json::Value, String> {
     match value {
         Value::Number(n) => Ok(serde_json::Value::Number(
             serde_json::Number::from_f64(*n).ok_or("Invalid number for JSON")?,
@@ -86,11 +85,14 @@ fn tilde_value_to_json_value(value: &Value) -> Result<serde_json::Value, String>
             // Convert pattern to its notation string
             Ok(serde_json::Value::String(pattern.notation()))
         }
+        Value::Task(_) => Err("Cannot serialize task handles to JSON; await them first".to_string()),
+        Value::Channel(_) => Err("Cannot serialize channels to JSON".to_string()),
+        Value::Handle(_) => Err("Cannot serialize host handles to JSON".to_string()),
     }
 }
 
 /// Convert a serde_json::Value to a Tilde Value
-fn json_value_to_tilde_value(json_value: serde_json::Value) -> Result<Value, String> {
+pub(crate) fn json_value_to_tilde_value(json_value: serde_json::Value) -> Result<Value, String> {
     match json_value {
         serde_json::Value::Null => Ok(Value::Null),
         serde_json::Value::Bool(b) => Ok(Value::Boolean(b)),
@@ -108,7 +110,7 @@ fn json_value_to_tilde_value(json_value: serde_json::Value) -> Result<Value, Str
             Ok(Value::List(tilde_items?))
         }
         serde_json::Value::Object(obj) => {
-            let mut tilde_map = HashMap::new();
+            let mut tilde_map = ObjectMap::new();
             for (key, val) in obj {
                 tilde_map.insert(key, json_value_to_tilde_value(val)?);
             }
@@ -1,7 +1,7 @@
 use super::utils::*;
 use crate::ast::Expression;
 use crate::evaluator::Evaluator;
-use crate::value::Value;
+use crate::value::{ObjectMap, Value};
 
 /// Fast iterative Fibonacci implementation
 fn fibonacci_iterative(n: u32) -> f64 {
@@ -41,7 +41,7 @@ macro_rules! native_reduce {
 }
 
 /// Generic helper that works with both named functions and anonymous functions
-fn eval_function_expression_on_item(
+pub(crate) fn eval_function_expression_on_item(
     func_expr: &Expression,
     item: &Value,
     evaluator: &mut Evaluator,
@@ -110,7 +110,11 @@ fn eval_function_on_item(
                         return Err(format!("Unknown core function: {}", func_name));
                     }
                 }
-                _ => return Err(format!("Unknown block: {}", block_name)),
+                _ => {
+                    return evaluator
+                        .call_native(function_name, vec![item.clone()])
+                        .unwrap_or_else(|| Err(format!("Unknown block: {}", block_name)));
+                }
             }
         } else {
             return Err(format!("Invalid block syntax: {}", function_name));
@@ -132,6 +136,11 @@ fn eval_function_on_item(
         return evaluator.eval_function(function, vec![arg_expr]);
     }
 
+    // Then host functions registered by an embedding application
+    if let Some(result) = evaluator.call_native(function_name, vec![item.clone()]) {
+        return result;
+    }
+
     // Then try stdlib functions
     if let Some(stdlib_func) = crate::stdlib::get_stdlib_function(function_name) {
         // For stdlib functions, we pass the item as a direct argument
@@ -879,7 +888,7 @@ pub fn eval_partition(args: Vec<Expression>, evaluator: &mut Evaluator) -> Resul
         }
     }
 
-    let mut result_map = std::collections::HashMap::new();
+    let mut result_map = ObjectMap::new();
     result_map.insert("matched".to_string(), Value::List(matched));
     result_map.insert("unmatched".to_string(), Value::List(unmatched));
 
@@ -898,7 +907,7 @@ pub fn eval_group_by(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result
         _ => return Err("group-by can only be used on lists".to_string()),
     };
 
-    let mut groups = std::collections::HashMap::new();
+    let mut groups = indexmap::IndexMap::new();
 
     for item in list {
         let key_result = eval_function_expression_on_item(&args[1], &item, evaluator)?;
@@ -922,7 +931,7 @@ pub fn eval_group_by(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result
         groups.entry(key_string).or_insert_with(Vec::new).push(item);
     }
 
-    let result_map: std::collections::HashMap<String, Value> = groups
+    let result_map: ObjectMap = groups
         .into_iter()
         .map(|(k, v)| (k, Value::List(v)))
         .collect();
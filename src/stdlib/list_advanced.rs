@@ -102,6 +102,9 @@ pub fn eval_unique(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<V
             Value::Date(dt) => format!("d:{}", dt.format("%Y-%m-%dT%H:%M:%SZ")),
             Value::Error(err) => format!("e:{}", err.message),
             Value::Pattern(pattern) => format!("p:{}", pattern.notation()),
+            Value::Task(task) => format!("t:{}", task.id()),
+            Value::Channel(channel) => format!("c:{}", channel.id()),
+            Value::Handle(handle) => format!("h:{}", handle.id()),
         };
 
         if seen.insert(key) {
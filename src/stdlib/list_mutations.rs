@@ -1,7 +1,7 @@
 use crate::ast::Expression;
 use crate::evaluator::Evaluator;
 use crate::stdlib::utils::evaluate_args;
-use crate::value::Value;
+use crate::value::{ObjectMap, Value};
 
 /// Remove first occurrence of a value from a list
 /// Usage: remove list value
@@ -198,7 +198,7 @@ pub fn eval_pop(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Valu
 
     let popped = list.pop().unwrap();
 
-    let mut result = std::collections::HashMap::new();
+    let mut result = ObjectMap::new();
     result.insert("value".to_string(), popped);
     result.insert("list".to_string(), Value::List(list));
 
@@ -229,7 +229,7 @@ pub fn eval_shift(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Va
 
     let shifted = list.remove(0);
 
-    let mut result = std::collections::HashMap::new();
+    let mut result = ObjectMap::new();
     result.insert("value".to_string(), shifted);
     result.insert("list".to_string(), Value::List(list));
 
@@ -0,0 +1,1467 @@
+//! Reference documentation for every stdlib function.
+//!
+//! `tilde doc --stdlib` renders the reference from this table, and a test
+//! checks that it lists exactly the functions in `get_stdlib_function_names`
+//! and that every runnable example passes, so the two can't drift apart.
+//! Examples check their results with `expect`.
+
+/// Documentation for one stdlib function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FunctionInfo {
+    pub name: &'static str,
+    /// Parameter names as the signature shows them: `[name]` is optional and
+    /// `name...` takes any number of values
+    pub params: &'static [&'static str],
+    /// What the function does, in Markdown
+    pub description: &'static str,
+    /// Example code, one statement per line
+    pub example: &'static str,
+    /// The reference section it's listed in
+    pub category: &'static str,
+    /// Whether the example can run anywhere: some need audio, files or an
+    /// enclosing script
+    pub runnable: bool,
+}
+
+impl FunctionInfo {
+    /// The function name followed by its parameters, e.g. `slice list start [end]`
+    pub fn signature(&self) -> String {
+        std::iter::once(self.name)
+            .chain(self.params.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// How many arguments a call needs: optional and repeated parameters
+    /// don't count
+    pub fn arity(&self) -> usize {
+        self.params
+            .iter()
+            .filter(|param| !param.starts_with('[') && !param.ends_with("..."))
+            .count()
+    }
+
+    /// The documentation as plain text, as `help` shows it
+    pub fn help(&self) -> String {
+        let example: String = self
+            .example
+            .lines()
+            .map(|line| format!("\n    {}", line))
+            .collect();
+        format!(
+            "{}\n\n{}\n\nExample:{}",
+            self.signature(),
+            self.description,
+            example
+        )
+    }
+
+    /// The documentation as Markdown, as editors show it on hover
+    pub fn markdown(&self) -> String {
+        format!(
+            "`{}`\n\n{}\n\n```tilde\n{}\n```",
+            self.signature(),
+            self.description,
+            self.example
+        )
+    }
+
+    const fn not_run(mut self) -> Self {
+        self.runnable = false;
+        self
+    }
+}
+
+const fn entry(
+    category: &'static str,
+    name: &'static str,
+    params: &'static [&'static str],
+    description: &'static str,
+    example: &'static str,
+) -> FunctionInfo {
+    FunctionInfo {
+        name,
+        params,
+        description,
+        example,
+        category,
+        runnable: true,
+    }
+}
+
+/// Reference sections, in the order they're shown
+pub const CATEGORIES: &[&str] = &[
+    LISTS,
+    STRINGS,
+    MATH,
+    HELPERS,
+    DATES,
+    OBJECTS,
+    TYPES,
+    ENCODING,
+    DATA_FORMATS,
+    FILES,
+    SYSTEM,
+    INTROSPECTION,
+    TASKS,
+    CHANNELS,
+    MUSIC,
+];
+
+const LISTS: &str = "Lists";
+const STRINGS: &str = "Strings";
+const MATH: &str = "Math";
+const HELPERS: &str = "Helpers";
+const DATES: &str = "Dates";
+const OBJECTS: &str = "Objects";
+const TYPES: &str = "Types";
+const ENCODING: &str = "JSON, Encoding and Hashing";
+const DATA_FORMATS: &str = "Data Formats";
+const FILES: &str = "Files";
+const SYSTEM: &str = "System";
+const INTROSPECTION: &str = "Introspection";
+const TASKS: &str = "Tasks";
+const CHANNELS: &str = "Channels";
+const MUSIC: &str = "Music";
+
+/// Every documented stdlib function, grouped by category
+pub fn stdlib_functions() -> &'static [FunctionInfo] {
+    FUNCTIONS
+}
+
+/// The documentation for the stdlib function `name`
+pub fn lookup(name: &str) -> Option<&'static FunctionInfo> {
+    FUNCTIONS.iter().find(|info| info.name == name)
+}
+
+static FUNCTIONS: &[FunctionInfo] = &[
+    // Lists
+    entry(
+        LISTS,
+        "map",
+        &["list", "function"],
+        "Transforms each item of a list with a function, giving a new list.",
+        "expect (map [1, 2, 3] double) == [2, 4, 6]\nexpect (map [1, 2] |~n (~n + 10)|) == [11, 12]",
+    ),
+    entry(
+        LISTS,
+        "filter",
+        &["list", "function"],
+        "Keeps the items of a list for which a predicate function gives `true`.",
+        "expect (filter [1, 2, 3, 4] is-even) == [2, 4]",
+    ),
+    entry(
+        LISTS,
+        "reduce",
+        &["list", "function", "initial"],
+        "Combines the items of a list into one value, calling the function with the total so far and each item.",
+        "expect (reduce [1, 2, 3, 4] add 0) == 10",
+    ),
+    entry(
+        LISTS,
+        "sort",
+        &["list"],
+        "Sorts a list in ascending order. Works with numbers, strings and booleans.",
+        "expect (sort [3, 1, 2]) == [1, 2, 3]\nexpect (sort [\"pear\", \"apple\"]) == [\"apple\", \"pear\"]",
+    ),
+    entry(
+        LISTS,
+        "reverse",
+        &["list"],
+        "Reverses the order of a list's items.",
+        "expect (reverse [1, 2, 3]) == [3, 2, 1]",
+    ),
+    entry(
+        LISTS,
+        "list",
+        &["length"],
+        "Creates a list of the numbers from 1 to `length`.",
+        "expect (list 4) == [1, 2, 3, 4]",
+    ),
+    entry(
+        LISTS,
+        "length",
+        &["value"],
+        "The number of items in a list, characters in a string or keys in an object.",
+        "expect (length [1, 2, 3]) == 3\nexpect (length \"hello\") == 5",
+    ),
+    entry(
+        LISTS,
+        "append",
+        &["list", "value"],
+        "A new list with the value added at the end. The original list is unchanged.",
+        "~numbers is [1, 2]\nexpect (append ~numbers 3) == [1, 2, 3]\nexpect ~numbers == [1, 2]",
+    ),
+    entry(
+        LISTS,
+        "find",
+        &["list", "function"],
+        "The first item for which the predicate gives `true`, or null if there isn't one.",
+        "expect (find [1, 4, 6] is-even) == 4\nexpect (is-null (find [1, 3] is-even)) == true",
+    ),
+    entry(
+        LISTS,
+        "find-index",
+        &["list", "function"],
+        "The index of the first item for which the predicate gives `true`, or null if there isn't one.",
+        "expect (find-index [1, 4, 6] is-even) == 1",
+    ),
+    entry(
+        LISTS,
+        "find-last",
+        &["list", "function"],
+        "The last item for which the predicate gives `true`, or null if there isn't one.",
+        "expect (find-last [2, 3, 4, 5] is-even) == 4",
+    ),
+    entry(
+        LISTS,
+        "every",
+        &["list", "function"],
+        "Whether the predicate gives `true` for every item. `true` for an empty list.",
+        "expect (every [2, 4] is-even) == true\nexpect (every [2, 3] is-even) == false",
+    ),
+    entry(
+        LISTS,
+        "some",
+        &["list", "function"],
+        "Whether the predicate gives `true` for at least one item.",
+        "expect (some [1, 2] is-even) == true\nexpect (some [1, 3] is-even) == false",
+    ),
+    entry(
+        LISTS,
+        "remove-if",
+        &["list", "function"],
+        "A new list without the items for which the predicate gives `true`.",
+        "expect (remove-if [1, 2, 3, 4] is-even) == [1, 3]",
+    ),
+    entry(
+        LISTS,
+        "count-if",
+        &["list", "function"],
+        "How many items the predicate gives `true` for.",
+        "expect (count-if [1, 2, 3, 4] is-even) == 2",
+    ),
+    entry(
+        LISTS,
+        "take-while",
+        &["list", "function"],
+        "The items from the start of the list up to the first one the predicate rejects.",
+        "expect (take-while [2, 4, 5, 6] is-even) == [2, 4]",
+    ),
+    entry(
+        LISTS,
+        "drop-while",
+        &["list", "function"],
+        "The items from the first one the predicate rejects to the end of the list.",
+        "expect (drop-while [2, 4, 5, 6] is-even) == [5, 6]",
+    ),
+    entry(
+        LISTS,
+        "partition",
+        &["list", "function"],
+        "Splits a list in two by a predicate, giving an object with `matched` and `unmatched` lists.",
+        "~parts is partition [1, 2, 3, 4] is-even\nexpect ~parts.matched == [2, 4]\nexpect ~parts.unmatched == [1, 3]",
+    ),
+    entry(
+        LISTS,
+        "group-by",
+        &["list", "function"],
+        "Groups items by the result of a function, giving an object whose keys are the results as strings and whose values are lists of items.",
+        "~groups is group-by [\"a\", \"bb\", \"cc\"] length\nexpect ~groups.2 == [\"bb\", \"cc\"]",
+    ),
+    entry(
+        LISTS,
+        "sort-by",
+        &["list", "function"],
+        "Sorts a list by the result of calling a function on each item.",
+        "expect (sort-by [\"ccc\", \"a\", \"bb\"] length) == [\"a\", \"bb\", \"ccc\"]",
+    ),
+    entry(
+        LISTS,
+        "remove",
+        &["list", "value"],
+        "A new list without the first occurrence of the value.",
+        "expect (remove [1, 2, 1] 1) == [2, 1]",
+    ),
+    entry(
+        LISTS,
+        "remove-at",
+        &["list", "index"],
+        "A new list without the item at the index.",
+        "expect (remove-at [\"a\", \"b\", \"c\"] 1) == [\"a\", \"c\"]",
+    ),
+    entry(
+        LISTS,
+        "insert",
+        &["list", "index", "value"],
+        "A new list with the value inserted at the index.",
+        "expect (insert [1, 3] 1 2) == [1, 2, 3]",
+    ),
+    entry(
+        LISTS,
+        "set-at",
+        &["list", "index", "value"],
+        "A new list with the item at the index replaced by the value.",
+        "expect (set-at [1, 2, 3] 0 9) == [9, 2, 3]",
+    ),
+    entry(
+        LISTS,
+        "pop",
+        &["list"],
+        "Removes the last item, giving an object with the item as `value` and the rest as `list`.",
+        "~result is pop [1, 2, 3]\nexpect ~result.value == 3\nexpect ~result.list == [1, 2]",
+    ),
+    entry(
+        LISTS,
+        "shift",
+        &["list"],
+        "Removes the first item, giving an object with the item as `value` and the rest as `list`.",
+        "~result is shift [1, 2, 3]\nexpect ~result.value == 1\nexpect ~result.list == [2, 3]",
+    ),
+    entry(
+        LISTS,
+        "unshift",
+        &["list", "value"],
+        "A new list with the value added at the start.",
+        "expect (unshift [2, 3] 1) == [1, 2, 3]",
+    ),
+    entry(
+        LISTS,
+        "index-of",
+        &["list_or_string", "value"],
+        "The index of the first occurrence of a value in a list, or of a substring in a string, or null if it's not there.",
+        "expect (index-of [\"a\", \"b\"] \"b\") == 1\nexpect (index-of \"hello\" \"ll\") == 2",
+    ),
+    entry(
+        LISTS,
+        "contains",
+        &["list_or_string", "value"],
+        "Whether a list contains a value, or a string contains a substring.",
+        "expect (contains [1, 2, 3] 2) == true\nexpect (contains \"hello\" \"xyz\") == false",
+    ),
+    entry(
+        LISTS,
+        "slice",
+        &["list", "start", "[end]"],
+        "The items from `start` up to but not including `end`, or to the end of the list.",
+        "expect (slice [1, 2, 3, 4] 1 3) == [2, 3]\nexpect (slice [1, 2, 3, 4] 2) == [3, 4]",
+    ),
+    entry(
+        LISTS,
+        "concat",
+        &["list", "lists..."],
+        "Joins lists into one.",
+        "expect (concat [1] [2, 3] [4]) == [1, 2, 3, 4]",
+    ),
+    entry(
+        LISTS,
+        "take",
+        &["list", "count"],
+        "The first `count` items.",
+        "expect (take [1, 2, 3] 2) == [1, 2]",
+    ),
+    entry(
+        LISTS,
+        "drop",
+        &["list", "count"],
+        "Everything after the first `count` items.",
+        "expect (drop [1, 2, 3] 2) == [3]",
+    ),
+    entry(
+        LISTS,
+        "flatten",
+        &["list", "[depth]"],
+        "Flattens nested lists into one list, all the way down or `depth` levels.",
+        "expect (flatten [1, [2, [3]]]) == [1, 2, 3]\nexpect (flatten [1, [2, [3]]] 1) == [1, 2, [3]]",
+    ),
+    entry(
+        LISTS,
+        "unique",
+        &["list"],
+        "The list without repeated values, keeping the first of each.",
+        "expect (unique [1, 2, 1, 3, 2]) == [1, 2, 3]",
+    ),
+    entry(
+        LISTS,
+        "zip",
+        &["list1", "list2"],
+        "Pairs up the items of two lists, stopping at the end of the shorter one.",
+        "expect (zip [1, 2] [\"a\", \"b\", \"c\"]) == [[1, \"a\"], [2, \"b\"]]",
+    ),
+    entry(
+        LISTS,
+        "chunk",
+        &["list", "size"],
+        "Splits a list into lists of `size` items; the last may be shorter.",
+        "expect (chunk [1, 2, 3, 4, 5] 2) == [[1, 2], [3, 4], [5]]",
+    ),
+    entry(
+        LISTS,
+        "transpose",
+        &["matrix"],
+        "Swaps the rows and columns of a list of lists. Missing items in ragged rows become null.",
+        "expect (transpose [[1, 2, 3], [4, 5, 6]]) == [[1, 4], [2, 5], [3, 6]]",
+    ),
+    entry(
+        LISTS,
+        "union",
+        &["list1", "list2"],
+        "The distinct items of both lists, those from the first list first.",
+        "expect (union [1, 2] [2, 3]) == [1, 2, 3]",
+    ),
+    entry(
+        LISTS,
+        "difference",
+        &["list1", "list2"],
+        "The items of the first list that aren't in the second.",
+        "expect (difference [1, 2, 3] [2]) == [1, 3]",
+    ),
+    entry(
+        LISTS,
+        "intersection",
+        &["list1", "list2"],
+        "The distinct items that are in both lists.",
+        "expect (intersection [1, 2, 2, 3] [2, 3, 4]) == [2, 3]",
+    ),
+    // Strings
+    entry(
+        STRINGS,
+        "split",
+        &["string", "delimiter"],
+        "Splits a string into a list at each delimiter.",
+        "expect (split \"a,b,c\" \",\") == [\"a\", \"b\", \"c\"]",
+    ),
+    entry(
+        STRINGS,
+        "join",
+        &["list", "delimiter"],
+        "Joins the items of a list into a string, with the delimiter between them.",
+        "expect (join [\"a\", \"b\", \"c\"] \"-\") == \"a-b-c\"",
+    ),
+    entry(
+        STRINGS,
+        "trim",
+        &["string"],
+        "Removes whitespace from both ends of a string.",
+        "expect (trim \"  hi  \") == \"hi\"",
+    ),
+    entry(
+        STRINGS,
+        "uppercase",
+        &["string"],
+        "Converts a string to uppercase.",
+        "expect (uppercase \"hello\") == \"HELLO\"",
+    ),
+    entry(
+        STRINGS,
+        "lowercase",
+        &["string"],
+        "Converts a string to lowercase.",
+        "expect (lowercase \"HeLLo\") == \"hello\"",
+    ),
+    entry(
+        STRINGS,
+        "starts-with",
+        &["string", "prefix"],
+        "Whether a string starts with the prefix.",
+        "expect (starts-with \"hello\" \"he\") == true",
+    ),
+    entry(
+        STRINGS,
+        "ends-with",
+        &["string", "suffix"],
+        "Whether a string ends with the suffix.",
+        "expect (ends-with \"hello\" \"lo\") == true",
+    ),
+    entry(
+        STRINGS,
+        "substring",
+        &["string", "start", "[length]"],
+        "The characters from `start`, `length` of them or to the end of the string.",
+        "expect (substring \"hello world\" 6) == \"world\"\nexpect (substring \"hello\" 1 3) == \"ell\"",
+    ),
+    entry(
+        STRINGS,
+        "replace",
+        &["string", "old", "new"],
+        "Replaces every occurrence of `old` in a string with `new`.",
+        "expect (replace \"a-b-c\" \"-\" \"+\") == \"a+b+c\"",
+    ),
+    entry(
+        STRINGS,
+        "repeat",
+        &["string", "count"],
+        "The string repeated `count` times.",
+        "expect (repeat \"ab\" 3) == \"ababab\"",
+    ),
+    entry(
+        STRINGS,
+        "pad-left",
+        &["string", "length", "[char]"],
+        "Pads the start of a string to `length` characters, with spaces or `char`.",
+        "expect (pad-left \"7\" 3 \"0\") == \"007\"",
+    ),
+    entry(
+        STRINGS,
+        "pad-right",
+        &["string", "length", "[char]"],
+        "Pads the end of a string to `length` characters, with spaces or `char`.",
+        "expect (pad-right \"ab\" 4 \".\") == \"ab..\"",
+    ),
+    entry(
+        STRINGS,
+        "regex-test",
+        &["string", "pattern"],
+        "Whether the regular expression `pattern` matches anywhere in `string`.",
+        "expect (regex-test \"order 66\" \"\\d+\")",
+    ),
+    entry(
+        STRINGS,
+        "regex-match",
+        &["string", "pattern"],
+        "The first match of `pattern`, or null. A pattern without groups gives the matched text, one with named groups `(?<name>...)` an object of its groups (unnamed ones keyed by number), and one with other groups a list of them.",
+        "expect (regex-match \"v1.20\" \"(\\d+)\\.(\\d+)\") == [\"1\", \"20\"]",
+    ),
+    entry(
+        STRINGS,
+        "regex-find-all",
+        &["string", "pattern"],
+        "Every match of `pattern`, each given the way `regex-match` gives one.",
+        "expect (regex-find-all \"a1 b22\" \"\\d+\") == [\"1\", \"22\"]",
+    ),
+    entry(
+        STRINGS,
+        "regex-replace",
+        &["string", "pattern", "replacement"],
+        "Replaces every match of `pattern`. `replacement` is text that can use groups as `$1` or `${name}`, or a function given each match the way `regex-match` gives it.",
+        "expect (regex-replace \"2024-01-15\" \"(\\d+)-(\\d+)-(\\d+)\" \"$3/$2/$1\") == \"15/01/2024\"",
+    ),
+    entry(
+        STRINGS,
+        "regex-split",
+        &["string", "pattern"],
+        "Splits `string` everywhere `pattern` matches.",
+        "expect (regex-split \"a, b;c\" \"[,;] *\") == [\"a\", \"b\", \"c\"]",
+    ),
+    entry(
+        STRINGS,
+        "render-template",
+        &["template", "data", "[options]"],
+        "Fills in a mustache-style template from `data`: `{{ name }}` values (HTML-escaped unless `{escape: \"none\"}`), `{{ name | filter }}` through stdlib functions, `{{# list }}...{{/ list }}` loops and sections, `{{^ name }}` for when it's empty or false, and `{{> file }}` partials, read from `options.partials` if given.",
+        "expect (render-template \"Hi {{ name | uppercase }}!\" {name: \"ada\"}) == \"Hi ADA!\"",
+    ),
+    // Math
+    entry(
+        MATH,
+        "absolute",
+        &["number"],
+        "The number without its sign.",
+        "expect (absolute -5) == 5",
+    ),
+    entry(
+        MATH,
+        "square-root",
+        &["number"],
+        "The square root of a number that isn't negative.",
+        "expect (square-root 16) == 4",
+    ),
+    entry(
+        MATH,
+        "random",
+        &["min", "max"],
+        "A random number from `min` to `max`, inclusive. Whole numbers unless either bound has a fractional part or is written like `1.0`.",
+        "~roll is random 1 6\nexpect ~roll >= 1 and ~roll <= 6",
+    ),
+    entry(
+        MATH,
+        "sin",
+        &["radians"],
+        "The sine of an angle in radians.",
+        "expect (sin 0) == 0",
+    ),
+    entry(
+        MATH,
+        "cos",
+        &["radians"],
+        "The cosine of an angle in radians.",
+        "expect (cos 0) == 1",
+    ),
+    entry(
+        MATH,
+        "tan",
+        &["radians"],
+        "The tangent of an angle in radians.",
+        "expect (tan 0) == 0",
+    ),
+    entry(
+        MATH,
+        "asin",
+        &["number"],
+        "The angle in radians whose sine is the number, which must be from -1 to 1.",
+        "expect (asin 0) == 0",
+    ),
+    entry(
+        MATH,
+        "acos",
+        &["number"],
+        "The angle in radians whose cosine is the number, which must be from -1 to 1.",
+        "expect (acos 1) == 0",
+    ),
+    entry(
+        MATH,
+        "atan",
+        &["number"],
+        "The angle in radians whose tangent is the number.",
+        "expect (atan 0) == 0",
+    ),
+    entry(
+        MATH,
+        "atan2",
+        &["y", "x"],
+        "The angle in radians from the x axis to the point (`x`, `y`).",
+        "expect (round (atan2 1 1) 4) == 0.7854",
+    ),
+    entry(
+        MATH,
+        "log",
+        &["number", "[base]"],
+        "The natural logarithm of a positive number, or its logarithm in `base`.",
+        "expect (log 1) == 0\nexpect (log 8 2) == 3",
+    ),
+    entry(
+        MATH,
+        "log10",
+        &["number"],
+        "The base-10 logarithm of a positive number.",
+        "expect (log10 1000) == 3",
+    ),
+    entry(
+        MATH,
+        "exp",
+        &["number"],
+        "e raised to the power of the number.",
+        "expect (exp 0) == 1",
+    ),
+    entry(
+        MATH,
+        "pow",
+        &["base", "exponent"],
+        "`base` raised to the power `exponent`.",
+        "expect (pow 2 10) == 1024",
+    ),
+    entry(
+        MATH,
+        "round",
+        &["number", "[places]"],
+        "Rounds to the nearest whole number, or to `places` decimal places.",
+        "expect (round 2.5) == 3\nexpect (round 3.14159 2) == 3.14",
+    ),
+    entry(
+        MATH,
+        "floor",
+        &["number"],
+        "Rounds down to a whole number.",
+        "expect (floor 2.7) == 2",
+    ),
+    entry(
+        MATH,
+        "ceil",
+        &["number"],
+        "Rounds up to a whole number.",
+        "expect (ceil 2.1) == 3",
+    ),
+    entry(
+        MATH,
+        "pi",
+        &[],
+        "The constant π.",
+        "expect (round pi 2) == 3.14",
+    ),
+    entry(
+        MATH,
+        "e",
+        &[],
+        "Euler's number e.",
+        "expect (round e 2) == 2.72",
+    ),
+    // Helpers
+    entry(
+        HELPERS,
+        "is-even",
+        &["number"],
+        "Whether a number is even. Handy with `filter`.",
+        "expect (is-even 4) == true",
+    ),
+    entry(
+        HELPERS,
+        "is-odd",
+        &["number"],
+        "Whether a number is odd.",
+        "expect (is-odd 3) == true",
+    ),
+    entry(
+        HELPERS,
+        "is-positive",
+        &["number"],
+        "Whether a number is greater than zero.",
+        "expect (is-positive 2) == true",
+    ),
+    entry(
+        HELPERS,
+        "is-negative",
+        &["number"],
+        "Whether a number is less than zero.",
+        "expect (is-negative -2) == true",
+    ),
+    entry(
+        HELPERS,
+        "is-zero",
+        &["number"],
+        "Whether a number is zero.",
+        "expect (is-zero 0) == true",
+    ),
+    entry(
+        HELPERS,
+        "double",
+        &["number"],
+        "The number times 2. Handy with `map`.",
+        "expect (double 4) == 8",
+    ),
+    entry(
+        HELPERS,
+        "triple",
+        &["number"],
+        "The number times 3.",
+        "expect (triple 4) == 12",
+    ),
+    entry(
+        HELPERS,
+        "quadruple",
+        &["number"],
+        "The number times 4.",
+        "expect (quadruple 4) == 16",
+    ),
+    entry(
+        HELPERS,
+        "half",
+        &["number"],
+        "The number divided by 2.",
+        "expect (half 5) == 2.5",
+    ),
+    entry(
+        HELPERS,
+        "square",
+        &["number"],
+        "The number times itself.",
+        "expect (square 5) == 25",
+    ),
+    entry(
+        HELPERS,
+        "increment",
+        &["number"],
+        "The number plus 1.",
+        "expect (increment 5) == 6",
+    ),
+    entry(
+        HELPERS,
+        "decrement",
+        &["number"],
+        "The number minus 1.",
+        "expect (decrement 5) == 4",
+    ),
+    entry(
+        HELPERS,
+        "add",
+        &["a", "b"],
+        "The sum of two numbers. Handy with `reduce`.",
+        "expect (add 2 3) == 5",
+    ),
+    entry(
+        HELPERS,
+        "multiply",
+        &["a", "b"],
+        "The product of two numbers.",
+        "expect (multiply 2 3) == 6",
+    ),
+    entry(
+        HELPERS,
+        "max",
+        &["a", "b"],
+        "The larger of two numbers.",
+        "expect (max 2 3) == 3",
+    ),
+    entry(
+        HELPERS,
+        "min",
+        &["a", "b"],
+        "The smaller of two numbers.",
+        "expect (min 2 3) == 2",
+    ),
+    entry(
+        HELPERS,
+        "fibonacci",
+        &["n"],
+        "The `n`th Fibonacci number, counting from `fibonacci 0` = 0.",
+        "expect (fibonacci 10) == 55",
+    ),
+    // Dates
+    entry(
+        DATES,
+        "now",
+        &[],
+        "The current date and time, in UTC.",
+        "~started is now\nexpect (date-year ~started) >= 2024",
+    ),
+    entry(
+        DATES,
+        "date",
+        &["string"],
+        "Parses an ISO 8601 date like `2024-03-15` or date and time like `2024-03-15T14:30:00+02:00`, converting it to UTC.",
+        "~meeting is date \"2024-03-15T16:30:00+02:00\"\nexpect (date-hour ~meeting) == 14",
+    ),
+    entry(
+        DATES,
+        "date-add",
+        &["date", "days"],
+        "The date `days` days later.",
+        "expect (date-add (date \"2024-02-28\") 2) == (date \"2024-03-01\")",
+    ),
+    entry(
+        DATES,
+        "date-subtract",
+        &["date", "days"],
+        "The date `days` days earlier.",
+        "expect (date-subtract (date \"2024-03-01\") 1) == (date \"2024-02-29\")",
+    ),
+    entry(
+        DATES,
+        "date-diff",
+        &["start", "end"],
+        "The time from `start` to `end`, as an object with the whole `days`, `hours`, `minutes`, `seconds` and `milliseconds` between them.",
+        "~gap is date-diff (date \"2024-03-15T10:00:00Z\") (date \"2024-03-17T12:00:00Z\")\nexpect ~gap.days == 2\nexpect ~gap.hours == 50",
+    ),
+    entry(
+        DATES,
+        "date-format",
+        &["date", "format"],
+        "Formats a date with a strftime-style format such as `%Y-%m-%d %H:%M`.",
+        "expect (date-format (date \"2024-03-15\") \"%d/%m/%Y\") == \"15/03/2024\"",
+    ),
+    entry(
+        DATES,
+        "date-parse",
+        &["string", "format"],
+        "Parses a date written in a strftime-style format. Dates without a timezone are taken as UTC.",
+        "expect (date-parse \"15/03/2024\" \"%d/%m/%Y\") == (date \"2024-03-15\")",
+    ),
+    entry(
+        DATES,
+        "date-year",
+        &["date"],
+        "The year of a date.",
+        "expect (date-year (date \"2024-03-15\")) == 2024",
+    ),
+    entry(
+        DATES,
+        "date-month",
+        &["date"],
+        "The month of a date, from 1 to 12.",
+        "expect (date-month (date \"2024-03-15\")) == 3",
+    ),
+    entry(
+        DATES,
+        "date-day",
+        &["date"],
+        "The day of the month of a date.",
+        "expect (date-day (date \"2024-03-15\")) == 15",
+    ),
+    entry(
+        DATES,
+        "date-hour",
+        &["date"],
+        "The hour of a date, from 0 to 23.",
+        "expect (date-hour (date \"2024-03-15T14:30:00Z\")) == 14",
+    ),
+    entry(
+        DATES,
+        "date-minute",
+        &["date"],
+        "The minute of a date.",
+        "expect (date-minute (date \"2024-03-15T14:30:00Z\")) == 30",
+    ),
+    entry(
+        DATES,
+        "date-second",
+        &["date"],
+        "The second of a date.",
+        "expect (date-second (date \"2024-03-15T14:30:45Z\")) == 45",
+    ),
+    entry(
+        DATES,
+        "date-weekday",
+        &["date"],
+        "The day of the week of a date, from 0 for Sunday to 6 for Saturday.",
+        "expect (date-weekday (date \"2024-03-15\")) == 5",
+    ),
+    entry(
+        DATES,
+        "date-before",
+        &["date1", "date2"],
+        "Whether the first date is earlier than the second.",
+        "expect (date-before (date \"2024-01-01\") (date \"2024-06-01\")) == true",
+    ),
+    entry(
+        DATES,
+        "date-after",
+        &["date1", "date2"],
+        "Whether the first date is later than the second.",
+        "expect (date-after (date \"2024-01-01\") (date \"2024-06-01\")) == false",
+    ),
+    entry(
+        DATES,
+        "date-equal",
+        &["date1", "date2"],
+        "Whether two dates are the same moment, even if written in different timezones.",
+        "expect (date-equal (date \"2024-03-15T12:00:00Z\") (date \"2024-03-15T14:00:00+02:00\")) == true",
+    ),
+    // Objects
+    entry(
+        OBJECTS,
+        "keys",
+        &["object"],
+        "The keys of an object, in no particular order.",
+        "expect (sort (keys {b: 2, a: 1})) == [\"a\", \"b\"]",
+    ),
+    entry(
+        OBJECTS,
+        "values",
+        &["object"],
+        "The values of an object, in no particular order.",
+        "expect (sort (values {b: 2, a: 1})) == [1, 2]",
+    ),
+    entry(
+        OBJECTS,
+        "has",
+        &["key", "object"],
+        "Whether an object has the key.",
+        "expect (has \"name\" {name: \"Ada\"}) == true",
+    ),
+    entry(
+        OBJECTS,
+        "merge",
+        &["object1", "object2"],
+        "A new object with the keys of both; the second object's values win.",
+        "expect (merge {a: 1, b: 2} {b: 3}) == {a: 1, b: 3}",
+    ),
+    entry(
+        OBJECTS,
+        "deep-merge",
+        &["object1", "object2"],
+        "Like `merge`, but objects inside both are merged too instead of replaced.",
+        "~settings is deep-merge {ui: {theme: \"dark\", size: 1}} {ui: {size: 2}}\nexpect ~settings.ui == {theme: \"dark\", size: 2}",
+    ),
+    entry(
+        OBJECTS,
+        "pick",
+        &["object", "keys"],
+        "A new object with only the listed keys.",
+        "expect (pick {a: 1, b: 2, c: 3} [\"a\", \"c\"]) == {a: 1, c: 3}",
+    ),
+    entry(
+        OBJECTS,
+        "omit",
+        &["object", "keys"],
+        "A new object without the listed keys.",
+        "expect (omit {a: 1, b: 2, c: 3} [\"b\"]) == {a: 1, c: 3}",
+    ),
+    entry(
+        OBJECTS,
+        "object-get",
+        &["object", "path"],
+        "The value at a dot-separated path such as `user.address.city`, or null if any part is missing.",
+        "~user is {address: {city: \"Oslo\"}}\nexpect (object-get ~user \"address.city\") == \"Oslo\"",
+    ),
+    entry(
+        OBJECTS,
+        "object-set",
+        &["object", "path", "value"],
+        "A new object with the value at a dot-separated path, creating objects along the way.",
+        "~user is object-set {} \"address.city\" \"Oslo\"\nexpect ~user.address.city == \"Oslo\"",
+    ),
+    // Types
+    entry(
+        TYPES,
+        "is-number",
+        &["value"],
+        "Whether the value is a number.",
+        "expect (is-number 42) == true\nexpect (is-number \"42\") == false",
+    ),
+    entry(
+        TYPES,
+        "is-string",
+        &["value"],
+        "Whether the value is a string.",
+        "expect (is-string \"hi\") == true",
+    ),
+    entry(
+        TYPES,
+        "is-boolean",
+        &["value"],
+        "Whether the value is `true` or `false`.",
+        "expect (is-boolean false) == true",
+    ),
+    entry(
+        TYPES,
+        "is-list",
+        &["value"],
+        "Whether the value is a list.",
+        "expect (is-list [1]) == true",
+    ),
+    entry(
+        TYPES,
+        "is-object",
+        &["value"],
+        "Whether the value is an object.",
+        "expect (is-object {a: 1}) == true",
+    ),
+    entry(
+        TYPES,
+        "is-null",
+        &["value"],
+        "Whether the value is null, such as the result of a `find` that found nothing.",
+        "expect (is-null (find [1] is-even)) == true",
+    ),
+    entry(
+        TYPES,
+        "is-empty",
+        &["value"],
+        "Whether a string, list or object is empty.",
+        "expect (is-empty []) == true\nexpect (is-empty \"a\") == false",
+    ),
+    entry(
+        TYPES,
+        "is-defined",
+        &["expression"],
+        "Whether the expression, usually a variable, can be evaluated without an error.",
+        "~name is \"Ada\"\nexpect (is-defined ~name) == true\nexpect (is-defined ~missing) == false",
+    ),
+    // JSON, encoding and hashing
+    entry(
+        ENCODING,
+        "to-json",
+        &["value"],
+        "The value as a JSON string.",
+        "expect (to-json [\"a\", true]) == \"[\\\"a\\\",true]\"",
+    ),
+    entry(
+        ENCODING,
+        "from-json",
+        &["string"],
+        "Parses a JSON string into a value. Object keys keep the order they appear in.",
+        "~data is from-json \"{\\\"items\\\": [1, 2]}\"\nexpect ~data.items == [1, 2]",
+    ),
+    // Data formats
+    entry(
+        DATA_FORMATS,
+        "csv-parse",
+        &["text", "[options]"],
+        "Parses CSV into a list of objects keyed by the header row, or a list of lists with `{header: false}`. Numbers, booleans and dates are recognised unless `{types: false}`, and empty fields become null. Other options are `delimiter`, `quote` and `columns`.",
+        "~rows is csv-parse \"name,age\\nAda,36\\n\\\"Hopper, Grace\\\",85\"\nexpect ~rows.1 == {name: \"Hopper, Grace\", age: 85}",
+    ),
+    entry(
+        DATA_FORMATS,
+        "csv-stringify",
+        &["rows", "[options]"],
+        "Writes a list of objects or lists as CSV, quoting fields where needed. Object columns come from `options.columns`, or else every key in the order it first appears.",
+        "expect (csv-stringify [{name: \"Ada\", age: 36}] {columns: [\"name\", \"age\"]}) == \"name,age\\nAda,36\\n\"",
+    ),
+    entry(
+        DATA_FORMATS,
+        "csv-read-file",
+        &["path", "[options]", "[function]"],
+        "Reads a CSV file a row at a time, taking the same options as `csv-parse`. Given a function, returns its non-null results for each row instead of the rows themselves.",
+        "~emails is csv-read-file \"users.csv\" |~row (~row.email)|",
+    )
+    .not_run(),
+    entry(
+        DATA_FORMATS,
+        "csv-write-file",
+        &["path", "rows", "[options]"],
+        "Writes rows to a CSV file the way `csv-stringify` does and returns how many rows were written.",
+        "csv-write-file \"report.csv\" ~rows {columns: [\"date\", \"total\"]}",
+    )
+    .not_run(),
+    entry(
+        DATA_FORMATS,
+        "from-yaml",
+        &["text"],
+        "Parses a YAML document. Keys keep their order, dates become date values, and anchors, aliases and `<<` merge keys are followed. Errors give the line and column.",
+        "~config is from-yaml \"name: web\\nports:\\n  - 80\\n  - 443\"\nexpect ~config.ports == [80, 443]",
+    ),
+    entry(
+        DATA_FORMATS,
+        "to-yaml",
+        &["value"],
+        "Writes a value as block-style YAML, quoting text only where it would otherwise read back as something else.",
+        "expect (to-yaml {name: \"web\", ports: [80]}) == \"name: web\\nports:\\n  - 80\\n\"",
+    ),
+    entry(
+        DATA_FORMATS,
+        "from-toml",
+        &["text"],
+        "Parses a TOML document into an object. Keys keep their order and dates become date values; a time of day on its own stays text. Errors give the line and column.",
+        "~manifest is from-toml \"[package]\\nname = \\\"tilde\\\"\"\nexpect ~manifest.package.name == \"tilde\"",
+    ),
+    entry(
+        DATA_FORMATS,
+        "to-toml",
+        &["object"],
+        "Writes an object as TOML, with nested objects as tables. Whole numbers are written as integers. TOML has no null, so null values are an error.",
+        "expect (to-toml {name: \"tilde\", port: 8080}) == \"name = \\\"tilde\\\"\\nport = 8080\\n\"",
+    ),
+    entry(
+        DATA_FORMATS,
+        "from-ini",
+        &["text"],
+        "Parses INI text into an object, with an object for each `[section]`. Values are typed like CSV fields unless double-quoted, and `;` or `#` starts a comment. Errors give the line and column.",
+        "~config is from-ini \"[db]\\nhost = localhost\\nport = 5432\"\nexpect ~config.db.port == 5432",
+    ),
+    entry(
+        DATA_FORMATS,
+        "to-ini",
+        &["object"],
+        "Writes an object as INI: plain keys first, then a `[section]` for each nested object. Sections can't be nested and values can't be lists.",
+        "expect (to-ini {db: {port: 5432}}) == \"[db]\\nport = 5432\\n\"",
+    ),
+    entry(
+        ENCODING,
+        "base64-encode",
+        &["string"],
+        "Encodes a string as base64.",
+        "expect (base64-encode \"hello\") == \"aGVsbG8=\"",
+    ),
+    entry(
+        ENCODING,
+        "base64-decode",
+        &["string"],
+        "Decodes a base64 string.",
+        "expect (base64-decode \"aGVsbG8=\") == \"hello\"",
+    ),
+    entry(
+        ENCODING,
+        "url-encode",
+        &["string"],
+        "Percent-encodes a string for use in a URL.",
+        "expect (url-encode \"a b&c\") == \"a%20b%26c\"",
+    ),
+    entry(
+        ENCODING,
+        "url-decode",
+        &["string"],
+        "Decodes a percent-encoded string.",
+        "expect (url-decode \"a%20b\") == \"a b\"",
+    ),
+    entry(
+        ENCODING,
+        "sha256",
+        &["string"],
+        "The SHA-256 hash of a string, in hexadecimal.",
+        "expect (length (sha256 \"hello\")) == 64",
+    ),
+    entry(
+        ENCODING,
+        "md5",
+        &["string"],
+        "The MD5 hash of a string, in hexadecimal. Not for security.",
+        "expect (md5 \"hello\") == \"5d41402abc4b2a76b9719d911017c592\"",
+    ),
+    entry(
+        ENCODING,
+        "hmac-sha256",
+        &["key", "message"],
+        "The HMAC-SHA256 of a message with a key, in hexadecimal, as used to sign API requests.",
+        "expect (length (hmac-sha256 \"secret\" \"message\")) == 64",
+    ),
+    // Files
+    entry(
+        FILES,
+        "file-exists",
+        &["path"],
+        "Whether there's a file at the path.",
+        "expect (file-exists \"no/such/file.txt\") == false",
+    ),
+    entry(
+        FILES,
+        "dir-exists",
+        &["path"],
+        "Whether there's a directory at the path.",
+        "expect (dir-exists \".\") == true",
+    ),
+    entry(
+        FILES,
+        "file-size",
+        &["path"],
+        "The size of a file in bytes.",
+        "~bytes is file-size \"data/report.csv\"",
+    )
+    .not_run(),
+    // System
+    entry(
+        SYSTEM,
+        "env",
+        &["name"],
+        "The value of an environment variable, or null if it isn't set.",
+        "~port is env \"TILDE_EXAMPLE_UNSET\" or 8080\nexpect ~port == 8080",
+    ),
+    entry(
+        SYSTEM,
+        "exit",
+        &["[code]"],
+        "Stops the script with a status code for the shell, 0 if none is given. attempt/rescue can't catch it.",
+        "if (length ~args) == 0 (\n    say \"usage: greet.tde <name>\"\n    exit 2\n)",
+    )
+    .not_run(),
+    entry(
+        SYSTEM,
+        "import",
+        &["spec"],
+        "Runs another file once, in the importing script. Paths starting with `.` or `/` or ending in `.tde` are relative to the importing file; other names are packages installed in `tilde_modules`.",
+        "import \"./helpers\"\nimport \"strings\"",
+    )
+    .not_run(),
+    // Introspection
+    entry(
+        INTROSPECTION,
+        "help",
+        &["[name]"],
+        "The documentation for a function, by name: one of your functions' parameters and `##` doc comment, or a stdlib function's signature, description and example. Your functions come first, as they do in calls; `core:name` means the stdlib one. With no name, lists the stdlib functions in each block.",
+        "~text is help \"slice\"\nexpect (starts-with ~text \"slice list start [end]\") == true",
+    ),
+    entry(
+        INTROSPECTION,
+        "functions-list",
+        &["[block]"],
+        "The names of the stdlib functions, block by block, followed by your functions as `*name`. With a block name such as `\"Strings\"`, only the stdlib functions in that block.",
+        "expect (contains (functions-list \"Strings\") \"trim\") == true",
+    ),
+    entry(
+        INTROSPECTION,
+        "function-arity",
+        &["name"],
+        "How many arguments a function takes. For stdlib functions with optional parameters, how many it needs.",
+        "function greet ~name ~greeting (\n    give ~greeting + \", \" + ~name\n)\nexpect (function-arity \"greet\") == 2\nexpect (function-arity \"slice\") == 2",
+    ),
+    entry(
+        INTROSPECTION,
+        "describe",
+        &["value"],
+        "A short description of a value: its type and, for strings, lists, objects and patterns, their size or contents.",
+        "expect (describe [1, 2, 3]) == \"list of 3 items\"\nexpect (describe {b: 1, a: 2}) == \"object with keys a, b\"",
+    ),
+    // Tasks
+    entry(
+        TASKS,
+        "spawn",
+        &["expression"],
+        "Starts evaluating an expression in the background and gives a task handle straight away.",
+        "~task is spawn (2 + 3)\nexpect (await ~task) == 5",
+    ),
+    entry(
+        TASKS,
+        "await",
+        &["task"],
+        "Waits for a task and gives its value. If the task failed, its error is raised here and can be rescued.",
+        "~task is spawn (square 4)\nexpect (await ~task) == 16",
+    ),
+    entry(
+        TASKS,
+        "await-all",
+        &["tasks"],
+        "Waits for a list of tasks and gives their results in the same order. A failed task's slot holds its error value.",
+        "~tasks is [(spawn (1 + 1)), (spawn (2 + 2))]\nexpect (await-all ~tasks) == [2, 4]",
+    ),
+    entry(
+        TASKS,
+        "parallel-map",
+        &["list", "function", "[options]"],
+        "Like `map`, with the items processed in parallel. Results keep the input order. `{limit: n}` caps how many run at once.",
+        "expect (parallel-map [1, 2, 3] |~n (~n * ~n)|) == [1, 4, 9]",
+    ),
+    entry(
+        TASKS,
+        "task-limit",
+        &["[limit]"],
+        "Gets or sets how many spawned tasks run at once, counting tasks spawned by other tasks. The default is the number of CPUs.",
+        "task-limit 4\nexpect task-limit == 4",
+    ),
+    // Channels
+    entry(
+        CHANNELS,
+        "channel",
+        &["[capacity]"],
+        "Creates a channel for passing values between tasks. With a capacity, `send` waits while that many values are queued.",
+        "~jobs is channel 10\nsend ~jobs \"first\"\nexpect (receive ~jobs) == \"first\"",
+    ),
+    entry(
+        CHANNELS,
+        "send",
+        &["channel", "value"],
+        "Sends a value on a channel. Sending on a closed channel raises a `channel_closed` error.",
+        "~c is channel\nsend ~c 1\nsend ~c 2\nexpect (receive ~c) == 1",
+    ),
+    entry(
+        CHANNELS,
+        "receive",
+        &["channel"],
+        "Waits for the next value on a channel. Gives null once the channel is closed and empty.",
+        "~c is channel\nsend ~c \"job\"\nclose ~c\nexpect (receive ~c) == \"job\"\nexpect (is-null (receive ~c)) == true",
+    ),
+    entry(
+        CHANNELS,
+        "receive-timeout",
+        &["channel", "seconds"],
+        "Like `receive`, but gives null if nothing arrives within the timeout.",
+        "~c is channel\nexpect (is-null (receive-timeout ~c 0.01)) == true",
+    ),
+    entry(
+        CHANNELS,
+        "close",
+        &["channel"],
+        "Closes a channel so nothing more can be sent. Queued values can still be received. Gives `false` if it was already closed.",
+        "~c is channel\nexpect (close ~c) == true\nexpect (close ~c) == false",
+    ),
+    entry(
+        CHANNELS,
+        "is-closed",
+        &["channel"],
+        "Whether a channel has been closed.",
+        "~c is channel\nclose ~c\nexpect (is-closed ~c) == true",
+    ),
+    entry(
+        CHANNELS,
+        "select",
+        &["channels", "[seconds]"],
+        "Waits on several channels and gives the first value to arrive as `{index, value}`, or null on timeout or when all are closed.",
+        "~a is channel\n~b is channel\nsend ~b \"hi\"\n~ready is select [~a, ~b] 1\nexpect ~ready.index == 1",
+    ),
+    // Music
+    entry(
+        MUSIC,
+        "pattern",
+        &["notation"],
+        "Creates a pattern from mini-notation: notes like `c3`, `~` for a rest, `[a b]` to subdivide a step.",
+        "~bass is pattern \"c2 ~ g2 [c3 c3]\"\nexpect (pattern-notation ~bass) == \"c2 ~ g2 [c3 c3]\"",
+    ),
+    entry(
+        MUSIC,
+        "pattern-length",
+        &["pattern"],
+        "The number of events in one cycle of a pattern, rests included.",
+        "expect (pattern-length (pattern \"c3 d3 e3\")) == 3",
+    ),
+    entry(
+        MUSIC,
+        "pattern-notation",
+        &["pattern"],
+        "The mini-notation a pattern was made from.",
+        "expect (pattern-notation (pattern \"c3 e3\")) == \"c3 e3\"",
+    ),
+    entry(
+        MUSIC,
+        "pattern-debug",
+        &["pattern"],
+        "A description of each event in a pattern, with its time and note.",
+        "say pattern-debug (pattern \"c3 ~\")",
+    ),
+    entry(
+        MUSIC,
+        "pattern-timeline",
+        &["pattern"],
+        "An ASCII timeline showing where a pattern's events fall in its cycle.",
+        "say pattern-timeline (pattern \"c3 ~ e3 ~\")",
+    ),
+    entry(
+        MUSIC,
+        "play",
+        &["pattern"],
+        "Starts playing a pattern in a loop, alongside any others.",
+        "play (pattern \"c3 e3 g3 e3\")",
+    )
+    .not_run(),
+    entry(
+        MUSIC,
+        "stop",
+        &[],
+        "Stops every playing pattern.",
+        "stop",
+    )
+    .not_run(),
+    entry(
+        MUSIC,
+        "tempo",
+        &["cpm"],
+        "Sets the tempo in cycles per minute.",
+        "tempo 30",
+    )
+    .not_run(),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Evaluator;
+    use crate::io::MemoryOutput;
+    use crate::parser::Parser;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_every_stdlib_function_is_documented() {
+        let names: Vec<&str> = crate::stdlib::get_stdlib_function_names()
+            .iter()
+            .copied()
+            .filter(|name| !name.starts_with("__"))
+            .collect();
+        for name in &names {
+            assert!(lookup(name).is_some(), "{} has no documentation", name);
+        }
+        for info in stdlib_functions() {
+            assert!(
+                names.contains(&info.name),
+                "{} isn't a stdlib function",
+                info.name
+            );
+            assert!(CATEGORIES.contains(&info.category), "{}", info.name);
+        }
+        assert_eq!(names.len(), stdlib_functions().len());
+    }
+
+    #[test]
+    fn test_examples_pass() {
+        let mut failures = Vec::new();
+        for info in stdlib_functions().iter().filter(|info| info.runnable) {
+            let mut evaluator = Evaluator::new();
+            evaluator.set_output(Arc::new(MemoryOutput::new()));
+            let result = Parser::new(info.example)
+                .parse()
+                .and_then(|program| evaluator.eval_program(program));
+            if let Err(error) = result {
+                failures.push(format!("{}: {}", info.name, error));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_signature() {
+        let slice = lookup("slice").unwrap();
+        assert_eq!(slice.signature(), "slice list start [end]");
+        assert_eq!(slice.arity(), 2);
+        assert!(
+            slice
+                .help()
+                .starts_with("slice list start [end]\n\nThe items from")
+        );
+        assert_eq!(lookup("pi").unwrap().signature(), "pi");
+        assert_eq!(lookup("concat").unwrap().arity(), 1);
+    }
+}
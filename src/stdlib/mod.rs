@@ -1,22 +1,31 @@
+pub mod channels;
 pub mod collection;
 pub mod crypto;
+pub mod csv;
 pub mod date;
 pub mod encoding;
 pub mod filesystem;
 pub mod helpers;
+pub mod ini;
+pub mod introspection;
 pub mod json;
 pub mod list;
 pub mod list_advanced;
 pub mod list_mutations;
 pub mod list_queries;
 pub mod math;
+pub mod metadata;
 pub mod music;
 pub mod object;
 pub mod object_manipulation;
 pub mod set_operations;
 pub mod string;
 pub mod system;
+pub mod tasks;
+pub mod template;
+pub mod toml;
 pub mod type_checking;
+pub mod yaml;
 mod utils;
 pub mod scheduler;
 
@@ -88,6 +97,12 @@ pub fn get_stdlib_function_names() -> &'static [&'static str] {
         "repeat",
         "pad-left",
         "pad-right",
+        "regex-test",
+        "regex-match",
+        "regex-find-all",
+        "regex-replace",
+        "regex-split",
+        "render-template",
         // Math functions
         "absolute",
         "square-root",
@@ -155,6 +170,13 @@ pub fn get_stdlib_function_names() -> &'static [&'static str] {
         "fibonacci",
         // System functions
         "env",
+        "exit",
+        "import",
+        // Introspection functions
+        "help",
+        "functions-list",
+        "function-arity",
+        "describe",
         // Type checking functions
         "is-number",
         "is-string",
@@ -167,6 +189,18 @@ pub fn get_stdlib_function_names() -> &'static [&'static str] {
         // JSON functions
         "to-json",
         "from-json",
+        // CSV functions
+        "csv-parse",
+        "csv-stringify",
+        "csv-read-file",
+        "csv-write-file",
+        // YAML, TOML and INI functions
+        "from-yaml",
+        "to-yaml",
+        "from-toml",
+        "to-toml",
+        "from-ini",
+        "to-ini",
         // Encoding functions
         "base64-encode",
         "base64-decode",
@@ -199,6 +233,20 @@ pub fn get_stdlib_function_names() -> &'static [&'static str] {
         "tempo",
         "__scheduler-debug",
         "__scheduler-tick",
+        // Task functions
+        "spawn",
+        "await",
+        "await-all",
+        "parallel-map",
+        "task-limit",
+        // Channel functions
+        "channel",
+        "send",
+        "receive",
+        "receive-timeout",
+        "close",
+        "is-closed",
+        "select",
     ]
 }
 
@@ -269,6 +317,12 @@ pub fn get_stdlib_function(name: &str) -> Option<StdlibFunction> {
         "repeat" => Some(string::eval_repeat),
         "pad-left" => Some(string::eval_pad_left),
         "pad-right" => Some(string::eval_pad_right),
+        "regex-test" => Some(string::eval_regex_test),
+        "regex-match" => Some(string::eval_regex_match),
+        "regex-find-all" => Some(string::eval_regex_find_all),
+        "regex-replace" => Some(string::eval_regex_replace),
+        "regex-split" => Some(string::eval_regex_split),
+        "render-template" => Some(template::eval_render_template),
 
         // Math functions
         "absolute" => Some(math::eval_absolute),
@@ -344,6 +398,13 @@ pub fn get_stdlib_function(name: &str) -> Option<StdlibFunction> {
 
         // System functions
         "env" => Some(system::eval_env),
+        "exit" => Some(system::eval_exit),
+
+        // Introspection functions
+        "help" => Some(introspection::eval_help),
+        "functions-list" => Some(introspection::eval_functions_list),
+        "function-arity" => Some(introspection::eval_function_arity),
+        "describe" => Some(introspection::eval_describe),
 
         // Type checking functions
         "is-number" => Some(type_checking::eval_is_number),
@@ -359,6 +420,20 @@ pub fn get_stdlib_function(name: &str) -> Option<StdlibFunction> {
         "to-json" => Some(json::eval_to_json),
         "from-json" => Some(json::eval_from_json),
 
+        // CSV functions
+        "csv-parse" => Some(csv::eval_csv_parse),
+        "csv-stringify" => Some(csv::eval_csv_stringify),
+        "csv-read-file" => Some(csv::eval_csv_read_file),
+        "csv-write-file" => Some(csv::eval_csv_write_file),
+
+        // YAML, TOML and INI functions
+        "from-yaml" => Some(yaml::eval_from_yaml),
+        "to-yaml" => Some(yaml::eval_to_yaml),
+        "from-toml" => Some(toml::eval_from_toml),
+        "to-toml" => Some(toml::eval_to_toml),
+        "from-ini" => Some(ini::eval_from_ini),
+        "to-ini" => Some(ini::eval_to_ini),
+
         // Encoding functions
         "base64-encode" => Some(encoding::eval_base64_encode),
         "base64-decode" => Some(encoding::eval_base64_decode),
@@ -397,6 +472,22 @@ pub fn get_stdlib_function(name: &str) -> Option<StdlibFunction> {
         "__scheduler-debug" => Some(scheduler::eval_scheduler_debug),
         "__scheduler-tick" => Some(scheduler::eval_scheduler_tick),
 
+        // Task functions
+        "spawn" => Some(tasks::eval_spawn),
+        "await" => Some(tasks::eval_await),
+        "await-all" => Some(tasks::eval_await_all),
+        "parallel-map" => Some(tasks::eval_parallel_map),
+        "task-limit" => Some(tasks::eval_task_limit),
+
+        // Channel functions
+        "channel" => Some(channels::eval_channel),
+        "send" => Some(channels::eval_send),
+        "receive" => Some(channels::eval_receive),
+        "receive-timeout" => Some(channels::eval_receive_timeout),
+        "close" => Some(channels::eval_close),
+        "is-closed" => Some(channels::eval_is_closed),
+        "select" => Some(channels::eval_select),
+
         _ => None,
     }
 }
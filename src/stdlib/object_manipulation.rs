@@ -1,7 +1,6 @@
 use crate::ast::Expression;
 use crate::evaluator::Evaluator;
-use crate::value::Value;
-use std::collections::HashMap;
+use crate::value::{ObjectMap, Value};
 
 /// Merges two objects, with the second object overwriting fields from the first
 pub fn eval_merge(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
@@ -49,7 +48,7 @@ pub fn eval_pick(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Val
         _ => return Err("pick second argument must be a list".to_string()),
     };
 
-    let mut result = HashMap::new();
+    let mut result = ObjectMap::new();
     for field_val in fields {
         match field_val {
             Value::String(field_name) => {
@@ -93,7 +92,7 @@ pub fn eval_omit(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Val
         }
     }
 
-    let mut result = HashMap::new();
+    let mut result = ObjectMap::new();
     for (key, value) in obj {
         if !omit_set.contains(&key) {
             result.insert(key, value);
@@ -178,7 +177,7 @@ fn set_nested_value(obj: &mut Value, path: &[&str], value: Value) -> Result<(),
             Value::Object(map) => {
                 let key = path[0].to_string();
                 if !map.contains_key(&key) {
-                    map.insert(key.clone(), Value::Object(HashMap::new()));
+                    map.insert(key.clone(), Value::Object(ObjectMap::new()));
                 }
                 if let Some(nested) = map.get_mut(&key) {
                     set_nested_value(nested, &path[1..], value)
@@ -209,8 +208,9 @@ fn deep_merge_values(val1: Value, val2: Value) -> Result<Value, String> {
     match (val1, val2) {
         (Value::Object(mut obj1), Value::Object(obj2)) => {
             for (key, val2) in obj2 {
-                if let Some(val1) = obj1.remove(&key) {
-                    obj1.insert(key, deep_merge_values(val1, val2)?);
+                if let Some(val1) = obj1.get_mut(&key) {
+                    let merged = deep_merge_values(std::mem::replace(val1, Value::Null), val2)?;
+                    *val1 = merged;
                 } else {
                     obj1.insert(key, val2);
                 }
@@ -11,13 +11,9 @@ pub fn eval_play(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Val
     let pattern = evaluator.eval_expression(args[0].clone())?;
     match pattern {
         Value::Pattern(ref pattern_value) => {
-            let engine = evaluator.ensure_music_engine();
-            let pattern_name = format!("pattern_{}", engine.get_pattern_names().len());
-            
-            engine.add_pattern_value(pattern_name, pattern_value)?;
-            if !engine.is_playing() {
-                engine.start()?;
-            }
+            let audio_check = evaluator.capabilities().check_audio("play");
+            evaluator.permit(audio_check)?;
+            evaluator.ensure_music_engine().play_pattern_value(pattern_value)?;
             Ok(Value::String("Pattern added to scheduler".to_string()))
         }
         _ => Err("play argument must be a pattern".to_string()),
@@ -48,6 +44,8 @@ pub fn eval_tempo(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Va
             if cpm <= 0.0 {
                 return Err("tempo must be positive".to_string());
             }
+            let audio_check = evaluator.capabilities().check_audio("tempo");
+            evaluator.permit(audio_check)?;
             let engine = evaluator.ensure_music_engine();
             engine.set_tempo(cpm);
             Ok(Value::String(format!("Tempo set to {} CPM", cpm)))
@@ -147,5 +147,8 @@ fn value_to_hash_key(value: &Value) -> Result<String, String> {
             Err("Set operations cannot be performed on lists containing objects".to_string())
         }
         Value::Pattern(pattern) => Ok(format!("p:{}", pattern.notation())),
+        Value::Task(task) => Ok(format!("t:{}", task.id())),
+        Value::Channel(channel) => Ok(format!("c:{}", channel.id())),
+        Value::Handle(handle) => Ok(format!("h:{}", handle.id())),
     }
 }
@@ -2,6 +2,15 @@ use super::utils::*;
 use crate::ast::Expression;
 use crate::evaluator::Evaluator;
 use crate::value::Value;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// Compiled patterns, shared by every evaluator. Scripts usually use a
+/// handful of patterns; the cache starts over if one builds patterns
+/// from data.
+static REGEX_CACHE: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+const REGEX_CACHE_LIMIT: usize = 256;
 
 pub fn eval_split(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
     let (string, delimiter) = extract_string_string_args(&args, evaluator, "split")?;
@@ -166,3 +175,149 @@ pub fn eval_pad_right(args: Vec<Expression>, evaluator: &mut Evaluator) -> Resul
         Ok(Value::String(format!("{}{}", string, padding)))
     }
 }
+
+/// Checks if a regular expression matches anywhere in a string
+pub fn eval_regex_test(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    let (string, regex) = extract_regex_args(&args, evaluator, "regex-test")?;
+    Ok(Value::Boolean(regex.is_match(&string)))
+}
+
+/// The first match of a regular expression in a string, or null
+pub fn eval_regex_match(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    let (string, regex) = extract_regex_args(&args, evaluator, "regex-match")?;
+    Ok(regex
+        .captures(&string)
+        .map(|captures| match_value(&regex, &captures))
+        .unwrap_or(Value::Null))
+}
+
+/// Every match of a regular expression in a string
+pub fn eval_regex_find_all(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    let (string, regex) = extract_regex_args(&args, evaluator, "regex-find-all")?;
+    Ok(Value::List(
+        regex
+            .captures_iter(&string)
+            .map(|captures| match_value(&regex, &captures))
+            .collect(),
+    ))
+}
+
+/// Replaces every match of a regular expression, with a replacement string
+/// that can refer to groups as `$1` or `${name}`, or with what a function
+/// returns for each match
+pub fn eval_regex_replace(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.len() != 3 {
+        return Err("regex-replace requires 3 arguments: string, pattern, replacement".to_string());
+    }
+    let (string, regex) = extract_regex_args(&args[..2], evaluator, "regex-replace")?;
+
+    if !is_function_argument(&args[2]) {
+        let replacement = extract_string_value(&args[2], evaluator)
+            .map_err(|_| "regex-replace replacement must be a string or a function".to_string())?;
+        return Ok(Value::String(
+            regex
+                .replace_all(&string, replacement.as_str())
+                .into_owned(),
+        ));
+    }
+
+    let mut result = String::new();
+    let mut last = 0;
+    for captures in regex.captures_iter(&string) {
+        let whole = captures.get(0).expect("a match always has group 0");
+        let value = match_value(&regex, &captures);
+        let replacement =
+            super::list::eval_function_expression_on_item(&args[2], &value, evaluator)?;
+        result.push_str(&string[last..whole.start()]);
+        result.push_str(&replacement.to_string());
+        last = whole.end();
+    }
+    result.push_str(&string[last..]);
+    Ok(Value::String(result))
+}
+
+/// Splits a string everywhere a regular expression matches
+pub fn eval_regex_split(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    let (string, regex) = extract_regex_args(&args, evaluator, "regex-split")?;
+    Ok(Value::List(
+        regex
+            .split(&string)
+            .map(|part| Value::String(part.to_string()))
+            .collect(),
+    ))
+}
+
+/// The string and compiled pattern every regex function starts with
+fn extract_regex_args(
+    args: &[Expression],
+    evaluator: &mut Evaluator,
+    function_name: &str,
+) -> Result<(String, Regex), String> {
+    let (string, pattern) = extract_string_string_args(args, evaluator, function_name)?;
+    let regex = compile_regex(&pattern).map_err(|e| {
+        // Syntax errors draw a caret under the pattern; the last line says what's wrong
+        let message = e.to_string();
+        let reason = message.lines().last().unwrap_or_default();
+        format!(
+            "{}: invalid pattern {:?}: {}",
+            function_name,
+            pattern,
+            reason.trim_start_matches("error: ")
+        )
+    })?;
+    Ok((string, regex))
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let cache = REGEX_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(regex) = cache.get(pattern) {
+        return Ok(regex.clone());
+    }
+
+    let regex = Regex::new(pattern)?;
+    if cache.len() >= REGEX_CACHE_LIMIT {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+/// What a match gives a script: the matched text for a pattern without
+/// groups, an object of the groups if any are named (unnamed ones keyed by
+/// their number), and otherwise a list of the groups. Groups that didn't
+/// take part in the match are null.
+fn match_value(regex: &Regex, captures: &Captures) -> Value {
+    let text = |group: Option<regex::Match>| {
+        group.map_or(Value::Null, |group| {
+            Value::String(group.as_str().to_string())
+        })
+    };
+
+    if regex.captures_len() == 1 {
+        return text(captures.get(0));
+    }
+    if regex.capture_names().flatten().next().is_none() {
+        Value::List((1..captures.len()).map(|i| text(captures.get(i))).collect())
+    } else {
+        Value::Object(
+            regex
+                .capture_names()
+                .enumerate()
+                .skip(1)
+                .map(|(i, name)| {
+                    let key = name.map_or_else(|| i.to_string(), str::to_string);
+                    (key, text(captures.get(i)))
+                })
+                .collect(),
+        )
+    }
+}
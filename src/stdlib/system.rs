@@ -13,9 +13,35 @@ use crate::value::Value;
 /// ```
 pub fn eval_env(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
     let var_name = extract_string_arg(&args, evaluator, "env")?;
+    let env_check = evaluator.capabilities().check_env("env", &var_name);
+    evaluator.permit(env_check)?;
 
     match std::env::var(&var_name) {
         Ok(value) => Ok(Value::String(value)),
         Err(_) => Ok(Value::Null),
     }
 }
+
+/// Stop the script, exiting with a status code: 0 if none is given
+///
+/// # Examples
+/// ```tilde
+/// if (length ~args) == 0 (
+///     say "usage: greet.tde <name>"
+///     exit 2
+/// )
+/// ```
+pub fn eval_exit(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    let code = match args.len() {
+        0 => 0.0,
+        1 => extract_number_arg(&args, evaluator, "exit")?,
+        _ => return Err("exit takes at most 1 argument (status code)".to_string()),
+    };
+    if code.fract() != 0.0 || !(0.0..=255.0).contains(&code) {
+        return Err(format!(
+            "exit status code must be a whole number from 0 to 255, got {}",
+            code
+        ));
+    }
+    evaluator.exit(code as i32)
+}
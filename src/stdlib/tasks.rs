@@ -0,0 +1,255 @@
+use crate::ast::Expression;
+use crate::concurrency::TaskHandle;
+use crate::evaluator::Evaluator;
+use crate::value::{ErrorValue, ObjectMap, Value};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Variable name the current item is bound to when parallel-map calls a named function
+const ITEM_BINDING: &str = "__parallel_item";
+
+/// Propagate a task failure as a structured error that attempt/rescue can bind
+fn raise_task_error(evaluator: &mut Evaluator, error: ErrorValue) -> Result<Value, String> {
+    let message = error.message.clone();
+    evaluator.set_last_error(Value::Error(error));
+    Err(message)
+}
+
+/// When a task hit an uncatchable limit, the limit's error rather than any
+/// other failure is what stops the spawner
+fn fatal_limit_error<'a>(
+    evaluator: &Evaluator,
+    mut errors: impl Iterator<Item = &'a ErrorValue>,
+) -> Option<ErrorValue> {
+    if !evaluator.limit_is_fatal() {
+        return None;
+    }
+    errors
+        .find(|error| error.code.as_deref() == Some("limit_exceeded"))
+        .cloned()
+}
+
+fn extract_task(value: Value, function_name: &str) -> Result<TaskHandle, String> {
+    match value {
+        Value::Task(task) => Ok(task),
+        _ => Err(format!("{} argument must be a task", function_name)),
+    }
+}
+
+/// Start evaluating an expression on the worker pool
+///
+/// The task sees a copy of the caller's variables and functions at the time of
+/// the spawn. Changes made inside the task are not visible to the caller.
+///
+/// # Examples
+/// ```tilde
+/// ~task is spawn (*fetch-user ~id)
+/// ~user is await ~task
+/// ```
+pub fn eval_spawn(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("spawn requires exactly 1 argument (expression)".to_string());
+    }
+
+    let pool = evaluator.shared_task_pool();
+    let context = Arc::new(evaluator.task_context());
+    let expression = args.into_iter().next().unwrap();
+    Ok(Value::Task(pool.spawn(context, Vec::new(), expression)))
+}
+
+/// Wait for a task to finish and return its value
+///
+/// If the task failed, its error is raised here and can be caught with
+/// attempt/rescue. The error has code `task_failed` unless the task produced
+/// a more specific structured error (for example from an HTTP call).
+pub fn eval_await(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("await requires exactly 1 argument (task)".to_string());
+    }
+
+    let value = evaluator.eval_expression(args[0].clone())?;
+    let task = extract_task(value, "await")?;
+    match evaluator.wait_for_task(&task) {
+        Ok(value) => Ok(value),
+        Err(error) => raise_task_error(evaluator, error),
+    }
+}
+
+/// Wait for every task in a list and return their results in order
+///
+/// Failed tasks do not stop the others; their slot in the result list holds
+/// the structured error instead of a value.
+pub fn eval_await_all(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("await-all requires exactly 1 argument (list of tasks)".to_string());
+    }
+
+    let value = evaluator.eval_expression(args[0].clone())?;
+    let tasks = match value {
+        Value::List(items) => items
+            .into_iter()
+            .map(|item| extract_task(item, "await-all"))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err("await-all argument must be a list of tasks".to_string()),
+    };
+
+    let results: Vec<Value> = tasks
+        .iter()
+        .map(|task| match evaluator.wait_for_task(task) {
+            Ok(value) => value,
+            Err(error) => Value::Error(error),
+        })
+        .collect();
+
+    let errors = results.iter().filter_map(|result| match result {
+        Value::Error(error) => Some(error),
+        _ => None,
+    });
+    if let Some(error) = fatal_limit_error(evaluator, errors) {
+        return raise_task_error(evaluator, error);
+    }
+    Ok(Value::List(results))
+}
+
+/// Apply a function to every item of a list in parallel
+///
+/// Accepts the same function forms as map: an anonymous function, a user
+/// function name or a stdlib function name. An optional options object
+/// `{limit: n}` caps how many items are processed at once. Results keep the
+/// order of the input list; the first failure is raised after all items finish.
+///
+/// # Examples
+/// ```tilde
+/// ~users is parallel-map ~ids fetch-user {limit: 4}
+/// ~squares is parallel-map [1, 2, 3] |~n (~n * ~n)|
+/// ```
+pub fn eval_parallel_map(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(
+            "parallel-map requires 2 or 3 arguments (list, function, [options])".to_string(),
+        );
+    }
+
+    let list = match evaluator.eval_expression(args[0].clone())? {
+        Value::List(items) => items,
+        _ => return Err("parallel-map first argument must be a list".to_string()),
+    };
+
+    // Resolve the function into an expression plus the name its item is bound to
+    let (param, body) = match &args[1] {
+        Expression::AnonymousFunction { params, body } => {
+            if params.len() != 1 {
+                return Err(
+                    "parallel-map anonymous function must take exactly 1 parameter".to_string(),
+                );
+            }
+            (params[0].clone(), *body.clone())
+        }
+        Expression::Variable(name) => named_function_call(name, evaluator)?,
+        Expression::FunctionCall { name, args } if args.is_empty() => {
+            named_function_call(name, evaluator)?
+        }
+        _ => {
+            return Err(
+                "parallel-map second argument must be a function name or anonymous function"
+                    .to_string(),
+            );
+        }
+    };
+
+    let limit = if args.len() == 3 {
+        match evaluator.eval_expression(args[2].clone())? {
+            Value::Object(options) => parse_limit_option(&options)?,
+            _ => return Err("parallel-map options must be an object".to_string()),
+        }
+    } else {
+        None
+    };
+
+    let pool = evaluator.shared_task_pool();
+    let context = Arc::new(evaluator.task_context());
+    let mut items = list.into_iter();
+    let mut spawn_next = |running: &mut VecDeque<TaskHandle>| match items.next() {
+        Some(item) => {
+            let bindings = vec![(param.clone(), item)];
+            running.push_back(pool.spawn(context.clone(), bindings, body.clone()));
+            true
+        }
+        None => false,
+    };
+
+    // A per-call limit holds items back until earlier ones finish, on the
+    // same pool as every other task
+    let mut running = VecDeque::new();
+    while running.len() < limit.unwrap_or(usize::MAX) && spawn_next(&mut running) {}
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(task) = running.pop_front() {
+        match evaluator.wait_for_task(&task) {
+            Ok(value) => results.push(value),
+            Err(error) => errors.push(error),
+        }
+        spawn_next(&mut running);
+    }
+
+    let first_error = fatal_limit_error(evaluator, errors.iter()).or(errors.into_iter().next());
+    match first_error {
+        Some(error) => raise_task_error(evaluator, error),
+        None => Ok(Value::List(results)),
+    }
+}
+
+/// Build a call to a named user or stdlib function on the bound item
+fn named_function_call(
+    name: &str,
+    evaluator: &Evaluator,
+) -> Result<(String, Expression), String> {
+    let name = name.strip_prefix('.').unwrap_or(name).to_string();
+    if !name.contains(':')
+        && !evaluator.functions.contains_key(&name)
+        && !evaluator.has_native_function(&name)
+        && crate::stdlib::get_stdlib_function(&name).is_none()
+    {
+        return Err(format!("Unknown function: {}", name));
+    }
+    Ok((
+        ITEM_BINDING.to_string(),
+        Expression::FunctionCall {
+            name,
+            args: vec![Expression::Variable(ITEM_BINDING.to_string())],
+        },
+    ))
+}
+
+fn parse_limit_option(options: &ObjectMap) -> Result<Option<usize>, String> {
+    match options.get("limit") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) if *n >= 1.0 => Ok(Some(*n as usize)),
+        Some(_) => Err("parallel-map limit must be a number of at least 1".to_string()),
+    }
+}
+
+/// Get or set how many spawned tasks may run at the same time
+///
+/// # Examples
+/// ```tilde
+/// task-limit 8        # At most 8 tasks run concurrently
+/// ~current is task-limit
+/// ```
+pub fn eval_task_limit(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    match args.len() {
+        0 => Ok(Value::Number(evaluator.task_limit() as f64)),
+        1 => match evaluator.eval_expression(args[0].clone())? {
+            Value::Number(n) if n >= 1.0 => {
+                evaluator.set_task_limit(n as usize);
+                Ok(Value::Number(evaluator.task_limit() as f64))
+            }
+            _ => Err("task-limit must be a number of at least 1".to_string()),
+        },
+        _ => Err("task-limit takes at most 1 argument (limit)".to_string()),
+    }
+}
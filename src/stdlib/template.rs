@@ -0,0 +1,527 @@
+//! `render-template`: mustache-style templates for generating files
+//!
+//! ```text
+//! {{ name }}                       a value, HTML-escaped
+//! {{{ html }}} or {{& html }}      a value as it is
+//! {{ user.name | uppercase }}      filters are stdlib functions
+//! {{ price | pad-left 8 "0" }}     and can take arguments
+//! {{# items }} ... {{/ items }}    once per item, or once if truthy
+//! {{^ items }} ... {{/ items }}    only if falsy or empty
+//! {{> footer.html }}               another template file
+//! {{! a comment }}
+//! ```
+
+use crate::ast::Expression;
+use crate::evaluator::Evaluator;
+use crate::package::{DiskFiles, ImportFiles};
+use crate::value::{ObjectMap, Value};
+use std::path::PathBuf;
+
+/// How deeply partials may include partials, which stops a partial that
+/// includes itself
+const MAX_PARTIAL_DEPTH: usize = 32;
+
+/// Render a template with the values in an object
+///
+/// # Examples
+/// ```tilde
+/// ~file is read "page.html"
+/// ~page is render-template ~file.content {title: "Report", rows: ~rows}
+/// ~config is render-template ~text ~settings {escape: "none"}
+/// ```
+pub fn eval_render_template(
+    args: Vec<Expression>,
+    evaluator: &mut Evaluator,
+) -> Result<Value, String> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(
+            "render-template requires 2 or 3 arguments: template, data, [options]".to_string(),
+        );
+    }
+
+    let template = match evaluator.eval_expression(args[0].clone())? {
+        Value::String(template) => template,
+        _ => return Err("render-template template must be a string".to_string()),
+    };
+    let data = evaluator.eval_expression(args[1].clone())?;
+    let options = match args.get(2) {
+        Some(options) => match evaluator.eval_expression(options.clone())? {
+            Value::Object(options) => options,
+            _ => return Err("render-template options must be an object".to_string()),
+        },
+        None => ObjectMap::new(),
+    };
+
+    let mut renderer = Renderer::new(evaluator, &options)?;
+    let nodes = parse(&template).map_err(|e| renderer.error(e.line, e.message))?;
+    let mut output = String::new();
+    renderer.render(&nodes, &mut vec![data], &mut output)?;
+    Ok(Value::String(output))
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Value {
+        lookup: Lookup,
+        raw: bool,
+        line: usize,
+    },
+    Section {
+        lookup: Lookup,
+        inverted: bool,
+        body: Vec<Node>,
+        line: usize,
+    },
+    Partial {
+        path: String,
+        line: usize,
+    },
+}
+
+/// A name to look up and the filters its value goes through
+#[derive(Debug)]
+struct Lookup {
+    name: String,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug)]
+struct Filter {
+    name: String,
+    args: Vec<Expression>,
+}
+
+#[derive(Debug)]
+struct TemplateError {
+    line: usize,
+    message: String,
+}
+
+/// A `{{# name }}` or `{{^ name }}` waiting for its `{{/ name }}`
+struct OpenSection {
+    lookup: Lookup,
+    inverted: bool,
+    line: usize,
+    body: Vec<Node>,
+}
+
+fn parse(source: &str) -> Result<Vec<Node>, TemplateError> {
+    let mut nodes = Vec::new();
+    let mut open: Vec<OpenSection> = Vec::new();
+    let mut position = 0;
+    let mut line = 1;
+    let mut counted = 0;
+
+    while let Some(offset) = source[position..].find("{{") {
+        let start = position + offset;
+        line += source[counted..start].matches('\n').count();
+        counted = start;
+        let error = |message: String| TemplateError { line, message };
+
+        let triple = source[start..].starts_with("{{{");
+        let (opener, closer) = if triple { ("{{{", "}}}") } else { ("{{", "}}") };
+        let inner_start = start + opener.len();
+        let Some(length) = source[inner_start..].find(closer) else {
+            return Err(error(format!("{} is never closed with {}", opener, closer)));
+        };
+        let content = source[inner_start..inner_start + length].trim();
+        let mut end = inner_start + length + closer.len();
+
+        let sigil = if triple { None } else { content.chars().next() };
+        let name = match sigil {
+            Some('#' | '^' | '/' | '!' | '>' | '&') => content[1..].trim(),
+            _ => content,
+        };
+        if name.is_empty() && sigil != Some('!') {
+            return Err(error(format!("empty tag {}{}", opener, closer)));
+        }
+
+        // A section, comment or partial alone on its line takes the line with it
+        let mut text_end = start;
+        if matches!(sigil, Some('#' | '^' | '/' | '!' | '>')) {
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = source[end..].find('\n').map_or(source.len(), |i| end + i);
+            if line_start >= position
+                && source[line_start..start].trim().is_empty()
+                && source[end..line_end].trim().is_empty()
+            {
+                text_end = line_start;
+                end = (line_end + 1).min(source.len());
+            }
+        }
+        let text = &source[position..text_end];
+        if !text.is_empty() {
+            current(&mut nodes, &mut open).push(Node::Text(text.to_string()));
+        }
+        position = end;
+
+        match sigil {
+            Some('!') => {}
+            Some('>') => current(&mut nodes, &mut open).push(Node::Partial {
+                path: name.to_string(),
+                line,
+            }),
+            Some(sigil @ ('#' | '^')) => open.push(OpenSection {
+                lookup: parse_lookup(name).map_err(error)?,
+                inverted: sigil == '^',
+                line,
+                body: Vec::new(),
+            }),
+            Some('/') => {
+                let Some(section) = open.pop() else {
+                    return Err(error(format!("{{{{/ {} }}}} closes no section", name)));
+                };
+                if section.lookup.name != name {
+                    return Err(error(format!(
+                        "{{{{/ {} }}}} closes {{{{{} {} }}}} from line {}",
+                        name,
+                        if section.inverted { '^' } else { '#' },
+                        section.lookup.name,
+                        section.line
+                    )));
+                }
+                current(&mut nodes, &mut open).push(Node::Section {
+                    lookup: section.lookup,
+                    inverted: section.inverted,
+                    body: section.body,
+                    line: section.line,
+                });
+            }
+            sigil => current(&mut nodes, &mut open).push(Node::Value {
+                lookup: parse_lookup(name).map_err(error)?,
+                raw: triple || sigil == Some('&'),
+                line,
+            }),
+        }
+    }
+
+    if let Some(section) = open.pop() {
+        return Err(TemplateError {
+            line: section.line,
+            message: format!(
+                "{{{{{} {} }}}} is never closed",
+                if section.inverted { '^' } else { '#' },
+                section.lookup.name
+            ),
+        });
+    }
+    if position < source.len() {
+        nodes.push(Node::Text(source[position..].to_string()));
+    }
+    Ok(nodes)
+}
+
+/// The nodes of the innermost open section, or the template's
+fn current<'a>(nodes: &'a mut Vec<Node>, open: &'a mut [OpenSection]) -> &'a mut Vec<Node> {
+    match open.last_mut() {
+        Some(section) => &mut section.body,
+        None => nodes,
+    }
+}
+
+/// Parse `name | filter arg | filter`
+fn parse_lookup(content: &str) -> Result<Lookup, String> {
+    let mut parts = split_outside_quotes(content, |c| c == '|').into_iter();
+    let name = parts.next().unwrap_or_default();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!(
+            "expected a name before the filters in '{}'",
+            content
+        ));
+    }
+
+    let mut filters = Vec::new();
+    for part in parts {
+        let mut words = split_outside_quotes(&part, char::is_whitespace)
+            .into_iter()
+            .filter(|word| !word.is_empty());
+        let Some(filter) = words.next() else {
+            return Err(format!("empty filter in '{}'", content));
+        };
+        if crate::stdlib::get_stdlib_function(&filter).is_none() {
+            return Err(format!("unknown filter '{}'", filter));
+        }
+        filters.push(Filter {
+            name: filter,
+            args: words.map(|word| filter_argument(&word)).collect(),
+        });
+    }
+    Ok(Lookup { name, filters })
+}
+
+/// A filter argument: a quoted string, number, true or false, or else the
+/// name of a function, as in `map uppercase`
+fn filter_argument(word: &str) -> Expression {
+    if let Some(quoted) = word
+        .strip_prefix('"')
+        .and_then(|word| word.strip_suffix('"'))
+        .or_else(|| {
+            word.strip_prefix('\'')
+                .and_then(|word| word.strip_suffix('\''))
+        })
+    {
+        return Expression::String(quoted.to_string());
+    }
+    match word {
+        "true" => Expression::Boolean(true),
+        "false" => Expression::Boolean(false),
+        _ => match word.parse::<f64>() {
+            Ok(number) => Expression::Number(number, false),
+            Err(_) => Expression::FunctionCall {
+                name: word.to_string(),
+                args: Vec::new(),
+            },
+        },
+    }
+}
+
+/// Split at separators that aren't inside quotes, trimming each part
+fn split_outside_quotes(text: &str, is_separator: impl Fn(char) -> bool) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quote = None;
+    for c in text.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if is_separator(c) => {
+                parts.push(part.trim().to_string());
+                part.clear();
+                continue;
+            }
+            None => {}
+        }
+        part.push(c);
+    }
+    parts.push(part.trim().to_string());
+    parts
+}
+
+#[derive(PartialEq)]
+enum Escape {
+    Html,
+    None,
+}
+
+struct Renderer<'a> {
+    evaluator: &'a mut Evaluator,
+    escape: Escape,
+    partials: PathBuf,
+    /// The partial being rendered, for error messages
+    source: Option<String>,
+    depth: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(evaluator: &'a mut Evaluator, options: &ObjectMap) -> Result<Self, String> {
+        let mut renderer = Renderer {
+            evaluator,
+            escape: Escape::Html,
+            partials: PathBuf::new(),
+            source: None,
+            depth: 0,
+        };
+        for (key, value) in options {
+            match (key.as_str(), value) {
+                ("escape", Value::String(mode)) if mode == "html" => renderer.escape = Escape::Html,
+                ("escape", Value::String(mode)) if mode == "none" => renderer.escape = Escape::None,
+                ("escape", _) => {
+                    return Err("render-template escape must be \"html\" or \"none\"".to_string());
+                }
+                ("partials", Value::String(directory)) => renderer.partials = directory.into(),
+                ("partials", _) => {
+                    return Err("render-template partials must be a directory path".to_string());
+                }
+                _ => {
+                    return Err(format!(
+                        "render-template: unknown option '{}' (expected escape or partials)",
+                        key
+                    ));
+                }
+            }
+        }
+        Ok(renderer)
+    }
+
+    fn error(&self, line: usize, message: String) -> String {
+        match &self.source {
+            Some(source) => format!("render-template: {} line {}: {}", source, line, message),
+            None => format!("render-template: line {}: {}", line, message),
+        }
+    }
+
+    /// Render nodes with `stack` as the context, innermost section last
+    fn render(
+        &mut self,
+        nodes: &[Node],
+        stack: &mut Vec<Value>,
+        output: &mut String,
+    ) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Value { lookup, raw, line } => {
+                    let text = match self.resolve(lookup, stack, *line)? {
+                        Value::Null => String::new(),
+                        Value::String(text) => text,
+                        value => value.to_string(),
+                    };
+                    if *raw || self.escape == Escape::None {
+                        output.push_str(&text);
+                    } else {
+                        output.push_str(&escape_html(&text));
+                    }
+                }
+                Node::Section {
+                    lookup,
+                    inverted: true,
+                    body,
+                    line,
+                } => {
+                    let value = self.resolve(lookup, stack, *line)?;
+                    if !self.evaluator.is_truthy(&value) {
+                        self.render(body, stack, output)?;
+                    }
+                }
+                Node::Section {
+                    lookup, body, line, ..
+                } => match self.resolve(lookup, stack, *line)? {
+                    Value::List(items) => {
+                        let count = items.len();
+                        for (index, item) in items.into_iter().enumerate() {
+                            let position = ObjectMap::from([
+                                ("@index".to_string(), Value::Number(index as f64)),
+                                ("@first".to_string(), Value::Boolean(index == 0)),
+                                ("@last".to_string(), Value::Boolean(index + 1 == count)),
+                            ]);
+                            stack.push(Value::Object(position));
+                            stack.push(item);
+                            let result = self.render(body, stack, output);
+                            stack.truncate(stack.len() - 2);
+                            result?;
+                        }
+                    }
+                    value if self.evaluator.is_truthy(&value) => {
+                        stack.push(value);
+                        let result = self.render(body, stack, output);
+                        stack.pop();
+                        result?;
+                    }
+                    _ => {}
+                },
+                Node::Partial { path, line } => self.render_partial(path, *line, stack, output)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn render_partial(
+        &mut self,
+        name: &str,
+        line: usize,
+        stack: &mut Vec<Value>,
+        output: &mut String,
+    ) -> Result<(), String> {
+        if self.depth >= MAX_PARTIAL_DEPTH {
+            return Err(self.error(
+                line,
+                format!(
+                    "partials nested more than {} deep; does {} include itself?",
+                    MAX_PARTIAL_DEPTH, name
+                ),
+            ));
+        }
+        let path = self.partials.join(name);
+        let shown = path.to_string_lossy().to_string();
+        let path_check = self
+            .evaluator
+            .capabilities()
+            .check_path("render-template", &shown);
+        self.evaluator.permit(path_check)?;
+
+        // Files built into a standalone executable shadow the filesystem
+        let source = match self.evaluator.bundle() {
+            Some(bundle) => bundle.read(&path),
+            None => DiskFiles.read(&path),
+        }
+        .map_err(|e| self.error(line, format!("partial {}: {}", name, e)))?;
+
+        let outer = self.source.replace(shown);
+        self.depth += 1;
+        let result = parse(&source)
+            .map_err(|e| self.error(e.line, e.message))
+            .and_then(|nodes| self.render(&nodes, stack, output));
+        self.depth -= 1;
+        self.source = outer;
+        result
+    }
+
+    /// Look a name up in the innermost section that has it, then run its filters
+    fn resolve(&mut self, lookup: &Lookup, stack: &[Value], line: usize) -> Result<Value, String> {
+        let mut value = find(&lookup.name, stack);
+        for filter in &lookup.filters {
+            value = self
+                .apply(filter, value)
+                .map_err(|e| self.error(line, format!("filter {}: {}", filter.name, e)))?;
+        }
+        Ok(value)
+    }
+
+    fn apply(&mut self, filter: &Filter, value: Value) -> Result<Value, String> {
+        let function = crate::stdlib::get_stdlib_function(&filter.name)
+            .ok_or_else(|| format!("unknown filter '{}'", filter.name))?;
+        let mut args = vec![Expression::Variable("value".to_string())];
+        args.extend(filter.args.iter().cloned());
+
+        self.evaluator.push_scope();
+        self.evaluator.set_local_variable("value", value);
+        let result = function(args, self.evaluator);
+        self.evaluator.pop_scope();
+        result
+    }
+}
+
+/// `.` is the innermost value; `a.b.c` finds `a` in the innermost object
+/// that has it and follows `b` and `c` from there. Missing names are null.
+fn find(name: &str, stack: &[Value]) -> Value {
+    if name == "." {
+        return stack.last().cloned().unwrap_or(Value::Null);
+    }
+    let mut path = name.split('.');
+    let first = path.next().unwrap_or_default();
+    let Some(mut value) = stack.iter().rev().find_map(|frame| match frame {
+        Value::Object(map) => map.get(first),
+        _ => None,
+    }) else {
+        return Value::Null;
+    };
+    for part in path {
+        let next = match value {
+            Value::Object(map) => map.get(part),
+            Value::List(items) => part.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        match next {
+            Some(next) => value = next,
+            None => return Value::Null,
+        }
+    }
+    value.clone()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
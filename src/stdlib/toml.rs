@@ -0,0 +1,139 @@
+use super::date::{format_date_text, parse_date_text};
+use crate::ast::Expression;
+use crate::embed::type_name_of;
+use crate::evaluator::Evaluator;
+use crate::value::{ObjectMap, Value};
+
+/// Parse TOML text into an object
+///
+/// # Examples
+/// ```tilde
+/// ~file is read "Cargo.toml"
+/// ~manifest is from-toml ~file.content
+/// say ~manifest.package.version
+/// ```
+pub fn eval_from_toml(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("from-toml requires exactly 1 argument".to_string());
+    }
+    let text = match evaluator.eval_expression(args[0].clone())? {
+        Value::String(text) => text,
+        _ => return Err("from-toml argument must be a string".to_string()),
+    };
+
+    let table = text.parse::<::toml::Table>().map_err(|e| {
+        let message = e.message().trim_end();
+        match e.span() {
+            Some(span) => {
+                let (line, column) = line_and_column(&text, span.start);
+                format!("from-toml: line {}, column {}: {}", line, column, message)
+            }
+            None => format!("from-toml: {}", message),
+        }
+    })?;
+    Ok(Value::Object(from_toml_table(table)))
+}
+
+/// Write an object as TOML, with nested objects as [tables]
+///
+/// # Examples
+/// ```tilde
+/// ~manifest.package.version is "0.7.0"
+/// write "Cargo.toml" (to-toml ~manifest)
+/// ```
+pub fn eval_to_toml(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("to-toml requires exactly 1 argument".to_string());
+    }
+    let table = match evaluator.eval_expression(args[0].clone())? {
+        Value::Object(map) => to_toml_table(&map, "")?,
+        _ => return Err("to-toml argument must be an object".to_string()),
+    };
+
+    ::toml::to_string(&table)
+        .map(Value::String)
+        .map_err(|e| format!("to-toml: {}", e))
+}
+
+/// The 1-based line and column of a byte offset into `text`
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = text.get(..offset).unwrap_or(text);
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+fn from_toml_table(table: ::toml::Table) -> ObjectMap {
+    table
+        .into_iter()
+        .map(|(key, value)| (key, from_toml_value(value)))
+        .collect()
+}
+
+fn from_toml_value(value: ::toml::Value) -> Value {
+    match value {
+        ::toml::Value::String(text) => Value::String(text),
+        ::toml::Value::Integer(n) => Value::Number(n as f64),
+        ::toml::Value::Float(n) => Value::Number(n),
+        ::toml::Value::Boolean(b) => Value::Boolean(b),
+        ::toml::Value::Datetime(datetime) => {
+            // Local dates and times have no zone, so they're taken as UTC; a
+            // time of day on its own stays text
+            let text = datetime.to_string();
+            parse_date_text(&text)
+                .or_else(|| parse_date_text(&format!("{}Z", text)))
+                .map_or(Value::String(text), Value::Date)
+        }
+        ::toml::Value::Array(items) => {
+            Value::List(items.into_iter().map(from_toml_value).collect())
+        }
+        ::toml::Value::Table(table) => Value::Object(from_toml_table(table)),
+    }
+}
+
+fn to_toml_table(map: &ObjectMap, path: &str) -> Result<::toml::Table, String> {
+    map.iter()
+        .map(|(key, value)| {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            Ok((key.clone(), to_toml_value(value, &path)?))
+        })
+        .collect()
+}
+
+/// Convert a value, naming it by its `path` in errors
+fn to_toml_value(value: &Value, path: &str) -> Result<::toml::Value, String> {
+    Ok(match value {
+        Value::String(text) => ::toml::Value::String(text.clone()),
+        // Whole numbers that fit exactly in an f64 are written as integers
+        Value::Number(n) if n.fract() == 0.0 && n.abs() <= 9_007_199_254_740_992.0 => {
+            ::toml::Value::Integer(*n as i64)
+        }
+        Value::Number(n) => ::toml::Value::Float(*n),
+        Value::Boolean(b) => ::toml::Value::Boolean(*b),
+        Value::Date(date) => ::toml::Value::Datetime(
+            format_date_text(date)
+                .parse()
+                .map_err(|e| format!("to-toml: {}: {}", path, e))?,
+        ),
+        Value::List(items) => ::toml::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| to_toml_value(item, &format!("{}.{}", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => ::toml::Value::Table(to_toml_table(map, path)?),
+        Value::Null => return Err(format!("to-toml: '{}' is null, and TOML has no null", path)),
+        other => {
+            return Err(format!(
+                "to-toml: can't write '{}' ({}) as TOML",
+                path,
+                type_name_of(other)
+            ));
+        }
+    })
+}
@@ -202,3 +202,23 @@ pub fn extract_string_number_args(
 
     Ok((string, number))
 }
+
+/// Whether an argument names a function to call rather than a value: an
+/// anonymous function, or a bare function name such as `uppercase`
+pub fn is_function_argument(expr: &Expression) -> bool {
+    match expr {
+        Expression::AnonymousFunction { .. } => true,
+        Expression::FunctionCall { args, .. } => args.is_empty(),
+        _ => false,
+    }
+}
+
+/// A number as text that reads back exactly: whole numbers without a
+/// decimal point, even when they're too big for an integer
+pub fn number_text(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
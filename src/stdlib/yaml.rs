@@ -0,0 +1,1197 @@
+use super::date::{format_date_text, parse_date_text};
+use super::utils::number_text;
+use crate::ast::Expression;
+use crate::embed::type_name_of;
+use crate::evaluator::Evaluator;
+use crate::value::{ObjectMap, Value};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// How deeply lists and objects can nest before parsing gives up
+const MAX_DEPTH: usize = 256;
+
+/// How many values aliases can copy in all, so a few nested anchors can't
+/// expand into gigabytes
+const MAX_ALIASED_VALUES: usize = 1_000_000;
+
+/// The standard tags; anything else, like `!Ref`, is rejected
+const TAGS: &[&str] = &[
+    "!!str",
+    "!!int",
+    "!!float",
+    "!!bool",
+    "!!null",
+    "!!timestamp",
+    "!!map",
+    "!!seq",
+];
+
+/// Parse a YAML document into a value
+///
+/// # Examples
+/// ```tilde
+/// ~file is read "docker-compose.yml"
+/// ~compose is from-yaml ~file.content
+/// say ~compose.services.web.image
+/// ```
+pub fn eval_from_yaml(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("from-yaml requires exactly 1 argument".to_string());
+    }
+    let text = match evaluator.eval_expression(args[0].clone())? {
+        Value::String(text) => text,
+        _ => return Err("from-yaml argument must be a string".to_string()),
+    };
+
+    YamlParser::new(&text)
+        .parse_document()
+        .map_err(|e| format!("from-yaml: {}", e))
+}
+
+/// Write a value as block-style YAML
+///
+/// # Examples
+/// ```tilde
+/// write "docker-compose.yml" (to-yaml ~compose)
+/// ```
+pub fn eval_to_yaml(args: Vec<Expression>, evaluator: &mut Evaluator) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("to-yaml requires exactly 1 argument".to_string());
+    }
+    let value = evaluator.eval_expression(args[0].clone())?;
+
+    let mut output = String::new();
+    match &value {
+        Value::Object(map) if !map.is_empty() => write_mapping(&mut output, map, 0, false),
+        Value::List(items) if !items.is_empty() => write_sequence(&mut output, items, 0, false),
+        scalar => scalar_text(scalar).map(|text| {
+            output.push_str(&text);
+            output.push('\n');
+        }),
+    }
+    .map_err(|e| format!("to-yaml: {}", e))?;
+    Ok(Value::String(output))
+}
+
+/// A line and column, both counting from 1
+type Position = (usize, usize);
+
+fn error_at(position: Position, message: impl std::fmt::Display) -> String {
+    format!("line {}, column {}: {}", position.0, position.1, message)
+}
+
+struct YamlParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    anchors: HashMap<String, Value>,
+    depth: usize,
+    aliased: usize,
+}
+
+impl YamlParser {
+    fn new(text: &str) -> Self {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        YamlParser {
+            chars: text.replace("\r\n", "\n").chars().collect(),
+            pos: 0,
+            line: 1,
+            column: 1,
+            anchors: HashMap::new(),
+            depth: 0,
+            aliased: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn position(&self) -> Position {
+        (self.line, self.column)
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        error_at(self.position(), message)
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error(format!("values are nested more than {} deep", MAX_DEPTH)));
+        }
+        Ok(())
+    }
+
+    /// Whether the character at `offset` ends a token
+    fn is_break_at(&self, offset: usize) -> bool {
+        matches!(self.peek_at(offset), None | Some(' ' | '\t' | '\n'))
+    }
+
+    fn at_line_end(&self) -> bool {
+        matches!(self.peek(), None | Some('\n'))
+    }
+
+    fn at_sequence_entry(&self) -> bool {
+        self.peek() == Some('-') && self.is_break_at(1)
+    }
+
+    fn at_mapping_colon(&self) -> bool {
+        self.peek() == Some(':') && self.is_break_at(1)
+    }
+
+    /// `---` or `...` at the start of a line
+    fn at_document_marker(&self) -> bool {
+        self.column == 1 && (self.at("---") || self.at("...")) && self.is_break_at(3)
+    }
+
+    fn at(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.advance();
+        }
+    }
+
+    /// Skip spaces and a comment, stopping at the end of the line
+    fn skip_to_line_end(&mut self) {
+        self.skip_spaces();
+        if self.peek() == Some('#') {
+            while !self.at_line_end() {
+                self.advance();
+            }
+        }
+    }
+
+    /// Require nothing but a comment after a value on its line
+    fn expect_line_end(&mut self) -> Result<(), String> {
+        self.skip_to_line_end();
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(self.error(format!("unexpected '{}' after the value", c))),
+        }
+    }
+
+    /// Move to the next content, returning its indentation, or `None` at the
+    /// end of the document
+    fn next_content(&mut self) -> Result<Option<usize>, String> {
+        loop {
+            if self.column == 1 {
+                while self.peek() == Some(' ') {
+                    self.advance();
+                }
+                if self.peek() == Some('\t') {
+                    self.skip_spaces();
+                    if !matches!(self.peek(), None | Some('\n' | '#')) {
+                        return Err(self.error("tabs can't be used to indent"));
+                    }
+                }
+            }
+            self.skip_to_line_end();
+            match self.peek() {
+                None => return Ok(None),
+                Some('\n') => {
+                    self.advance();
+                }
+                Some(_) if self.at_document_marker() => return Ok(None),
+                Some(_) => return Ok(Some(self.column - 1)),
+            }
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Value, String> {
+        // Directives such as %YAML 1.2 come before the document
+        let mut content = self.next_content()?;
+        while content == Some(0) && self.peek() == Some('%') {
+            while !self.at_line_end() {
+                self.advance();
+            }
+            content = self.next_content()?;
+        }
+
+        let value = if content.is_none() && self.at("---") {
+            for _ in 0..3 {
+                self.advance();
+            }
+            self.parse_block_value(-1, false)?
+        } else if content.is_some() {
+            self.parse_block_value(-1, false)?
+        } else {
+            Value::Null
+        };
+
+        loop {
+            if self.next_content()?.is_some() {
+                return Err(self.error("expected the end of the document"));
+            }
+            if !self.at("...") {
+                break;
+            }
+            for _ in 0..3 {
+                self.advance();
+            }
+        }
+        if self.peek().is_some() {
+            return Err(self.error("only one document is supported; split the text on ---"));
+        }
+        Ok(value)
+    }
+
+    /// Parse the value after `key:`, `-` or `---`, which is either on the
+    /// same line or indented on the lines below
+    fn parse_block_value(
+        &mut self,
+        parent_indent: isize,
+        after_key: bool,
+    ) -> Result<Value, String> {
+        self.enter()?;
+        let start = self.position();
+        let (anchor, tag) = self.parse_properties()?;
+        self.skip_to_line_end();
+
+        let value = if self.at_line_end() {
+            match self.next_content()? {
+                // A list under a key may line up with the key itself
+                Some(indent)
+                    if indent as isize > parent_indent
+                        || (after_key
+                            && indent as isize == parent_indent
+                            && self.at_sequence_entry()) =>
+                {
+                    self.parse_node(parent_indent, true, tag.as_deref())?
+                }
+                _ => self.scalar(String::new(), true, tag.as_deref(), start)?,
+            }
+        } else {
+            self.parse_node(parent_indent, !after_key, tag.as_deref())?
+        };
+
+        self.depth -= 1;
+        if let Some(anchor) = anchor {
+            self.anchors.insert(anchor, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Parse a node starting at the current character. Lists and objects
+    /// written in block style can't start on the same line as a key.
+    fn parse_node(
+        &mut self,
+        parent_indent: isize,
+        block_collections: bool,
+        tag: Option<&str>,
+    ) -> Result<Value, String> {
+        let indent = self.column - 1;
+        let start = self.position();
+        match self.peek() {
+            Some('-') if self.at_sequence_entry() => {
+                if !block_collections {
+                    return Err(self.error("a list can't start on the same line as its key"));
+                }
+                let list = self.parse_block_sequence(indent)?;
+                self.collection(list, tag, start)
+            }
+            Some('[' | '{') => {
+                let collection = self.parse_flow()?;
+                self.expect_line_end()?;
+                self.collection(collection, tag, start)
+            }
+            Some('|' | '>') => {
+                let text = self.parse_block_scalar(parent_indent)?;
+                self.scalar(text, false, tag, start)
+            }
+            Some('*') => {
+                let value = self.parse_alias()?;
+                self.expect_line_end()?;
+                Ok(value)
+            }
+            Some('?') if self.is_break_at(1) => {
+                Err(self.error("explicit keys (?) aren't supported"))
+            }
+            Some('"' | '\'') => {
+                let text = self.parse_quoted()?;
+                self.skip_spaces();
+                if self.at_mapping_colon() {
+                    return self.start_block_mapping(
+                        indent,
+                        text,
+                        false,
+                        start,
+                        block_collections,
+                        tag,
+                    );
+                }
+                self.expect_line_end()?;
+                self.scalar(text, false, tag, start)
+            }
+            _ => {
+                let mut text = self.read_plain(false);
+                if self.at_mapping_colon() {
+                    if text.is_empty() {
+                        return Err(self.error("expected a key before ':'"));
+                    }
+                    return self.start_block_mapping(
+                        indent,
+                        text,
+                        true,
+                        start,
+                        block_collections,
+                        tag,
+                    );
+                }
+                if text.is_empty() {
+                    return Err(self.error(format!("unexpected '{}'", self.peek().unwrap_or(' '))));
+                }
+                self.continue_plain(&mut text, parent_indent)?;
+                self.scalar(text, true, tag, start)
+            }
+        }
+    }
+
+    fn start_block_mapping(
+        &mut self,
+        indent: usize,
+        key: String,
+        plain: bool,
+        start: Position,
+        block_collections: bool,
+        tag: Option<&str>,
+    ) -> Result<Value, String> {
+        if !block_collections {
+            return Err(error_at(
+                start,
+                "an object can't start on the same line as its key",
+            ));
+        }
+        let object = self.parse_block_mapping(indent, key, plain, start)?;
+        self.collection(object, tag, start)
+    }
+
+    /// Parse `key: value` lines at `indent`, starting at the first key's `:`
+    fn parse_block_mapping(
+        &mut self,
+        indent: usize,
+        first_key: String,
+        first_plain: bool,
+        first_start: Position,
+    ) -> Result<Value, String> {
+        let mut map = ObjectMap::new();
+        let mut keys = HashSet::new();
+        let (mut key, mut plain, mut start) = (first_key, first_plain, first_start);
+        loop {
+            self.advance();
+            let value = self.parse_block_value(indent as isize, true)?;
+            insert_entry(&mut map, &mut keys, key, plain, value, start)?;
+
+            match self.next_content()? {
+                Some(next) if next == indent => {}
+                Some(next) if next > indent => {
+                    return Err(self.error("this line is indented more than the keys above it"));
+                }
+                _ => break,
+            }
+            start = self.position();
+            (key, plain) = self.parse_key()?;
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// Read a key and stop at its `:`
+    fn parse_key(&mut self) -> Result<(String, bool), String> {
+        let (key, plain) = match self.peek() {
+            Some('"' | '\'') => (self.parse_quoted()?, false),
+            Some('-') if self.at_sequence_entry() => {
+                return Err(self.error("expected a key but found a list item"));
+            }
+            Some('?') if self.is_break_at(1) => {
+                return Err(self.error("explicit keys (?) aren't supported"));
+            }
+            Some('[' | '{' | '&' | '*' | '!' | '|' | '>') => {
+                return Err(self.error("keys must be plain or quoted text"));
+            }
+            _ => (self.read_plain(false), true),
+        };
+        self.skip_spaces();
+        if plain && key.is_empty() {
+            return Err(self.error("expected a key"));
+        }
+        if !self.at_mapping_colon() {
+            return Err(self.error(format!("expected ':' after '{}'", key)));
+        }
+        Ok((key, plain))
+    }
+
+    /// Parse `- item` lines at `indent`, starting at the first `-`
+    fn parse_block_sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+        loop {
+            self.advance();
+            items.push(self.parse_block_value(indent as isize, false)?);
+
+            match self.next_content()? {
+                Some(next) if next == indent && self.at_sequence_entry() => {}
+                Some(next) if next > indent => {
+                    return Err(
+                        self.error("this line is indented more than the list items above it")
+                    );
+                }
+                _ => break,
+            }
+        }
+        Ok(Value::List(items))
+    }
+
+    /// Read an anchor (`&name`) and tag (`!!str`), in either order
+    fn parse_properties(&mut self) -> Result<(Option<String>, Option<String>), String> {
+        let (mut anchor, mut tag) = (None, None);
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some('&') if anchor.is_none() => {
+                    self.advance();
+                    anchor = Some(self.read_name("&")?);
+                }
+                Some('!') if tag.is_none() => {
+                    let start = self.position();
+                    let name = self.read_name("!")?;
+                    if !TAGS.contains(&name.as_str()) {
+                        return Err(error_at(start, format!("tag {} isn't supported", name)));
+                    }
+                    tag = Some(name);
+                }
+                _ => return Ok((anchor, tag)),
+            }
+        }
+    }
+
+    fn read_name(&mut self, indicator: &str) -> Result<String, String> {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if matches!(c, ' ' | '\t' | '\n' | ',' | '[' | ']' | '{' | '}') {
+                break;
+            }
+            name.push(c);
+            self.advance();
+        }
+        if name.is_empty() {
+            return Err(self.error(format!("expected a name after {}", indicator)));
+        }
+        Ok(name)
+    }
+
+    fn parse_alias(&mut self) -> Result<Value, String> {
+        let start = self.position();
+        self.advance();
+        let name = self.read_name("*")?;
+        let Some(value) = self.anchors.get(&name) else {
+            return Err(error_at(start, format!("*{} refers to no anchor", name)));
+        };
+        self.aliased += count_values(value);
+        if self.aliased > MAX_ALIASED_VALUES {
+            return Err(error_at(
+                start,
+                format!("aliases copy more than {} values", MAX_ALIASED_VALUES),
+            ));
+        }
+        Ok(value.clone())
+    }
+
+    /// Read plain (unquoted) text up to the end of the line, a `: `, a
+    /// comment or, in `[ ]` and `{ }`, a flow indicator
+    fn read_plain(&mut self, flow: bool) -> String {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            let ends = match c {
+                '\n' => true,
+                ':' => {
+                    self.is_break_at(1)
+                        || (flow && matches!(self.peek_at(1), Some(',' | '[' | ']' | '{' | '}')))
+                }
+                '#' => text.ends_with([' ', '\t']),
+                ',' | '[' | ']' | '{' | '}' => flow,
+                _ => false,
+            };
+            if ends {
+                break;
+            }
+            text.push(c);
+            self.advance();
+        }
+        text.truncate(text.trim_end().len());
+        text
+    }
+
+    /// Plain text carries on over more-indented lines, which fold into
+    /// spaces
+    fn continue_plain(&mut self, text: &mut String, parent_indent: isize) -> Result<(), String> {
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some('\n') {
+                return Ok(());
+            }
+            let saved = (self.pos, self.line, self.column);
+            let mut blank_lines = 0;
+            loop {
+                self.advance();
+                self.skip_spaces();
+                if self.peek() != Some('\n') {
+                    break;
+                }
+                blank_lines += 1;
+            }
+            let continues = !matches!(self.peek(), None | Some('#'))
+                && self.column as isize - 1 > parent_indent
+                && !self.at_document_marker();
+            if !continues {
+                (self.pos, self.line, self.column) = saved;
+                return Ok(());
+            }
+
+            let line_start = self.position();
+            let line = self.read_plain(false);
+            if self.at_mapping_colon() {
+                return Err(error_at(line_start, "this key is indented under a value"));
+            }
+            if blank_lines == 0 {
+                text.push(' ');
+            } else {
+                text.push_str(&"\n".repeat(blank_lines));
+            }
+            text.push_str(&line);
+        }
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, String> {
+        let start = self.position();
+        let quote = self.advance().unwrap_or('"');
+        let mut text = String::new();
+        loop {
+            match self.advance() {
+                None => return Err(error_at(start, "quoted text is never closed")),
+                Some('\'') if quote == '\'' && self.peek() == Some('\'') => {
+                    self.advance();
+                    text.push('\'');
+                }
+                Some(c) if c == quote => return Ok(text),
+                Some('\\') if quote == '"' => {
+                    if self.peek() == Some('\n') {
+                        // An escaped line break joins the lines without a space
+                        self.advance();
+                        self.skip_spaces();
+                    } else {
+                        text.push(self.parse_escape()?);
+                    }
+                }
+                Some('\n') => self.fold_quoted_line(&mut text),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    /// Line breaks in quoted text become spaces, or newlines where there
+    /// are blank lines
+    fn fold_quoted_line(&mut self, text: &mut String) {
+        text.truncate(text.trim_end_matches([' ', '\t']).len());
+        let mut blank_lines = 0;
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some('\n') {
+                break;
+            }
+            self.advance();
+            blank_lines += 1;
+        }
+        if blank_lines == 0 {
+            text.push(' ');
+        } else {
+            text.push_str(&"\n".repeat(blank_lines));
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, String> {
+        let Some(escape) = self.advance() else {
+            return Err(self.error("quoted text is never closed"));
+        };
+        let digits = match escape {
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            _ => {
+                return match escape {
+                    '0' => Ok('\0'),
+                    'a' => Ok('\x07'),
+                    'b' => Ok('\x08'),
+                    't' | '\t' => Ok('\t'),
+                    'n' => Ok('\n'),
+                    'v' => Ok('\x0b'),
+                    'f' => Ok('\x0c'),
+                    'r' => Ok('\r'),
+                    'e' => Ok('\x1b'),
+                    ' ' | '"' | '/' | '\\' => Ok(escape),
+                    'N' => Ok('\u{85}'),
+                    '_' => Ok('\u{a0}'),
+                    'L' => Ok('\u{2028}'),
+                    'P' => Ok('\u{2029}'),
+                    _ => Err(self.error(format!("unknown escape \\{}", escape))),
+                };
+            }
+        };
+
+        let mut code = 0;
+        for _ in 0..digits {
+            let Some(digit) = self.peek().and_then(|c| c.to_digit(16)) else {
+                return Err(self.error(format!("\\{} needs {} hex digits", escape, digits)));
+            };
+            self.advance();
+            code = code * 16 + digit;
+        }
+        char::from_u32(code)
+            .ok_or_else(|| self.error(format!("\\{} escape isn't a valid character", escape)))
+    }
+
+    /// Parse `|` (literal) or `>` (folded) text on the indented lines that
+    /// follow
+    fn parse_block_scalar(&mut self, parent_indent: isize) -> Result<String, String> {
+        let literal = self.advance() == Some('|');
+        let mut keep = None;
+        let mut indent = None;
+        loop {
+            match self.peek() {
+                Some('-') if keep.is_none() => keep = Some(false),
+                Some('+') if keep.is_none() => keep = Some(true),
+                Some(c @ '1'..='9') if indent.is_none() => {
+                    let extra = c.to_digit(10).unwrap_or(1) as isize;
+                    indent = Some((parent_indent + extra).max(0) as usize);
+                }
+                _ => break,
+            }
+            self.advance();
+        }
+        self.expect_line_end()?;
+        self.advance();
+
+        let mut lines = Vec::new();
+        while self.peek().is_some() {
+            let mut spaces = 0;
+            while self.peek_at(spaces) == Some(' ') {
+                spaces += 1;
+            }
+            let blank = matches!(self.peek_at(spaces), None | Some('\n'));
+            if !blank {
+                if indent.is_none() && spaces as isize <= parent_indent {
+                    break;
+                }
+                let indent = *indent.get_or_insert(spaces);
+                if spaces < indent || (spaces == 0 && self.at_document_marker()) {
+                    break;
+                }
+            }
+
+            let skip = if blank {
+                spaces
+            } else {
+                indent.unwrap_or(spaces)
+            };
+            for _ in 0..skip {
+                self.advance();
+            }
+            let mut line = String::new();
+            while let Some(c) = self.peek().filter(|&c| c != '\n') {
+                line.push(c);
+                self.advance();
+            }
+            self.advance();
+            lines.push(line);
+        }
+
+        let trailing = lines
+            .iter()
+            .rev()
+            .take_while(|line| line.is_empty())
+            .count();
+        let content = &lines[..lines.len() - trailing];
+        let mut text = if literal {
+            content.join("\n")
+        } else {
+            fold_lines(content)
+        };
+        match keep {
+            Some(false) => {}
+            None if !content.is_empty() => text.push('\n'),
+            None => {}
+            Some(true) => {
+                if !content.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&"\n".repeat(trailing));
+            }
+        }
+        Ok(text)
+    }
+
+    /// Parse a `[ ]` list or `{ }` object, which may span lines
+    fn parse_flow(&mut self) -> Result<Value, String> {
+        self.enter()?;
+        let start = self.position();
+        let (open, close) = match self.advance() {
+            Some('[') => ('[', ']'),
+            _ => ('{', '}'),
+        };
+        let mut items = Vec::new();
+        let mut map = ObjectMap::new();
+        let mut keys = HashSet::new();
+        loop {
+            self.skip_flow_space();
+            match self.peek() {
+                None => return Err(error_at(start, format!("{} is never closed", open))),
+                Some(c) if c == close => {
+                    self.advance();
+                    break;
+                }
+                _ => {}
+            }
+
+            if open == '[' {
+                items.push(self.parse_flow_node()?);
+            } else {
+                let key_start = self.position();
+                let (key, plain) = match self.peek() {
+                    Some('"' | '\'') => (self.parse_quoted()?, false),
+                    _ => (self.read_plain(true), true),
+                };
+                if plain && key.is_empty() {
+                    return Err(self.error(format!(
+                        "expected a key but found '{}'",
+                        self.peek().unwrap_or(' ')
+                    )));
+                }
+                self.skip_flow_space();
+                let mut value = Value::Null;
+                if self.peek() == Some(':') {
+                    self.advance();
+                    self.skip_flow_space();
+                    if self.peek() != Some(',') && self.peek() != Some(close) {
+                        value = self.parse_flow_node()?;
+                    }
+                }
+                insert_entry(&mut map, &mut keys, key, plain, value, key_start)?;
+            }
+
+            self.skip_flow_space();
+            match self.peek() {
+                Some(',') => {
+                    self.advance();
+                }
+                Some(c) if c != close => {
+                    return Err(
+                        self.error(format!("expected ',' or '{}' but found '{}'", close, c))
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        self.depth -= 1;
+        Ok(if open == '[' {
+            Value::List(items)
+        } else {
+            Value::Object(map)
+        })
+    }
+
+    fn parse_flow_node(&mut self) -> Result<Value, String> {
+        let start = self.position();
+        let (anchor, tag) = self.parse_properties()?;
+        self.skip_flow_space();
+        let value = match self.peek() {
+            Some('[' | '{') => {
+                let collection = self.parse_flow()?;
+                self.collection(collection, tag.as_deref(), start)?
+            }
+            Some('"' | '\'') => {
+                let text = self.parse_quoted()?;
+                self.scalar(text, false, tag.as_deref(), start)?
+            }
+            Some('*') => self.parse_alias()?,
+            _ => {
+                let text = self.read_plain(true);
+                if text.is_empty() && anchor.is_none() && tag.is_none() {
+                    return Err(match self.peek() {
+                        Some(c) => self.error(format!("expected a value but found '{}'", c)),
+                        None => self.error("expected a value"),
+                    });
+                }
+                self.scalar(text, true, tag.as_deref(), start)?
+            }
+        };
+        if let Some(anchor) = anchor {
+            self.anchors.insert(anchor, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Skip spaces, comments and line breaks between flow entries
+    fn skip_flow_space(&mut self) {
+        loop {
+            self.skip_to_line_end();
+            if self.peek() != Some('\n') {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    /// Turn scalar text into a value, following its tag if it has one
+    fn scalar(
+        &self,
+        text: String,
+        plain: bool,
+        tag: Option<&str>,
+        start: Position,
+    ) -> Result<Value, String> {
+        let Some(tag) = tag else {
+            return Ok(if plain {
+                resolve_plain(&text)
+            } else {
+                Value::String(text)
+            });
+        };
+        let expected = match tag {
+            "!!str" => return Ok(Value::String(text)),
+            "!!int" | "!!float" => "number",
+            "!!bool" => "boolean",
+            "!!null" => "null",
+            "!!timestamp" => "date",
+            _ => "",
+        };
+        let value = resolve_plain(&text);
+        if type_name_of(&value) != expected {
+            return Err(error_at(start, format!("'{}' isn't a valid {}", text, tag)));
+        }
+        Ok(value)
+    }
+
+    fn collection(
+        &self,
+        value: Value,
+        tag: Option<&str>,
+        start: Position,
+    ) -> Result<Value, String> {
+        match (tag, &value) {
+            (None, _) | (Some("!!map"), Value::Object(_)) | (Some("!!seq"), Value::List(_)) => {
+                Ok(value)
+            }
+            (Some(tag), _) => Err(error_at(
+                start,
+                format!("{} doesn't match this {}", tag, type_name_of(&value)),
+            )),
+        }
+    }
+}
+
+/// Add a key to an object. A plain `<<` key merges in the keys of another
+/// object, or a list of them, that aren't already set.
+fn insert_entry(
+    map: &mut ObjectMap,
+    keys: &mut HashSet<String>,
+    key: String,
+    plain: bool,
+    value: Value,
+    start: Position,
+) -> Result<(), String> {
+    if plain && key == "<<" {
+        let sources = match value {
+            Value::List(items) => items,
+            value => vec![value],
+        };
+        for source in sources {
+            let Value::Object(source) = source else {
+                return Err(error_at(start, "<< can only merge objects"));
+            };
+            for (key, value) in source {
+                map.entry(key).or_insert(value);
+            }
+        }
+        return Ok(());
+    }
+
+    if !keys.insert(key.clone()) {
+        return Err(error_at(start, format!("duplicate key '{}'", key)));
+    }
+    map.insert(key, value);
+    Ok(())
+}
+
+fn count_values(value: &Value) -> usize {
+    match value {
+        Value::List(items) => 1 + items.iter().map(count_values).sum::<usize>(),
+        Value::Object(map) => 1 + map.values().map(count_values).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+/// Join the lines of `>` text with spaces, keeping line breaks around blank
+/// and more-indented lines
+fn fold_lines(lines: &[String]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&str> = None;
+    let mut blank_lines = 0;
+    for line in lines {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        match previous {
+            None => text.push_str(&"\n".repeat(blank_lines)),
+            Some(previous) => {
+                let indented = previous.starts_with([' ', '\t']) || line.starts_with([' ', '\t']);
+                if blank_lines == 0 && !indented {
+                    text.push(' ');
+                } else {
+                    text.push_str(&"\n".repeat(blank_lines + usize::from(indented)));
+                }
+            }
+        }
+        text.push_str(line);
+        previous = Some(line);
+        blank_lines = 0;
+    }
+    text
+}
+
+/// What unquoted text means: null, a boolean, a number or a date under the
+/// YAML 1.2 core schema, or else the text itself
+fn resolve_plain(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Boolean(true),
+        "false" | "False" | "FALSE" => return Value::Boolean(false),
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+            return Value::Number(f64::INFINITY);
+        }
+        "-.inf" | "-.Inf" | "-.INF" => return Value::Number(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => return Value::Number(f64::NAN),
+        _ => {}
+    }
+    if let Some(number) = parse_number(text) {
+        return Value::Number(number);
+    }
+    if let Some(date) = parse_timestamp(text) {
+        return Value::Date(date);
+    }
+    Value::String(text.to_string())
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    let radix = |digits: &str, radix: u32| {
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        i64::from_str_radix(digits, radix).ok().map(|n| n as f64)
+    };
+    if let Some(digits) = text.strip_prefix("0x") {
+        return radix(digits, 16);
+    }
+    if let Some(digits) = text.strip_prefix("0o") {
+        return radix(digits, 8);
+    }
+
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let decimal = unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && unsigned.chars().any(|c| c.is_ascii_digit())
+        && unsigned
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+'));
+    if !decimal {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    // Most text isn't a date, so check the year before trying to parse
+    let bytes = text.as_bytes();
+    if bytes.len() < 8 || !bytes[..4].iter().all(u8::is_ascii_digit) || bytes[4] != b'-' {
+        return None;
+    }
+    parse_date_text(text).or_else(|| {
+        // YAML also allows a space before the time, and no zone for UTC
+        let text = text.replacen([' ', 't'], "T", 1).replace(' ', "");
+        parse_date_text(&text).or_else(|| parse_date_text(&format!("{}Z", text)))
+    })
+}
+
+fn write_mapping(
+    output: &mut String,
+    map: &ObjectMap,
+    indent: usize,
+    same_line: bool,
+) -> Result<(), String> {
+    for (i, (key, value)) in map.iter().enumerate() {
+        if i > 0 || !same_line {
+            output.push_str(&" ".repeat(indent));
+        }
+        output.push_str(&quote_if_needed(key));
+        output.push(':');
+        write_node(output, value, indent, false)?;
+    }
+    Ok(())
+}
+
+fn write_sequence(
+    output: &mut String,
+    items: &[Value],
+    indent: usize,
+    same_line: bool,
+) -> Result<(), String> {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 || !same_line {
+            output.push_str(&" ".repeat(indent));
+        }
+        output.push('-');
+        write_node(output, item, indent, true)?;
+    }
+    Ok(())
+}
+
+/// Write a value after the `key:` or `-` that introduces it. Objects in a
+/// list start on the `-` line; everything else nested goes on the lines
+/// below.
+fn write_node(
+    output: &mut String,
+    value: &Value,
+    indent: usize,
+    in_list: bool,
+) -> Result<(), String> {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            output.push(if in_list { ' ' } else { '\n' });
+            write_mapping(output, map, indent + 2, in_list)
+        }
+        Value::List(items) if !items.is_empty() => {
+            output.push(if in_list { ' ' } else { '\n' });
+            write_sequence(output, items, indent + 2, in_list)
+        }
+        Value::String(text) if is_literal_block(text) => {
+            write_literal_block(output, text, indent + 2);
+            Ok(())
+        }
+        scalar => {
+            output.push(' ');
+            output.push_str(&scalar_text(scalar)?);
+            output.push('\n');
+            Ok(())
+        }
+    }
+}
+
+/// Whether multi-line text can be written as a `|` block and read back the
+/// same
+fn is_literal_block(text: &str) -> bool {
+    let body = text.trim_end_matches('\n');
+    text.contains('\n')
+        && body
+            .split('\n')
+            .find(|line| !line.is_empty())
+            .is_some_and(|line| !line.starts_with([' ', '\t']))
+        && body
+            .split('\n')
+            .all(|line| line.is_empty() || !line.trim().is_empty())
+        && !text
+            .chars()
+            .any(|c| c.is_control() && c != '\n' && c != '\t')
+}
+
+fn write_literal_block(output: &mut String, text: &str, indent: usize) {
+    let body = text.trim_end_matches('\n');
+    let trailing = text.len() - body.len();
+    output.push_str(match trailing {
+        0 => " |-\n",
+        1 => " |\n",
+        _ => " |+\n",
+    });
+    for line in body.split('\n') {
+        if !line.is_empty() {
+            output.push_str(&" ".repeat(indent));
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+    for _ in 1..trailing {
+        output.push('\n');
+    }
+}
+
+fn scalar_text(value: &Value) -> Result<String, String> {
+    Ok(match value {
+        Value::Null => "null".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Number(n) if n.is_nan() => ".nan".to_string(),
+        Value::Number(n) if n.is_infinite() => if *n > 0.0 { ".inf" } else { "-.inf" }.to_string(),
+        Value::Number(n) => number_text(*n),
+        Value::Date(date) => format_date_text(date),
+        Value::String(text) => quote_if_needed(text),
+        Value::List(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        other => return Err(format!("can't write a {} as YAML", type_name_of(other))),
+    })
+}
+
+/// Text as written when it would read back as the same string, otherwise
+/// double-quoted
+fn quote_if_needed(text: &str) -> String {
+    let plain = !text.is_empty()
+        && matches!(resolve_plain(text), Value::String(_))
+        && !text.starts_with(|c: char| c.is_whitespace() || "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        && !text.ends_with(|c: char| c.is_whitespace() || c == ':')
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.chars().any(char::is_control)
+        // YAML 1.1 readers, still common, take these as booleans and numbers
+        && !matches!(
+            text.to_ascii_lowercase().as_str(),
+            "y" | "n" | "yes" | "no" | "on" | "off"
+        )
+        && !text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, ':' | '.' | '_'));
+    if plain {
+        return text.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -6,30 +6,15 @@ use crate::value::Value;
 /// Handles terminal manipulation commands like clear screen
 pub fn eval_clear_positional(
     args: Vec<Expression>,
-    _evaluator: &mut Evaluator,
+    evaluator: &mut Evaluator,
 ) -> Result<Value, String> {
     if !args.is_empty() {
         return Err("clear takes no arguments".to_string());
     }
 
-    // Send ANSI escape sequence to clear screen and move cursor to top-left
-    // This works on most modern terminals (Unix, Windows Terminal, etc.)
-    #[cfg(target_arch = "wasm32")]
-    {
-        // For WASM/web environments, we can't directly clear the terminal
-        // This would need to be handled by JavaScript
-        use crate::wasm::clear_console;
-        clear_console();
-    }
-
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        // ANSI escape sequence: ESC[c (clear/reset screen) + ESC[H (move cursor to home)
-        print!("\x1bc\x1b[H");
-        // Ensure the escape sequence is immediately sent to terminal
-        use std::io::{self, Write};
-        io::stdout().flush().unwrap();
-    }
+    // The output stream decides what clearing means: ANSI escapes on a
+    // terminal, emptying the buffer for in-memory output
+    evaluator.streams().output.clear()?;
 
     Ok(Value::Null)
 }
@@ -39,14 +24,20 @@ mod tests {
     use super::*;
     use crate::ast::Expression;
     use crate::evaluator::Evaluator;
+    use crate::io::{MemoryOutput, OutputStream};
+    use std::sync::Arc;
 
     #[test]
     fn test_clear_no_arguments() {
+        let output = Arc::new(MemoryOutput::new());
+        output.write_line("old output").unwrap();
         let mut evaluator = Evaluator::new();
+        evaluator.set_output(output.clone());
         let args = vec![];
 
         let result = eval_clear_positional(args, &mut evaluator).unwrap();
         assert_eq!(result, Value::Null);
+        assert_eq!(output.contents(), "");
     }
 
     #[test]
@@ -0,0 +1,465 @@
+//! The test runner behind `tilde test`, and the failure messages for `expect`.
+//!
+//! Test files end in `_test.tde` and hold `test "name" ( ... )` blocks that
+//! check results with `expect`. Each test runs in a fresh evaluator: the
+//! file's other top-level code runs first, then each `setup` block, the test
+//! itself, and each `teardown` block, which runs even if the test failed.
+//! What a test prints is captured, and only shown if it fails.
+
+use crate::ast::{BinaryOperator, Statement};
+use crate::evaluator::Evaluator;
+use crate::io::MemoryOutput;
+use crate::parser::Parser;
+use crate::value::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Test files are the `.tde` files ending in this
+pub const TEST_FILE_SUFFIX: &str = "_test.tde";
+
+/// How results are reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TestFormat {
+    /// A line per test, with details of failures
+    #[default]
+    Text,
+    /// Test Anything Protocol, version 13
+    Tap,
+    /// JUnit XML, as CI servers read it
+    Junit,
+}
+
+impl TestFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(TestFormat::Text),
+            "tap" => Some(TestFormat::Tap),
+            "junit" => Some(TestFormat::Junit),
+            _ => None,
+        }
+    }
+
+    pub fn report(&self, results: &[TestResult]) -> String {
+        match self {
+            TestFormat::Text => text_report(results),
+            TestFormat::Tap => tap_report(results),
+            TestFormat::Junit => junit_report(results),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub file: String,
+    pub name: String,
+    /// Why the test failed, or `None` if it passed
+    pub failure: Option<String>,
+    /// What the test printed
+    pub output: String,
+    pub duration: Duration,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// The test files to run for the paths given on the command line.
+/// Directories are searched recursively; files are used as they are.
+pub fn find_test_files(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path.is_dir() {
+            collect_test_files(path, &mut files)?;
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        } else {
+            return Err(format!("No such file or directory: {}", path.display()));
+        }
+    }
+    Ok(files)
+}
+
+fn collect_test_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Error reading directory '{}': {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_test_files(&path, files)?;
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(TEST_FILE_SUFFIX))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Run the tests in a script whose names contain `filter`, or all of them.
+/// Fails only if the script doesn't parse.
+pub fn run_source(
+    source: &str,
+    file: &str,
+    filter: Option<&str>,
+) -> Result<Vec<TestResult>, String> {
+    let program = Parser::new(source).with_locations().parse()?;
+
+    let mut prelude = Vec::new();
+    let mut setups = Vec::new();
+    let mut teardowns = Vec::new();
+    let mut tests = Vec::new();
+    for statement in program {
+        match unlocated(&statement) {
+            Statement::Test { name, body } => tests.push((name.clone(), body.clone())),
+            Statement::Setup { body } => setups.push(body.clone()),
+            Statement::Teardown { body } => teardowns.push(body.clone()),
+            _ => prelude.push(statement),
+        }
+    }
+
+    Ok(tests
+        .into_iter()
+        .filter(|(name, _)| filter.is_none_or(|filter| name.contains(filter)))
+        .map(|(name, body)| {
+            let output = Arc::new(MemoryOutput::new());
+            let mut evaluator = Evaluator::new();
+            evaluator.set_output(output.clone());
+
+            let start = Instant::now();
+            let mut result = run_part(&mut evaluator, &prelude);
+            for setup in &setups {
+                result = result.and_then(|_| run_part(&mut evaluator, setup));
+            }
+            result = result.and_then(|_| run_part(&mut evaluator, &body));
+            for teardown in &teardowns {
+                let teardown_result = run_part(&mut evaluator, teardown);
+                result = result.and(teardown_result);
+            }
+
+            TestResult {
+                file: file.to_string(),
+                name,
+                failure: result.err(),
+                output: output.contents(),
+                duration: start.elapsed(),
+            }
+        })
+        .collect())
+}
+
+/// Run the tests in a file
+pub fn run_file(path: &Path, filter: Option<&str>) -> Result<Vec<TestResult>, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading file '{}': {}", path.display(), e))?;
+    run_source(&source, &path.display().to_string(), filter)
+        .map_err(|e| format!("{}: parse error: {}", path.display(), e))
+}
+
+fn run_part(evaluator: &mut Evaluator, statements: &[Statement]) -> Result<(), String> {
+    evaluator
+        .eval_program(statements.to_vec())
+        .map(|_| ())
+        .map_err(|e| match evaluator.current_line() {
+            Some(line) => format!("line {}: {}", line, e),
+            None => e,
+        })
+}
+
+fn unlocated(statement: &Statement) -> &Statement {
+    match statement {
+        Statement::Located { statement, .. } => unlocated(statement),
+        statement => statement,
+    }
+}
+
+/// Whether `expect` reports both sides of this operator when it fails
+pub fn is_comparison(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::LessThan
+            | BinaryOperator::LessThanOrEqual
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::GreaterThanOrEqual
+    )
+}
+
+/// The message for a failed `expect` comparison. For `==` it also lists
+/// where lists and objects differ.
+pub fn comparison_failure(
+    source: &str,
+    op: &BinaryOperator,
+    actual: &Value,
+    expected: &Value,
+) -> String {
+    let mut message = format!("Expectation failed: expect {}", source);
+    if *op != BinaryOperator::Equal {
+        message.push_str(&format!(
+            "\n  left:  {}\n  right: {}",
+            actual.inspect(),
+            expected.inspect()
+        ));
+        return message;
+    }
+
+    message.push_str(&format!(
+        "\n  actual:   {}\n  expected: {}",
+        actual.inspect(),
+        expected.inspect()
+    ));
+    let mut differences = Vec::new();
+    diff(actual, expected, &mut String::new(), &mut differences);
+    // A single difference at the top is just the two values again
+    if differences.len() > 1 || differences.first().is_some_and(|d| !d.starts_with(':')) {
+        message.push_str("\n  differences:");
+        for difference in differences {
+            message.push_str(&format!("\n    {}", difference));
+        }
+    }
+    message
+}
+
+/// Collect where two values differ, as `path: description` lines
+fn diff(actual: &Value, expected: &Value, path: &mut String, out: &mut Vec<String>) {
+    match (actual, expected) {
+        (Value::List(actual), Value::List(expected)) => {
+            for i in 0..actual.len().max(expected.len()) {
+                let length = path.len();
+                path.push_str(&format!("[{}]", i));
+                match (actual.get(i), expected.get(i)) {
+                    (Some(actual), Some(expected)) => diff(actual, expected, path, out),
+                    (Some(actual), None) => {
+                        out.push(format!("{}: unexpected {}", path, actual.inspect()))
+                    }
+                    (None, Some(expected)) => out.push(format!(
+                        "{}: missing, expected {}",
+                        path,
+                        expected.inspect()
+                    )),
+                    (None, None) => {}
+                }
+                path.truncate(length);
+            }
+        }
+        (Value::Object(actual), Value::Object(expected)) => {
+            let mut keys: Vec<&String> = actual.keys().chain(expected.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let length = path.len();
+                path.push('.');
+                path.push_str(key);
+                match (actual.get(key), expected.get(key)) {
+                    (Some(actual), Some(expected)) => diff(actual, expected, path, out),
+                    (Some(actual), None) => {
+                        out.push(format!("{}: unexpected {}", path, actual.inspect()))
+                    }
+                    (None, Some(expected)) => out.push(format!(
+                        "{}: missing, expected {}",
+                        path,
+                        expected.inspect()
+                    )),
+                    (None, None) => {}
+                }
+                path.truncate(length);
+            }
+        }
+        (actual, expected) if actual != expected => out.push(format!(
+            "{}: got {}, expected {}",
+            path,
+            actual.inspect(),
+            expected.inspect()
+        )),
+        _ => {}
+    }
+}
+
+fn summary(results: &[TestResult]) -> String {
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    format!("{} passed, {} failed", results.len() - failed, failed)
+}
+
+fn text_report(results: &[TestResult]) -> String {
+    let mut report = String::new();
+    for result in results {
+        match &result.failure {
+            None => report.push_str(&format!("PASS {}: {}\n", result.file, result.name)),
+            Some(failure) => {
+                report.push_str(&format!("FAIL {}: {}\n", result.file, result.name));
+                for line in failure.lines() {
+                    report.push_str(&format!("    {}\n", line));
+                }
+                if !result.output.is_empty() {
+                    report.push_str("    output:\n");
+                    for line in result.output.lines() {
+                        report.push_str(&format!("      {}\n", line));
+                    }
+                }
+            }
+        }
+    }
+    report.push_str(&summary(results));
+    report.push('\n');
+    report
+}
+
+fn tap_report(results: &[TestResult]) -> String {
+    let mut report = format!("TAP version 13\n1..{}\n", results.len());
+    for (i, result) in results.iter().enumerate() {
+        let status = if result.passed() { "ok" } else { "not ok" };
+        report.push_str(&format!(
+            "{} {} - {}: {}\n",
+            status,
+            i + 1,
+            result.file,
+            result.name
+        ));
+        if let Some(failure) = &result.failure {
+            report.push_str("  ---\n  message: |\n");
+            for line in failure.lines() {
+                report.push_str(&format!("    {}\n", line));
+            }
+            report.push_str("  ...\n");
+        }
+    }
+    report.push_str(&format!("# {}\n", summary(results)));
+    report
+}
+
+fn junit_report(results: &[TestResult]) -> String {
+    let failures = |results: &[&TestResult]| results.iter().filter(|r| !r.passed()).count();
+    let seconds = |results: &[&TestResult]| {
+        results
+            .iter()
+            .map(|result| result.duration.as_secs_f64())
+            .sum::<f64>()
+    };
+
+    // One suite per file, in the order they ran
+    let mut suites: Vec<(&str, Vec<&TestResult>)> = Vec::new();
+    for result in results {
+        match suites.last_mut() {
+            Some((file, suite)) if *file == result.file => suite.push(result),
+            _ => suites.push((&result.file, vec![result])),
+        }
+    }
+
+    let all: Vec<&TestResult> = results.iter().collect();
+    let mut report = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        all.len(),
+        failures(&all),
+        seconds(&all)
+    );
+    for (file, suite) in &suites {
+        report.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(file),
+            suite.len(),
+            failures(suite),
+            seconds(suite)
+        ));
+        for result in suite {
+            report.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                xml_escape(&result.name),
+                xml_escape(file),
+                result.duration.as_secs_f64()
+            ));
+            if result.passed() && result.output.is_empty() {
+                report.push_str("/>\n");
+                continue;
+            }
+            report.push_str(">\n");
+            if let Some(failure) = &result.failure {
+                report.push_str(&format!(
+                    "      <failure message=\"{}\">{}</failure>\n",
+                    xml_escape(failure.lines().next().unwrap_or_default()),
+                    xml_escape(failure)
+                ));
+            }
+            if !result.output.is_empty() {
+                report.push_str(&format!(
+                    "      <system-out>{}</system-out>\n",
+                    xml_escape(&result.output)
+                ));
+            }
+            report.push_str("    </testcase>\n");
+        }
+        report.push_str("  </testsuite>\n");
+    }
+    report.push_str("</testsuites>\n");
+    report
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ObjectMap;
+
+    #[test]
+    fn test_diff_paths() {
+        let actual = Value::List(vec![
+            Value::Number(1.0),
+            Value::Object(ObjectMap::from([
+                ("name".to_string(), Value::String("Bo".to_string())),
+                ("extra".to_string(), Value::Boolean(true)),
+            ])),
+        ]);
+        let expected = Value::List(vec![
+            Value::Number(1.0),
+            Value::Object(ObjectMap::from([(
+                "name".to_string(),
+                Value::String("Bob".to_string()),
+            )])),
+            Value::Number(3.0),
+        ]);
+
+        let mut differences = Vec::new();
+        diff(&actual, &expected, &mut String::new(), &mut differences);
+        assert_eq!(
+            differences,
+            vec![
+                "[1].extra: unexpected true",
+                "[1].name: got \"Bo\", expected \"Bob\"",
+                "[2]: missing, expected 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scalar_failures_skip_differences() {
+        let message = comparison_failure(
+            "~x == 2",
+            &BinaryOperator::Equal,
+            &Value::Number(1.0),
+            &Value::Number(2.0),
+        );
+        assert_eq!(
+            message,
+            "Expectation failed: expect ~x == 2\n  actual:   1\n  expected: 2"
+        );
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape("a < \"b\" & c"), "a &lt; &quot;b&quot; &amp; c");
+    }
+}
@@ -0,0 +1,319 @@
+//! Structured execution traces for `tilde --trace`.
+//!
+//! A `Tracer` on the evaluator records one event per statement, function
+//! entry and exit, HTTP request, shell `run` and file read or write. Events
+//! are written as they happen, either as readable lines or as JSON lines,
+//! so a trace shows what a script actually did. Statement events need a
+//! program parsed with `Parser::with_locations`.
+
+use crate::ast::Statement;
+use crate::io::OutputStream;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceKind {
+    Statement,
+    Call,
+    Return,
+    Http,
+    Process,
+    File,
+}
+
+impl TraceKind {
+    pub const ALL: [TraceKind; 6] = [
+        TraceKind::Statement,
+        TraceKind::Call,
+        TraceKind::Return,
+        TraceKind::Http,
+        TraceKind::Process,
+        TraceKind::File,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TraceKind::Statement => "statement",
+            TraceKind::Call => "call",
+            TraceKind::Return => "return",
+            TraceKind::Http => "http",
+            TraceKind::Process => "run",
+            TraceKind::File => "file",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<TraceKind, String> {
+        TraceKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown trace event '{}' (expected one of: {})",
+                    name,
+                    TraceKind::ALL.map(|kind| kind.name()).join(", ")
+                )
+            })
+    }
+
+    /// Parse a comma-separated list such as `call,http`
+    pub fn parse_list(list: &str) -> Result<Vec<TraceKind>, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(TraceKind::parse)
+            .collect()
+    }
+}
+
+/// How events are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One readable line per event
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// One traced event
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    pub kind: TraceKind,
+    /// When the event started
+    pub time: DateTime<Utc>,
+    pub line: Option<usize>,
+    pub duration: Option<Duration>,
+    /// Event details, such as `url` and `status` for HTTP requests
+    pub fields: Vec<(&'static str, serde_json::Value)>,
+}
+
+impl TraceEvent {
+    /// An event starting now
+    pub fn new(kind: TraceKind) -> Self {
+        Self::at(kind, Utc::now())
+    }
+
+    /// An event that started at `time`
+    pub fn at(kind: TraceKind, time: DateTime<Utc>) -> Self {
+        TraceEvent {
+            kind,
+            time,
+            line: None,
+            duration: None,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn with_line(mut self, line: Option<usize>) -> Self {
+        self.line = line;
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn with_field(mut self, name: &'static str, value: impl Into<serde_json::Value>) -> Self {
+        self.fields.push((name, value.into()));
+        self
+    }
+
+    pub fn field(&self, name: &str) -> Option<&serde_json::Value> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut object = serde_json::Map::new();
+        object.insert(
+            "time".to_string(),
+            self.time.to_rfc3339_opts(SecondsFormat::Micros, true).into(),
+        );
+        object.insert("event".to_string(), self.kind.name().into());
+        if let Some(line) = self.line {
+            object.insert("line".to_string(), line.into());
+        }
+        if let Some(duration) = self.duration {
+            object.insert("duration_ms".to_string(), millis(duration).into());
+        }
+        for (name, value) in &self.fields {
+            object.insert(name.to_string(), value.clone());
+        }
+        serde_json::Value::Object(object)
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:<9}",
+            self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.kind.name()
+        )?;
+        if let Some(line) = self.line {
+            write!(f, " line {}", line)?;
+        }
+        for (name, value) in &self.fields {
+            match value {
+                serde_json::Value::String(text) if !text.contains(char::is_whitespace) => {
+                    write!(f, " {}={}", name, text)?
+                }
+                other => write!(f, " {}={}", name, other)?,
+            }
+        }
+        if let Some(duration) = self.duration {
+            write!(f, " ({:.3} ms)", millis(duration))?;
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Writes the events it's interested in to an output stream. Cloning shares
+/// the stream, so spawned tasks trace to the same place.
+#[derive(Clone)]
+pub struct Tracer {
+    output: Arc<dyn OutputStream>,
+    format: TraceFormat,
+    kinds: Vec<TraceKind>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("format", &self.format)
+            .field("kinds", &self.kinds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tracer {
+    /// Trace every kind of event as text
+    pub fn new(output: Arc<dyn OutputStream>) -> Self {
+        Tracer {
+            output,
+            format: TraceFormat::Text,
+            kinds: TraceKind::ALL.to_vec(),
+        }
+    }
+
+    pub fn with_format(mut self, format: TraceFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Only trace these kinds of event
+    pub fn with_kinds(mut self, kinds: impl IntoIterator<Item = TraceKind>) -> Self {
+        self.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    pub fn traces(&self, kind: TraceKind) -> bool {
+        self.kinds.contains(&kind)
+    }
+
+    pub fn record(&self, event: &TraceEvent) {
+        if !self.traces(event.kind) {
+            return;
+        }
+        let line = match self.format {
+            TraceFormat::Text => event.to_string(),
+            TraceFormat::Json => event.to_json().to_string(),
+        };
+        // A broken trace output shouldn't stop the script
+        let _ = self.output.write_line(&line);
+    }
+}
+
+/// A short description of a statement for statement events
+pub fn describe_statement(statement: &Statement) -> String {
+    match statement {
+        Statement::Assignment { variable, .. } | Statement::FunctionChain { variable, .. } => {
+            format!("~{} is", variable)
+        }
+        Statement::PropertyAssignment { property, .. } => format!("set .{}", property),
+        Statement::If { .. } => "if".to_string(),
+        Statement::Loop { .. } => "loop".to_string(),
+        Statement::ForEach { variables, .. } => format!(
+            "for-each {}",
+            variables
+                .iter()
+                .map(|name| format!("~{}", name))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        Statement::Increment { variable, .. } => format!("~{} up", variable),
+        Statement::Decrement { variable, .. } => format!("~{} down", variable),
+        Statement::Block { .. } => "block".to_string(),
+        Statement::Breakloop => "break-loop".to_string(),
+        Statement::Open(_) => "open".to_string(),
+        Statement::FunctionDefinition { name, .. } => format!("function {}", name),
+        Statement::Give(_) => "give".to_string(),
+        Statement::AttemptRescue { .. } => "attempt".to_string(),
+        Statement::Expression(crate::ast::Expression::FunctionCall { name, .. }) => name.clone(),
+        Statement::Expression(_) => "expression".to_string(),
+        Statement::Test { name, .. } => format!("test {:?}", name),
+        Statement::Setup { .. } => "setup".to_string(),
+        Statement::Teardown { .. } => "teardown".to_string(),
+        Statement::Expect(_) => "expect".to_string(),
+        Statement::Located { statement, .. } => describe_statement(statement),
+        Statement::Trivia(_) => "comment".to_string(),
+        Statement::Error(_) => "error".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MemoryOutput;
+
+    fn event() -> TraceEvent {
+        let time = DateTime::parse_from_rfc3339("2026-01-02T03:04:05.678Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        TraceEvent::at(TraceKind::Http, time)
+            .with_line(Some(4))
+            .with_duration(Duration::from_micros(12_500))
+            .with_field("method", "GET")
+            .with_field("url", "https://example.com")
+            .with_field("status", 200)
+    }
+
+    #[test]
+    fn test_text_format() {
+        assert_eq!(
+            event().to_string(),
+            "2026-01-02T03:04:05.678Z http      line 4 method=GET url=https://example.com status=200 (12.500 ms)"
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        let json = event().to_json();
+        assert_eq!(json["event"], "http");
+        assert_eq!(json["line"], 4);
+        assert_eq!(json["duration_ms"], 12.5);
+        assert_eq!(json["status"], 200);
+        assert_eq!(json["time"], "2026-01-02T03:04:05.678000Z");
+    }
+
+    #[test]
+    fn test_filters_by_kind() {
+        let output = Arc::new(MemoryOutput::new());
+        let tracer = Tracer::new(output.clone()).with_kinds(TraceKind::parse_list("call,run").unwrap());
+        tracer.record(&TraceEvent::new(TraceKind::Http));
+        tracer.record(&TraceEvent::new(TraceKind::Process));
+        assert_eq!(output.lines().len(), 1);
+        assert!(TraceKind::parse_list("call,nope").is_err());
+    }
+}
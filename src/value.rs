@@ -1,17 +1,23 @@
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use indexmap::IndexMap;
 use std::fmt;
 
+/// An object's fields, in the order they were added
+pub type ObjectMap = IndexMap<String, Value>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Number(f64),
     String(String),
     Boolean(bool),
     List(Vec<Value>),
-    Object(HashMap<String, Value>),
+    Object(ObjectMap),
     Date(DateTime<Utc>),
     Error(ErrorValue),
     Pattern(PatternValue),
+    Task(TaskHandle),
+    Channel(ChannelHandle),
+    Handle(HostHandle),
     Null,
 }
 
@@ -20,7 +26,8 @@ pub struct ErrorValue {
     pub message: String,
     pub code: Option<String>,
     pub source: Option<String>,
-    pub context: HashMap<String, Value>,
+    /// Boxed so errors stay small enough to return by value
+    pub context: Box<ObjectMap>,
 }
 
 impl ErrorValue {
@@ -29,7 +36,7 @@ impl ErrorValue {
             message: message.into(),
             code: None,
             source: None,
-            context: HashMap::new(),
+            context: Box::default(),
         }
     }
 
@@ -98,6 +105,10 @@ impl PatternValue {
 // Re-export the better event format from music module
 pub use crate::music::{Event as PatternEvent, EventData as EventType};
 
+// Re-export task and channel handles so they can be matched alongside other values
+pub use crate::concurrency::{ChannelHandle, TaskHandle};
+pub use crate::embed::HostHandle;
+
 impl Value {
     pub fn is_truthy(&self) -> bool {
         match self {
@@ -110,6 +121,31 @@ impl Value {
             Value::Date(_) => true,   // Dates are always truthy
             Value::Error(_) => false, // Errors are falsy
             Value::Pattern(p) => !p.is_empty(), // Patterns with events are truthy
+            Value::Task(_) => true,             // Task handles are always truthy
+            Value::Channel(_) => true,          // Channel handles are always truthy
+            Value::Handle(_) => true,           // Host handles are always truthy
+        }
+    }
+
+    /// Show a value unambiguously, for the REPL and test failures: strings
+    /// are quoted and object keys are sorted
+    pub fn inspect(&self) -> String {
+        match self {
+            Value::String(text) => format!("{:?}", text),
+            Value::List(items) => format!(
+                "[{}]",
+                items.iter().map(Value::inspect).collect::<Vec<_>>().join(", ")
+            ),
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                let pairs: Vec<String> = keys
+                    .into_iter()
+                    .map(|key| format!("{}: {}", key, map[key].inspect()))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            }
+            value => value.to_string(),
         }
     }
 }
@@ -137,6 +173,9 @@ impl fmt::Display for Value {
             Value::Date(dt) => write!(f, "{}", dt.format("%Y-%m-%dT%H:%M:%SZ")),
             Value::Error(err) => write!(f, "Error: {}", err.message),
             Value::Pattern(pattern) => write!(f, "pattern(\"{}\")", pattern.notation()),
+            Value::Task(task) => write!(f, "task({})", task.id()),
+            Value::Channel(channel) => write!(f, "channel({})", channel.id()),
+            Value::Handle(handle) => write!(f, "handle({}#{})", handle.type_name(), handle.id()),
             Value::Null => write!(f, "null"),
         }
     }
@@ -194,13 +233,13 @@ mod tests {
 
     #[test]
     fn test_empty_object_display() {
-        let value = Value::Object(HashMap::new());
+        let value = Value::Object(ObjectMap::new());
         assert_eq!(value.to_string(), "{}");
     }
 
     #[test]
     fn test_object_display() {
-        let mut map = HashMap::new();
+        let mut map = ObjectMap::new();
         map.insert("name".to_string(), Value::String("Alice".to_string()));
         map.insert("age".to_string(), Value::Number(30.0));
         let value = Value::Object(map);
@@ -241,9 +280,9 @@ mod tests {
 
     #[test]
     fn test_is_truthy_object() {
-        let mut map = HashMap::new();
+        let mut map = ObjectMap::new();
         map.insert("key".to_string(), Value::String("value".to_string()));
         assert!(Value::Object(map).is_truthy());
-        assert!(!Value::Object(HashMap::new()).is_truthy());
+        assert!(!Value::Object(ObjectMap::new()).is_truthy());
     }
 }
@@ -1,4 +1,9 @@
-use crate::{evaluator::Evaluator, parser::Parser, value::Value};
+use crate::io::{InputStream, MemoryOutput, OutputStream};
+use crate::limits::ResourceLimits;
+use crate::sandbox::EvaluatorConfig;
+use crate::{evaluator::Evaluator, formatter, parser::Parser, value::Value};
+use std::sync::Arc;
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
 
 // Import JavaScript functions we might need
@@ -25,9 +30,56 @@ macro_rules! console_log {
     ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
 }
 
+/// Limits that keep a runaway script from freezing the browser tab
+fn repl_config() -> EvaluatorConfig {
+    let limits = ResourceLimits::default()
+        .with_max_steps(10_000_000)
+        .with_timeout(Duration::from_secs(10))
+        .with_max_memory(64 * 1024 * 1024)
+        .with_max_output(1024 * 1024);
+    EvaluatorConfig::default().with_limits(limits)
+}
+
+/// Collects REPL output and also clears the page's console on `clear`
+#[derive(Debug, Default)]
+struct ReplOutput {
+    buffer: MemoryOutput,
+}
+
+impl OutputStream for ReplOutput {
+    fn write(&self, text: &str) -> Result<(), String> {
+        self.buffer.write(text)
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        clear_console();
+        self.buffer.clear()
+    }
+}
+
+/// Answers `ask` with a browser prompt dialog
+#[derive(Debug, Default)]
+struct BrowserPrompt;
+
+impl InputStream for BrowserPrompt {
+    fn read_line(&self, prompt: &str) -> Result<Option<String>, String> {
+        Ok(Some(prompt_user(prompt)))
+    }
+}
+
+fn repl_evaluator(output: &Arc<ReplOutput>) -> Evaluator {
+    let mut evaluator = Evaluator::with_config(repl_config());
+    evaluator
+        .set_output(output.clone())
+        .set_error_output(output.clone())
+        .set_input(Arc::new(BrowserPrompt));
+    evaluator
+}
+
 #[wasm_bindgen]
 pub struct WasmTildeRepl {
     evaluator: Evaluator,
+    output: Arc<ReplOutput>,
 }
 
 #[wasm_bindgen]
@@ -37,8 +89,10 @@ impl WasmTildeRepl {
         // Set up panic hook for better error reporting in the browser
         console_error_panic_hook::set_once();
 
+        let output = Arc::new(ReplOutput::default());
         WasmTildeRepl {
-            evaluator: Evaluator::new(),
+            evaluator: repl_evaluator(&output),
+            output,
         }
     }
 
@@ -46,24 +100,23 @@ impl WasmTildeRepl {
     #[wasm_bindgen]
     pub fn execute(&mut self, code: &str) -> String {
         // Clear output buffer for this execution
-        self.evaluator.output_buffer.clear();
+        self.output.buffer.take();
 
         let mut parser = Parser::new(code);
         match parser.parse() {
             Ok(program) => match self.evaluator.eval_program(program) {
                 Ok(value) => {
-                    let result_value =
-                        if self.evaluator.output_buffer.is_empty() && value != Value::Null {
-                            Some(value_to_js_value(&value))
-                        } else {
-                            None
-                        };
-                    ExecutionResult::success(result_value, self.evaluator.output_buffer.clone())
-                        .to_json()
+                    let output = self.output.buffer.lines();
+                    let result_value = if output.is_empty() && value != Value::Null {
+                        Some(value_to_js_value(&value))
+                    } else {
+                        None
+                    };
+                    ExecutionResult::success(result_value, output).to_json()
                 }
                 Err(e) => ExecutionResult::error(
                     format!("Runtime error: {}", e),
-                    self.evaluator.output_buffer.clone(),
+                    self.output.buffer.lines(),
                 )
                 .to_json(),
             },
@@ -74,19 +127,19 @@ impl WasmTildeRepl {
     /// Get the current output buffer (for debugging)
     #[wasm_bindgen]
     pub fn get_output(&self) -> String {
-        self.evaluator.output_buffer.join("\n")
+        self.output.buffer.lines().join("\n")
     }
 
     /// Clear the output buffer
     #[wasm_bindgen]
     pub fn clear_output(&mut self) {
-        self.evaluator.output_buffer.clear();
+        self.output.buffer.take();
     }
 
     /// Reset the REPL state (clear variables and functions)
     #[wasm_bindgen]
     pub fn reset(&mut self) {
-        self.evaluator = Evaluator::new();
+        self.evaluator = repl_evaluator(&self.output);
     }
 
     /// Get the current version of Tilde
@@ -95,9 +148,21 @@ impl WasmTildeRepl {
         env!("CARGO_PKG_VERSION").to_string()
     }
 
+    /// Format Tilde code in the standard layout, keeping its comments.
+    /// Returns the same JSON as `execute`, with the formatted code as the value.
+    #[wasm_bindgen]
+    pub fn format(&self, code: &str) -> String {
+        match formatter::format_source(code) {
+            Ok(formatted) => {
+                ExecutionResult::success(Some(formatted.into()), Vec::new()).to_json()
+            }
+            Err(e) => ExecutionResult::error(format!("Parse error: {}", e), Vec::new()).to_json(),
+        }
+    }
+
     /// Add output to the buffer (called internally by Tilde operations)
     pub fn add_output(&mut self, output: String) {
-        self.evaluator.output_buffer.push(output);
+        let _ = self.output.write_line(&output);
     }
 }
 
@@ -172,7 +237,7 @@ fn value_to_js_value(value: &Value) -> serde_json::Value {
             }
             if !err.context.is_empty() {
                 let mut context_map = serde_json::Map::new();
-                for (k, v) in &err.context {
+                for (k, v) in err.context.iter() {
                     context_map.insert(k.clone(), value_to_js_value(v));
                 }
                 error_map.insert(
@@ -194,6 +259,33 @@ fn value_to_js_value(value: &Value) -> serde_json::Value {
             );
             serde_json::Value::Object(pattern_map)
         }
+        Value::Task(task) => {
+            let mut task_map = serde_json::Map::new();
+            task_map.insert(
+                "type".to_string(),
+                serde_json::Value::String("task".to_string()),
+            );
+            task_map.insert("id".to_string(), serde_json::Value::from(task.id()));
+            serde_json::Value::Object(task_map)
+        }
+        Value::Channel(channel) => {
+            let mut channel_map = serde_json::Map::new();
+            channel_map.insert(
+                "type".to_string(),
+                serde_json::Value::String("channel".to_string()),
+            );
+            channel_map.insert("id".to_string(), serde_json::Value::from(channel.id()));
+            serde_json::Value::Object(channel_map)
+        }
+        Value::Handle(handle) => {
+            let mut handle_map = serde_json::Map::new();
+            handle_map.insert(
+                "type".to_string(),
+                serde_json::Value::String("handle".to_string()),
+            );
+            handle_map.insert("id".to_string(), serde_json::Value::from(handle.id()));
+            serde_json::Value::Object(handle_map)
+        }
         Value::Null => serde_json::Value::Null,
     }
 }
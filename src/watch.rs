@@ -0,0 +1,181 @@
+//! `tilde --watch`: re-run a script every time it changes.
+//!
+//! Changes are found by polling the file's modification time and size, which
+//! works on every platform and filesystem. Each version runs in a fresh
+//! evaluator, except that a playing music engine is handed from one run to
+//! the next: the patterns a new version plays replace the old ones in place,
+//! so scheduler time carries on instead of starting over. A version that
+//! doesn't parse never runs, leaving the last good one playing.
+
+use crate::evaluator::Evaluator;
+use crate::io::OutputStream;
+use crate::parser::Parser;
+use crate::sandbox::EvaluatorConfig;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// How often the file is checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often playing patterns are advanced between checks
+pub const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Notices when a file is saved by polling its metadata
+pub struct FileWatcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl FileWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileWatcher {
+            path: path.into(),
+            stamp: None,
+        }
+    }
+
+    /// Whether the file changed since the last call. The first call is true
+    /// if the file exists. A missing file doesn't count as a change, so a
+    /// save that deletes and recreates it is seen once.
+    pub fn changed(&mut self) -> bool {
+        let stamp = fs::metadata(&self.path).ok().map(|metadata| {
+            (
+                metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                metadata.len(),
+            )
+        });
+        if stamp.is_some() && stamp != self.stamp {
+            self.stamp = stamp;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Runs successive versions of one script
+pub struct WatchSession {
+    config: EvaluatorConfig,
+    args: Vec<String>,
+    script: Option<PathBuf>,
+    output: Option<Arc<dyn OutputStream>>,
+    /// The evaluator that ran the last version that parsed, holding any music engine
+    evaluator: Option<Evaluator>,
+}
+
+impl WatchSession {
+    /// A session running each version with `config`, and `args` as its `~args`
+    pub fn new(config: EvaluatorConfig, args: Vec<String>) -> Self {
+        WatchSession {
+            config,
+            args,
+            script: None,
+            output: None,
+            evaluator: None,
+        }
+    }
+
+    /// Resolve the script's imports relative to `path`
+    pub fn with_script_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.script = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Send what the script prints to `output` instead of stdout
+    pub fn with_output(mut self, output: Arc<dyn OutputStream>) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Run a new version of the script. A version that doesn't parse is not
+    /// run, and whatever the last good version started keeps playing.
+    pub fn run(&mut self, source: &str) -> Result<(), String> {
+        let program = Parser::new(source).parse().map_err(|e| {
+            if self.is_playing() {
+                format!("Parse error: {} (the last good version keeps playing)", e)
+            } else {
+                format!("Parse error: {}", e)
+            }
+        })?;
+
+        let mut evaluator = Evaluator::with_config(self.config.clone());
+        evaluator.set_args(&self.args);
+        if let Some(script) = &self.script {
+            evaluator.set_script_path(script);
+        }
+        if let Some(output) = &self.output {
+            evaluator.set_output(output.clone());
+        }
+        let mut engine = self
+            .evaluator
+            .take()
+            .and_then(|mut previous| previous.music_engine.take())
+            .filter(|engine| engine.is_playing());
+        if let Some(engine) = &mut engine {
+            engine.begin_reload();
+        }
+        evaluator.music_engine = engine;
+
+        let result = evaluator.eval_program(program);
+        if let Some(engine) = &mut evaluator.music_engine {
+            // A version that fails part-way keeps the patterns it didn't get to
+            match result {
+                Ok(_) => engine.finish_reload(),
+                Err(_) => engine.abandon_reload(),
+            }
+        }
+        self.evaluator = Some(evaluator);
+        match result {
+            Err(e) if self.exit_code().is_none() => Err(format!("Runtime error: {}", e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The evaluator that ran the last version that parsed
+    pub fn evaluator(&self) -> Option<&Evaluator> {
+        self.evaluator.as_ref()
+    }
+
+    /// The status code the last version passed to `exit`, if any
+    pub fn exit_code(&self) -> Option<i32> {
+        self.evaluator.as_ref().and_then(Evaluator::exit_code)
+    }
+
+    /// Whether patterns are playing
+    pub fn is_playing(&self) -> bool {
+        self.evaluator
+            .as_ref()
+            .and_then(|evaluator| evaluator.music_engine.as_ref())
+            .is_some_and(|engine| engine.is_playing())
+    }
+
+    /// Advance playing patterns to the current time
+    pub fn tick(&mut self) {
+        if let Some(evaluator) = &mut self.evaluator {
+            evaluator.tick_scheduler();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_watcher() {
+        let path = std::env::temp_dir().join("tilde_file_watcher_test.tde");
+        let _ = fs::remove_file(&path);
+        let mut watcher = FileWatcher::new(&path);
+        assert!(!watcher.changed());
+
+        fs::write(&path, "say 1").unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        fs::write(&path, "say 12").unwrap();
+        assert!(watcher.changed());
+        let _ = fs::remove_file(&path);
+    }
+}
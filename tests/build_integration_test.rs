@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tilde_build_{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+fn tilde(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_built_executable_carries_its_files() {
+    let dir = scratch("files");
+    write(
+        &dir.join("src/tool.tde"),
+        "import \"./lib/greet\"\nimport \"colors\"\n~config is read \"data/config.json\"\n~greeting is *greet ~args.0\n~color is *red\nsay ~greeting \" \" ~color \" \" ~config.size\nexit (length ~args)\n",
+    );
+    write(
+        &dir.join("src/lib/greet.tde"),
+        "function greet ~name (\n    give \"Hi \" + ~name\n)\n",
+    );
+    write(
+        &dir.join("src/tilde_modules/colors/tilde.toml"),
+        "[project]\nname = \"colors\"\nentry = \"colors.tde\"\n",
+    );
+    write(
+        &dir.join("src/tilde_modules/colors/colors.tde"),
+        "function red (\n    give \"red\"\n)\n",
+    );
+    write(&dir.join("data/config.json"), "{\"a\":1}");
+
+    let output = tilde(
+        &dir,
+        &["build", "src/tool.tde", "-o", "tool", "--include=data"],
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Built tool with 5 embedded files\n",
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The executable needs none of the files it was built from
+    let tool = dir.join("tool");
+    let elsewhere = scratch("files_elsewhere");
+    fs::rename(&tool, elsewhere.join("tool")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let output = Command::new(elsewhere.join("tool"))
+        .args(["Ada", "--help"])
+        .current_dir(&elsewhere)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Hi Ada red 7\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_build_errors() {
+    let dir = scratch("errors");
+    write(&dir.join("ok.tde"), "import \"./broken\"\n");
+    write(&dir.join("broken.tde"), "say (\n");
+
+    let output = tilde(&dir, &["build", "ok.tde"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Parse error in ./broken.tde:"));
+
+    write(&dir.join("broken.tde"), "say 1\n");
+    let output = tilde(&dir, &["build", "ok.tde", "--include=missing"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Can't include 'missing'"));
+
+    assert_eq!(tilde(&dir, &["build"]).status.code(), Some(2));
+    assert_eq!(
+        tilde(&dir, &["build", "ok.tde", "--strip"]).status.code(),
+        Some(2)
+    );
+    assert!(!dir.join("ok").exists());
+}
@@ -0,0 +1,179 @@
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+fn run(input: &str) -> Evaluator {
+    let mut parser = Parser::new(input);
+    let program = parser.parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(program).unwrap();
+    evaluator
+}
+
+fn run_err(input: &str) -> String {
+    let mut parser = Parser::new(input);
+    let program = parser.parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(program).unwrap_err()
+}
+
+#[test]
+fn test_send_and_receive_in_order() {
+    let evaluator = run(r#"
+        ~ch is channel 3
+        send ~ch 1
+        send ~ch "two"
+        ~first is receive ~ch
+        ~second is receive ~ch
+    "#);
+
+    assert_eq!(evaluator.get_variable("first"), Some(&Value::Number(1.0)));
+    assert_eq!(
+        evaluator.get_variable("second"),
+        Some(&Value::String("two".to_string()))
+    );
+}
+
+#[test]
+fn test_close_drains_then_returns_null() {
+    let evaluator = run(r#"
+        ~ch is channel
+        send ~ch 42
+        ~closed is close ~ch
+        ~closed_again is close ~ch
+        ~is_closed is is-closed ~ch
+        ~pending is receive ~ch
+        ~after is receive ~ch
+    "#);
+
+    assert_eq!(evaluator.get_variable("closed"), Some(&Value::Boolean(true)));
+    assert_eq!(
+        evaluator.get_variable("closed_again"),
+        Some(&Value::Boolean(false))
+    );
+    assert_eq!(evaluator.get_variable("is_closed"), Some(&Value::Boolean(true)));
+    assert_eq!(evaluator.get_variable("pending"), Some(&Value::Number(42.0)));
+    assert_eq!(evaluator.get_variable("after"), Some(&Value::Null));
+}
+
+#[test]
+fn test_send_on_closed_channel_is_rescuable() {
+    let evaluator = run(r#"
+        ~ch is channel 1
+        close ~ch
+        attempt (
+            send ~ch "late"
+        ) rescue ~error (
+            ~code is ~error.code
+        )
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("channel_closed".to_string()))
+    );
+}
+
+#[test]
+fn test_receive_timeout_returns_null() {
+    let evaluator = run(r#"
+        ~ch is channel
+        ~value is receive-timeout ~ch 0.01
+        ~open is is-closed ~ch
+    "#);
+
+    assert_eq!(evaluator.get_variable("value"), Some(&Value::Null));
+    assert_eq!(evaluator.get_variable("open"), Some(&Value::Boolean(false)));
+}
+
+#[test]
+fn test_producer_consumer_pipeline_with_tasks() {
+    let evaluator = run(r#"
+        ~jobs is channel 10
+        ~results is channel 10
+
+        function produce ~out (
+            for-each ~n in [1, 2, 3] (
+                send ~out ~n
+            )
+            close ~out
+            give "produced"
+        )
+
+        function transform ~in ~out (
+            loop (
+                ~item is receive ~in
+                if (is-null ~item) break-loop
+                send ~out (~item * 10)
+            )
+            close ~out
+            give "transformed"
+        )
+
+        ~producer is spawn (*produce ~jobs)
+        ~transformer is spawn (*transform ~jobs ~results)
+
+        ~collected is []
+        loop (
+            ~result is receive ~results
+            if (is-null ~result) break-loop
+            ~collected is append ~collected ~result
+        )
+        ~status is await-all [~producer, ~transformer]
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("collected"),
+        Some(&Value::List(vec![
+            Value::Number(10.0),
+            Value::Number(20.0),
+            Value::Number(30.0),
+        ]))
+    );
+    assert_eq!(
+        evaluator.get_variable("status"),
+        Some(&Value::List(vec![
+            Value::String("produced".to_string()),
+            Value::String("transformed".to_string()),
+        ]))
+    );
+}
+
+#[test]
+fn test_select_picks_ready_channel() {
+    let evaluator = run(r#"
+        ~a is channel
+        ~b is channel
+        send ~b "from b"
+        ~ready is select [~a, ~b]
+        ~index is ~ready.index
+        ~value is ~ready.value
+    "#);
+
+    assert_eq!(evaluator.get_variable("index"), Some(&Value::Number(1.0)));
+    assert_eq!(
+        evaluator.get_variable("value"),
+        Some(&Value::String("from b".to_string()))
+    );
+}
+
+#[test]
+fn test_select_timeout_and_all_closed() {
+    let evaluator = run(r#"
+        ~a is channel
+        ~timed_out is select [~a] 0.01
+        close ~a
+        ~all_closed is select [~a]
+    "#);
+
+    assert_eq!(evaluator.get_variable("timed_out"), Some(&Value::Null));
+    assert_eq!(evaluator.get_variable("all_closed"), Some(&Value::Null));
+}
+
+#[test]
+fn test_channel_errors() {
+    assert!(run_err("channel -1").contains("non-negative whole number"));
+    assert!(run_err("send 1 2").contains("send argument must be a channel"));
+    assert!(run_err("receive \"x\"").contains("receive argument must be a channel"));
+    assert!(run_err("~ch is channel\nreceive-timeout ~ch -1").contains("cannot be negative"));
+    assert!(run_err("select []").contains("at least one channel"));
+    assert!(run_err("select [1]").contains("select argument must be a channel"));
+}
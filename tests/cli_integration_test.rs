@@ -0,0 +1,104 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use tilde::evaluator::Evaluator;
+use tilde::parser::Parser;
+
+fn tilde(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_script_arguments() {
+    let path = std::env::temp_dir().join("tilde_cli_args.tde");
+    std::fs::write(
+        &path,
+        "#!/usr/bin/env tilde\nsay (length ~args) \" \" ~args.0\n",
+    )
+    .unwrap();
+    let output = tilde(&[path.to_str().unwrap(), "first", "--not-a-flag"]);
+    assert_eq!(stdout(&output), "2 first\n");
+    assert!(output.status.success());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_eval_flag() {
+    let output = tilde(&["-e", "say \"hi \" ~args", "a"]);
+    assert_eq!(stdout(&output), "hi [a]\n");
+    assert!(output.status.success());
+
+    let output = tilde(&["-e"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_script_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .args(["-", "x"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"say \"from stdin \" ~args\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(stdout(&output), "from stdin [x]\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_exit_codes() {
+    let output = tilde(&["-e", "say \"partial\"\nexit 3\nsay \"never\""]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "partial\n");
+    assert_eq!(stderr(&output), "");
+
+    assert_eq!(tilde(&["-e", "exit"]).status.code(), Some(0));
+
+    let output = tilde(&["-e", "~x is 1 / 0"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "Runtime error: Division by zero\n");
+
+    let output = tilde(&["-e", "say (1 +"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("Parse error:"));
+
+    let output = tilde(&["-e", "~x is\nsay ~x\n~y is [1,"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stderr(&output).matches("Parse error: line").count(), 2);
+
+    assert_eq!(tilde(&["missing_script.tde"]).status.code(), Some(2));
+    assert_eq!(tilde(&["-e", "exit 256"]).status.code(), Some(1));
+}
+
+#[test]
+fn test_exit_is_not_rescued() {
+    let program = Parser::new(
+        "function quit (\n    exit 4\n)\nattempt (\n    *quit\n) rescue (\n    say \"caught\"\n)",
+    )
+    .parse()
+    .unwrap();
+    let mut evaluator = Evaluator::new();
+    assert!(evaluator.eval_program(program).is_err());
+    assert_eq!(evaluator.exit_code(), Some(4));
+
+    evaluator
+        .eval_program(Parser::new("~ok is true").parse().unwrap())
+        .unwrap();
+    assert_eq!(evaluator.exit_code(), None);
+}
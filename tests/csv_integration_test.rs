@@ -0,0 +1,202 @@
+use std::fs;
+use tilde::{
+    evaluator::Evaluator,
+    parser::Parser,
+    value::{ObjectMap, Value},
+};
+
+fn run(input: &str) -> Result<Evaluator, String> {
+    let program = Parser::new(input).parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(program)?;
+    Ok(evaluator)
+}
+
+fn string(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+fn object(fields: &[(&str, Value)]) -> Value {
+    Value::Object(
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<ObjectMap>(),
+    )
+}
+
+#[test]
+fn test_csv_parse_infers_types() {
+    let evaluator = run(r#"
+        ~rows is csv-parse "name,age,active,joined,note\nAda,36,TRUE,2024-01-15,\n\n\"Hopper, Grace\",85,false,soon,\"said \"\"hi\"\"\nthen left\"\nLin,007"
+        ~plain is csv-parse "id,ok\n1,true" {types: false}
+    "#)
+    .unwrap();
+
+    let Some(Value::List(rows)) = evaluator.get_variable("rows") else {
+        panic!("Expected a list of rows");
+    };
+    assert_eq!(rows.len(), 3);
+    assert_eq!(
+        rows[1],
+        object(&[
+            ("name", string("Hopper, Grace")),
+            ("age", Value::Number(85.0)),
+            ("active", Value::Boolean(false)),
+            ("joined", string("soon")),
+            ("note", string("said \"hi\"\nthen left")),
+        ])
+    );
+    let Value::Object(first) = &rows[0] else {
+        panic!("Expected an object");
+    };
+    assert!(matches!(first.get("joined"), Some(Value::Date(_))));
+    assert_eq!(first.get("active"), Some(&Value::Boolean(true)));
+    assert_eq!(first.get("note"), Some(&Value::Null));
+    let Value::Object(last) = &rows[2] else {
+        panic!("Expected an object");
+    };
+    assert_eq!(last.get("age"), Some(&string("007")));
+    assert_eq!(last.get("joined"), Some(&Value::Null));
+
+    assert_eq!(
+        evaluator.get_variable("plain"),
+        Some(&Value::List(vec![object(&[
+            ("id", string("1")),
+            ("ok", string("true")),
+        ])]))
+    );
+}
+
+#[test]
+fn test_csv_parse_options() {
+    let evaluator = run(r#"
+        ~lists is csv-parse "a;b\r\n1;'x;y'" {delimiter: ";", quote: "'", header: false}
+        ~named is csv-parse "1,2\n3,4" {header: false, columns: ["x", "y"]}
+    "#)
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("lists"),
+        Some(&Value::List(vec![
+            Value::List(vec![string("a"), string("b")]),
+            Value::List(vec![Value::Number(1.0), string("x;y")]),
+        ]))
+    );
+    assert_eq!(
+        evaluator.get_variable("named"),
+        Some(&Value::List(vec![
+            object(&[("x", Value::Number(1.0)), ("y", Value::Number(2.0))]),
+            object(&[("x", Value::Number(3.0)), ("y", Value::Number(4.0))]),
+        ]))
+    );
+}
+
+#[test]
+fn test_csv_stringify() {
+    let evaluator = run(r#"
+        ~people is [{name: "Ada", age: 36}, {name: "Hopper, Grace", note: "said \"hi\""}]
+        ~in-order is csv-stringify ~people
+        ~ordered is csv-stringify ~people {columns: ["note", "name"], delimiter: ";"}
+        ~lists is csv-stringify [[1, "a\nb"], [true]] {header: false}
+        ~round-trip is csv-parse (csv-stringify ~people)
+    "#)
+    .unwrap();
+
+    let expected = [
+        (
+            "in-order",
+            "name,age,note\nAda,36,\n\"Hopper, Grace\",,\"said \"\"hi\"\"\"\n",
+        ),
+        (
+            "ordered",
+            "note;name\n;Ada\n\"said \"\"hi\"\"\";Hopper, Grace\n",
+        ),
+        ("lists", "1,\"a\nb\"\ntrue\n"),
+    ];
+    for (name, text) in expected {
+        assert_eq!(
+            evaluator.get_variable(name),
+            Some(&string(text)),
+            "~{}",
+            name
+        );
+    }
+    assert_eq!(
+        evaluator.get_variable("round-trip"),
+        Some(&Value::List(vec![
+            object(&[
+                ("name", string("Ada")),
+                ("age", Value::Number(36.0)),
+                ("note", Value::Null),
+            ]),
+            object(&[
+                ("name", string("Hopper, Grace")),
+                ("age", Value::Null),
+                ("note", string("said \"hi\"")),
+            ]),
+        ]))
+    );
+}
+
+#[test]
+fn test_csv_files() {
+    let dir = std::env::temp_dir().join("tilde_csv_files");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("orders.csv");
+    let output = dir.join("large.csv");
+    fs::write(&input, "\u{feff}id,total\r\n1,250\r\n2,1800\r\n3,1200\r\n").unwrap();
+
+    let evaluator = run(&format!(
+        r#"
+        ~orders is csv-read-file "{input}"
+        ~ids is csv-read-file "{input}" {{types: false}} |~row (~row.id)|
+        ~large is filter ~orders |~row (~row.total > 1000)|
+        ~written is csv-write-file "{output}" ~large
+        "#,
+        input = input.display(),
+        output = output.display()
+    ))
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("ids"),
+        Some(&Value::List(vec![string("1"), string("2"), string("3")]))
+    );
+    assert_eq!(evaluator.get_variable("written"), Some(&Value::Number(2.0)));
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "id,total\n2,1800\n3,1200\n"
+    );
+}
+
+#[test]
+fn test_csv_errors() {
+    let error = |input: &str| run(input).err().unwrap();
+
+    assert_eq!(
+        error(r#"csv-parse "a,b\n1,2,3""#),
+        "csv-parse: line 2 has 3 fields but there are 2 columns"
+    );
+    assert_eq!(
+        error(r#"csv-parse "a\n\"open""#),
+        "csv-parse: line 2: quoted field is never closed"
+    );
+    assert_eq!(
+        error(r#"csv-parse "a" {separator: ";"}"#),
+        "csv-parse: unknown option 'separator' (expected delimiter, quote, header, types or columns)"
+    );
+    assert_eq!(
+        error(r#"csv-stringify [{a: 1}, [2]]"#),
+        "csv-stringify: row 2 is a list but row 1 is an object"
+    );
+    assert_eq!(
+        error(r#"csv-read-file "x.csv" {} 5"#),
+        "csv-read-file third argument must be a function"
+    );
+    assert!(
+        error(r#"csv-read-file "/nonexistent/x.csv""#)
+            .starts_with("csv-read-file: /nonexistent/x.csv: ")
+    );
+}
@@ -0,0 +1,337 @@
+use std::fs;
+use std::path::Path;
+use tilde::{
+    evaluator::Evaluator,
+    parser::Parser,
+    value::{ObjectMap, Value},
+};
+
+fn run(input: &str) -> Result<Evaluator, String> {
+    let program = Parser::new(input).parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(program)?;
+    Ok(evaluator)
+}
+
+/// The ```tilde examples in `text`, with `prefix` (e.g. `/// `) taken off
+/// each line
+fn examples(text: &str, prefix: &str) -> Vec<String> {
+    let mut examples = Vec::new();
+    let mut current: Option<String> = None;
+    for line in text.lines() {
+        let line = line
+            .trim_start()
+            .strip_prefix(prefix.trim_end())
+            .unwrap_or(line);
+        let line = line.strip_prefix(' ').unwrap_or(line);
+        match (&mut current, line.trim()) {
+            (None, "```tilde") => current = Some(String::new()),
+            (Some(_), "```") => examples.extend(current.take()),
+            (Some(example), _) => {
+                example.push_str(line);
+                example.push('\n');
+            }
+            (None, _) => {}
+        }
+    }
+    examples
+}
+
+/// Run `examples` in order in one evaluator, with the files they read and
+/// write kept in `dir`
+fn run_examples(examples: &[String], dir: &Path) {
+    let mut evaluator = Evaluator::new();
+    for example in examples {
+        let mut script = example.clone();
+        for file in ["docker-compose.yml", "Cargo.toml", "settings.ini"] {
+            script = script.replace(
+                &format!("\"{}\"", file),
+                &format!("\"{}\"", dir.join(file).display()),
+            );
+        }
+        let program = Parser::new(&script).parse().unwrap();
+        if let Err(e) = evaluator.eval_program(program) {
+            panic!("Example failed: {}\n{}", e, example);
+        }
+    }
+}
+
+fn string(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+fn object(fields: &[(&str, Value)]) -> Value {
+    Value::Object(
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<ObjectMap>(),
+    )
+}
+
+fn keys(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Object(map)) => map.keys().cloned().collect(),
+        other => panic!("Expected an object, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_from_yaml() {
+    let evaluator = run(r#"
+        ~compose is from-yaml "version: '3.8'
+services:
+  web:
+    image: nginx:1.25   # pinned
+    ports:
+    - \"80:80\"
+    - 443
+    env: &env
+      DEBUG: false
+      LEVEL: ~
+  worker:
+    <<: *env
+    DEBUG: true
+    started: 2024-01-15 09:30:00
+    command: >
+      run the
+      worker
+    script: |-
+      echo hi
+      echo bye
+    tags: [a, 'b c', {d: 1.5}]
+"
+    "#)
+    .unwrap();
+
+    let compose = evaluator.get_variable("compose");
+    assert_eq!(keys(compose), ["version", "services"]);
+    let Some(Value::Object(compose)) = compose else {
+        unreachable!()
+    };
+    assert_eq!(compose.get("version"), Some(&string("3.8")));
+    let Some(Value::Object(services)) = compose.get("services") else {
+        panic!("Expected services");
+    };
+    assert_eq!(
+        services.get("web"),
+        Some(&object(&[
+            ("image", string("nginx:1.25")),
+            (
+                "ports",
+                Value::List(vec![string("80:80"), Value::Number(443.0)])
+            ),
+            (
+                "env",
+                object(&[("DEBUG", Value::Boolean(false)), ("LEVEL", Value::Null)])
+            ),
+        ]))
+    );
+
+    let worker = services.get("worker");
+    assert_eq!(
+        keys(worker),
+        ["DEBUG", "LEVEL", "started", "command", "script", "tags"]
+    );
+    let Some(Value::Object(worker)) = worker else {
+        unreachable!()
+    };
+    assert_eq!(worker.get("DEBUG"), Some(&Value::Boolean(true)));
+    assert!(
+        matches!(worker.get("started"), Some(Value::Date(date)) if date.to_rfc3339() == "2024-01-15T09:30:00+00:00")
+    );
+    assert_eq!(worker.get("command"), Some(&string("run the worker\n")));
+    assert_eq!(worker.get("script"), Some(&string("echo hi\necho bye")));
+    assert_eq!(
+        worker.get("tags"),
+        Some(&Value::List(vec![
+            string("a"),
+            string("b c"),
+            object(&[("d", Value::Number(1.5))]),
+        ]))
+    );
+}
+
+#[test]
+fn test_to_yaml_round_trip() {
+    let evaluator = run(r#"
+        ~config is {name: "web", version: "3.8", on: "yes", started: (date "2024-01-15"), notes: "line one\nline two\n", ports: [80, 443], env: [{key: "A", value: 1}], empty: [], unset: (from-yaml "~")}
+        ~text is to-yaml ~config
+        ~same is (from-yaml ~text) == ~config
+    "#)
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("text"),
+        Some(&string(
+            "name: web\nversion: \"3.8\"\n\"on\": \"yes\"\nstarted: 2024-01-15\nnotes: |\n  line one\n  line two\nports:\n  - 80\n  - 443\nenv:\n  - key: A\n    value: 1\nempty: []\nunset: null\n"
+        ))
+    );
+    assert_eq!(evaluator.get_variable("same"), Some(&Value::Boolean(true)));
+}
+
+#[test]
+fn test_toml() {
+    let evaluator = run(r#"
+        ~manifest is from-toml "[package]
+name = \"tilde\"
+version = \"0.6.1\"
+released = 2024-03-15T14:30:00Z
+edition = 2024
+
+[dependencies]
+toml = { version = \"0.9\", features = [\"preserve_order\"] }
+regex = \"1\"
+"
+        ~manifest.package.version is "0.7.0"
+        ~text is to-toml ~manifest
+        ~same is (from-toml ~text) == ~manifest
+    "#)
+    .unwrap();
+
+    let manifest = evaluator.get_variable("manifest");
+    assert_eq!(keys(manifest), ["package", "dependencies"]);
+    let Some(Value::Object(manifest)) = manifest else {
+        unreachable!()
+    };
+    assert_eq!(keys(manifest.get("dependencies")), ["toml", "regex"]);
+    let Some(Value::Object(package)) = manifest.get("package") else {
+        panic!("Expected a package table");
+    };
+    assert_eq!(package.get("edition"), Some(&Value::Number(2024.0)));
+    assert!(matches!(package.get("released"), Some(Value::Date(_))));
+
+    assert_eq!(
+        evaluator.get_variable("text"),
+        Some(&string(
+            "[package]\nname = \"tilde\"\nversion = \"0.7.0\"\nreleased = 2024-03-15T14:30:00Z\nedition = 2024\n\n[dependencies]\nregex = \"1\"\n\n[dependencies.toml]\nversion = \"0.9\"\nfeatures = [\"preserve_order\"]\n"
+        ))
+    );
+    assert_eq!(evaluator.get_variable("same"), Some(&Value::Boolean(true)));
+}
+
+#[test]
+fn test_ini() {
+    let evaluator = run(r#"
+        ~config is from-ini "; settings
+name = app
+[database]
+host = localhost  ; the default
+port: 5432
+password = \"p;ss\\\"word\"
+debug = false
+empty =
+"
+        ~text is to-ini ~config
+        ~same is (from-ini ~text) == ~config
+    "#)
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("config"),
+        Some(&object(&[
+            ("name", string("app")),
+            (
+                "database",
+                object(&[
+                    ("host", string("localhost")),
+                    ("port", Value::Number(5432.0)),
+                    ("password", string("p;ss\"word")),
+                    ("debug", Value::Boolean(false)),
+                    ("empty", string("")),
+                ])
+            ),
+        ]))
+    );
+    assert_eq!(
+        evaluator.get_variable("text"),
+        Some(&string(
+            "name = app\n\n[database]\nhost = localhost\nport = 5432\npassword = p;ss\"word\ndebug = false\nempty =\n"
+        ))
+    );
+    assert_eq!(evaluator.get_variable("same"), Some(&Value::Boolean(true)));
+}
+
+#[test]
+fn test_data_format_errors() {
+    let error = |input: &str| run(input).err().unwrap();
+
+    assert_eq!(
+        error(r#"from-yaml "name: web\n  image: nginx""#),
+        "from-yaml: line 2, column 3: this key is indented under a value"
+    );
+    assert_eq!(
+        error(r#"from-yaml "a:\n  b:\n    c: 1\n   d: 2""#),
+        "from-yaml: line 4, column 4: this line is indented more than the keys above it"
+    );
+    assert_eq!(
+        error(r#"from-yaml "a: 1\nb: [1, 2\nc: 3""#),
+        "from-yaml: line 3, column 1: expected ',' or ']' but found 'c'"
+    );
+    assert_eq!(
+        error(r#"from-yaml "a: 1\na: 2""#),
+        "from-yaml: line 2, column 1: duplicate key 'a'"
+    );
+    assert_eq!(
+        error(r#"from-yaml "a: *missing""#),
+        "from-yaml: line 1, column 4: *missing refers to no anchor"
+    );
+    assert_eq!(
+        error(r#"from-toml "a = 1\nb = = 2""#),
+        "from-toml: line 2, column 5: extra `=`, expected nothing"
+    );
+    assert_eq!(
+        error(r#"from-ini "[db]\nport = 5432\nbad line""#),
+        "from-ini: line 3, column 1: expected key = value or [section], found 'bad line'"
+    );
+    assert_eq!(
+        error(r#"from-ini "[db]\nport = 1\n  port = 2""#),
+        "from-ini: line 3, column 3: duplicate key 'port'"
+    );
+    assert_eq!(
+        error(r#"to-toml {a: {b: (from-yaml "~")}}"#),
+        "to-toml: 'a.b' is null, and TOML has no null"
+    );
+    assert_eq!(
+        error(r#"to-ini {db: {pool: {size: 5}}}"#),
+        "to-ini: [db] pool is an object, but sections can't be nested"
+    );
+}
+
+#[test]
+fn test_documented_examples_run() {
+    let dir = std::env::temp_dir().join("tilde_data_format_examples");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("docker-compose.yml"),
+        "services:\n  web:\n    image: nginx:1.25\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"tilde\"\nversion = \"0.6.1\"\n",
+    )
+    .unwrap();
+    fs::write(dir.join("settings.ini"), "[database]\nport = 5432\n").unwrap();
+
+    let docs = include_str!("../docs/STDLIB.md");
+    let start = docs.find("## YAML, TOML and INI Functions").unwrap();
+    let end = start + docs[start..].find("\n## Type Checking").unwrap();
+    let docs = examples(&docs[start..end], "");
+    assert_eq!(docs.len(), 7);
+    run_examples(&docs, &dir);
+    let written = fs::read_to_string(dir.join("Cargo.toml")).unwrap();
+    assert!(written.contains("version = \"0.7.0\""));
+
+    for source in [
+        include_str!("../src/stdlib/yaml.rs"),
+        include_str!("../src/stdlib/toml.rs"),
+        include_str!("../src/stdlib/ini.rs"),
+    ] {
+        let examples = examples(source, "/// ");
+        assert_eq!(examples.len(), 2);
+        run_examples(&examples, &dir);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
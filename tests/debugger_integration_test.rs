@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use tilde::debugger::{Breakpoint, DebugHook, Debugger, Location};
+use tilde::io::{MemoryInput, MemoryOutput};
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+const SCRIPT: &str = r#"function double ~n (
+    ~result is ~n * 2
+    give ~result
+)
+~a is 5
+~b is *double ~a
+~c is ~b + 1"#;
+
+/// Run `code` under a debugger fed `commands`, returning the evaluator and the transcript
+fn debug(code: &str, commands: &[&str]) -> (Evaluator, Result<Value, String>, String) {
+    let output = Arc::new(MemoryOutput::new());
+    let input = Arc::new(MemoryInput::new(commands.iter().copied()));
+    let debugger = Debugger::new(code, input, output.clone());
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_debug_hook(Box::new(debugger));
+    let program = Parser::new(code).with_locations().parse().unwrap();
+    let result = evaluator.eval_program(program);
+    (evaluator, result, output.contents())
+}
+
+#[test]
+fn test_pauses_before_first_statement() {
+    let (evaluator, result, transcript) = debug(SCRIPT, &["c"]);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(transcript.starts_with("Paused at line 1 in <script>: function double ~n ("));
+    assert_eq!(evaluator.get_variable("c"), Some(&Value::Number(11.0)));
+}
+
+#[test]
+fn test_line_breakpoint_and_print() {
+    let (_, result, transcript) = debug(SCRIPT, &["break 6", "c", "p ~a", "p ~a * 3", "c"]);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(transcript.contains("Breakpoint set at line 6"));
+    assert!(transcript.contains("Paused at line 6 in <script>: ~b is *double ~a"));
+    assert!(transcript.contains("(debug) p ~a\n5\n"));
+    assert!(transcript.contains("(debug) p ~a * 3\n15\n"));
+}
+
+#[test]
+fn test_function_breakpoint_shows_stack_and_locals() {
+    let (_, result, transcript) = debug(SCRIPT, &["b double", "c", "bt", "locals", "c"]);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(transcript.contains("Paused at line 2 in double: ~result is ~n * 2"));
+    assert!(transcript.contains("#0 double at line 2\n#1 <script> at line 6\n"));
+    assert!(transcript.contains("(debug) locals\n~n = 5\n"));
+}
+
+#[test]
+fn test_set_changes_the_running_program() {
+    let (evaluator, result, _) = debug(SCRIPT, &["b 2", "c", "set ~n 50", "c"]);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(evaluator.get_variable("b"), Some(&Value::Number(100.0)));
+}
+
+#[test]
+fn test_step_over_into_and_out() {
+    let (_, result, transcript) = debug(SCRIPT, &["b 6", "c", "n", "c"]);
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(transcript.contains("Paused at line 7 in <script>"));
+    assert!(!transcript.contains("in double"));
+
+    let (_, result, transcript) = debug(SCRIPT, &["b 6", "c", "s", "out", "c"]);
+    assert!(result.is_ok(), "{:?}", result);
+    let into = transcript.find("Paused at line 2 in double").unwrap();
+    let out = transcript.find("Paused at line 7 in <script>").unwrap();
+    assert!(into < out);
+}
+
+#[test]
+fn test_quit_stops_the_program() {
+    let (evaluator, result, _) = debug(SCRIPT, &["b 6", "c", "quit"]);
+
+    assert_eq!(result.unwrap_err(), "Debugging stopped");
+    assert_eq!(evaluator.get_variable("a"), Some(&Value::Number(5.0)));
+    assert_eq!(evaluator.get_variable("b"), None);
+}
+
+#[test]
+fn test_loop_body_pauses_each_iteration() {
+    let code = "~total is 0\nfor-each ~n in [1, 2, 3] (\n    ~total up ~n\n)";
+    let (_, result, transcript) = debug(code, &["b 3", "c", "c", "p ~total", "c", "c"]);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(transcript.matches("Paused at line 3").count(), 3);
+    assert!(transcript.contains("(debug) p ~total\n1\n"));
+}
+
+#[test]
+fn test_custom_hook_sees_every_line() {
+    struct Recorder(Arc<std::sync::Mutex<Vec<Location>>>);
+
+    impl DebugHook for Recorder {
+        fn before_statement(&mut self, _: &mut Evaluator, location: Location) -> Result<(), String> {
+            self.0.lock().unwrap().push(location);
+            Ok(())
+        }
+    }
+
+    let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut evaluator = Evaluator::new();
+    evaluator.set_debug_hook(Box::new(Recorder(seen.clone())));
+    let program = Parser::new(SCRIPT).with_locations().parse().unwrap();
+    evaluator.eval_program(program).unwrap();
+
+    let lines: Vec<(usize, usize)> = seen
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|location| (location.line, location.depth))
+        .collect();
+    assert_eq!(lines, vec![(1, 0), (5, 0), (6, 0), (2, 1), (3, 1), (7, 0)]);
+}
+
+#[test]
+fn test_breakpoint_parsing() {
+    assert_eq!(Breakpoint::parse("12"), Ok(Breakpoint::Line(12)));
+    assert_eq!(
+        Breakpoint::parse("*double"),
+        Ok(Breakpoint::Function("double".to_string()))
+    );
+}
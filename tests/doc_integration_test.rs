@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tilde::doc::{self, DocFormat};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tilde_doc_{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn tilde(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+const LIBRARY: &str = r#"## Wraps text in a border.
+##
+## ```
+## expect (*boxed "hi") == "[hi]"
+## ```
+function boxed ~text (
+    give "[" + ~text + "]"
+)
+
+## Sums a list.
+##
+## ```tilde
+## expect (*total [1, 2, 3]) == 6
+## ```
+##
+## Not run:
+##
+## ```text
+## *total "oops"
+## ```
+function total ~items (
+    give reduce ~items add 0
+)
+"#;
+
+#[test]
+fn test_stdlib_reference_is_up_to_date() {
+    let generated = doc::stdlib_page().render(DocFormat::Markdown);
+    let committed = fs::read_to_string("docs/STDLIB_REFERENCE.md").unwrap();
+    assert!(
+        generated == committed,
+        "docs/STDLIB_REFERENCE.md is stale; regenerate it with `tilde doc --stdlib > docs/STDLIB_REFERENCE.md`"
+    );
+}
+
+#[test]
+fn test_doc_writes_pages() {
+    let dir = scratch("pages");
+    fs::write(dir.join("text.tde"), LIBRARY).unwrap();
+
+    let output = tilde(&dir, &["doc", "text.tde"]);
+    assert!(output.status.success());
+    let markdown = String::from_utf8_lossy(&output.stdout);
+    assert!(markdown.starts_with(
+        "# text\n\n## Functions\n\n### boxed\n\n`*boxed ~text`\n\nWraps text in a border.\n"
+    ));
+    assert!(markdown.contains("### total\n\n`*total ~items`\n\nSums a list."));
+
+    let output = tilde(&dir, &["doc", "--format=html", "--output=site", "text.tde"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Wrote site/text.html\n"
+    );
+    let html = fs::read_to_string(dir.join("site/text.html")).unwrap();
+    assert!(html.contains(
+        "<h3 id=\"total\">total</h3>\n<p><code>*total ~items</code></p>\n<p>Sums a list.</p>"
+    ));
+    assert!(html.contains("<code class=\"language-text\">*total &quot;oops&quot;</code>"));
+}
+
+#[test]
+fn test_doc_test_runs_examples() {
+    let dir = scratch("test");
+    fs::write(dir.join("text.tde"), LIBRARY).unwrap();
+
+    let output = tilde(&dir, &["doc", "--test", "text.tde"]);
+    assert_eq!(output.status.code(), Some(0));
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(report.contains("PASS text.tde: *boxed example"));
+    assert!(report.contains("PASS text.tde: *total example"));
+    assert!(report.contains("2 passed, 0 failed"));
+
+    fs::write(dir.join("text.tde"), LIBRARY.replace("== 6", "== 7")).unwrap();
+    let output = tilde(&dir, &["doc", "--test", "text.tde"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("FAIL text.tde: *total example"));
+
+    fs::write(dir.join("broken.tde"), "function (").unwrap();
+    let output = tilde(&dir, &["doc", "broken.tde"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("broken.tde: parse error"));
+}
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tilde::embed::{HostHandle, Serde, to_value};
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct User {
+    id: u32,
+    name: String,
+}
+
+fn eval(evaluator: &mut Evaluator, input: &str) -> Result<Value, String> {
+    let program = evaluator.parser(input).parse().unwrap();
+    evaluator.eval_program(program)
+}
+
+#[test]
+fn test_register_fn_with_typed_args() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_fn("greet", |args| {
+        let name: String = args.get(0)?;
+        let excited: Option<bool> = args.optional(1)?;
+        Ok(format!("Hello, {}{}", name, if excited == Some(true) { "!" } else { "" }))
+    });
+
+    eval(
+        &mut evaluator,
+        r#"
+        ~plain is *greet "Ada"
+        ~loud is *greet "Ada" true
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("plain"),
+        Some(&Value::String("Hello, Ada".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("loud"),
+        Some(&Value::String("Hello, Ada!".to_string()))
+    );
+}
+
+#[test]
+fn test_namespace_functions_are_called_as_a_block() {
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .namespace("app")
+        .unwrap()
+        .register_fn("lookup-user", |args| {
+            let id: u32 = args.get(0)?;
+            to_value(&User {
+                id,
+                name: format!("user-{}", id),
+            })
+        })
+        .register_fn("double", |args| Ok(args.get::<f64>(0)? * 2.0));
+
+    eval(
+        &mut evaluator,
+        r#"
+        ~user is app:lookup-user 7
+        ~name is ~user.name
+        ~doubled is map [1, 2, 3] app:double
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("name"),
+        Some(&Value::String("user-7".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("doubled"),
+        Some(&Value::List(vec![
+            Value::Number(2.0),
+            Value::Number(4.0),
+            Value::Number(6.0),
+        ]))
+    );
+}
+
+#[test]
+fn test_serde_arguments() {
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .namespace("users")
+        .unwrap()
+        .register_fn("describe", |args| {
+            let Serde(user): Serde<User> = args.get(0)?;
+            Ok(format!("{} (#{})", user.name, user.id))
+        });
+
+    eval(
+        &mut evaluator,
+        r#"~text is users:describe {id: 3, name: "Grace"}"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("text"),
+        Some(&Value::String("Grace (#3)".to_string()))
+    );
+}
+
+#[test]
+fn test_host_handles_round_trip() {
+    struct Counter {
+        count: Mutex<u32>,
+    }
+
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .namespace("counter")
+        .unwrap()
+        .register_fn("new", |_| {
+            Ok(HostHandle::new(Counter {
+                count: Mutex::new(0),
+            }))
+        })
+        .register_fn("bump", |args| {
+            let counter = args.handle::<Counter>(0)?;
+            let mut count = counter.count.lock().unwrap();
+            *count += 1;
+            Ok(*count)
+        });
+
+    eval(
+        &mut evaluator,
+        r#"
+        ~c is counter:new
+        counter:bump ~c
+        ~count is counter:bump ~c
+        ~kind is ~c
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(evaluator.get_variable("count"), Some(&Value::Number(2.0)));
+    assert!(matches!(evaluator.get_variable("c"), Some(Value::Handle(_))));
+
+    let err = eval(&mut evaluator, "counter:bump 5").unwrap_err();
+    assert_eq!(err, "counter:bump argument 1: expected handle, got number");
+}
+
+#[test]
+fn test_native_errors_are_rescuable() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_fn("fail", |_| -> Result<Value, String> { Err("host refused".to_string()) });
+
+    eval(
+        &mut evaluator,
+        r#"
+        attempt (
+            *fail
+        ) rescue ~error (
+            ~message is ~error.message
+        )
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("message"),
+        Some(&Value::String("host refused".to_string()))
+    );
+}
+
+#[test]
+fn test_script_functions_take_precedence() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_fn("pick", |_| Ok("native"));
+
+    eval(
+        &mut evaluator,
+        r#"
+        ~before is *pick
+        function pick (
+            give "script"
+        )
+        ~after is *pick
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("before"),
+        Some(&Value::String("native".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("after"),
+        Some(&Value::String("script".to_string()))
+    );
+}
+
+#[test]
+fn test_spawned_tasks_see_native_functions() {
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .namespace("host")
+        .unwrap()
+        .register_fn("config", |_| {
+            let mut config = HashMap::new();
+            config.insert("region".to_string(), "eu".to_string());
+            Ok(config)
+        });
+
+    eval(
+        &mut evaluator,
+        r#"
+        ~task is spawn (host:config)
+        ~config is await ~task
+        ~region is ~config.region
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("region"),
+        Some(&Value::String("eu".to_string()))
+    );
+}
+
+#[test]
+fn test_unknown_namespace_function() {
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .namespace("shop")
+        .unwrap()
+        .register_fn("price", |_| Ok(1.0));
+
+    let err = eval(&mut evaluator, "shop:missing 1").unwrap_err();
+    assert_eq!(err, "Unknown shop function: missing");
+}
+
+#[test]
+fn test_builtin_blocks_are_reserved() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+        evaluator.namespace("core").err(),
+        Some("cannot register host functions in the built-in 'core' block".to_string())
+    );
+}
+
+#[test]
+fn test_host_blocks_belong_to_their_evaluator() {
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .namespace("billing")
+        .unwrap()
+        .register_fn("total", |_| Ok(10.0));
+
+    let program = evaluator.parser("~sum is billing:total").parse().unwrap();
+    evaluator.eval_program(program).unwrap();
+    assert_eq!(evaluator.get_variable("sum"), Some(&Value::Number(10.0)));
+
+    // Parsers from other evaluators, or with no evaluator, don't know the block
+    let other = Evaluator::new();
+    assert_ne!(
+        other.parser("billing:total").parse(),
+        evaluator.parser("billing:total").parse()
+    );
+    assert_eq!(
+        Parser::new("billing:total").parse(),
+        other.parser("billing:total").parse()
+    );
+}
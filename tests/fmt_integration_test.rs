@@ -0,0 +1,117 @@
+use tilde::formatter::format_source;
+use tilde::parser::Parser;
+
+/// Formatting keeps the program the same and formatting again changes nothing
+fn assert_formats_cleanly(name: &str, source: &str) {
+    let formatted = format_source(source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+    assert_eq!(
+        Parser::new(&formatted).parse(),
+        Parser::new(source).parse(),
+        "{} parses differently after formatting:\n{}",
+        name,
+        formatted
+    );
+    assert_eq!(
+        format_source(&formatted).unwrap(),
+        formatted,
+        "{} is not stable",
+        name
+    );
+}
+
+#[test]
+fn test_examples_round_trip() {
+    let mut checked = 0;
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "tde") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+        // Scripts that don't parse can't be formatted
+        if Parser::new(&source).parse().is_err() {
+            continue;
+        }
+        assert_formats_cleanly(&path.display().to_string(), &source);
+        checked += 1;
+    }
+    assert!(checked > 20);
+}
+
+#[test]
+fn test_keeps_comments() {
+    let source = r#"#!/usr/bin/env tilde
+# Totals
+
+
+function total ~items (   # sum a list
+  ~sum is 0
+  for-each ~item in ~items (
+      # add it
+      ~sum up ~item
+  )
+
+
+  give ~sum  # done
+)
+say *total [1, 2]
+# end
+"#;
+    let formatted = format_source(source).unwrap();
+    assert_eq!(
+        formatted,
+        r#"#!/usr/bin/env tilde
+# Totals
+
+function total ~items ( # sum a list
+    ~sum is 0
+    for-each ~item in ~items (
+        # add it
+        ~sum up ~item
+    )
+
+    give ~sum # done
+)
+say (*total [1, 2])
+# end
+"#
+    );
+    assert_formats_cleanly("comments", source);
+}
+
+#[test]
+fn test_formatted_code_is_unchanged() {
+    let source = r#"~config is {name: "demo", retries: 3, "two words": true}
+~scores is [45, 67, 89]
+
+function grade ~score (
+    if ~score >= 80 give "A" else if ~score >= 60 give "B" else give "C"
+)
+
+attempt (
+    ~data is read "missing.txt"
+) rescue ~error (
+    say "Could not read: " ~error
+)
+
+~high:
+    ~scores
+    filter |~x (~x >= 60)|
+    map *grade
+say "Grades: " ~high "for `~config.name`"
+"#;
+    assert_eq!(format_source(source).unwrap(), source);
+}
+
+#[test]
+fn test_keeps_meaningful_parentheses() {
+    let source =
+        "~x is (1 + 2) * (3 - (4 - 5))\nsay \"n\" (length ~x) \"!\"\nif (*check ~x) say \"yes\"\n";
+    assert_eq!(format_source(source).unwrap(), source);
+    assert_formats_cleanly("parentheses", source);
+}
+
+#[test]
+fn test_parse_errors_are_reported() {
+    assert!(format_source("~x is (").is_err());
+}
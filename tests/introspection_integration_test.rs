@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use tilde::io::MemoryOutput;
+use tilde::value::Value;
+use tilde::{evaluator::Evaluator, parser::Parser};
+
+fn run(source: &str) -> Result<Evaluator, String> {
+    let mut evaluator = Evaluator::new();
+    evaluator.set_output(Arc::new(MemoryOutput::new()));
+    let program = Parser::new(source).parse()?;
+    evaluator.eval_program(program)?;
+    Ok(evaluator)
+}
+
+fn variable(evaluator: &Evaluator, name: &str) -> String {
+    evaluator.get_variable(name).unwrap().to_string()
+}
+
+#[test]
+fn test_help() {
+    let evaluator = run(r#"
+## Greets someone by name.
+##
+## Says hello.
+function greet ~name (
+    give "Hello, " + ~name
+)
+function shout ~text (
+    give uppercase ~text
+)
+~stdlib is help "date-format"
+~mine is help "greet"
+~starred is help "*greet"
+~undocumented is help "shout"
+~overview is help
+"#)
+    .unwrap();
+
+    let stdlib = variable(&evaluator, "stdlib");
+    assert!(stdlib.starts_with("date-format date format\n\nFormats a date"));
+    assert!(stdlib.contains("Example:\n    expect (date-format"));
+    assert_eq!(
+        variable(&evaluator, "mine"),
+        "*greet ~name\n\nGreets someone by name.\n\nSays hello."
+    );
+    assert_eq!(
+        variable(&evaluator, "starred"),
+        variable(&evaluator, "mine")
+    );
+    assert!(variable(&evaluator, "undocumented").contains("No documentation"));
+
+    let overview = variable(&evaluator, "overview");
+    assert!(overview.contains("\nStrings: split, join, trim,"));
+    assert!(overview.contains("\nYours: *greet, *shout\n"));
+
+    let error = run(r#"help "no-such-thing""#).err().unwrap();
+    assert_eq!(
+        error,
+        "help: no function named 'no-such-thing' (functions-list lists them all)"
+    );
+}
+
+#[test]
+fn test_functions_list_and_arity() {
+    let evaluator = run(r#"
+function pair ~a ~b (
+    give [~a, ~b]
+)
+~all is functions-list
+~files is functions-list "files"
+~pair is function-arity "pair"
+~slice is function-arity "slice"
+~now is function-arity "now"
+"#)
+    .unwrap();
+
+    let Some(Value::List(all)) = evaluator.get_variable("all") else {
+        panic!("functions-list gives a list");
+    };
+    assert_eq!(all.first(), Some(&Value::String("map".to_string())));
+    assert_eq!(all.last(), Some(&Value::String("*pair".to_string())));
+    assert_eq!(
+        variable(&evaluator, "files"),
+        "[file-exists, dir-exists, file-size]"
+    );
+    assert_eq!(variable(&evaluator, "pair"), "2");
+    assert_eq!(variable(&evaluator, "slice"), "2");
+    assert_eq!(variable(&evaluator, "now"), "0");
+
+    let error = run(r#"functions-list "Nope""#).err().unwrap();
+    assert!(
+        error
+            .starts_with("functions-list: no block named 'Nope' (expected one of: Lists, Strings,")
+    );
+    assert!(run(r#"function-arity "nope""#).is_err());
+}
+
+#[test]
+fn test_describe() {
+    let evaluator = run(r#"
+~number is describe 4.5
+~text is describe "héllo"
+~empty is describe []
+~object is describe {name: "Ada", age: 36}
+attempt (
+    ~value is ~missing + 1
+) rescue ~problem (
+    ~error is describe ~problem
+)
+~channel is describe (channel)
+~date is describe (date "2024-03-15")
+"#)
+    .unwrap();
+
+    assert_eq!(variable(&evaluator, "number"), "number 4.5");
+    assert_eq!(variable(&evaluator, "text"), "string of 5 characters");
+    assert_eq!(variable(&evaluator, "empty"), "empty list");
+    assert_eq!(variable(&evaluator, "object"), "object with keys age, name");
+    assert_eq!(
+        variable(&evaluator, "error"),
+        "error: Undefined variable: missing"
+    );
+    assert_eq!(variable(&evaluator, "channel"), "open channel");
+    assert_eq!(variable(&evaluator, "date"), "date 2024-03-15T00:00:00Z");
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use tilde::io::{IoStreams, MemoryInput, MemoryOutput};
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+fn run_with(input: &[&str], code: &str) -> (Evaluator, Arc<MemoryOutput>, Result<Value, String>) {
+    let output = Arc::new(MemoryOutput::new());
+    let mut evaluator = Evaluator::new();
+    evaluator
+        .set_output(output.clone())
+        .set_input(Arc::new(MemoryInput::new(input.iter().copied())));
+
+    let mut parser = Parser::new(code);
+    let program = parser.parse().unwrap();
+    let result = evaluator.eval_program(program);
+    (evaluator, output, result)
+}
+
+#[test]
+fn test_say_writes_to_output_stream() {
+    let (_, output, result) = run_with(
+        &[],
+        r#"
+        say "Hello, " "world"
+        for-each ~n in [1, 2] (
+            say "n = " ~n
+        )
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(output.lines(), vec!["Hello, world", "n = 1", "n = 2"]);
+}
+
+#[test]
+fn test_ask_reads_scripted_answers() {
+    let (evaluator, output, result) = run_with(
+        &["Ada", " 42 "],
+        r#"
+        ~name is ask "Name?"
+        ~age is ask "Age?"
+        say ~name " is " ~age
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(
+        evaluator.get_variable("name"),
+        Some(&Value::String("Ada".to_string()))
+    );
+    assert_eq!(evaluator.get_variable("age"), Some(&Value::Number(42.0)));
+    assert_eq!(output.lines(), vec!["Name?Ada", "Age? 42 ", "Ada is 42"]);
+}
+
+#[test]
+fn test_ask_at_end_of_input_gives_empty_string() {
+    let (evaluator, _, result) = run_with(&[], "~answer is ask");
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(
+        evaluator.get_variable("answer"),
+        Some(&Value::String(String::new()))
+    );
+}
+
+#[test]
+fn test_clear_empties_memory_output() {
+    let (_, output, result) = run_with(
+        &[],
+        r#"
+        say "before"
+        clear
+        say "after"
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(output.contents(), "after\n");
+}
+
+#[test]
+fn test_spawned_tasks_share_output() {
+    let (_, output, result) = run_with(
+        &[],
+        r#"
+        ~task is spawn (say "from task")
+        await ~task
+        say "done"
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(output.lines(), vec!["from task", "done"]);
+}
+
+#[test]
+fn test_evaluators_can_share_streams() {
+    let output = Arc::new(MemoryOutput::new());
+    let streams = IoStreams {
+        output: output.clone(),
+        ..IoStreams::default()
+    };
+
+    for message in ["first", "second"] {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_streams(streams.clone());
+        let mut parser = Parser::new(&format!("say \"{}\"", message));
+        evaluator.eval_program(parser.parse().unwrap()).unwrap();
+    }
+
+    assert_eq!(output.lines(), vec!["first", "second"]);
+}
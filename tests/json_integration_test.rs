@@ -127,3 +127,27 @@ fn test_from_json_invalid() {
     let result = evaluator.eval_program(program);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_json_keeps_key_order() {
+    let input = r#"
+        ~obj is from-json "{\"zebra\": 1, \"apple\": {\"b\": 2, \"a\": 3}, \"mango\": 4}"
+        ~keys is keys ~obj
+        ~text is to-json ~obj
+    "#;
+
+    let mut parser = Parser::new(input);
+    let program = parser.parse().unwrap();
+    let mut evaluator = Evaluator::new();
+
+    evaluator.eval_program(program).unwrap();
+
+    assert_eq!(
+        evaluator.get_variable("keys").unwrap().to_string(),
+        "[zebra, apple, mango]"
+    );
+    assert_eq!(
+        evaluator.get_variable("text").unwrap().to_string(),
+        "{\"zebra\":1.0,\"apple\":{\"b\":2.0,\"a\":3.0},\"mango\":4.0}"
+    );
+}
@@ -0,0 +1,240 @@
+use std::time::Duration;
+use tilde::limits::ResourceLimits;
+use tilde::sandbox::EvaluatorConfig;
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+fn run_with(limits: ResourceLimits, input: &str) -> (Evaluator, Result<Value, String>) {
+    let mut parser = Parser::new(input);
+    let program = parser.parse().unwrap();
+    let mut evaluator = Evaluator::with_config(EvaluatorConfig::default().with_limits(limits));
+    let result = evaluator.eval_program(program);
+    (evaluator, result)
+}
+
+#[test]
+fn test_step_limit_stops_empty_loop() {
+    let (_, result) = run_with(ResourceLimits::default().with_max_steps(1000), "loop ( )");
+    assert_eq!(result.unwrap_err(), "Step limit exceeded (max 1000 steps)");
+}
+
+#[test]
+fn test_timeout_stops_infinite_loop() {
+    let (_, result) = run_with(
+        ResourceLimits::default().with_timeout(Duration::from_millis(50)),
+        r#"
+        ~n is 0
+        loop (
+            ~n is ~n + 1
+        )
+    "#,
+    );
+    assert!(result.unwrap_err().contains("Time limit exceeded"));
+}
+
+#[test]
+fn test_uncatchable_limit_skips_rescue() {
+    let (evaluator, result) = run_with(
+        ResourceLimits::default().with_max_steps(100),
+        r#"
+        attempt (
+            loop ( )
+        ) rescue ~error (
+            ~rescued is true
+        )
+    "#,
+    );
+
+    assert!(result.unwrap_err().contains("Step limit exceeded"));
+    assert_eq!(evaluator.get_variable("rescued"), None);
+}
+
+#[test]
+fn test_catchable_limit_can_be_rescued() {
+    let (evaluator, result) = run_with(
+        ResourceLimits::default().with_max_steps(100).catchable(true),
+        r#"
+        attempt (
+            loop ( )
+        ) rescue ~error (
+            ~code is ~error.code
+            ~limit is ~error.context.limit
+        )
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("limit_exceeded".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("limit"),
+        Some(&Value::String("steps".to_string()))
+    );
+}
+
+#[test]
+fn test_memory_limit_stops_growing_list() {
+    let (evaluator, result) = run_with(
+        ResourceLimits::default().with_max_memory(64 * 1024),
+        r#"
+        ~items is []
+        loop (
+            ~items is append ~items "some text that takes up space"
+        )
+    "#,
+    );
+
+    assert!(result.unwrap_err().contains("Memory limit exceeded"));
+    if let Some(Value::List(items)) = evaluator.get_variable("items") {
+        assert!(!items.is_empty());
+    } else {
+        panic!("Expected the last list that fit");
+    }
+}
+
+/// Run `assignment` under a 10,000 byte memory limit, rescuing the limit error
+fn rescue_memory_limit(setup: &str, assignment: &str) -> Evaluator {
+    let (evaluator, result) = run_with(
+        ResourceLimits::default()
+            .with_max_memory(10_000)
+            .catchable(true),
+        &format!(
+            r#"
+        {}
+        attempt (
+            {}
+        ) rescue ~error (
+            ~code is ~error.code
+            ~limit is ~error.context.limit
+        )
+    "#,
+            setup, assignment
+        ),
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("limit_exceeded".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("limit"),
+        Some(&Value::String("memory".to_string()))
+    );
+    evaluator
+}
+
+#[test]
+fn test_memory_limit_covers_index_assignment() {
+    let evaluator = rescue_memory_limit("~l is []", "~l.2000000 is 1");
+    assert_eq!(evaluator.get_variable("l"), Some(&Value::List(Vec::new())));
+
+    let evaluator = rescue_memory_limit("", "~fresh.2000000 is 1");
+    assert_eq!(evaluator.get_variable("fresh"), None);
+}
+
+#[test]
+fn test_memory_limit_covers_property_assignment() {
+    let evaluator = rescue_memory_limit("~o is {}", r#"~o.a is repeat "x" 1000000"#);
+    assert_eq!(
+        evaluator.get_variable("o"),
+        Some(&Value::Object(Default::default()))
+    );
+
+    let evaluator =
+        rescue_memory_limit("~o is {nested: {}}", r#"~o.nested.a is repeat "x" 1000000"#);
+    assert!(matches!(
+        evaluator.get_variable("o"),
+        Some(Value::Object(o)) if o.get("nested") == Some(&Value::Object(Default::default()))
+    ));
+}
+
+#[test]
+fn test_output_limit() {
+    let (_, result) = run_with(
+        ResourceLimits::default().with_max_output(20),
+        r#"
+        say "0123456789"
+        say "0123456789"
+    "#,
+    );
+    assert!(result.unwrap_err().contains("Output limit exceeded"));
+}
+
+#[test]
+fn test_call_depth_limit() {
+    let (_, result) = run_with(
+        ResourceLimits::default().with_max_call_depth(10),
+        r#"
+        function deep ~n (
+            ~next is *deep (~n + 1)
+            give ~next
+        )
+        *deep 0
+    "#,
+    );
+    assert_eq!(result.unwrap_err(), "Maximum call depth (10) exceeded");
+}
+
+#[test]
+fn test_limits_reset_between_runs() {
+    let limits = ResourceLimits::default().with_max_steps(50);
+    let mut evaluator = Evaluator::with_config(EvaluatorConfig::default().with_limits(limits));
+
+    for _ in 0..3 {
+        let mut parser = Parser::new("~a is 1\n~b is 2\n~c is ~a + ~b");
+        let program = parser.parse().unwrap();
+        assert!(evaluator.eval_program(program).is_ok());
+    }
+}
+
+#[test]
+fn test_spawned_tasks_share_the_limits() {
+    let (evaluator, result) = run_with(
+        ResourceLimits::default().with_max_steps(1000),
+        r#"
+        function spin (
+            loop ( )
+        )
+        ~task is spawn (*spin)
+        ~results is await-all [~task]
+        ~after is "still running"
+    "#,
+    );
+
+    assert_eq!(result.unwrap_err(), "Step limit exceeded (max 1000 steps)");
+    assert_eq!(evaluator.get_variable("after"), None);
+}
+
+#[test]
+fn test_task_steps_count_against_the_spawner() {
+    let count_to = |n: usize| {
+        format!(
+            r#"
+            function count ~n (
+                ~i is 0
+                loop (
+                    if ~i >= ~n ( break-loop )
+                    ~i is ~i + 1
+                )
+                give ~i
+            )
+            ~counts is parallel-map [{n}, {n}, {n}, {n}] count
+        "#
+        )
+    };
+    let limits = ResourceLimits::default().with_max_steps(2000);
+
+    // Each task fits in the budget on its own, but not all four together
+    let (_, alone) = run_with(limits.clone(), &count_to(100));
+    assert!(alone.is_ok(), "{:?}", alone);
+    let (_, together) = run_with(limits.clone(), &count_to(300));
+    assert_eq!(together.unwrap_err(), "Step limit exceeded (max 2000 steps)");
+
+    let (_, rescued) = run_with(
+        limits,
+        &format!("attempt (\n{}\n) rescue ~e ( say \"caught\" )", count_to(300)),
+    );
+    assert!(rescued.is_err(), "an uncatchable limit in a task was rescued");
+}
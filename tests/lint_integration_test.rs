@@ -0,0 +1,180 @@
+use tilde::lint::{Diagnostic, Rule, Severity, lint_source};
+
+fn lint(input: &str) -> Vec<Diagnostic> {
+    lint_source(input).unwrap()
+}
+
+fn found(diagnostics: &[Diagnostic]) -> Vec<(Rule, usize)> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.rule, diagnostic.line.unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_clean_script_has_no_diagnostics() {
+    let diagnostics = lint(
+        r#"
+function twice ~n (
+    give ~n * 2
+)
+~numbers is [1, 2, 3]
+~doubled is map ~numbers *twice
+~total is 0
+for-each ~n in ~doubled (
+    ~total up ~n
+)
+~i is 0
+loop (
+    ~i up 1
+    if ~i >= 3 break-loop
+)
+say "Total: `~total`, odd: " (filter ~numbers |~x (~x % 2 == 1)|)
+"#,
+    );
+    assert_eq!(diagnostics, vec![]);
+}
+
+#[test]
+fn test_unused_variables_and_functions() {
+    let diagnostics = lint(
+        r#"~unused is 1
+function helper ~x (
+    ~scratch is ~x
+    give ~x
+)
+"#,
+    );
+    assert_eq!(
+        found(&diagnostics),
+        vec![
+            (Rule::UnusedVariable, 1),
+            (Rule::UnusedFunction, 2),
+            (Rule::UnusedVariable, 3),
+        ]
+    );
+    assert_eq!(diagnostics[0].message, "~unused is assigned but never used");
+}
+
+#[test]
+fn test_functions_see_globals() {
+    let diagnostics = lint(
+        r#"function show (
+    say ~greeting
+)
+~greeting is "hi"
+*show
+"#,
+    );
+    assert_eq!(diagnostics, vec![]);
+}
+
+#[test]
+fn test_undefined_variables_and_unknown_functions() {
+    let diagnostics = lint("say ~nowhere\n~x is *missing 1\nsay ~x unknown-thing");
+    assert_eq!(
+        found(&diagnostics),
+        vec![
+            (Rule::UndefinedVariable, 1),
+            (Rule::UnknownFunction, 2),
+            (Rule::UnknownFunction, 3),
+        ]
+    );
+    assert!(diagnostics.iter().all(|d| d.severity() == Severity::Error));
+}
+
+#[test]
+fn test_shadowed_stdlib_and_arity() {
+    let diagnostics = lint(
+        r#"function length ~x (
+    give 1
+)
+function greet ~first ~last (
+    say ~first ~last
+)
+*greet "Ada"
+~n is *length "abc"
+say ~n
+"#,
+    );
+    assert_eq!(
+        found(&diagnostics),
+        vec![(Rule::ShadowedStdlib, 1), (Rule::ArityMismatch, 7)]
+    );
+    assert_eq!(
+        diagnostics[1].message,
+        "*greet takes 2 arguments but is given 1"
+    );
+}
+
+#[test]
+fn test_control_flow_rules() {
+    let diagnostics = lint(
+        r#"function early (
+    give 1
+    say "never"
+)
+say *early
+break-loop
+loop (
+    say "forever"
+)
+"#,
+    );
+    assert_eq!(
+        found(&diagnostics),
+        vec![
+            (Rule::UnreachableCode, 3),
+            (Rule::BreakOutsideLoop, 6),
+            (Rule::InfiniteLoop, 7),
+        ]
+    );
+}
+
+#[test]
+fn test_loop_exits_through_nested_statements() {
+    let diagnostics = lint(
+        r#"function find-first ~items (
+    loop (
+        if ~items give 1 else (
+            break-loop
+        )
+    )
+)
+say *find-first [1]
+"#,
+    );
+    assert_eq!(diagnostics, vec![]);
+}
+
+#[test]
+fn test_suppression_comments() {
+    let diagnostics = lint(
+        r#"~a is 1 # lint-ignore
+# lint-ignore unused-variable
+~b is 2
+~c is 3 # lint-ignore unknown-function
+"#,
+    );
+    assert_eq!(found(&diagnostics), vec![(Rule::UnusedVariable, 4)]);
+}
+
+#[test]
+fn test_json_output() {
+    let diagnostics = lint("say ~nowhere");
+    let json = diagnostics[0].to_json();
+    assert_eq!(json["rule"], "undefined-variable");
+    assert_eq!(json["severity"], "error");
+    assert_eq!(json["line"], 1);
+    assert_eq!(json["message"], "~nowhere is never assigned");
+}
+
+#[test]
+fn test_parse_errors_are_reported() {
+    assert!(lint_source("~x is (").is_err());
+
+    let errors =
+        lint_source("~a is (1 +\nsay ~a\nloop (\n    ~b is\n    break-loop\n)\n").unwrap_err();
+    let lines: Vec<Option<usize>> = errors.iter().map(|error| error.line).collect();
+    assert_eq!(lines, vec![Some(1), Some(4)]);
+}
@@ -0,0 +1,289 @@
+use serde_json::{Value, json};
+use std::io::BufReader;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use tilde::lsp::{read_message, write_message};
+
+const URI: &str = "file:///tmp/script.tde";
+
+const SCRIPT: &str = r#"~count is 0
+function bump ~by (
+    ~count up ~by
+    give ~count
+)
+say *bump 2
+say "total: `~count`" (length "abc")
+"#;
+
+/// Drives `tilde lsp` over its stdin and stdout, as an editor would
+struct Client {
+    server: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    next_id: i64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut server = Command::new(env!("CARGO_BIN_EXE_tilde"))
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let input = server.stdin.take().unwrap();
+        let output = BufReader::new(server.stdout.take().unwrap());
+        let mut client = Client {
+            server,
+            input,
+            output,
+            next_id: 0,
+        };
+        let result = client.request("initialize", json!({"capabilities": {}}));
+        assert_eq!(result["capabilities"]["renameProvider"], true);
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        let message = json!({"jsonrpc": "2.0", "method": method, "params": params});
+        write_message(&mut self.input, &message).unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        read_message(&mut self.output).unwrap().unwrap()
+    }
+
+    fn send_request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let message =
+            json!({"jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params});
+        write_message(&mut self.input, &message).unwrap();
+        loop {
+            let reply = self.receive();
+            if reply["id"] == self.next_id {
+                return reply;
+            }
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let reply = self.send_request(method, params);
+        assert!(reply.get("error").is_none(), "{}", reply);
+        reply["result"].clone()
+    }
+
+    /// Open a document and return the diagnostics published for it
+    fn open(&mut self, text: &str) -> Vec<Value> {
+        self.notify(
+            "textDocument/didOpen",
+            json!({"textDocument": {"uri": URI, "languageId": "tilde", "version": 1, "text": text}}),
+        );
+        self.diagnostics()
+    }
+
+    fn diagnostics(&mut self) -> Vec<Value> {
+        let message = self.receive();
+        assert_eq!(message["method"], "textDocument/publishDiagnostics");
+        message["params"]["diagnostics"].as_array().unwrap().clone()
+    }
+
+    fn at(&mut self, method: &str, line: usize, character: usize) -> Value {
+        self.request(
+            method,
+            json!({"textDocument": {"uri": URI}, "position": {"line": line, "character": character}}),
+        )
+    }
+
+    fn finish(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.server.wait().unwrap().success());
+    }
+}
+
+fn range(start: (usize, usize), end: (usize, usize)) -> Value {
+    json!({
+        "start": {"line": start.0, "character": start.1},
+        "end": {"line": end.0, "character": end.1},
+    })
+}
+
+#[test]
+fn test_parse_and_lint_diagnostics() {
+    let mut client = Client::start();
+    assert_eq!(client.open(SCRIPT), Vec::<Value>::new());
+
+    client.notify(
+        "textDocument/didChange",
+        json!({"textDocument": {"uri": URI, "version": 2}, "contentChanges": [{"text": "~x is 1\nsay ~y\n"}]}),
+    );
+    let diagnostics = client.diagnostics();
+    let codes: Vec<&str> = diagnostics
+        .iter()
+        .map(|d| d["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, vec!["unused-variable", "undefined-variable"]);
+    assert_eq!(diagnostics[1]["range"], range((1, 0), (1, 6)));
+    assert_eq!(diagnostics[1]["severity"], 1);
+
+    client.notify(
+        "textDocument/didChange",
+        json!({"textDocument": {"uri": URI, "version": 3}, "contentChanges": [{"text": "say 1\n~x is (\n"}]}),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["source"], "tilde");
+
+    client.notify(
+        "textDocument/didChange",
+        json!({"textDocument": {"uri": URI, "version": 4}, "contentChanges": [{"text": "~x is (\nsay ~x\n~y is\n"}]}),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 0);
+    assert_eq!(diagnostics[1]["range"]["start"]["line"], 2);
+
+    client.notify(
+        "textDocument/didClose",
+        json!({"textDocument": {"uri": URI}}),
+    );
+    assert_eq!(client.diagnostics(), Vec::<Value>::new());
+    client.finish();
+}
+
+#[test]
+fn test_completion() {
+    let mut client = Client::start();
+    client.open(&format!("{}~c", SCRIPT));
+
+    let items = client.at("textDocument/completion", 7, 2);
+    let labels: Vec<&str> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, vec!["~by", "~count"]);
+    assert_eq!(items[0]["textEdit"]["range"], range((7, 0), (7, 2)));
+
+    let items = client.at("textDocument/completion", 5, 6);
+    let labels: Vec<&str> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, vec!["*bump"]);
+
+    let items = client.at("textDocument/completion", 6, 23);
+    let labels: Vec<&str> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert!(labels.contains(&"*bump"));
+    assert!(labels.contains(&"length"));
+    assert!(labels.contains(&"map"));
+    client.finish();
+}
+
+#[test]
+fn test_hover() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    let hover = client.at("textDocument/hover", 6, 24);
+    let docs = hover["contents"]["value"].as_str().unwrap();
+    assert!(
+        docs.starts_with("`length value`\n\nThe number of items"),
+        "{}",
+        docs
+    );
+    assert_eq!(hover["range"], range((6, 23), (6, 29)));
+
+    let hover = client.at("textDocument/hover", 5, 6);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```tilde\nfunction bump ~by\n```"
+    );
+
+    assert_eq!(client.at("textDocument/hover", 0, 9), Value::Null);
+    client.finish();
+}
+
+#[test]
+fn test_definition() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    let location = client.at("textDocument/definition", 5, 7);
+    assert_eq!(location["uri"], URI);
+    assert_eq!(location["range"], range((1, 9), (1, 13)));
+
+    // `~count` inside the string refers to the global
+    let location = client.at("textDocument/definition", 6, 15);
+    assert_eq!(location["range"], range((0, 1), (0, 6)));
+
+    let location = client.at("textDocument/definition", 2, 16);
+    assert_eq!(location["range"], range((1, 15), (1, 17)));
+    client.finish();
+}
+
+#[test]
+fn test_document_symbols() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({"textDocument": {"uri": URI}}),
+    );
+    assert_eq!(symbols.as_array().unwrap().len(), 1);
+    assert_eq!(symbols[0]["name"], "bump");
+    assert_eq!(symbols[0]["kind"], 12);
+    assert_eq!(symbols[0]["range"], range((1, 0), (4, 1)));
+    assert_eq!(symbols[0]["selectionRange"], range((1, 9), (1, 13)));
+    client.finish();
+}
+
+#[test]
+fn test_rename() {
+    let mut client = Client::start();
+    client.open(SCRIPT);
+
+    let edit = client.request(
+        "textDocument/rename",
+        json!({"textDocument": {"uri": URI}, "position": {"line": 0, "character": 2}, "newName": "~total"}),
+    );
+    let ranges: Vec<&Value> = edit["changes"][URI]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edit| {
+            assert_eq!(edit["newText"], "total");
+            &edit["range"]
+        })
+        .collect();
+    assert_eq!(ranges.len(), 4);
+    assert_eq!(*ranges[3], range((6, 14), (6, 19)));
+
+    let reply = client.send_request(
+        "textDocument/rename",
+        json!({"textDocument": {"uri": URI}, "position": {"line": 1, "character": 10}, "newName": "no good"}),
+    );
+    assert!(reply["error"]["message"].is_string());
+    client.finish();
+}
+
+#[test]
+fn test_unknown_methods_and_early_exit() {
+    let mut client = Client::start();
+    let reply = client.send_request("workspace/symbol", json!({"query": ""}));
+    assert_eq!(reply["error"]["code"], -32601);
+
+    client.notify("exit", Value::Null);
+    assert!(!client.server.wait().unwrap().success());
+}
@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::Arc;
+use tilde::evaluator::Evaluator;
+use tilde::io::MemoryOutput;
+use tilde::package::{self, Lockfile};
+use tilde::parser::Parser;
+
+/// A fresh scratch directory for one test
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tilde_package_{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// A bare repository at `dir/name.git` holding `main.tde`, and a working copy to commit from
+fn git_package(dir: &Path, name: &str, main: &str) -> PathBuf {
+    let bare = dir.join(format!("{}.git", name));
+    let work = dir.join(format!("{}-work", name));
+    fs::create_dir_all(&bare).unwrap();
+    git(&bare, &["init", "--quiet", "--bare"]);
+    git(
+        dir,
+        &[
+            "clone",
+            "--quiet",
+            bare.to_str().unwrap(),
+            work.to_str().unwrap(),
+        ],
+    );
+    commit(&work, main);
+    work
+}
+
+fn commit(work: &Path, main: &str) -> String {
+    write(&work.join("main.tde"), main);
+    git(work, &["add", "-A"]);
+    git(work, &["commit", "--quiet", "-m", "update"]);
+    git(work, &["push", "--quiet", "origin", "HEAD"]);
+    git(work, &["rev-parse", "HEAD"])
+}
+
+fn run_script(project: &Path, source: &str) -> Result<Vec<String>, String> {
+    let output = Arc::new(MemoryOutput::new());
+    let mut evaluator = Evaluator::new();
+    evaluator.set_output(output.clone());
+    evaluator.set_script_path(project.join("main.tde"));
+    evaluator.eval_program(Parser::new(source).parse()?)?;
+    Ok(output.lines())
+}
+
+fn tilde(project: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .args(args)
+        .current_dir(project)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_installs_path_dependencies_transitively() {
+    let dir = scratch("paths");
+    write(
+        &dir.join("strings/tilde.toml"),
+        "[project]\nname = \"strings\"\nentry = \"lib.tde\"\n\n[dependencies]\nhelpers = { path = \"../helpers\" }\n",
+    );
+    write(
+        &dir.join("strings/lib.tde"),
+        "import \"helpers\"\nfunction shout ~text (\n    give *exclaim (uppercase ~text)\n)\n",
+    );
+    write(
+        &dir.join("strings/extra/quiet.tde"),
+        "function quiet ~text (\n    give lowercase ~text\n)\n",
+    );
+    write(
+        &dir.join("helpers/main.tde"),
+        "function exclaim ~text (\n    give ~text + \"!\"\n)\n",
+    );
+    let project = dir.join("app");
+    write(
+        &project.join("tilde.toml"),
+        "[project]\nname = \"app\"\n\n[dependencies]\nstrings = { path = \"../strings\" }\n",
+    );
+
+    let lockfile = package::install(&project).unwrap();
+    let names: Vec<&str> = lockfile.packages.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["helpers", "strings"]);
+    assert_eq!(lockfile.packages[1].source, "path+../strings");
+    assert!(project.join("tilde_modules/helpers/main.tde").is_file());
+    assert_eq!(Lockfile::load(&project).unwrap(), Some(lockfile));
+
+    let lines = run_script(
+        &project,
+        "import \"strings\"\nimport \"strings/extra/quiet\"\nimport \"strings\"\nsay *shout \"hi\"\nsay *quiet \"HI\"",
+    )
+    .unwrap();
+    assert_eq!(lines, vec!["HI!", "hi"]);
+
+    let error = run_script(&project, "import \"missing\"").unwrap_err();
+    assert!(
+        error.contains("no package named 'missing' is installed"),
+        "{}",
+        error
+    );
+    let error = run_script(&project, "import \"strings/nope\"").unwrap_err();
+    assert!(
+        error.contains("package 'strings' has no nope.tde"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_git_dependencies_are_locked() {
+    let dir = scratch("git");
+    let work = git_package(&dir, "colors", "function red (\n    give \"v1\"\n)\n");
+    let first = git(&work, &["rev-parse", "HEAD"]);
+    git(&work, &["tag", "v1"]);
+    git(&work, &["push", "--quiet", "origin", "v1"]);
+    let second = commit(&work, "function red (\n    give \"v2\"\n)\n");
+
+    let project = dir.join("app");
+    write(
+        &project.join("tilde.toml"),
+        "[project]\nname = \"app\"\n\n[dependencies]\ncolors = { git = \"../colors.git\" }\npinned = { git = \"../colors.git\", rev = \"v1\" }\n",
+    );
+    let lockfile = package::install(&project).unwrap();
+    assert_eq!(
+        lockfile.find("colors").unwrap().commit.as_deref(),
+        Some(second.as_str())
+    );
+    assert_eq!(
+        lockfile.find("pinned").unwrap().commit.as_deref(),
+        Some(first.as_str())
+    );
+    assert!(!project.join("tilde_modules/colors/.git").exists());
+
+    // New commits upstream don't change what a locked install gets
+    commit(&work, "function red (\n    give \"v3\"\n)\n");
+    package::install(&project).unwrap();
+    assert_eq!(
+        run_script(&project, "import \"colors\"\nsay *red").unwrap(),
+        vec!["v2"]
+    );
+
+    // Dropping the lockfile picks up the latest commit
+    fs::remove_file(project.join("tilde.lock")).unwrap();
+    package::install(&project).unwrap();
+    assert_eq!(
+        run_script(&project, "import \"colors\"\nsay *red").unwrap(),
+        vec!["v3"]
+    );
+}
+
+#[test]
+fn test_install_errors_leave_modules_alone() {
+    let dir = scratch("errors");
+    write(&dir.join("a/main.tde"), "say 1\n");
+    let project = dir.join("app");
+    write(
+        &project.join("tilde.toml"),
+        "[project]\nname = \"app\"\n\n[dependencies]\na = { path = \"../a\" }\n",
+    );
+    package::install(&project).unwrap();
+
+    write(
+        &project.join("tilde.toml"),
+        "[project]\nname = \"app\"\n\n[dependencies]\na = { path = \"../a\" }\nb = { path = \"../missing\" }\n",
+    );
+    let error = package::install(&project).unwrap_err();
+    assert!(error.contains("is not a directory"), "{}", error);
+    assert!(project.join("tilde_modules/a/main.tde").is_file());
+    assert!(!project.join("tilde_modules.partial").exists());
+
+    write(
+        &dir.join("b/tilde.toml"),
+        "[project]\nname = \"b\"\ntilde = \">=99\"\n",
+    );
+    write(
+        &project.join("tilde.toml"),
+        "[project]\nname = \"app\"\n\n[dependencies]\nb = { path = \"../b\" }\n",
+    );
+    let error = package::install(&project).unwrap_err();
+    assert!(
+        error.starts_with("Dependency 'b': b needs Tilde >=99"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_relative_imports() {
+    let dir = scratch("relative");
+    write(&dir.join("lib/a.tde"), "import \"./b\"\n~from_a is \"a\"\n");
+    write(
+        &dir.join("lib/b.tde"),
+        "import \"a.tde\"\nfunction from-b (\n    give \"b\"\n)\n",
+    );
+    let lines = run_script(&dir, "import \"./lib/a.tde\"\nsay ~from_a *from-b").unwrap();
+    assert_eq!(lines, vec!["ab"]);
+}
+
+#[test]
+fn test_install_and_run_commands() {
+    let dir = scratch("cli");
+    write(
+        &dir.join("greeting/main.tde"),
+        "function greet ~name (\n    give \"Hello, \" + ~name\n)\n",
+    );
+    let project = dir.join("app");
+    write(
+        &project.join("tilde.toml"),
+        "[project]\nname = \"app\"\nentry = \"src/app.tde\"\ntilde = \">=0.1\"\n\n[dependencies]\ngreeting = { path = \"../greeting\" }\n",
+    );
+    write(
+        &project.join("src/app.tde"),
+        "import \"greeting\"\nsay *greet ~args.0\n",
+    );
+    fs::create_dir_all(project.join("src/nested")).unwrap();
+
+    let output = tilde(&project, &["run", "Ada"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("run `tilde install`"));
+
+    let output = tilde(&project.join("src/nested"), &["install"]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .ends_with("Installed 1 package into tilde_modules\n")
+    );
+
+    let output = tilde(&project, &["run", "Ada"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Hello, Ada\n");
+    assert!(output.status.success());
+
+    // Editing an installed package is noticed
+    write(&project.join("tilde_modules/greeting/main.tde"), "say 1\n");
+    let output = tilde(&project, &["run"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("doesn't match tilde.lock"));
+
+    assert_eq!(tilde(&dir, &["run"]).status.code(), Some(2));
+}
@@ -0,0 +1,123 @@
+use tilde::profiler::{CallKind, Profile};
+use tilde::{evaluator::Evaluator, parser::Parser};
+
+fn profile(evaluator: &mut Evaluator, input: &str) -> Profile {
+    let mut parser = Parser::new(input);
+    let program = parser.parse().unwrap();
+    evaluator.enable_profiling();
+    evaluator.eval_program(program).unwrap();
+    evaluator.take_profile().unwrap()
+}
+
+fn calls(profile: &Profile, kind: CallKind, name: &str) -> u64 {
+    profile
+        .functions
+        .iter()
+        .find(|stats| stats.kind == kind && stats.name == name)
+        .map_or(0, |stats| stats.calls)
+}
+
+#[test]
+fn test_counts_user_and_stdlib_calls() {
+    let profile = profile(
+        &mut Evaluator::new(),
+        r#"
+        function fib ~n (
+            if ~n <= 1 give ~n
+            ~a is *fib (~n - 1)
+            ~b is *fib (~n - 2)
+            give ~a + ~b
+        )
+        ~result is *fib 10
+        ~sorted is sort [3, 1, 2]
+        ~length is length ~sorted
+    "#,
+    );
+
+    assert_eq!(calls(&profile, CallKind::User, "fib"), 177);
+    assert_eq!(calls(&profile, CallKind::Stdlib, "sort"), 1);
+    assert_eq!(calls(&profile, CallKind::Stdlib, "length"), 1);
+}
+
+#[test]
+fn test_native_calls_and_nested_stacks() {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_fn("host-value", |_| Ok(42.0));
+    let profile = profile(
+        &mut evaluator,
+        r#"
+        function outer (
+            give *host-value
+        )
+        ~value is *outer
+    "#,
+    );
+
+    assert_eq!(calls(&profile, CallKind::Native, "host-value"), 1);
+    assert!(
+        profile
+            .stacks
+            .iter()
+            .any(|(stack, _)| stack == "<script>;outer;host-value")
+    );
+    let outer = &profile.functions.iter().find(|f| f.name == "outer").unwrap();
+    assert!(outer.inclusive >= outer.exclusive);
+}
+
+#[test]
+fn test_counts_values_by_kind() {
+    let profile = profile(
+        &mut Evaluator::new(),
+        r#"
+        ~items is [1, 2]
+        ~name is "tilde"
+    "#,
+    );
+
+    let count = |kind: &str| {
+        profile
+            .values
+            .iter()
+            .find(|(name, _)| *name == kind)
+            .map_or(0, |(_, count)| *count)
+    };
+    assert_eq!(count("list"), 1);
+    assert_eq!(count("string"), 1);
+    assert!(count("number") >= 2);
+}
+
+#[test]
+fn test_report_and_collapsed_stacks() {
+    let profile = profile(
+        &mut Evaluator::new(),
+        r#"
+        function slow (
+            ~i is 0
+            loop (
+                if ~i >= 2000 break-loop
+                ~i up 1
+            )
+        )
+        *slow
+    "#,
+    );
+
+    let report = profile.report();
+    assert!(report.starts_with("Profile: "));
+    assert!(report.contains("slow\n"));
+
+    let stacks = profile.collapsed_stacks();
+    assert!(stacks.lines().any(|line| line.starts_with("<script>;slow ")));
+    for line in stacks.lines() {
+        let (_, micros) = line.rsplit_once(' ').unwrap();
+        assert!(micros.parse::<u128>().unwrap() > 0);
+    }
+}
+
+#[test]
+fn test_profiling_is_off_by_default() {
+    let mut evaluator = Evaluator::new();
+    let mut parser = Parser::new("~x is 1");
+    evaluator.eval_program(parser.parse().unwrap()).unwrap();
+    assert!(evaluator.take_profile().is_none());
+}
@@ -0,0 +1,171 @@
+use tilde::{
+    evaluator::Evaluator,
+    parser::Parser,
+    value::{ObjectMap, Value},
+};
+
+fn run(input: &str) -> Evaluator {
+    let program = Parser::new(input).parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(program).unwrap();
+    evaluator
+}
+
+fn strings(items: &[&str]) -> Value {
+    Value::List(
+        items
+            .iter()
+            .map(|item| Value::String(item.to_string()))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_regex_test_and_match() {
+    let evaluator = run(r#"
+        ~line is "2024-01-15 ERROR [db] timeout after 30s"
+        ~is-error is regex-test ~line "ERROR|WARN"
+        ~is-debug is regex-test ~line "DEBUG"
+        ~duration is regex-match ~line "\d+s"
+        ~date is regex-match ~line "(\d+)-(\d+)-(\d+)"
+        ~missing is regex-match ~line "nothing"
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("is-error"),
+        Some(&Value::Boolean(true))
+    );
+    assert_eq!(
+        evaluator.get_variable("is-debug"),
+        Some(&Value::Boolean(false))
+    );
+    assert_eq!(
+        evaluator.get_variable("duration"),
+        Some(&Value::String("30s".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("date"),
+        Some(&strings(&["2024", "01", "15"]))
+    );
+    assert_eq!(evaluator.get_variable("missing"), Some(&Value::Null));
+}
+
+#[test]
+fn test_named_groups_and_find_all() {
+    let evaluator = run(r#"
+        ~entry is regex-match "[db] timeout" "\[(?<module>\w+)\] (?<message>.*)"
+        ~numbers is regex-find-all "a1 b22 c333" "\d+"
+        ~pairs is regex-find-all "a=1, b=2" "(\w)=(\d)"
+        ~optional is regex-match "ab" "(a)(x)?(b)"
+        ~none is regex-find-all "abc" "\d"
+        ~mixed is regex-match "2024-01-15" "(?<year>\d+)-(\d+)-(?<day>\d+)"
+        ~mixed-all is regex-find-all "a=1" "(?<key>\w)=(\d)"
+    "#);
+
+    let entry = ObjectMap::from([
+        ("module".to_string(), Value::String("db".to_string())),
+        ("message".to_string(), Value::String("timeout".to_string())),
+    ]);
+    assert_eq!(evaluator.get_variable("entry"), Some(&Value::Object(entry)));
+    assert_eq!(
+        evaluator.get_variable("numbers"),
+        Some(&strings(&["1", "22", "333"]))
+    );
+    assert_eq!(
+        evaluator.get_variable("pairs"),
+        Some(&Value::List(vec![
+            strings(&["a", "1"]),
+            strings(&["b", "2"])
+        ]))
+    );
+    assert_eq!(
+        evaluator.get_variable("optional"),
+        Some(&Value::List(vec![
+            Value::String("a".to_string()),
+            Value::Null,
+            Value::String("b".to_string()),
+        ]))
+    );
+    assert_eq!(evaluator.get_variable("none"), Some(&Value::List(vec![])));
+
+    // Unnamed groups next to named ones are keyed by their number
+    let mixed = ObjectMap::from([
+        ("year".to_string(), Value::String("2024".to_string())),
+        ("2".to_string(), Value::String("01".to_string())),
+        ("day".to_string(), Value::String("15".to_string())),
+    ]);
+    assert_eq!(evaluator.get_variable("mixed"), Some(&Value::Object(mixed)));
+    let pair = ObjectMap::from([
+        ("key".to_string(), Value::String("a".to_string())),
+        ("2".to_string(), Value::String("1".to_string())),
+    ]);
+    assert_eq!(
+        evaluator.get_variable("mixed-all"),
+        Some(&Value::List(vec![Value::Object(pair)]))
+    );
+}
+
+#[test]
+fn test_regex_replace() {
+    let evaluator = run(r#"
+        function shout ~word (
+            give uppercase ~word
+        )
+        ~numbered is regex-replace "2024-01-15" "(\d+)-(\d+)-(\d+)" "$3/$2/$1"
+        ~named is regex-replace "2024-01-15" "(?<y>\d+)-(?<m>\d+)-(?<d>\d+)" "${d}.${m}.${y}"
+        ~template is "[$0]"
+        ~from-variable is regex-replace "a b" "\w" ~template
+        ~shouted is regex-replace "hello big world" "b\w+" shout
+        ~swapped is regex-replace "a1 b2" "(\w)(\d)" |~groups (~groups.1 + ~groups.0)|
+        ~counted is regex-replace "x y" "\w" |~letter (length ~letter)|
+    "#);
+
+    let expected = [
+        ("numbered", "15/01/2024"),
+        ("named", "15.01.2024"),
+        ("from-variable", "[a] [b]"),
+        ("shouted", "hello BIG world"),
+        ("swapped", "1a 2b"),
+        ("counted", "1 1"),
+    ];
+    for (name, value) in expected {
+        assert_eq!(
+            evaluator.get_variable(name),
+            Some(&Value::String(value.to_string())),
+            "~{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_regex_split() {
+    let evaluator = run(r#"
+        ~words is regex-split "a, b;c  d" "[,;\s]+"
+        ~whole is regex-split "abc" "\d"
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("words"),
+        Some(&strings(&["a", "b", "c", "d"]))
+    );
+    assert_eq!(evaluator.get_variable("whole"), Some(&strings(&["abc"])));
+}
+
+#[test]
+fn test_regex_errors() {
+    let error = |input: &str| {
+        let program = Parser::new(input).parse().unwrap();
+        Evaluator::new().eval_program(program).unwrap_err()
+    };
+
+    assert_eq!(
+        error(r#"regex-test "x" "(""#),
+        "regex-test: invalid pattern \"(\": unclosed group"
+    );
+    assert_eq!(
+        error(r#"regex-replace "x" "x" 5"#),
+        "regex-replace replacement must be a string or a function"
+    );
+    assert!(error(r#"regex-match "x""#).contains("requires exactly 2 arguments"));
+}
@@ -0,0 +1,163 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use tilde::io::MemoryOutput;
+use tilde::repl::Repl;
+use tilde::sandbox::EvaluatorConfig;
+
+fn session(entries: &[&str]) -> Vec<String> {
+    let output = Arc::new(MemoryOutput::new());
+    let mut repl = Repl::new(EvaluatorConfig::default(), output.clone());
+    for entry in entries {
+        repl.run_entry(entry);
+    }
+    output.lines()
+}
+
+#[test]
+fn test_echoes_values_unless_something_was_printed() {
+    let lines = session(&["~x is [1, \"two\"]", "~x", "say \"hi\"", "~missing"]);
+    assert_eq!(
+        lines,
+        vec![
+            "[1, \"two\"]",
+            "[1, \"two\"]",
+            "hi",
+            "Runtime error: Undefined variable: missing"
+        ]
+    );
+}
+
+#[test]
+fn test_inspection_commands() {
+    let lines = session(&[
+        "~b is {z: 1, a: true}",
+        "function add ~x ~y (\n    give ~x + ~y\n)",
+        ":vars",
+        ":funcs",
+        ":type *add 1 2",
+        ":type",
+        ":nope",
+    ]);
+    assert_eq!(
+        &lines[1..],
+        &[
+            "~b is {a: true, z: 1}",
+            "function add ~x ~y",
+            "number",
+            "Usage: :type <expr>",
+            "Unknown command :nope (type :help for a list)",
+        ]
+    );
+}
+
+#[test]
+fn test_save_reset_and_load() {
+    let path = std::env::temp_dir().join("tilde_repl_session.tde");
+    let path = path.to_str().unwrap();
+    let output = Arc::new(MemoryOutput::new());
+    let mut repl = Repl::new(EvaluatorConfig::default(), output.clone());
+    repl.run_entry("~count is 2");
+    repl.run_entry("~count is ~count + oops");
+    repl.run_entry("~count up 1");
+    repl.run_entry(&format!(":save {}", path));
+    assert_eq!(
+        std::fs::read_to_string(path).unwrap(),
+        "~count is 2\n~count up 1\n"
+    );
+
+    repl.run_entry(":reset");
+    assert!(repl.evaluator().visible_variables().is_empty());
+    repl.run_entry(&format!(":load {}", path));
+    repl.run_entry("say ~count");
+    assert_eq!(output.lines().last().unwrap(), "3");
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_load_respects_the_sandbox() {
+    let output = Arc::new(MemoryOutput::new());
+    let config = EvaluatorConfig {
+        capabilities: tilde::sandbox::Capabilities::none(),
+        ..EvaluatorConfig::default()
+    };
+    let mut repl = Repl::new(config, output.clone());
+    repl.run_entry(":load secrets.tde");
+    assert!(
+        output.contents().starts_with("Permission denied: load"),
+        "{}",
+        output.contents()
+    );
+}
+
+#[test]
+fn test_help_command() {
+    let lines = session(&[
+        ":help slice",
+        "## Adds two numbers.\nfunction add ~x ~y (\n    give ~x + ~y\n)",
+        ":help add",
+        ":help nope",
+    ]);
+    assert_eq!(lines[0], "slice list start [end]");
+    assert!(lines.contains(&"    expect (slice [1, 2, 3, 4] 1 3) == [2, 3]".to_string()));
+    let add = lines.iter().position(|line| line == "*add ~x ~y").unwrap();
+    assert_eq!(lines[add + 2], "Adds two numbers.");
+    assert_eq!(
+        lines.last().unwrap(),
+        "help: no function named 'nope' (functions-list lists them all)"
+    );
+}
+
+#[test]
+fn test_quitting() {
+    let output = Arc::new(MemoryOutput::new());
+    let mut repl = Repl::new(EvaluatorConfig::default(), output);
+    assert!(repl.run_entry(":help"));
+    assert!(!repl.run_entry("exit"));
+    assert!(!repl.run_entry(":quit"));
+}
+
+#[test]
+fn test_completion() {
+    let output = Arc::new(MemoryOutput::new());
+    let mut repl = Repl::new(EvaluatorConfig::default(), output);
+    repl.run_entry("~name is 1\n~number is 2\nfunction greet (\n    say 1\n)");
+
+    assert_eq!(
+        repl.complete("say ~n", 6),
+        (4, vec!["~name".to_string(), "~number".to_string()])
+    );
+    assert_eq!(repl.complete("*gr", 3), (0, vec!["*greet".to_string()]));
+    assert_eq!(repl.complete(":va", 3), (0, vec![":vars".to_string()]));
+    let (start, candidates) = repl.complete("~x is uppe", 10);
+    assert_eq!(start, 6);
+    assert!(candidates.contains(&"uppercase".to_string()));
+}
+
+#[test]
+fn test_piped_session() {
+    let history = std::env::temp_dir().join("tilde_repl_history");
+    let _ = std::fs::remove_file(&history);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .env("TILDE_HISTORY", &history)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"if true (\n    say 'a ('\n)\n1 + 2\nexit\nsay \"unreached\"\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success());
+    assert!(stdout.contains("> ... ... a (\n> 3\n"), "{}", stdout);
+    assert!(!stdout.contains("unreached"));
+    assert_eq!(
+        std::fs::read_to_string(&history).unwrap(),
+        "if true (\n    say 'a ('\n)\n1 + 2\nexit\n"
+    );
+    let _ = std::fs::remove_file(&history);
+}
@@ -0,0 +1,176 @@
+use tilde::sandbox::{Capabilities, EvaluatorConfig};
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+fn run_with(capabilities: Capabilities, input: &str) -> (Evaluator, Result<Value, String>) {
+    let mut parser = Parser::new(input);
+    let program = parser.parse().unwrap();
+    let config = EvaluatorConfig::default().with_capabilities(capabilities);
+    let mut evaluator = Evaluator::with_config(config);
+    let result = evaluator.eval_program(program);
+    (evaluator, result)
+}
+
+#[test]
+fn test_denied_run_is_rescuable_permission_denied() {
+    let (evaluator, result) = run_with(
+        Capabilities::none(),
+        r#"
+        attempt (
+            ~output is run "echo hacked"
+        ) rescue ~error (
+            ~code is ~error.code
+            ~capability is ~error.context.capability
+            ~source is ~error.source
+        )
+    "#,
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(evaluator.get_variable("output"), None);
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("permission_denied".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("capability"),
+        Some(&Value::String("process".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("source"),
+        Some(&Value::String("echo hacked".to_string()))
+    );
+}
+
+#[test]
+fn test_allowed_process() {
+    let (evaluator, result) = run_with(
+        Capabilities::none().allow_process(),
+        r#"~result is run "echo allowed""#,
+    );
+
+    assert!(result.is_ok());
+    assert!(matches!(evaluator.get_variable("result"), Some(Value::Object(_))));
+}
+
+#[test]
+fn test_filesystem_roots() {
+    let root = std::env::temp_dir().join("tilde_sandbox_integration");
+    std::fs::create_dir_all(&root).unwrap();
+    let inside = root.join("allowed.txt");
+    let outside = std::env::temp_dir().join("tilde_sandbox_outside.txt");
+
+    let input = format!(
+        r#"
+        ~written is write "{inside}" "inside"
+        ~read_back is read "{inside}"
+        ~exists is file-exists "{inside}"
+        attempt (
+            write "{outside}" "outside"
+        ) rescue ~error (
+            ~code is ~error.code
+        )
+        attempt (
+            read "{escape}"
+        ) rescue ~error (
+            ~escape_code is ~error.code
+        )
+    "#,
+        inside = inside.display(),
+        outside = outside.display(),
+        escape = root.join("..").join("tilde_sandbox_outside.txt").display(),
+    );
+
+    let (evaluator, result) = run_with(Capabilities::none().allow_path(&root), &input);
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(evaluator.get_variable("exists"), Some(&Value::Boolean(true)));
+    if let Some(Value::Object(read)) = evaluator.get_variable("read_back") {
+        assert_eq!(
+            read.get("content"),
+            Some(&Value::String("inside".to_string()))
+        );
+    } else {
+        panic!("Expected read result object");
+    }
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("permission_denied".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("escape_code"),
+        Some(&Value::String("permission_denied".to_string()))
+    );
+    assert!(!outside.exists());
+
+    std::fs::remove_file(&inside).unwrap();
+}
+
+#[test]
+fn test_network_host_allowlist() {
+    let (evaluator, result) = run_with(
+        Capabilities::none().allow_host("httpbin.org"),
+        r#"
+        ~response is get "https://httpbin.org/json"
+        attempt (
+            get "https://example.com/data"
+        ) rescue ~error (
+            ~code is ~error.code
+            ~capability is ~error.context.capability
+        )
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(matches!(evaluator.get_variable("response"), Some(Value::Object(_))));
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("permission_denied".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("capability"),
+        Some(&Value::String("network".to_string()))
+    );
+}
+
+#[test]
+fn test_env_denied() {
+    let (_, result) = run_with(Capabilities::none(), r#"~home is env "HOME""#);
+    assert!(result.unwrap_err().contains("needs the env capability"));
+}
+
+#[test]
+fn test_audio_denied() {
+    let (_, result) = run_with(Capabilities::none(), "play (pattern \"bd sd\")");
+    assert!(result.unwrap_err().contains("needs the audio capability"));
+}
+
+#[test]
+fn test_spawned_tasks_keep_the_sandbox() {
+    let (evaluator, result) = run_with(
+        Capabilities::none(),
+        r#"
+        ~task is spawn (run "echo from-task")
+        attempt (
+            await ~task
+        ) rescue ~error (
+            ~code is ~error.code
+        )
+    "#,
+    );
+
+    assert!(result.is_ok());
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("permission_denied".to_string()))
+    );
+}
+
+#[test]
+fn test_default_evaluator_allows_everything() {
+    let mut parser = Parser::new(r#"~result is run "echo ok""#);
+    let program = parser.parse().unwrap();
+    let mut evaluator = Evaluator::new();
+
+    assert!(evaluator.eval_program(program).is_ok());
+    assert_eq!(evaluator.capabilities(), &Capabilities::all());
+}
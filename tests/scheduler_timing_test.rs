@@ -38,6 +38,9 @@ fn test_scheduler_event_firing() {
 
     // At least some output should be generated after time has passed
     println!("Outputs after 150ms: {:?}", outputs);
+
+    // Pattern outputs are collected in the output buffer
+    assert_eq!(evaluator.output_buffer, outputs);
     
     // Verify that the scheduler is working
     if let Some(ref engine) = evaluator.music_engine {
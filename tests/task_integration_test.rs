@@ -0,0 +1,236 @@
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+fn run(input: &str) -> Evaluator {
+    let mut parser = Parser::new(input);
+    let program = parser.parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(program).unwrap();
+    evaluator
+}
+
+#[test]
+fn test_spawn_and_await_user_function() {
+    let evaluator = run(r#"
+        function square ~n (
+            give ~n * ~n
+        )
+        ~id is 7
+        ~task is spawn (*square ~id)
+        ~result is await ~task
+    "#);
+
+    assert_eq!(evaluator.get_variable("result"), Some(&Value::Number(49.0)));
+    assert!(matches!(evaluator.get_variable("task"), Some(Value::Task(_))));
+}
+
+#[test]
+fn test_await_same_task_twice() {
+    let evaluator = run(r#"
+        ~task is spawn (1 + 2)
+        ~first is await ~task
+        ~second is await ~task
+    "#);
+
+    assert_eq!(evaluator.get_variable("first"), Some(&Value::Number(3.0)));
+    assert_eq!(evaluator.get_variable("second"), Some(&Value::Number(3.0)));
+}
+
+#[test]
+fn test_task_state_is_isolated() {
+    let evaluator = run(r#"
+        ~counter is 1
+        function bump (
+            ~counter is ~counter + 100
+            give ~counter
+        )
+        ~task is spawn (*bump)
+        ~from_task is await ~task
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("from_task"),
+        Some(&Value::Number(101.0))
+    );
+    assert_eq!(evaluator.get_variable("counter"), Some(&Value::Number(1.0)));
+}
+
+#[test]
+fn test_await_all_preserves_order_and_errors() {
+    let evaluator = run(r#"
+        ~tasks is []
+        ~tasks is append ~tasks (spawn (10 * 2))
+        ~tasks is append ~tasks (spawn (~missing + 1))
+        ~tasks is append ~tasks (spawn "done")
+        ~results is await-all ~tasks
+        ~first is ~results.0
+        ~failed is ~results.1
+        ~code is ~failed.code
+        ~last is ~results.2
+    "#);
+
+    assert_eq!(evaluator.get_variable("first"), Some(&Value::Number(20.0)));
+    assert!(matches!(evaluator.get_variable("failed"), Some(Value::Error(_))));
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("task_failed".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("last"),
+        Some(&Value::String("done".to_string()))
+    );
+}
+
+#[test]
+fn test_await_failed_task_is_rescuable() {
+    let evaluator = run(r#"
+        ~task is spawn (~undefined_thing * 2)
+        attempt (
+            ~value is await ~task
+        ) rescue ~error (
+            ~code is ~error.code
+            ~source is ~error.source
+            ~message is ~error.message
+        )
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("code"),
+        Some(&Value::String("task_failed".to_string()))
+    );
+    assert_eq!(
+        evaluator.get_variable("source"),
+        Some(&Value::String("spawn".to_string()))
+    );
+    if let Some(Value::String(message)) = evaluator.get_variable("message") {
+        assert!(message.contains("Undefined variable"));
+    } else {
+        panic!("Expected error message");
+    }
+}
+
+#[test]
+fn test_parallel_map_with_anonymous_function() {
+    let evaluator = run(r#"
+        ~squares is parallel-map [1, 2, 3, 4] |~n (~n * ~n)|
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("squares"),
+        Some(&Value::List(vec![
+            Value::Number(1.0),
+            Value::Number(4.0),
+            Value::Number(9.0),
+            Value::Number(16.0),
+        ]))
+    );
+}
+
+#[test]
+fn test_parallel_map_with_named_functions_and_limit() {
+    let evaluator = run(r#"
+        function label ~item (
+            give "item-" + ~item.name
+        )
+        ~items is [{name: "a"}, {name: "b"}, {name: "c"}]
+        ~labels is parallel-map ~items label {limit: 2}
+        ~doubled is parallel-map [1, 2, 3] double
+    "#);
+
+    assert_eq!(
+        evaluator.get_variable("labels"),
+        Some(&Value::List(vec![
+            Value::String("item-a".to_string()),
+            Value::String("item-b".to_string()),
+            Value::String("item-c".to_string()),
+        ]))
+    );
+    assert_eq!(
+        evaluator.get_variable("doubled"),
+        Some(&Value::List(vec![
+            Value::Number(2.0),
+            Value::Number(4.0),
+            Value::Number(6.0),
+        ]))
+    );
+}
+
+#[test]
+fn test_parallel_map_raises_first_error() {
+    let mut parser = Parser::new(
+        r#"
+        function check ~n (
+            if ~n == 2 (
+                give ~nope
+            )
+            give ~n
+        )
+        ~result is parallel-map [1, 2, 3] check
+    "#,
+    );
+    let program = parser.parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    let result = evaluator.eval_program(program);
+
+    assert!(result.unwrap_err().contains("Undefined variable"));
+}
+
+#[test]
+fn test_parallel_map_runs_concurrently() {
+    let start = std::time::Instant::now();
+    let evaluator = run(r#"
+        task-limit 4
+        ~results is parallel-map [1, 2, 3, 4] |~n (wait 0.1)| {limit: 4}
+    "#);
+
+    assert!(start.elapsed() < std::time::Duration::from_millis(350));
+    assert!(matches!(evaluator.get_variable("results"), Some(Value::List(items)) if items.len() == 4));
+}
+
+#[test]
+fn test_nested_parallel_map_shares_the_pool() {
+    // Every outer task waits on inner tasks; with their own pools gone, the
+    // two workers have to run the inner tasks while they wait
+    let evaluator = run(r#"
+        task-limit 2
+        ~grid is parallel-map [1, 2, 3, 4] |~row (parallel-map [1, 2, 3] |~col (~row * ~col)|)|
+        ~nested is await (spawn (await (spawn (await (spawn 42)))))
+    "#);
+
+    let row = |n: f64| Value::List(vec![Value::Number(n), Value::Number(2.0 * n), Value::Number(3.0 * n)]);
+    assert_eq!(
+        evaluator.get_variable("grid"),
+        Some(&Value::List(vec![row(1.0), row(2.0), row(3.0), row(4.0)]))
+    );
+    assert_eq!(evaluator.get_variable("nested"), Some(&Value::Number(42.0)));
+}
+
+#[test]
+fn test_task_limit() {
+    let evaluator = run(r#"
+        ~before is task-limit
+        task-limit 3
+        ~after is task-limit
+    "#);
+
+    assert!(matches!(evaluator.get_variable("before"), Some(Value::Number(n)) if *n >= 1.0));
+    assert_eq!(evaluator.get_variable("after"), Some(&Value::Number(3.0)));
+}
+
+#[test]
+fn test_task_errors() {
+    let cases = [
+        ("await 42", "await argument must be a task"),
+        ("await-all [1]", "await-all argument must be a task"),
+        ("parallel-map 5 double", "parallel-map first argument must be a list"),
+        ("parallel-map [1] no-such-fn", "Unknown function: no-such-fn"),
+        ("task-limit 0", "task-limit must be a number of at least 1"),
+    ];
+
+    for (input, expected) in cases {
+        let mut parser = Parser::new(input);
+        let program = parser.parse().unwrap();
+        let mut evaluator = Evaluator::new();
+        let err = evaluator.eval_program(program).unwrap_err();
+        assert!(err.contains(expected), "{}: {}", input, err);
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use tilde::{evaluator::Evaluator, parser::Parser, value::Value};
+
+/// Run a script and return what it left in ~result
+fn render(input: &str) -> Result<String, String> {
+    let program = Parser::new(input).parse().unwrap();
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(program)?;
+    match evaluator.get_variable("result") {
+        Some(Value::String(text)) => Ok(text.clone()),
+        other => panic!("Expected a string result, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_values_and_escaping() {
+    let result = render(
+        r#"
+        ~data is {user: {name: "Ada", roles: ["admin", "dev"]}, html: "<b>\"Tom & Jerry's\"</b>", count: 3}
+        ~result is render-template "{{ user.name }} ({{ user.roles.0 }}) has {{ count }} and {{ missing }}. {{ html }} {{{ html }}} {{& html }}" ~data
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        "Ada (admin) has 3 and . &lt;b&gt;&quot;Tom &amp; Jerry&#39;s&quot;&lt;/b&gt; <b>\"Tom & Jerry's\"</b> <b>\"Tom & Jerry's\"</b>"
+    );
+
+    let result =
+        render(r#"~result is render-template "host = {{ host }}" {host: "a&b"} {escape: "none"}"#)
+            .unwrap();
+    assert_eq!(result, "host = a&b");
+}
+
+#[test]
+fn test_sections_and_loops() {
+    let result = render(
+        r#"
+        ~template is "Items:
+{{# items }}
+  {{ @index }}. {{ name }}{{^ @last }},{{/ @last }}
+{{/ items }}
+{{^ items }}
+  none
+{{/ items }}
+{{# admin }}{{ name }} is an admin{{/ admin }}
+{{# numbers }}[{{ . }}]{{/ numbers }}"
+        ~result is render-template ~template {items: [{name: "tea"}, {name: "cake"}], admin: {name: "Ada"}, numbers: [1, 2]}
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        "Items:\n  0. tea,\n  1. cake\nAda is an admin\n[1][2]"
+    );
+
+    let result = render(
+        r#"~result is render-template "{{# items }}x{{/ items }}{{^ items }}empty{{/ items }}{{# off }}on{{/ off }}" {items: [], off: false}"#,
+    )
+    .unwrap();
+    assert_eq!(result, "empty");
+}
+
+#[test]
+fn test_filters() {
+    let result = render(
+        r#"
+        ~data is {name: "  ada  ", tags: ["x", "y"], code: "7"}
+        ~result is render-template "{{ name | trim | uppercase }} {{ tags | join \", \" }} {{ tags | map uppercase | join \"+\" }} {{ code | pad-left 3 \"0\" }} {{ tags | length }}" ~data
+    "#,
+    )
+    .unwrap();
+    assert_eq!(result, "ADA x, y X+Y 007 2");
+}
+
+#[test]
+fn test_partials() {
+    let dir = std::env::temp_dir().join("tilde_template_partials");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("footer.txt"), "-- {{ name }}\n").unwrap();
+    fs::write(dir.join("loop.txt"), "{{> loop.txt }}").unwrap();
+    fs::write(dir.join("broken.txt"), "ok\n{{# open }}\n").unwrap();
+
+    let script = |template: &str| {
+        format!(
+            "~result is render-template \"{}\" {{name: \"Ada\"}} {{partials: \"{}\"}}",
+            template,
+            dir.display()
+        )
+    };
+
+    assert_eq!(
+        render(&script("Hi\n{{> footer.txt }}")).unwrap(),
+        "Hi\n-- Ada\n"
+    );
+    assert!(
+        render(&script("{{> loop.txt }}"))
+            .unwrap_err()
+            .contains("loop.txt line 1: partials nested more than 32 deep")
+    );
+    assert!(
+        render(&script("{{> broken.txt }}"))
+            .unwrap_err()
+            .contains("broken.txt line 2: {{# open }} is never closed")
+    );
+    assert!(
+        render(&script("\n{{> missing.txt }}"))
+            .unwrap_err()
+            .starts_with("render-template: line 2: partial missing.txt:")
+    );
+}
+
+#[test]
+fn test_template_from_a_file() {
+    let dir = std::env::temp_dir().join("tilde_template_file");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("page.html");
+    fs::write(
+        &path,
+        "<h1>{{ title }}</h1>{{# rows }}<p>{{ . }}</p>{{/ rows }}",
+    )
+    .unwrap();
+
+    // As in the docs: bind the file, then render its content
+    let result = render(&format!(
+        "~rows is [1, 2]\n~file is read \"{}\"\n~result is render-template ~file.content {{title: \"Report\", rows: ~rows}}",
+        path.display()
+    ))
+    .unwrap();
+    assert_eq!(result, "<h1>Report</h1><p>1</p><p>2</p>");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_errors_name_the_line() {
+    let error = |template: &str| {
+        render(&format!("~result is render-template \"{}\" {{}}", template)).unwrap_err()
+    };
+
+    assert_eq!(
+        error("a\\n{{# rows }}\\nb"),
+        "render-template: line 2: {{# rows }} is never closed"
+    );
+    assert_eq!(
+        error("a\\n\\n{{ name | nonsense }}"),
+        "render-template: line 3: unknown filter 'nonsense'"
+    );
+    assert_eq!(
+        error("{{# a }}\\n{{/ b }}"),
+        "render-template: line 2: {{/ b }} closes {{# a }} from line 1"
+    );
+    assert_eq!(
+        error("{{/ a }}"),
+        "render-template: line 1: {{/ a }} closes no section"
+    );
+    assert_eq!(
+        error("x {{ name"),
+        "render-template: line 1: {{ is never closed with }}"
+    );
+    assert_eq!(
+        render(r#"~result is render-template "{{ a | pad-left \"z\" }}" {a: "q"}"#).unwrap_err(),
+        "render-template: line 1: filter pad-left: Argument must be a number"
+    );
+    assert_eq!(
+        render(r#"~result is render-template "" {} {escape: "xml"}"#).unwrap_err(),
+        "render-template escape must be \"html\" or \"none\""
+    );
+}
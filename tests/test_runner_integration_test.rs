@@ -0,0 +1,188 @@
+use std::process::Command;
+use tilde::evaluator::Evaluator;
+use tilde::io::MemoryOutput;
+use tilde::parser::Parser;
+use tilde::testing::{TestFormat, TestResult, run_source};
+
+fn run(source: &str) -> Vec<TestResult> {
+    run_source(source, "example_test.tde", None).unwrap()
+}
+
+fn failure(result: &TestResult) -> &str {
+    result.failure.as_deref().unwrap()
+}
+
+#[test]
+fn test_runs_each_test_separately() {
+    let results = run(r#"
+~count is 0
+
+test "first" (
+    ~count up 1
+    expect ~count == 1
+)
+
+test "second starts fresh" (
+    ~count up 1
+    expect ~count == 1
+)
+"#);
+    let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, vec!["first", "second starts fresh"]);
+    assert!(results.iter().all(TestResult::passed));
+}
+
+#[test]
+fn test_setup_and_teardown() {
+    let results = run(r#"
+setup (
+    ~list is [1, 2]
+)
+teardown (
+    say "teardown"
+)
+
+test "uses setup" (
+    expect length ~list == 2
+)
+
+test "fails" (
+    say "before"
+    expect ~list == [1, 3]
+    say "never"
+)
+"#);
+    assert!(results[0].passed());
+    assert_eq!(results[0].output, "teardown\n");
+    assert_eq!(results[1].output, "before\nteardown\n");
+    assert!(failure(&results[1]).starts_with("line 15: Expectation failed"));
+}
+
+#[test]
+fn test_teardown_failures_fail_the_test() {
+    let results = run("teardown (\n    expect false\n)\ntest \"t\" (\n    say 1\n)\n");
+    assert_eq!(
+        failure(&results[0]),
+        "line 2: Expectation failed: expect false"
+    );
+}
+
+#[test]
+fn test_structural_diffs() {
+    let results = run(r#"
+test "objects" (
+    ~user is {name: "Bo", tags: ["a", "b"], age: 3}
+    expect ~user == {name: "Bob", tags: ["a"], age: 3}
+)
+
+test "comparisons" (
+    expect 2 + 2 > 5
+)
+"#);
+    assert_eq!(
+        failure(&results[0]),
+        r#"line 4: Expectation failed: expect ~user == {name: "Bob", tags: ["a"], age: 3}
+  actual:   {age: 3, name: "Bo", tags: ["a", "b"]}
+  expected: {age: 3, name: "Bob", tags: ["a"]}
+  differences:
+    .name: got "Bo", expected "Bob"
+    .tags[1]: unexpected "b""#
+    );
+    assert_eq!(
+        failure(&results[1]),
+        "line 8: Expectation failed: expect 2 + 2 > 5\n  left:  4\n  right: 5"
+    );
+}
+
+#[test]
+fn test_runtime_errors_fail_tests() {
+    let results = run("test \"boom\" (\n    ~x is 1 / 0\n)\n");
+    assert_eq!(failure(&results[0]), "line 2: Division by zero");
+}
+
+#[test]
+fn test_filters_by_name() {
+    let source = "test \"adds\" (\n    expect 1 + 1 == 2\n)\ntest \"subtracts\" (\n    expect 1 - 1 == 0\n)\n";
+    let results = run_source(source, "math_test.tde", Some("sub")).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].name, "subtracts");
+}
+
+#[test]
+fn test_scripts_skip_tests_but_check_expectations() {
+    let output = std::sync::Arc::new(MemoryOutput::new());
+    let mut evaluator = Evaluator::new();
+    evaluator.set_output(output.clone());
+    let program = Parser::new("test \"t\" (\n    say \"in test\"\n)\nexpect 1 == 1\nsay \"done\"")
+        .parse()
+        .unwrap();
+    evaluator.eval_program(program).unwrap();
+    assert_eq!(output.lines(), vec!["done"]);
+
+    let program = Parser::new("expect 1 == 2").parse().unwrap();
+    assert!(evaluator.eval_program(program).is_err());
+}
+
+#[test]
+fn test_keywords_stay_usable_as_names() {
+    let output = std::sync::Arc::new(MemoryOutput::new());
+    let mut evaluator = Evaluator::new();
+    evaluator.set_output(output.clone());
+    let program = Parser::new("~o is {test: 1, expect: 2}\nsay ~o.test ~o.expect").parse();
+    evaluator.eval_program(program.unwrap()).unwrap();
+    assert_eq!(output.lines(), vec!["12"]);
+}
+
+#[test]
+fn test_tap_and_junit_reports() {
+    let results = run("test \"ok\" (\n    expect true\n)\ntest \"a < b\" (\n    expect false\n)\n");
+
+    let tap = TestFormat::Tap.report(&results);
+    assert!(tap.starts_with("TAP version 13\n1..2\nok 1 - example_test.tde: ok\n"));
+    assert!(tap.contains("not ok 2 - example_test.tde: a < b\n  ---\n  message: |\n"));
+
+    let junit = TestFormat::Junit.report(&results);
+    assert!(junit.contains("<testsuites tests=\"2\" failures=\"1\""));
+    assert!(junit.contains("<testcase name=\"a &lt; b\" classname=\"example_test.tde\""));
+    assert!(junit.contains("<failure message=\"line 5: Expectation failed: expect false\">"));
+}
+
+#[test]
+fn test_cli_discovers_test_files() {
+    let dir = std::env::temp_dir().join("tilde_test_runner_cli");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(
+        dir.join("nested/good_test.tde"),
+        "test \"passes\" (\n    expect true\n)\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("helper.tde"),
+        "test \"ignored\" (\n    expect false\n)\n",
+    )
+    .unwrap();
+
+    let tilde = env!("CARGO_BIN_EXE_tilde");
+    let output = Command::new(tilde).arg("test").arg(&dir).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("PASS"));
+    assert!(stdout.ends_with("1 passed, 0 failed\n"));
+
+    std::fs::write(
+        dir.join("bad_test.tde"),
+        "test \"fails\" (\n    expect 1 == 2\n)\n",
+    )
+    .unwrap();
+    let output = Command::new(tilde).arg("test").arg(&dir).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = Command::new(tilde)
+        .args(["test", "examples/testing_test.tde"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
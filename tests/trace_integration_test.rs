@@ -0,0 +1,141 @@
+use std::sync::Arc;
+use tilde::io::MemoryOutput;
+use tilde::trace::{TraceFormat, TraceKind, Tracer};
+use tilde::{evaluator::Evaluator, parser::Parser};
+
+fn trace_with(tracer: impl FnOnce(Tracer) -> Tracer, input: &str) -> Vec<String> {
+    let output = Arc::new(MemoryOutput::new());
+    let mut evaluator = Evaluator::new();
+    evaluator.set_output(Arc::new(MemoryOutput::new()));
+    evaluator.set_tracer(tracer(Tracer::new(output.clone())));
+    let program = Parser::new(input).with_locations().parse().unwrap();
+    evaluator.eval_program(program).unwrap();
+    output.lines()
+}
+
+fn trace(input: &str) -> Vec<String> {
+    trace_with(|tracer| tracer, input)
+}
+
+fn json_events(tracer: impl FnOnce(Tracer) -> Tracer, input: &str) -> Vec<serde_json::Value> {
+    trace_with(|t| tracer(t).with_format(TraceFormat::Json), input)
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_traces_statements_with_lines() {
+    let lines = trace("~x is 1\n~x up 2\nsay ~x");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].contains("statement line 1 statement=\"~x is\""));
+    assert!(lines[1].contains("statement line 2 statement=\"~x up\""));
+    assert!(lines[2].contains("statement line 3 statement=say"));
+}
+
+#[test]
+fn test_traces_calls_and_returns() {
+    let events = json_events(
+        |tracer| tracer.with_kinds([TraceKind::Call, TraceKind::Return]),
+        r#"
+function double ~n (
+    give ~n * 2
+)
+~x is *double 4
+"#,
+    );
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"], "call");
+    assert_eq!(events[0]["function"], "double");
+    assert_eq!(events[0]["line"], 5);
+    assert_eq!(events[1]["event"], "return");
+    assert_eq!(events[1]["value"], "8");
+    assert!(events[1]["duration_ms"].as_f64().unwrap() >= 0.0);
+}
+
+#[test]
+fn test_traces_tail_calls() {
+    let events = json_events(
+        |tracer| tracer.with_kinds([TraceKind::Call]),
+        r#"
+function count-down ~n (
+    if ~n <= 0 give 0
+    give *count-down (~n - 1)
+)
+~x is *count-down 3
+"#,
+    );
+
+    assert!(events.iter().any(|event| event["tail_call"] == true));
+}
+
+#[test]
+fn test_traces_file_reads_and_writes() {
+    let path = "/tmp/tilde_trace_test.txt";
+    let events = json_events(
+        |tracer| tracer.with_kinds([TraceKind::File]),
+        &format!(
+            r#"
+~w is write "{path}" "hello"
+~r is read "{path}"
+~missing is read "{path}.missing"
+"#
+        ),
+    );
+    let _ = std::fs::remove_file(path);
+
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0]["operation"], "write");
+    assert_eq!(events[0]["bytes"], 5);
+    assert_eq!(events[1]["operation"], "read");
+    assert_eq!(events[1]["path"], path);
+    assert_eq!(events[1]["bytes"], 5);
+    assert!(events[2]["error"].is_string());
+}
+
+#[test]
+fn test_traces_run_commands() {
+    let events = json_events(
+        |tracer| tracer.with_kinds([TraceKind::Process]),
+        r#"~out is run "exit 3""#,
+    );
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"], "run");
+    assert_eq!(events[0]["command"], "exit 3");
+    assert_eq!(events[0]["exit_code"], 3);
+}
+
+#[test]
+fn test_filters_events() {
+    let lines = trace_with(
+        |tracer| tracer.with_kinds(TraceKind::parse_list("return").unwrap()),
+        r#"
+function one (
+    give 1
+)
+~x is *one
+say ~x
+"#,
+    );
+
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("return"));
+}
+
+#[test]
+fn test_spawned_tasks_share_the_tracer() {
+    let events = json_events(
+        |tracer| tracer.with_kinds([TraceKind::Call]),
+        r#"
+function work (
+    give 1
+)
+~task is spawn (*work)
+~result is await ~task
+"#,
+    );
+
+    assert!(events.iter().any(|event| event["function"] == "work"));
+}
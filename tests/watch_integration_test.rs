@@ -0,0 +1,95 @@
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tilde::io::MemoryOutput;
+use tilde::sandbox::EvaluatorConfig;
+use tilde::watch::WatchSession;
+
+fn session() -> (WatchSession, Arc<MemoryOutput>) {
+    let output = Arc::new(MemoryOutput::new());
+    let session = WatchSession::new(EvaluatorConfig::default(), vec!["arg".to_string()])
+        .with_output(output.clone());
+    (session, output)
+}
+
+fn pattern_names(session: &WatchSession) -> Vec<String> {
+    let engine = session.evaluator().unwrap().music_engine.as_ref().unwrap();
+    let mut names: Vec<String> = engine.get_pattern_names().into_iter().cloned().collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_each_version_runs_fresh() {
+    let (mut session, output) = session();
+    session.run("~count is 1\nsay ~count ~args").unwrap();
+    session.run("~count up 1\nsay ~count").unwrap_err();
+    session.run("say \"v3\"").unwrap();
+    assert_eq!(output.lines(), vec!["1[arg]", "v3"]);
+}
+
+#[test]
+fn test_errors_are_reported() {
+    let (mut session, _) = session();
+    let error = session.run("say (").unwrap_err();
+    assert!(error.starts_with("Parse error:"), "{}", error);
+    assert!(!error.contains("keeps playing"));
+    let error = session.run("~x is 1 / 0").unwrap_err();
+    assert_eq!(error, "Runtime error: Division by zero");
+    assert!(session.run("exit 3").is_ok());
+    assert_eq!(session.exit_code(), Some(3));
+}
+
+#[test]
+fn test_patterns_are_swapped_in_place() {
+    let (mut session, _) = session();
+    session
+        .run("play (pattern \"c3\")\nplay (pattern \"e3\")")
+        .unwrap();
+    assert!(session.is_playing());
+    assert_eq!(pattern_names(&session), vec!["pattern_0", "pattern_1"]);
+
+    // A broken edit keeps the last good version playing
+    let error = session.run("play (pattern \"g3\"").unwrap_err();
+    assert!(
+        error.ends_with("(the last good version keeps playing)"),
+        "{}",
+        error
+    );
+    assert_eq!(pattern_names(&session), vec!["pattern_0", "pattern_1"]);
+
+    // Removing a `play` drops its pattern without restarting the others
+    session.run("play (pattern \"d3\")").unwrap();
+    assert!(session.is_playing());
+    assert_eq!(pattern_names(&session), vec!["pattern_0"]);
+}
+
+#[test]
+fn test_cli_reruns_on_save() {
+    let path = std::env::temp_dir().join("tilde_watch_cli.tde");
+    std::fs::write(&path, "say \"first\"\n").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .arg("--watch")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(600));
+    std::fs::write(&path, "say \"second version\"\n").unwrap();
+    thread::sleep(Duration::from_millis(800));
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stdout, "first\nsecond version\n");
+    assert!(stderr.contains("changed, re-running"), "{}", stderr);
+    let _ = std::fs::remove_file(&path);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tilde"))
+        .args(["--watch", "-e", "say 1"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}